@@ -0,0 +1,145 @@
+/*
+ * Pinetree command line arguments.
+ *
+ * pinetree [options] [song.mp3 | folder]
+ * pinetree [--data-dir <folder>] ctl <command>
+ */
+
+pub const USAGE: &str = "Usage: pinetree [options] [song.mp3 | folder]
+       pinetree [--data-dir <folder>] ctl <command>
+
+Options:
+  --tui                  Uses the terminal interface instead of opening a window
+  --shuffle              Starts in shuffle mode
+  --volume <0.0-1.0>     Starts at this volume, on the same scale as the volume slider
+  --no-install           Ignores any installed data and doesn't save anything
+  --data-dir <folder>    Keeps all data in this folder instead of the install location,
+                         so several separate profiles can be used side by side
+  --help                 Shows this message
+
+Run \"pinetree ctl help\" to see the remote control commands.";
+
+#[derive(Default)]
+pub struct StartupArgs {
+	/* Song to play, or folder to open */
+	pub open_path: Option<String>,
+	pub tui: bool,
+	pub shuffle: bool,
+	pub volume: Option<f32>,
+	pub no_install: bool,
+	pub data_dir: Option<String>,
+	/* Everything after "ctl", if Pinetree is being used as a remote control */
+	pub ctl_args: Option<Vec<String>>,
+	pub show_help: bool,
+}
+
+fn take_value(args: &[String], i: &mut usize, flag: &str, inline_value: &Option<String>) -> Result<String, String> {
+	if let Some(value) = inline_value {
+		return Ok(value.clone());
+	}
+	*i += 1;
+	args.get(*i).cloned().ok_or(format!("Missing value for {}", flag))
+}
+
+/**
+ * Parses the arguments (without the executable name). Returns a message describing the problem on error.
+ */
+pub fn parse_args(args: &[String]) -> Result<StartupArgs, String> {
+	let mut startup_args = StartupArgs::default();
+	let mut i = 0;
+	while i < args.len() {
+		let arg = &args[i];
+		/* Both "--volume 0.5" and "--volume=0.5" work */
+		let (flag, inline_value) = match arg.split_once('=') {
+			Some((flag, value)) if arg.starts_with("--") => (flag, Some(value.to_string())),
+			_ => (arg.as_str(), None),
+		};
+		match flag {
+			"--tui" => startup_args.tui = true,
+			"--shuffle" => startup_args.shuffle = true,
+			"--no-install" => startup_args.no_install = true,
+			"--help" | "-h" => startup_args.show_help = true,
+			"--volume" => {
+				let value = take_value(args, &mut i, flag, &inline_value)?;
+				match value.parse::<f32>() {
+					Ok(volume) if (0.0..=1.0).contains(&volume) => startup_args.volume = Some(volume),
+					_ => return Err(format!("Invalid volume {} (expected a number from 0.0 to 1.0)", value)),
+				}
+			},
+			"--data-dir" => {
+				startup_args.data_dir = Some(crate::remote::absolute_path(&take_value(args, &mut i, flag, &inline_value)?));
+			},
+			"ctl" if startup_args.open_path.is_none() => {
+				startup_args.ctl_args = Some(args[i + 1..].to_vec());
+				break;
+			},
+			_ => {
+				if flag.starts_with("--") {
+					return Err(format!("Unknown option {}", flag));
+				}
+				if startup_args.open_path.is_some() {
+					return Err(format!("Only one song or folder can be opened, got {}", arg));
+				}
+				startup_args.open_path = Some(crate::remote::absolute_path(arg));
+			},
+		}
+		i += 1;
+	}
+	Ok(startup_args)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(args: &[&str]) -> Result<StartupArgs, String> {
+		let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+		parse_args(&args)
+	}
+
+	#[test]
+	fn parses_flags() {
+		let args = parse(&["--tui", "--shuffle", "--no-install", "--volume", "0.5"]).unwrap();
+		assert!(args.tui && args.shuffle && args.no_install && !args.show_help);
+		assert_eq!(args.volume, Some(0.5));
+		assert!(args.open_path.is_none() && args.ctl_args.is_none());
+
+		let args = parse(&["--volume=1", "-h"]).unwrap();
+		assert_eq!(args.volume, Some(1.0));
+		assert!(args.show_help);
+	}
+
+	#[test]
+	fn paths_are_made_absolute() {
+		let args = parse(&["/music/song.mp3", "--data-dir=/tmp/profile"]).unwrap();
+		assert_eq!(args.open_path.as_deref(), Some("/music/song.mp3"));
+		assert_eq!(args.data_dir.as_deref(), Some("/tmp/profile"));
+
+		/* Tests run from the crate folder */
+		let args = parse(&["src/args.rs"]).unwrap();
+		let open_path = args.open_path.unwrap();
+		assert!(std::path::Path::new(&open_path).is_absolute());
+		assert!(open_path.ends_with("args.rs"));
+	}
+
+	#[test]
+	fn ctl_takes_the_rest_of_the_arguments() {
+		let args = parse(&["--data-dir", "/tmp/profile", "ctl", "seek", "+10", "--json"]).unwrap();
+		assert_eq!(args.ctl_args, Some(vec!["seek".to_string(), "+10".to_string(), "--json".to_string()]));
+		assert_eq!(args.data_dir.as_deref(), Some("/tmp/profile"));
+
+		/* A song that happens to be called ctl */
+		let args = parse(&["/music/a.mp3", "ctl"]);
+		assert!(args.is_err());
+	}
+
+	#[test]
+	fn rejects_bad_arguments() {
+		assert!(parse(&["--volume", "2"]).is_err());
+		assert!(parse(&["--volume", "loud"]).is_err());
+		assert!(parse(&["--volume"]).is_err());
+		assert!(parse(&["--data-dir"]).is_err());
+		assert!(parse(&["--colour"]).is_err());
+		assert!(parse(&["a.mp3", "b.mp3"]).is_err());
+	}
+}
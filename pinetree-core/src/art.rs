@@ -0,0 +1,302 @@
+/*
+ * Pinetree cover art. Finds the picture that goes with a song: the one embedded in the file, or else an image
+ * next to it like cover.jpg or folder.png.
+ *
+ * Embedded pictures are read from ID3 APIC (PIC in ID3v2.2) frames, FLAC PICTURE blocks (the same layout
+ * METADATA_BLOCK_PICTURE uses in Ogg comments) and the covr atom of MP4 files. Only the encoded image bytes are
+ * returned, decoding them is up to the front-end.
+ */
+
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::files;
+use crate::tags;
+
+/* Folder images that count as cover art, in the order they're preferred. Matched without caring about case */
+pub const COVER_FILE_NAMES: [&str; 8] = ["cover.jpg", "cover.jpeg", "cover.png", "folder.jpg", "folder.jpeg", "folder.png", "front.jpg", "front.png"];
+/* APIC picture type of the front cover, preferred over any other picture in the file */
+const FRONT_COVER: u8 = 3;
+
+/**
+ * Length of a null terminated ID3 string at the start of the bytes, including the terminator.
+ * UTF-16 strings end in two zero bytes on a character boundary.
+ */
+fn terminated_len(encoding: u8, bytes: &[u8]) -> Option<usize> {
+	if encoding == 1 || encoding == 2 {
+		bytes.chunks_exact(2).position(|pair| pair == [0, 0]).map(|pair| pair * 2 + 2)
+	} else {
+		bytes.iter().position(|byte| *byte == 0).map(|end| end + 1)
+	}
+}
+
+/**
+ * Picture type and image bytes of an APIC (or ID3v2.2 PIC) frame.
+ */
+pub fn parse_apic(data: &[u8], version: u8) -> Option<(u8, &[u8])> {
+	let encoding = *data.first()?;
+	/* ID3v2.2 has a three letter image format instead of a MIME type */
+	let mime_end = if version == 2 {4} else {1 + data.get(1..)?.iter().position(|byte| *byte == 0)? + 1};
+	let picture_type = *data.get(mime_end)?;
+	let description = data.get(mime_end + 1..)?;
+	let picture = &description[terminated_len(encoding, description)?..];
+	if picture.is_empty() {None} else {Some((picture_type, picture))}
+}
+
+fn id3_picture(song: &str) -> Option<Vec<u8>> {
+	let tag = tags::read_id3_frames(song)?;
+	let pictures: Vec<(u8, &[u8])> = tag.frames.iter()
+		.filter(|frame| frame.id == "APIC" || frame.id == "PIC")
+		.filter_map(|frame| parse_apic(&frame.data, tag.version))
+		.collect();
+	pictures.iter()
+		.find(|(picture_type, _)| *picture_type == FRONT_COVER)
+		.or(pictures.first())
+		.map(|(_, picture)| picture.to_vec())
+}
+
+fn read_u32(bytes: &[u8], position: usize) -> Option<usize> {
+	let bytes = bytes.get(position..position + 4)?;
+	Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/**
+ * Picture type and image bytes of a FLAC PICTURE block. This is also what a (base64 decoded)
+ * METADATA_BLOCK_PICTURE comment holds.
+ */
+pub fn parse_flac_picture(block: &[u8]) -> Option<(u8, &[u8])> {
+	let picture_type = read_u32(block, 0)?;
+	let mime_len = read_u32(block, 4)?;
+	let description_len = read_u32(block, 8 + mime_len)?;
+	/* Width, height, colour depth and number of colours come before the data length */
+	let data_len_at = 12 + mime_len + description_len + 16;
+	let data_len = read_u32(block, data_len_at)?;
+	let picture = block.get(data_len_at + 4..data_len_at + 4 + data_len)?;
+	Some((picture_type.min(u8::MAX as usize) as u8, picture))
+}
+
+fn flac_picture(song: &str) -> Option<Vec<u8>> {
+	let mut file = std::fs::File::open(song).ok()?;
+	let mut magic = [0u8; 4];
+	file.read_exact(&mut magic).ok()?;
+	if &magic != b"fLaC" {
+		return None;
+	}
+	let mut pictures = Vec::<(u8, Vec<u8>)>::new();
+	loop {
+		let mut header = [0u8; 4];
+		file.read_exact(&mut header).ok()?;
+		let last = header[0] & 0x80 != 0;
+		let block_type = header[0] & 0x7F;
+		let block_len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+		let mut block = vec![0u8; block_len];
+		file.read_exact(&mut block).ok()?;
+		if block_type == 6 && let Some((picture_type, picture)) = parse_flac_picture(&block) {
+			pictures.push((picture_type, picture.to_vec()));
+		}
+		if last {
+			break;
+		}
+	}
+	let front = pictures.iter().position(|(picture_type, _)| *picture_type == FRONT_COVER).unwrap_or(0);
+	if pictures.is_empty() {None} else {Some(pictures.swap_remove(front).1)}
+}
+
+/**
+ * Finds the first atom with the given name among the atoms in the bytes.
+ */
+fn find_atom<'a>(bytes: &'a [u8], name: &[u8]) -> Option<&'a [u8]> {
+	let mut position = 0;
+	while position + 8 <= bytes.len() {
+		let size = read_u32(bytes, position)?;
+		if size < 8 || position + size > bytes.len() {
+			return None;
+		}
+		if &bytes[position + 4..position + 8] == name {
+			return Some(&bytes[position + 8..position + size]);
+		}
+		position += size;
+	}
+	None
+}
+
+/**
+ * The image in the moov/udta/meta/ilst/covr atom of an MP4 (m4a, m4b) file.
+ */
+pub fn parse_mp4_cover(file: &[u8]) -> Option<&[u8]> {
+	let udta = find_atom(find_atom(file, b"moov")?, b"udta")?;
+	/* meta is a "full atom", with a version and flags in front of its children */
+	let meta = find_atom(udta, b"meta")?.get(4..)?;
+	let covr = find_atom(find_atom(meta, b"ilst")?, b"covr")?;
+	/* The data atom starts with a type and a locale */
+	find_atom(covr, b"data")?.get(8..).filter(|picture| !picture.is_empty())
+}
+
+/**
+ * Reads the moov atom of an MP4 file, with a plain 8 byte header. The atoms around it (the audio in mdat can be
+ * hundreds of MB in an audiobook) are skipped over without reading them.
+ */
+fn read_moov(file: &mut std::fs::File) -> Option<Vec<u8>> {
+	let file_len = file.metadata().ok()?.len();
+	loop {
+		let start = file.stream_position().ok()?;
+		let mut header = [0u8; 8];
+		file.read_exact(&mut header).ok()?;
+		let (size, header_len) = match u32::from_be_bytes([header[0], header[1], header[2], header[3]]) {
+			/* The real size is in the 8 bytes after the name */
+			1 => {
+				let mut large_size = [0u8; 8];
+				file.read_exact(&mut large_size).ok()?;
+				(u64::from_be_bytes(large_size), 16)
+			},
+			/* The atom goes on to the end of the file */
+			0 => (file_len.saturating_sub(start), 8),
+			size => (size as u64, 8),
+		};
+		if size < header_len {
+			return None;
+		}
+		let body_len = size - header_len;
+		if &header[4..8] == b"moov" {
+			let mut moov = u32::try_from(body_len + 8).ok()?.to_be_bytes().to_vec();
+			moov.extend_from_slice(b"moov");
+			/* take() so a broken size doesn't get a huge buffer allocated up front */
+			file.take(body_len).read_to_end(&mut moov).ok()?;
+			return if moov.len() as u64 == body_len + 8 {Some(moov)} else {None};
+		}
+		file.seek(SeekFrom::Current(i64::try_from(body_len).ok()?)).ok()?;
+	}
+}
+
+fn mp4_picture(song: &str) -> Option<Vec<u8>> {
+	let mut file = std::fs::File::open(song).ok()?;
+	let mut header = [0u8; 8];
+	file.read_exact(&mut header).ok()?;
+	if &header[4..8] != b"ftyp" {
+		return None;
+	}
+	file.rewind().ok()?;
+	let moov = read_moov(&mut file)?;
+	parse_mp4_cover(&moov).map(|picture| picture.to_vec())
+}
+
+/**
+ * The first of the cover file names that exists in the folder.
+ */
+pub fn folder_cover(folder: &str) -> Option<String> {
+	let names: Vec<String> = std::fs::read_dir(folder).ok()?
+		.filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+		.collect();
+	COVER_FILE_NAMES.iter()
+		.find_map(|cover| names.iter().find(|name| name.eq_ignore_ascii_case(cover)))
+		.map(|name| files::build_full_filepath(folder, name))
+}
+
+/**
+ * The encoded cover art of a song (usually JPEG or PNG), None if it has none.
+ */
+pub fn find_cover_art(song: &str) -> Option<Vec<u8>> {
+	if let Some(picture) = id3_picture(song).or_else(|| flac_picture(song)).or_else(|| mp4_picture(song)) {
+		return Some(picture);
+	}
+	let folder = std::path::Path::new(song).parent()?.to_str()?;
+	std::fs::read(folder_cover(folder)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn atom(name: &[u8], body: &[u8]) -> Vec<u8> {
+		let mut bytes = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+		bytes.extend_from_slice(name);
+		bytes.extend_from_slice(body);
+		bytes
+	}
+
+	#[test]
+	fn reads_apic_frames() {
+		let mut frame = vec![0];
+		frame.extend_from_slice(b"image/png\0");
+		frame.push(FRONT_COVER);
+		frame.extend_from_slice(b"Cover\0PNGDATA");
+		assert_eq!(parse_apic(&frame, 3), Some((FRONT_COVER, &b"PNGDATA"[..])));
+
+		/* UTF-16 description, its terminator is two bytes */
+		let mut frame = vec![1];
+		frame.extend_from_slice(b"image/jpeg\0");
+		frame.push(0);
+		frame.extend_from_slice(&[0xFF, 0xFE, b'A', 0, 0, 0, 0, 0xD8]);
+		assert_eq!(parse_apic(&frame, 4), Some((0, &[0, 0xD8][..])));
+
+		let mut frame = vec![0];
+		frame.extend_from_slice(b"JPG");
+		frame.push(FRONT_COVER);
+		frame.extend_from_slice(b"\0JPEGDATA");
+		assert_eq!(parse_apic(&frame, 2), Some((FRONT_COVER, &b"JPEGDATA"[..])));
+		assert_eq!(parse_apic(&[0, b'x'], 3), None);
+	}
+
+	#[test]
+	fn reads_flac_and_mp4_pictures() {
+		let mut block = (FRONT_COVER as u32).to_be_bytes().to_vec();
+		block.extend_from_slice(&9u32.to_be_bytes());
+		block.extend_from_slice(b"image/png");
+		block.extend_from_slice(&0u32.to_be_bytes());
+		block.extend_from_slice(&[0; 16]);
+		block.extend_from_slice(&4u32.to_be_bytes());
+		block.extend_from_slice(b"FLAC");
+		assert_eq!(parse_flac_picture(&block), Some((FRONT_COVER, &b"FLAC"[..])));
+
+		let mut file = b"fLaC".to_vec();
+		file.push(0x80 | 6);
+		file.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+		file.extend_from_slice(&block);
+		let path = temp_file_path("cover.flac");
+		std::fs::write(&path, file).unwrap();
+		let picture = find_cover_art(&path);
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(picture.as_deref(), Some(&b"FLAC"[..]));
+
+		let data = atom(b"data", b"\0\0\0\x0d\0\0\0\0MP4ART");
+		let mut meta_body = vec![0; 4];
+		meta_body.extend_from_slice(&atom(b"hdlr", &[0; 8]));
+		meta_body.extend_from_slice(&atom(b"ilst", &atom(b"covr", &data)));
+		let mut mp4 = atom(b"ftyp", b"M4A ");
+		let moov = atom(b"moov", &atom(b"udta", &atom(b"meta", &meta_body)));
+		let mut in_memory = mp4.clone();
+		in_memory.extend_from_slice(&moov);
+		assert_eq!(parse_mp4_cover(&in_memory), Some(&b"MP4ART"[..]));
+
+		/* From a file, with the audio in front of moov in an atom with a 64 bit size */
+		let audio = [0xAAu8; 1000];
+		mp4.extend_from_slice(&1u32.to_be_bytes());
+		mp4.extend_from_slice(b"mdat");
+		mp4.extend_from_slice(&(audio.len() as u64 + 16).to_be_bytes());
+		mp4.extend_from_slice(&audio);
+		mp4.extend_from_slice(&moov);
+		let path = temp_file_path("cover.m4b");
+		std::fs::write(&path, &mp4).unwrap();
+		let picture = find_cover_art(&path);
+		/* A moov cut off by the end of the file has no cover */
+		std::fs::write(&path, &mp4[..mp4.len() - 4]).unwrap();
+		let cut_off = mp4_picture(&path);
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(picture.as_deref(), Some(&b"MP4ART"[..]));
+		assert_eq!(cut_off, None);
+	}
+
+	#[test]
+	fn falls_back_to_folder_images() {
+		let folder = temp_file_path("cover-folder");
+		std::fs::create_dir_all(&folder).unwrap();
+		let song = files::build_full_filepath(&folder, "song.mp3");
+		std::fs::write(&song, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+		assert_eq!(find_cover_art(&song), None);
+		std::fs::write(files::build_full_filepath(&folder, "Folder.PNG"), b"folder").unwrap();
+		std::fs::write(files::build_full_filepath(&folder, "cover.jpg"), b"cover").unwrap();
+		let picture = find_cover_art(&song);
+		let _ = std::fs::remove_dir_all(&folder);
+		assert_eq!(picture.as_deref(), Some(&b"cover"[..]));
+	}
+}
@@ -0,0 +1,1233 @@
+use rodio;
+use std::{time, u128};
+
+use crate::files;
+use crate::library;
+use crate::record;
+use crate::resume;
+use crate::scrobble;
+use crate::visualiser;
+
+/* Exists because rodio is terrible */
+use mp3_duration;
+
+
+#[derive(PartialEq)]
+pub enum LoopBehavior {
+	Stop,
+	Loop,
+	Shuffle,
+	Next,
+}
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum PrevBehavior {
+	History,
+	Above,
+}
+
+#[derive(Clone)]
+pub struct RodioData {
+	pub playback_position: usize,
+	pub song_length: usize,
+	pub is_paused: bool,
+	pub song_name: String,
+	pub error_message: Option<String>,
+	pub shuffle_memory: usize,
+	/* The actual sink volume, after volume_curve */
+	pub volume: f32,
+	/* Changes on its own while an A-B loop speeds up */
+	pub speed: f32,
+	/* The A-B loop of the current song, if there is one */
+	pub ab_loop: Option<ABLoop>,
+	pub sleep_timer: Option<SleepTimerStatus>,
+}
+
+/**
+* A region of the current song that gets repeated, for practising. Positions are in milliseconds.
+*/
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct ABLoop {
+	pub start_ms: usize,
+	pub end_ms: usize,
+	/* Silence before every repeat, 0 for none */
+	pub count_in_ms: u64,
+	/* Added to the speed after every repeat until target_speed is reached, 0 to keep the speed */
+	pub speed_step: f32,
+	pub target_speed: f32,
+}
+
+/**
+* When the sleep timer pauses playback.
+*/
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum SleepTimer {
+	/* Milliseconds from when it was set */
+	AfterTime(u64),
+	/* At the end of this many songs, the current one counts as the first */
+	AfterSongs(usize),
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct SleepTimerStatus {
+	/* What is left: the time until it stops, or the songs until it stops */
+	pub remaining: SleepTimer,
+	pub fading: bool,
+}
+
+pub const DEFAULT_SLEEP_FADE_SECS: u64 = 10;
+
+struct ActiveSleepTimer {
+	deadline: Option<SystemTime>,
+	songs_left: usize,
+	fade_ms: u64,
+	fading: bool,
+}
+
+/* Songs get paused this long before their end, after that it's too late to catch them before the next song starts */
+const SLEEP_TIMER_SONG_END_MARGIN_MS: u64 = 300;
+/* How often the fade gain gets worked out while fading out, and how often the song picks it up */
+const FADE_STEP: Duration = Duration::from_millis(50);
+const FADE_ACCESS_PERIOD: Duration = Duration::from_millis(5);
+
+/**
+* Volume multiplier while the sleep timer fades out. Goes linearly from 1 at the start of the fade to 0 when it stops.
+*/
+pub fn fade_gain(remaining_ms: u64, fade_ms: u64) -> f32 {
+	if fade_ms == 0 {
+		return 1.0;
+	}
+	(remaining_ms as f32 / fade_ms as f32).clamp(0.0, 1.0)
+}
+
+/**
+* Speed for the next repeat of an A-B loop. Rounded so the steps don't drift away from e.g. exactly 1.0.
+*/
+pub fn next_loop_speed(ab_loop: &ABLoop, speed: f32) -> f32 {
+	if ab_loop.speed_step <= 0.0 || speed >= ab_loop.target_speed {
+		return speed;
+	}
+	((speed + ab_loop.speed_step).min(ab_loop.target_speed) * 100.0).round() / 100.0
+}
+
+pub struct PlaylistTreeElement {
+	// None = it's a playlist
+	pub song_name: Option<String>,
+	pub playlist_position: usize,
+}
+
+pub fn request_rodio_data(send_pair: &std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>, recv_pair: &std::sync::Arc<(std::sync::Mutex<Vec<RodioData>>, std::sync::Condvar)>) -> RodioData {
+	let lock = &recv_pair.0;
+	let cvar = &recv_pair.1;
+	send_audio_signal(send_pair, MessageToAudio::RequestRodioData);
+
+	if let Ok(mut vec) = lock.lock() {
+		loop {
+			while vec.len() == 0 {
+				vec = cvar.wait(vec).unwrap();
+			}
+			if let Some(element) = vec.pop() {
+				return element;
+			}
+		}
+	}
+	return RodioData {
+		playback_position: 0,
+		song_length: 0,
+		song_name: "".to_string(),
+		is_paused: false,
+		error_message: None,
+		shuffle_memory: 0,
+		volume: 0.0,
+		speed: DEFAULT_SPEED,
+		ab_loop: None,
+		sleep_timer: None,
+	};
+	// vec = if let Ok(mut vec) 
+}
+
+pub fn send_audio_signal(pair: &std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>, message: MessageToAudio) {
+	let lock = &pair.0;
+	let cvar = &pair.1;
+	if let Ok(mut data) = lock.lock() {
+		data.push(message);
+		cvar.notify_one();
+	}
+}
+
+/**
+* Plays a song with the rest of its folder as the song collection, so next and previous go on from it. For songs that
+* come from outside (the command line, the remote control) rather than from an open folder or playlist.
+*/
+pub fn play_song_in_folder(pair: &std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>, song: &str) {
+	let folder = std::path::Path::new(song).parent().and_then(|folder| folder.to_str()).unwrap_or("");
+	let mut dir_map = std::collections::HashMap::<String, files::Directory>::new();
+	if files::init_directory_at_filepath(folder, &mut dir_map) && let Some(directory) = dir_map.remove(folder) {
+		let index = directory.songs.iter().position(|folder_song| folder_song == song);
+		send_audio_signal(pair, MessageToAudio::SetSongCollection(directory.songs, index));
+	}
+	send_audio_signal(pair, MessageToAudio::PlaySong(song.to_string()));
+}
+
+#[derive(PartialEq)]
+pub enum MessageToAudio {
+	// None,
+	PlaySong(String),
+	UpdateEndBehavior(LoopBehavior),
+	UpdateVolume(f32),
+	UpdateSpeed(f32),
+	RequestRodioData,
+	Seek(usize),
+	SeekStop,
+	/* A seek that is over right away, like Seek followed by SeekStop */
+	SeekOnce(usize),
+	TogglePause,
+	SongEnd,
+	/**
+	* Tells the audio thread to set its current collection to Vec<String>,
+	* and that the current playing song is at the optional location
+	*/
+	SetSongCollection(Vec<String>, Option<usize>),
+	ClearError,
+	PreviousSong,
+	UpdateShuffleMemory(usize),
+	UpdatePrevBehavior(PrevBehavior),
+	/* Loops a region of the song that is playing right now. Dropped when another song starts */
+	SetABLoop(Option<ABLoop>),
+	UpdateResumeSettings(resume::ResumeSettings),
+	/* Starts (or with None, cancels) the sleep timer. The number is how long the fade out takes in milliseconds */
+	SetSleepTimer(Option<SleepTimer>, u64),
+}
+
+struct EndCallback {
+    callback: Option<Box<dyn FnOnce() + Send>>,
+}
+
+impl Iterator for EndCallback {
+    type Item = f32;
+    fn next(&mut self) -> Option<f32> {
+		if let Some(cb) = self.callback.take() {
+            cb();
+        }
+        None
+    }
+}
+
+impl Source for EndCallback {
+    fn current_frame_len(&self) -> Option<usize> { None }
+    fn channels(&self) -> u16 { 2 }
+    fn sample_rate(&self) -> u32 { 44100 }
+    fn total_duration(&self) -> Option<Duration> { Some(Duration::ZERO) }
+}
+
+/**
+* Keeps track of how long the current song has actually been listened to (pauses and seeking don't count)
+*/
+struct ListeningSession {
+	song: String,
+	/* Unix time the song started */
+	started_at: u64,
+	listened_ms: u64,
+	last_tick: SystemTime,
+	playing: bool,
+}
+
+struct AudioThreadData {
+	// This has to exist even if unused, otherwise the lifetime causes the program to crash
+	// (None when running on an idle sink)
+	_stream: Option<rodio::OutputStream>,
+	sink: rodio::Sink,
+	volume: f32,
+	speed: f32,
+	end_behavior: LoopBehavior,
+	library: std::sync::Arc<std::sync::Mutex<library::Library>>,
+	listening_session: Option<ListeningSession>,
+	/* Plays for record_plays_loop to add to the library */
+	play_events: std::sync::Arc<(std::sync::Mutex<Vec<PlayEvent>>, std::sync::Condvar)>,
+	sample_tap: std::sync::Arc<visualiser::SampleTap>,
+	resume_settings: resume::ResumeSettings,
+	sleep_timer: Option<ActiveSleepTimer>,
+	/* Gain of the sleep timer fade as the bits of an f32, the song's source applies it on top of the volume */
+	sleep_fade: std::sync::Arc<std::sync::atomic::AtomicU32>,
+}
+
+fn set_sleep_fade(audio_thread_data: &AudioThreadData, gain: f32) {
+	audio_thread_data.sleep_fade.store(gain.to_bits(), std::sync::atomic::Ordering::Relaxed);
+}
+
+/**
+* Adds the time since the last tick to the listening session if the song was playing during it.
+* Called around every message, since those are the only points where the playing state can change.
+*/
+fn tick_listening_session(audio_thread_data: &mut AudioThreadData) {
+	if let Some(session) = &mut audio_thread_data.listening_session {
+		let now = SystemTime::now();
+		if session.playing && let Ok(elapsed) = now.duration_since(session.last_tick) {
+			session.listened_ms += elapsed.as_millis() as u64;
+		}
+		session.last_tick = now;
+		session.playing = !audio_thread_data.sink.is_paused() && !audio_thread_data.sink.empty();
+	}
+}
+
+/**
+* What the audio thread hands to record_plays_loop. Recording a play reads tags, works out song lengths and saves the
+* library, none of which the audio thread should wait on.
+*/
+enum PlayEvent {
+	Start(String),
+	Finish(ListeningSession),
+}
+
+/* How long no plays have to come in before the library gets saved, so skipping through songs saves once */
+const LIBRARY_SAVE_DELAY: Duration = Duration::from_secs(2);
+
+fn send_play_event(audio_thread_data: &AudioThreadData, event: PlayEvent) {
+	let (lock, cvar) = &*audio_thread_data.play_events;
+	if let Ok(mut events) = lock.lock() {
+		events.push(event);
+		cvar.notify_one();
+	}
+}
+
+fn record_play_event(event: PlayEvent, library: &std::sync::Mutex<library::Library>, scrobbler: &std::sync::Mutex<scrobble::Scrobbler>) {
+	match event {
+		PlayEvent::Start(song) => {
+			if let Ok(mut library) = library.lock() {
+				library::record_play_start(&mut library, &song);
+			}
+		},
+		PlayEvent::Finish(session) => {
+			let known_length = library.lock().ok().and_then(|library| library.songs.get(&session.song).map(|record| record.length_ms)).unwrap_or(0);
+			let song_length = if known_length > 0 {known_length} else {get_song_len_ms(&session.song) as u64};
+			let mut listen: Option<scrobble::Listen> = None;
+			if let Ok(mut library) = library.lock() {
+				library::record_play_finish(&mut library, &session.song, session.listened_ms, song_length);
+				if scrobble::should_scrobble(session.listened_ms, song_length) {
+					let record = library::get_or_create_record(&mut library, &session.song);
+					listen = Some(scrobble::Listen {
+						title: library::display_title(&session.song, record),
+						artist: record.artist.clone(),
+						album: record.album.clone(),
+						track: record.track.clone(),
+						duration_ms: song_length,
+						listened_at: session.started_at,
+					});
+				}
+			}
+			if let Some(listen) = listen
+			&& let Ok(mut scrobbler) = scrobbler.lock()
+			&& scrobble::record_listen(&mut scrobbler, &listen).is_err() {
+				println!("Error in writing scrobble log");
+			}
+		},
+	}
+}
+
+/**
+* Records the plays the audio thread sends, and saves the library once they stop coming for a bit.
+* The library is only locked while it gets turned into text, not while the file is written.
+*/
+fn record_plays_loop(
+	play_events: std::sync::Arc<(std::sync::Mutex<Vec<PlayEvent>>, std::sync::Condvar)>,
+	library: std::sync::Arc<std::sync::Mutex<library::Library>>,
+	scrobbler: std::sync::Arc<std::sync::Mutex<scrobble::Scrobbler>>,
+) {
+	let (lock, cvar) = &*play_events;
+	let mut events = lock.lock().unwrap();
+	loop {
+		while events.is_empty() {
+			events = cvar.wait(events).unwrap();
+		}
+		while !events.is_empty() {
+			let batch: Vec<PlayEvent> = events.drain(..).collect();
+			drop(events);
+			for event in batch {
+				record_play_event(event, &library, &scrobbler);
+			}
+			events = cvar.wait_timeout(lock.lock().unwrap(), LIBRARY_SAVE_DELAY).unwrap().0;
+		}
+		let contents = library.lock().ok().and_then(|library| Some((library.file_path.clone()?, library::library_to_string(&library).ok()?)));
+		if let Some((path, contents)) = contents && library::write_library_contents(&path, &contents).is_err() {
+			println!("Error in saving library");
+		}
+	}
+}
+
+fn end_listening_session(audio_thread_data: &mut AudioThreadData) {
+	tick_listening_session(audio_thread_data);
+	if let Some(session) = audio_thread_data.listening_session.take() {
+		send_play_event(audio_thread_data, PlayEvent::Finish(session));
+	}
+}
+
+fn begin_listening_session(audio_thread_data: &mut AudioThreadData, song: &str) {
+	end_listening_session(audio_thread_data);
+	send_play_event(audio_thread_data, PlayEvent::Start(song.to_string()));
+	audio_thread_data.listening_session = Some(ListeningSession {
+		song: song.to_string(),
+		started_at: library::unix_time_now(),
+		listened_ms: 0,
+		last_tick: SystemTime::now(),
+		playing: true,
+	});
+}
+
+use rodio::Source;
+use std::time::{Duration, SystemTime};
+
+/**
+ * Defaults to 0
+ */
+pub fn get_song_len_ms(file_path: &str) -> usize {
+	if let Ok(len) = mp3_duration::from_path(&file_path) {
+		return len.as_millis() as usize;
+	}
+	return 0;
+}
+
+pub fn audio_thread_play_song(file_path: &str,
+	sink: &mut rodio::Sink,
+	recieve_pair: &std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>,
+	sample_tap: &std::sync::Arc<visualiser::SampleTap>,
+	sleep_fade: &std::sync::Arc<std::sync::atomic::AtomicU32>) -> Option<String> {
+	let mut return_value = None;
+	if let Ok(file) = std::fs::File::open(&file_path) {
+		let reader = std::io::BufReader::<std::fs::File>::new(file);
+		
+		if let Ok(elem) = rodio::Decoder::new_mp3(reader) {
+			sink.clear();
+			let _ = sink.try_seek(std::time::Duration::from_millis(0));
+			
+			/* The sleep timer fades the song out with an amplify stage it keeps turning down */
+			let fade = std::sync::Arc::clone(sleep_fade);
+			sink.append(visualiser::tap_source(elem, std::sync::Arc::clone(sample_tap))
+				.amplify(1.0)
+				.periodic_access(FADE_ACCESS_PERIOD, move |src| src.set_factor(f32::from_bits(fade.load(std::sync::atomic::Ordering::Relaxed)))));
+
+			let rodio_pair = std::sync::Arc::clone(recieve_pair);
+			sink.append(EndCallback {
+				callback: Some(Box::new(move || {
+					song_end_callback(rodio_pair);
+				})),
+			});
+		} else {
+			return_value = Some(format!("Error: Invalid mp3 format {}", file_path));
+		}
+	} else {
+		return_value = Some(format!("Error: failed to open mp3 file {}", file_path));
+	}
+	sink.play();
+	return return_value;
+}
+
+pub const DEFAULT_VOLUME: f32 = 0.75;
+pub const DEFAULT_SPEED: f32 = 1.0;
+/* How often the position of a resumed file gets saved while it plays */
+const REMEMBER_POSITION_INTERVAL_MS: usize = 10000;
+
+pub fn clone_loop_behavior(behavior: &LoopBehavior) -> LoopBehavior {
+	return match *behavior {
+		LoopBehavior::Stop => LoopBehavior::Stop,
+		LoopBehavior::Loop => LoopBehavior::Loop,
+		LoopBehavior::Shuffle => LoopBehavior::Shuffle,
+		LoopBehavior::Next => LoopBehavior::Next,
+	}
+}
+
+/**
+* The order the on-finish behavior cycles through when it gets toggled from the keyboard
+*/
+pub fn next_loop_behavior(loop_behavior: &LoopBehavior) -> LoopBehavior {
+	match loop_behavior {
+		LoopBehavior::Stop => LoopBehavior::Loop,
+		LoopBehavior::Loop => LoopBehavior::Next,
+		LoopBehavior::Next => LoopBehavior::Shuffle,
+		LoopBehavior::Shuffle => LoopBehavior::Stop,
+	}
+}
+
+pub fn song_end_callback(pair: std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>) {
+	send_audio_signal(&pair, MessageToAudio::SongEnd);
+}
+
+pub fn generate_random_number(random_seed: u128) -> u128 {
+	let aff = random_seed.wrapping_mul(928594379).wrapping_add(531881627);
+	// This XOR operation is necessary because the low bits have low entropy.
+	// The higher bits are highly random though, so mixing them in with the lower bits gives more randomness
+	let xor_1 = aff ^ (aff >> 12);
+	let xor_2 = xor_1 ^ (xor_1 >> 23);
+	let xor_3 = xor_2 ^ (xor_2 >> 50);
+	let xor_4 = xor_3 ^ (xor_3 >> 73);
+	let xor_5 = xor_4 ^ (xor_4 >> 97);
+
+	return xor_5;
+}
+
+pub fn initialize_random_seed() -> u128 {
+	let start = std::time::SystemTime::now();
+	let since_the_epoch = start
+		.duration_since(std::time::UNIX_EPOCH)
+		.expect("time should go forward");
+	let mut random_seed: u128 = since_the_epoch.as_micros();
+
+	for _ in 0..50 {
+		random_seed = generate_random_number(random_seed);
+	}
+
+	return random_seed;
+}
+
+struct SongRingBuffer {
+	vec: Vec<String>,
+	front: usize,
+	back: usize,
+	current_element: usize,
+}
+
+fn new_ring_buffer(capacity: usize) -> SongRingBuffer {
+	let mut v = Vec::<String>::new();
+	for _ in 0..capacity {
+		v.push("".to_string());
+	}
+	return SongRingBuffer {
+		vec: v,
+		front: 0,
+		back: 0,
+		current_element: 0,
+	};
+}
+
+/*
+******XXXXXX*******
+      ^Back ^Front
+*/
+fn push_to_ring_buffer(buffer: &mut SongRingBuffer, element: &str) {
+	if ((buffer.front + 1) % buffer.vec.len()) == buffer.back {
+		buffer.back = (buffer.back + 1) % buffer.vec.len();
+	}
+	buffer.vec[buffer.front] = element.to_string();
+	buffer.front = (buffer.front + 1) % buffer.vec.len();
+}
+
+/**
+* Returns true if it successfully goes back to the previous song
+*/
+fn try_go_to_previous_song(buffer: &mut SongRingBuffer) -> bool {
+	if buffer.current_element == buffer.back {
+		return false;
+	} else {
+		let cap = buffer.vec.len();
+		buffer.current_element = (buffer.current_element + cap - 1) % cap;
+		return true;
+	}
+}
+fn _try_go_to_next_song(buffer: &mut SongRingBuffer) -> bool {
+	let cap = buffer.vec.len();
+	let max_el = ((buffer.front + cap) - 1) % cap;
+	if buffer.current_element == max_el {
+		return false;
+	} else {
+		buffer.current_element = (buffer.current_element + 1) % cap;
+		return true;
+	}
+}
+
+fn song_is_in_last_n(song: &String, history_buffer: &SongRingBuffer, count: usize) -> bool {
+	let mut found = false;
+
+	let cap = history_buffer.vec.len();
+	/* Just helps make sure we don't divide by zero */
+	if cap == 0 || history_buffer.front == history_buffer.back {
+		return false;
+	}
+	/* Front is where the next song goes, so the most recent one is right behind it */
+	let mut cur = (history_buffer.front + cap - 1) % cap;
+
+	for _ in 0..count {
+		if let Some(s) = history_buffer.vec.get(cur) {
+			if s == song {
+				found = true;
+				break;
+			}
+		}
+		
+		if cur == history_buffer.back {
+			break;
+		}
+		cur = (cur + cap - 1) % cap;
+	}
+
+	return found;
+}
+
+fn play_song(try_save_to_history: bool,
+	song: &str,
+	current_song: &mut String,
+	history_buffer: &mut SongRingBuffer,
+	audio_thread_data: &mut AudioThreadData,
+	recieve_pair: &std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>) -> Option<String>
+{
+	if try_save_to_history && song != *current_song {
+		push_to_ring_buffer(history_buffer, &song);
+		history_buffer.current_element = ((history_buffer.front + history_buffer.vec.len()) - 1) % history_buffer.vec.len();
+	}
+
+	{ /* Song playing */
+		let err = audio_thread_play_song(&song, &mut audio_thread_data.sink, recieve_pair, &audio_thread_data.sample_tap, &audio_thread_data.sleep_fade);
+		if err.is_none() {
+			*current_song = song.to_string();
+			begin_listening_session(audio_thread_data, song);
+		}
+		return err;
+	}
+}
+/**
+* Resets the timestamps for a song that just started. Long files continue where they were left off.
+*/
+fn update_timestamps(song: &str,
+	song_length: &mut usize,
+	current_timestamp: &mut u128,
+	saved_timestamp: &mut Option<SystemTime>,
+	audio_thread_data: &mut AudioThreadData)
+{
+	*song_length = get_song_len_ms(song);
+	let mut start_ms = 0;
+	if let Ok(mut library) = audio_thread_data.library.lock() {
+		/* The tags get read by record_plays_loop */
+		let record = library.songs.entry(song.to_string()).or_default();
+		record.length_ms = *song_length as u64;
+		start_ms = resume::resume_position(&audio_thread_data.resume_settings, song, record);
+	}
+	if start_ms > 0 {
+		let _ = audio_thread_data.sink.try_seek(std::time::Duration::from_millis((start_ms as f32 / audio_thread_data.speed) as u64));
+	}
+	*current_timestamp = start_ms as u128 * 1000;
+	*saved_timestamp = Some(time::SystemTime::now());
+
+	/* A song timer only counts songs that start, the last one should have been paused before its end */
+	if let Some(timer) = &mut audio_thread_data.sleep_timer && timer.deadline.is_none() {
+		if timer.songs_left <= 1 {
+			audio_thread_data.sink.pause();
+			set_sleep_fade(audio_thread_data, 1.0);
+			audio_thread_data.sleep_timer = None;
+			*saved_timestamp = None;
+		} else {
+			timer.songs_left -= 1;
+		}
+	}
+}
+
+/**
+* Saves where the current song is at, for songs that get resumed.
+*/
+fn remember_position(audio_thread_data: &AudioThreadData, song: &str, position_ms: usize) {
+	if song.is_empty() {
+		return;
+	}
+	if let Ok(mut library) = audio_thread_data.library.lock() {
+		resume::remember_position(&mut library, &audio_thread_data.resume_settings, song, position_ms);
+	}
+}
+
+
+/**
+* Human hearing is logarithmic, so the volume slider follows an exponential curve to compensate.
+*
+* Specifically this is one that is meant to very closely match the decibel scale (hence the magic number of 6.908).
+* Has to be clamped though unfortunately, because exponential curves never technically reach 0.
+*
+* This creates a sharp cutoff at the lowest volume, but at that point it's quiet enough I don't imagine people will notice.
+*
+* Surprised this isn't more common.
+*/
+pub fn volume_curve(input: f32) -> f32 {
+	if input <= -0.195 {return 0.0;}
+	return (input * 6.908).exp() / 1000.0
+}
+
+/**
+* Turns a sink volume back into a slider position, for when the volume gets changed from outside the GUI.
+*/
+pub fn inverse_volume_curve(volume: f32) -> f32 {
+	if volume <= 0.0 {return -0.2;}
+	((volume * 1000.0).ln() / 6.908).clamp(-0.2, 1.0)
+}
+
+/**
+* Audio thread maintains its own list it works on, going through each element in that during the "Next".
+* 
+* When a new song is played:
+* - GUI thread checks if the current directory tree is the same as the one the audio thread has
+* 	- If it isn't, send an updated one.
+* - Send the current song.
+* - Audio thread looks through to find the new index.
+*/
+pub fn audio_thread_loop(
+	recieve_pair: std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>,
+	send_pair: std::sync::Arc<(std::sync::Mutex<Vec<RodioData>>, std::sync::Condvar)>,
+	library: std::sync::Arc<std::sync::Mutex<library::Library>>,
+	scrobbler: std::sync::Arc<std::sync::Mutex<scrobble::Scrobbler>>,
+	sample_tap: std::sync::Arc<visualiser::SampleTap>,
+	record_tap: std::sync::Arc<record::RecordTap>,
+) {
+	let (output_stream, audio_sink) = rodio::OutputStream::try_default().unwrap();
+	/* Everything the sink plays goes past the recorder on its way to the sound card */
+	let (sink, queue) = rodio::Sink::new_idle();
+	audio_sink.play_raw(record::record_source(queue, record_tap)).unwrap();
+	audio_thread_loop_with_sink(Some(output_stream), sink, recieve_pair, send_pair, library, scrobbler, sample_tap);
+}
+
+/**
+* Runs the audio thread on the given sink. Playing through rodio::Sink::new_idle (and pulling the samples from its queue)
+* doesn't need a sound card, which is how the tests drive it.
+*/
+pub fn audio_thread_loop_with_sink(
+	output_stream: Option<rodio::OutputStream>,
+	sink: rodio::Sink,
+	recieve_pair: std::sync::Arc<(std::sync::Mutex<Vec<MessageToAudio>>, std::sync::Condvar)>,
+	send_pair: std::sync::Arc<(std::sync::Mutex<Vec<RodioData>>, std::sync::Condvar)>,
+	library: std::sync::Arc<std::sync::Mutex<library::Library>>,
+	scrobbler: std::sync::Arc<std::sync::Mutex<scrobble::Scrobbler>>,
+	sample_tap: std::sync::Arc<visualiser::SampleTap>,
+) {
+	let mut random_seed = initialize_random_seed();
+
+	let mut song_path = "".to_string();
+	let mut song_index = 0;
+	let mut song_length = 0;
+	let play_events = std::sync::Arc::new((std::sync::Mutex::new(Vec::new()), std::sync::Condvar::new()));
+	{
+		let play_events = std::sync::Arc::clone(&play_events);
+		let library = std::sync::Arc::clone(&library);
+		std::thread::spawn(move || record_plays_loop(play_events, library, scrobbler));
+	}
+	let mut audio_thread_data = AudioThreadData {
+		// This has to exist even if unused, otherwise the lifetime causes the program to crash
+		_stream: output_stream,
+		sink,
+		volume: volume_curve(DEFAULT_VOLUME),
+		speed: DEFAULT_SPEED,
+		end_behavior: LoopBehavior::Stop,
+		library,
+		listening_session: None,
+		play_events,
+		sample_tap,
+		resume_settings: resume::default_resume_settings(),
+		sleep_timer: None,
+		sleep_fade: std::sync::Arc::new(std::sync::atomic::AtomicU32::new(1.0f32.to_bits())),
+	};
+	audio_thread_data.sink.set_volume(audio_thread_data.volume);
+	let lock = &recieve_pair.0;
+	let cvar = &recieve_pair.1;
+	let mut data_vec = lock.lock().unwrap();
+	let mut current_songs_collection = Vec::<String>::new();
+	let mut saved_timestamp: Option<time::SystemTime> = None;
+	let mut current_timestamp: u128 = 0;
+	let mut song_play_err = None;
+	let mut seeking = false;
+	let mut paused_from_seeking = false;
+	let mut randomization_memory = 5;
+	let mut prev_behavior: PrevBehavior = PrevBehavior::Above;
+	/* The loop and the song it was set for */
+	let mut ab_loop: Option<(String, ABLoop)> = None;
+	/* Set while paused for the count-in before a repeat */
+	let mut count_in_until: Option<time::SystemTime> = None;
+	let mut last_remembered_ms: usize = 0;
+
+	let mut history_buffer = new_ring_buffer(255);
+
+	loop {
+		while let Some(data) = data_vec.pop() {
+			tick_listening_session(&mut audio_thread_data);
+			if let Some(ts) = saved_timestamp && let Ok(a) = ts.elapsed() {
+				current_timestamp += (a.as_micros() as f32 * audio_thread_data.speed) as u128;
+				saved_timestamp = Some(time::SystemTime::now());
+			}
+			/* Long files remember where they were left off, for when they get played again */
+			let position_ms = (current_timestamp / 1000) as usize;
+			let changes_song = matches!(data, MessageToAudio::PlaySong(_) | MessageToAudio::SongEnd | MessageToAudio::PreviousSong);
+			if changes_song || (matches!(data, MessageToAudio::TogglePause) && !audio_thread_data.sink.is_paused())
+			|| position_ms.abs_diff(last_remembered_ms) >= REMEMBER_POSITION_INTERVAL_MS {
+				remember_position(&audio_thread_data, &song_path, position_ms);
+				last_remembered_ms = position_ms;
+			}
+			match data {
+				// MessageToAudio::None => {println!("Do nothing");},
+				MessageToAudio::PlaySong(song) => {
+					song_play_err = play_song(true, &song, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+					if song_play_err.is_none() {
+						update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+
+						/* TODO: Avoid having to do this O(n) loop */
+						song_index = 0;
+						for i in 0..current_songs_collection.len() {
+							if let Some(e) = current_songs_collection.get(i) && *e == song_path {
+								song_index = i;
+								break;
+							}
+						}
+					}
+				},
+				MessageToAudio::UpdateEndBehavior(loop_behavior) => {
+					audio_thread_data.end_behavior = clone_loop_behavior(&loop_behavior);
+				},
+				MessageToAudio::UpdateVolume(volume) => {
+					audio_thread_data.volume = volume;
+					audio_thread_data.sink.set_volume(audio_thread_data.volume);
+				},
+				MessageToAudio::UpdateSpeed(speed) => {
+					if let Some(ts) = saved_timestamp && let Ok(a) = ts.elapsed() {
+						current_timestamp += (a.as_micros() as f32 * audio_thread_data.speed) as u128;
+						saved_timestamp = Some(time::SystemTime::now());
+					}
+					audio_thread_data.speed = speed;
+					audio_thread_data.sink.set_speed(speed);
+				},
+				MessageToAudio::RequestRodioData => {
+					if let Some(ts) = saved_timestamp && let Ok(a) = ts.elapsed() {
+						current_timestamp += (a.as_micros() as f32 * audio_thread_data.speed) as u128;
+						saved_timestamp = Some(time::SystemTime::now());
+					}
+
+					let send_lock = &send_pair.0;
+					let send_cvar = &send_pair.1;
+					let mut response_vec = send_lock.lock().unwrap();
+					response_vec.push(RodioData {
+						song_length: song_length,
+						playback_position: (current_timestamp / 1000) as usize,
+						is_paused: audio_thread_data.sink.is_paused(),
+						song_name: song_path.clone(),
+						error_message: song_play_err.clone(),
+						shuffle_memory: randomization_memory,
+						volume: audio_thread_data.volume,
+						speed: audio_thread_data.speed,
+						ab_loop: ab_loop.as_ref().filter(|(loop_song, _)| *loop_song == song_path).map(|(_, ab)| *ab),
+						sleep_timer: audio_thread_data.sleep_timer.as_ref().map(|timer| SleepTimerStatus {
+							remaining: if let Some(deadline) = timer.deadline {
+								SleepTimer::AfterTime(deadline.duration_since(time::SystemTime::now()).map(|left| left.as_millis() as u64).unwrap_or(0))
+							} else {
+								SleepTimer::AfterSongs(timer.songs_left)
+							},
+							fading: timer.fading,
+						}),
+					});
+					/* More than one thread can be waiting for data (e.g. the GUI and MPRIS) */
+					send_cvar.notify_all();
+				},
+				MessageToAudio::Seek(position) | MessageToAudio::SeekOnce(position) => {
+					/* Only a seek that goes on (dragging the seek bar) waits at the end of the song */
+					let dragging = matches!(data, MessageToAudio::Seek(_));
+					seeking = dragging;
+					if !audio_thread_data.sink.empty() {
+						let seek_time_ms: u64 = (position as f32 / audio_thread_data.speed) as u64;
+						if dragging && (song_length as u64).saturating_sub(seek_time_ms) < 1000 {
+							if !audio_thread_data.sink.is_paused() && !paused_from_seeking {
+								paused_from_seeking = true;
+							}
+							audio_thread_data.sink.pause();
+						} else {
+							if audio_thread_data.sink.is_paused() && paused_from_seeking {
+								audio_thread_data.sink.play();
+								paused_from_seeking = false;
+							}
+						}
+						let _ = audio_thread_data.sink.try_seek(std::time::Duration::from_millis(seek_time_ms));
+						current_timestamp = (position * 1000) as u128;
+						saved_timestamp = if audio_thread_data.sink.is_paused() {None} else {Some(time::SystemTime::now())};
+					}
+				},
+				MessageToAudio::SeekStop => {
+					if seeking && !audio_thread_data.sink.empty() {
+						if audio_thread_data.sink.is_paused() && paused_from_seeking {
+							audio_thread_data.sink.play();
+							paused_from_seeking = false;
+						}
+						seeking = false;
+						saved_timestamp = if audio_thread_data.sink.is_paused() {None} else {Some(time::SystemTime::now())};
+					}
+				},
+				MessageToAudio::TogglePause => {
+					count_in_until = None;
+					if audio_thread_data.sink.is_paused() {
+						saved_timestamp = Some(time::SystemTime::now());
+						audio_thread_data.sink.play();
+					} else {
+						if let Some(ts) = saved_timestamp && let Ok(a) = ts.elapsed() {
+							current_timestamp += (a.as_micros() as f32 * audio_thread_data.speed) as u128;
+							saved_timestamp = None;
+						}
+						audio_thread_data.sink.pause();
+					}
+				},
+				MessageToAudio::SongEnd => {
+					audio_thread_data.sink.clear();
+					/* A song deleted while it played can't be looped, it just ends */
+					let song_gone = !song_path.is_empty() && !std::path::Path::new(&song_path).exists();
+					match audio_thread_data.end_behavior {
+						LoopBehavior::Loop if song_gone => {
+							end_listening_session(&mut audio_thread_data);
+							song_path = "".to_string();
+							current_timestamp = 0;
+							saved_timestamp = None;
+						},
+						LoopBehavior::Stop => {
+							end_listening_session(&mut audio_thread_data);
+							song_path = "".to_string();
+
+							current_timestamp = 0;
+							saved_timestamp = None;
+						},
+						LoopBehavior::Loop => {
+							let path_clone = song_path.clone(); /* Borrow checker agony */
+							let err = play_song(false, &path_clone, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+							if err.is_none() {
+								update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+							}
+						},
+						LoopBehavior::Next => {
+							let mut next_song: Option<String> = None;
+							let mut push_to_history = false;
+							if current_songs_collection.len() > 0 {
+								song_index = (song_index + 1) % current_songs_collection.len() as usize;
+
+								if let Some(song) = current_songs_collection.get(song_index) {
+									push_to_history = true;
+									next_song = Some(song.clone());
+								}
+							}
+							if let Some(song) = next_song {
+								song_play_err = play_song(push_to_history, &song, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+								if song_play_err.is_none() {
+									update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+								}
+							} else {
+								end_listening_session(&mut audio_thread_data);
+								song_length = 1;
+								song_path = "".to_string();
+								current_timestamp = 0;
+								saved_timestamp = None;
+							}
+						}
+						LoopBehavior::Shuffle => {
+							if current_songs_collection.len() > 0 {
+								let mut song_chosen = false;
+								for _ in 0..32 {
+									random_seed = generate_random_number(random_seed);
+									let song_index = ((random_seed % (usize::MAX as u128)) as usize) % current_songs_collection.len();
+									if let Some(song) = current_songs_collection.get(song_index) {
+										if song_is_in_last_n(song, &history_buffer, randomization_memory) {
+											continue;
+										}
+										song_play_err = play_song(true, &song, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+										if song_play_err.is_none() {
+											update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+										}
+										song_chosen = true;
+										break;
+									}
+								}
+								if !song_chosen {
+									random_seed = generate_random_number(random_seed);
+									let song_index = ((random_seed % (usize::MAX as u128)) as usize) % current_songs_collection.len();
+									if let Some(song) = current_songs_collection.get(song_index) {
+										song_play_err = play_song(true, &song, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+										if song_play_err.is_none() {
+											update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+										}
+									}
+								}
+							} else {
+								end_listening_session(&mut audio_thread_data);
+								song_length = 1;
+								song_path = "".to_string();
+								current_timestamp = 0;
+								saved_timestamp = None;
+							}
+						}
+					}
+				},
+				MessageToAudio::SetSongCollection(vec, optional_index) => {
+					current_songs_collection = vec;
+					song_index = if let Some(index) = optional_index {index}
+						else {current_songs_collection.len().saturating_sub(1)};
+				},
+				MessageToAudio::ClearError => {
+					song_play_err = None;
+				},
+				MessageToAudio::PreviousSong => {
+					match prev_behavior {
+						PrevBehavior::Above => {
+							let mut prev_song: Option<String> = None;
+							let mut push_to_history = false;
+							if current_songs_collection.len() > 0 {
+								song_index = (song_index + current_songs_collection.len() - 1) % current_songs_collection.len() as usize;
+
+								if let Some(song) = current_songs_collection.get(song_index) {
+									push_to_history = true;
+									prev_song = Some(song.clone());
+								}
+							}
+							if let Some(song) = prev_song {
+								song_play_err = play_song(push_to_history, &song, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+								if song_play_err.is_none() {
+									update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+								}
+							} else {
+								end_listening_session(&mut audio_thread_data);
+								song_length = 1;
+								song_path = "".to_string();
+								current_timestamp = 0;
+								saved_timestamp = None;
+							}
+						},
+						PrevBehavior::History => {
+							if try_go_to_previous_song(&mut history_buffer) {
+								let song = history_buffer.vec[history_buffer.current_element].clone();
+								song_play_err = play_song(false, &song, &mut song_path, &mut history_buffer, &mut audio_thread_data, &recieve_pair);
+								if song_play_err.is_none() {
+									update_timestamps(&song_path, &mut song_length, &mut current_timestamp, &mut saved_timestamp, &mut audio_thread_data);
+									song_index = 0;
+									for i in 0..current_songs_collection.len() {
+										if let Some(e) = current_songs_collection.get(i) && *e == song {
+											song_index = i;
+											break;
+										}
+									}
+								}
+							}
+						},
+					}
+					
+				},
+				MessageToAudio::UpdateShuffleMemory(mem) => {
+					randomization_memory = mem;
+				},
+				MessageToAudio::UpdatePrevBehavior(new_behavior) => {
+					prev_behavior = new_behavior;
+				},
+				MessageToAudio::SetSleepTimer(timer, fade_ms) => {
+					if audio_thread_data.sleep_timer.as_ref().is_some_and(|timer| timer.fading) {
+						set_sleep_fade(&audio_thread_data, 1.0);
+					}
+					audio_thread_data.sleep_timer = timer.map(|timer| match timer {
+						SleepTimer::AfterTime(ms) => ActiveSleepTimer {
+							deadline: Some(time::SystemTime::now() + Duration::from_millis(ms)),
+							songs_left: 0,
+							fade_ms,
+							fading: false,
+						},
+						SleepTimer::AfterSongs(songs) => ActiveSleepTimer {deadline: None, songs_left: songs.max(1), fade_ms, fading: false},
+					});
+				},
+				MessageToAudio::UpdateResumeSettings(settings) => {
+					audio_thread_data.resume_settings = settings;
+				},
+				MessageToAudio::SetABLoop(new_loop) => {
+					ab_loop = new_loop.filter(|ab| ab.start_ms < ab.end_ms).map(|ab| (song_path.clone(), ab));
+					if count_in_until.take().is_some() {
+						audio_thread_data.sink.play();
+						saved_timestamp = Some(time::SystemTime::now());
+					}
+				},
+			}
+			tick_listening_session(&mut audio_thread_data);
+		}
+
+		/* A-B looping. Nothing wakes the thread up when B is reached, so it sleeps until then instead of waiting for a message */
+		let mut wake_up_in: Option<Duration> = None;
+		if let Some(until) = count_in_until {
+			if let Ok(remaining) = until.duration_since(time::SystemTime::now()) {
+				wake_up_in = Some(remaining);
+			} else {
+				count_in_until = None;
+				audio_thread_data.sink.play();
+				saved_timestamp = Some(time::SystemTime::now());
+			}
+		}
+		if count_in_until.is_none()
+		&& !audio_thread_data.sink.is_paused()
+		&& let Some((loop_song, ab)) = &ab_loop
+		&& *loop_song == song_path
+		&& let Some(ts) = saved_timestamp {
+			let elapsed = ts.elapsed().map(|elapsed| elapsed.as_micros()).unwrap_or(0);
+			let position_us = current_timestamp + (elapsed as f32 * audio_thread_data.speed) as u128;
+			let end_us = ab.end_ms as u128 * 1000;
+			if position_us + 1000 >= end_us {
+				audio_thread_data.speed = next_loop_speed(ab, audio_thread_data.speed);
+				audio_thread_data.sink.set_speed(audio_thread_data.speed);
+				let seek_time_ms = (ab.start_ms as f32 / audio_thread_data.speed) as u64;
+				let _ = audio_thread_data.sink.try_seek(std::time::Duration::from_millis(seek_time_ms));
+				current_timestamp = ab.start_ms as u128 * 1000;
+				saved_timestamp = Some(time::SystemTime::now());
+				if ab.count_in_ms > 0 {
+					audio_thread_data.sink.pause();
+					saved_timestamp = None;
+					count_in_until = Some(time::SystemTime::now() + Duration::from_millis(ab.count_in_ms));
+					wake_up_in = Some(Duration::from_millis(ab.count_in_ms));
+				} else {
+					wake_up_in = Some(Duration::from_micros(((end_us - current_timestamp) as f32 / audio_thread_data.speed) as u64));
+				}
+			} else {
+				wake_up_in = Some(Duration::from_micros(((end_us - position_us) as f32 / audio_thread_data.speed) as u64));
+			}
+		}
+
+		/* Sleep timer. The fade gain goes down step by step, the song's source picks it up (see audio_thread_play_song) */
+		let is_paused = audio_thread_data.sink.is_paused() || song_path.is_empty();
+		if let Some(timer) = &mut audio_thread_data.sleep_timer {
+			let remaining_ms = if let Some(deadline) = timer.deadline {
+				Some(deadline.duration_since(time::SystemTime::now()).map(|left| left.as_millis() as u64).unwrap_or(0))
+			} else if timer.songs_left <= 1 && !is_paused && song_length > 0 {
+				let elapsed = saved_timestamp.and_then(|ts| ts.elapsed().ok()).map(|elapsed| elapsed.as_micros()).unwrap_or(0);
+				let position_ms = ((current_timestamp + (elapsed as f32 * audio_thread_data.speed) as u128) / 1000) as u64;
+				let song_left_ms = (song_length as u64).saturating_sub(SLEEP_TIMER_SONG_END_MARGIN_MS).saturating_sub(position_ms);
+				Some((song_left_ms as f32 / audio_thread_data.speed) as u64)
+			} else {
+				None
+			};
+			let mut timer_done = false;
+			if is_paused && timer.fading {
+				/* Paused by hand while fading, the volume shouldn't stay down */
+				audio_thread_data.sleep_fade.store(1.0f32.to_bits(), std::sync::atomic::Ordering::Relaxed);
+				timer.fading = false;
+			}
+			if let Some(remaining_ms) = remaining_ms {
+				if remaining_ms == 0 {
+					timer_done = true;
+				} else if remaining_ms <= timer.fade_ms && !is_paused {
+					timer.fading = true;
+					audio_thread_data.sleep_fade.store(fade_gain(remaining_ms, timer.fade_ms).to_bits(), std::sync::atomic::Ordering::Relaxed);
+					wake_up_in = Some(wake_up_in.map_or(FADE_STEP, |wake| wake.min(FADE_STEP)));
+				} else {
+					let until_fade = Duration::from_millis(if is_paused {remaining_ms} else {remaining_ms - timer.fade_ms});
+					wake_up_in = Some(wake_up_in.map_or(until_fade, |wake| wake.min(until_fade)));
+				}
+			}
+			if timer_done {
+				tick_listening_session(&mut audio_thread_data);
+				if !is_paused {
+					if let Some(ts) = saved_timestamp && let Ok(a) = ts.elapsed() {
+						current_timestamp += (a.as_micros() as f32 * audio_thread_data.speed) as u128;
+					}
+					saved_timestamp = None;
+					audio_thread_data.sink.pause();
+					remember_position(&audio_thread_data, &song_path, (current_timestamp / 1000) as usize);
+				}
+				set_sleep_fade(&audio_thread_data, 1.0);
+				audio_thread_data.sleep_timer = None;
+			}
+		}
+
+		// If this unwrap fails, it should crash.
+		data_vec = if let Some(timeout) = wake_up_in {
+			cvar.wait_timeout(data_vec, timeout).unwrap().0
+		} else {
+			cvar.wait(data_vec).unwrap()
+		};
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/* Same as what play_song does when a song gets saved to the history */
+	fn push_and_select(buffer: &mut SongRingBuffer, song: &str) {
+		push_to_ring_buffer(buffer, song);
+		buffer.current_element = (buffer.front + buffer.vec.len() - 1) % buffer.vec.len();
+	}
+
+	#[test]
+	fn ring_buffer_drops_oldest_song_when_full() {
+		let mut buffer = new_ring_buffer(5);
+		for song in ["a", "b", "c", "d", "e", "f"] {
+			push_and_select(&mut buffer, song);
+		}
+		/* One slot always stays free, so 5 slots hold the last 4 songs */
+		assert_eq!(buffer.vec[buffer.back], "c");
+		assert_eq!(buffer.vec[buffer.current_element], "f");
+	}
+
+	#[test]
+	fn ring_buffer_goes_back_through_history_after_wrapping() {
+		let mut buffer = new_ring_buffer(5);
+		for song in ["a", "b", "c", "d", "e", "f"] {
+			push_and_select(&mut buffer, song);
+		}
+		let mut previous = Vec::<String>::new();
+		while try_go_to_previous_song(&mut buffer) {
+			previous.push(buffer.vec[buffer.current_element].clone());
+		}
+		assert_eq!(previous, vec!["e", "d", "c"]);
+	}
+
+	#[test]
+	fn ring_buffer_finds_recent_songs() {
+		let mut buffer = new_ring_buffer(255);
+		assert!(!song_is_in_last_n(&"a".to_string(), &buffer, 3));
+		for song in ["a", "b", "c"] {
+			push_and_select(&mut buffer, song);
+		}
+		assert!(song_is_in_last_n(&"c".to_string(), &buffer, 1));
+		assert!(!song_is_in_last_n(&"b".to_string(), &buffer, 1));
+		assert!(song_is_in_last_n(&"a".to_string(), &buffer, 3));
+		assert!(!song_is_in_last_n(&"a".to_string(), &buffer, 2));
+		assert!(!song_is_in_last_n(&"d".to_string(), &buffer, 255));
+	}
+
+	#[test]
+	fn ring_buffer_only_remembers_what_fits() {
+		let mut buffer = new_ring_buffer(3);
+		for song in ["a", "b", "c", "d"] {
+			push_and_select(&mut buffer, song);
+		}
+		assert!(song_is_in_last_n(&"c".to_string(), &buffer, 10));
+		assert!(!song_is_in_last_n(&"b".to_string(), &buffer, 10));
+	}
+
+	#[test]
+	fn volume_curve_round_trips() {
+		for slider in [-0.1, 0.0, 0.25, DEFAULT_VOLUME, 1.0] {
+			assert!((inverse_volume_curve(volume_curve(slider)) - slider).abs() < 0.001);
+		}
+		assert_eq!(volume_curve(-0.2), 0.0);
+		assert_eq!(inverse_volume_curve(0.0), -0.2);
+	}
+
+	#[test]
+	fn loops_speed_up_to_the_target() {
+		let mut ab_loop = ABLoop {start_ms: 0, end_ms: 1000, count_in_ms: 0, speed_step: 0.05, target_speed: 0.7};
+		let mut speed = 0.6;
+		speed = next_loop_speed(&ab_loop, speed);
+		assert_eq!(speed, 0.65);
+		speed = next_loop_speed(&ab_loop, speed);
+		assert_eq!(speed, 0.7);
+		assert_eq!(next_loop_speed(&ab_loop, speed), 0.7);
+		/* Already faster than the target, or not speeding up at all */
+		assert_eq!(next_loop_speed(&ab_loop, 1.5), 1.5);
+		ab_loop.speed_step = 0.0;
+		assert_eq!(next_loop_speed(&ab_loop, 0.6), 0.6);
+	}
+
+	#[test]
+	fn sleep_timer_fades_linearly() {
+		assert_eq!(fade_gain(10000, 10000), 1.0);
+		assert_eq!(fade_gain(2500, 10000), 0.25);
+		assert_eq!(fade_gain(0, 10000), 0.0);
+		assert_eq!(fade_gain(20000, 10000), 1.0);
+		/* No fade at all */
+		assert_eq!(fade_gain(0, 0), 1.0);
+	}
+}
@@ -0,0 +1,132 @@
+/*
+ * Pinetree chapters, read from ID3 CHAP frames. Podcasts and audiobooks often come with these.
+ *
+ * A CHAP frame holds an element id, the start and end time in milliseconds, two byte offsets nothing uses,
+ * and then frames of its own. The title is the TIT2 frame inside it.
+ *
+ * Pinetree only plays mp3 files, so chapters from MP4 containers (m4b audiobooks) aren't read.
+ */
+
+use crate::library;
+use crate::tags;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Chapter {
+	pub start_ms: usize,
+	pub end_ms: usize,
+	pub title: String,
+}
+
+pub fn parse_chap(data: &[u8], version: u8) -> Option<Chapter> {
+	let id_end = data.iter().position(|byte| *byte == 0)?;
+	let times = data.get(id_end + 1..id_end + 17)?;
+	let read_u32 = |bytes: &[u8]| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize;
+	let start_ms = read_u32(&times[0..4]);
+	let end_ms = read_u32(&times[4..8]);
+	let title = tags::parse_frames(&data[id_end + 17..], version).iter()
+		.find(|frame| frame.id == "TIT2")
+		.map(|frame| tags::decode_text_frame(&frame.data))
+		.unwrap_or_default();
+	Some(Chapter {start_ms, end_ms, title})
+}
+
+/**
+ * Chapters of a song sorted by start time, empty if it has none. Untitled chapters get numbered.
+ */
+pub fn find_chapters(song_path: &str) -> Vec<Chapter> {
+	let tag = if let Some(tag) = tags::read_id3_frames(song_path) {tag} else {return Vec::new();};
+	/* CHAP only exists since ID3v2.3 */
+	if tag.version < 3 {
+		return Vec::new();
+	}
+	let mut chapters: Vec<Chapter> = tag.frames.iter()
+		.filter(|frame| frame.id == "CHAP")
+		.filter_map(|frame| parse_chap(&frame.data, tag.version))
+		.collect();
+	chapters.sort_by_key(|chapter| chapter.start_ms);
+	for (i, chapter) in chapters.iter_mut().enumerate() {
+		if chapter.title.trim().is_empty() {
+			chapter.title = format!("Chapter {}", i + 1);
+		}
+	}
+	chapters
+}
+
+pub fn current_chapter(chapters: &[Chapter], position_ms: usize) -> Option<usize> {
+	chapters.iter().rposition(|chapter| chapter.start_ms <= position_ms)
+}
+
+pub fn next_chapter(chapters: &[Chapter], position_ms: usize) -> Option<&Chapter> {
+	chapters.iter().find(|chapter| chapter.start_ms > position_ms)
+}
+
+/**
+ * Goes back to the start of the current chapter, or to the one before it right after a chapter started.
+ */
+pub fn previous_chapter(chapters: &[Chapter], position_ms: usize) -> Option<&Chapter> {
+	chapters.iter().rev().find(|chapter| chapter.start_ms + library::BOOKMARK_GRACE_MS < position_ms)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn chap_frame(id: &str, start_ms: u32, end_ms: u32, title: Option<&str>) -> Vec<u8> {
+		let mut data = id.as_bytes().to_vec();
+		data.push(0);
+		data.extend_from_slice(&start_ms.to_be_bytes());
+		data.extend_from_slice(&end_ms.to_be_bytes());
+		data.extend_from_slice(&[0xFF; 8]);
+		if let Some(title) = title {
+			/* ID3v2.3 TIT2 frame, Latin-1 */
+			data.extend_from_slice(b"TIT2");
+			data.extend_from_slice(&(title.len() as u32 + 1).to_be_bytes());
+			data.extend_from_slice(&[0, 0, 0]);
+			data.extend_from_slice(title.as_bytes());
+		}
+		data
+	}
+
+	#[test]
+	fn reads_chapters_from_a_file() {
+		let mut body = Vec::<u8>::new();
+		for data in [chap_frame("ch2", 60000, 120000, None), chap_frame("ch1", 0, 60000, Some("Intro"))] {
+			body.extend_from_slice(b"CHAP");
+			body.extend_from_slice(&(data.len() as u32).to_be_bytes());
+			body.extend_from_slice(&[0, 0]);
+			body.extend_from_slice(&data);
+		}
+		let mut bytes = b"ID3".to_vec();
+		bytes.extend_from_slice(&[3, 0, 0]);
+		let size = body.len();
+		bytes.extend_from_slice(&[(size >> 21) as u8 & 0x7F, (size >> 14) as u8 & 0x7F, (size >> 7) as u8 & 0x7F, size as u8 & 0x7F]);
+		bytes.extend_from_slice(&body);
+		bytes.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+
+		let path = temp_file_path("chapters.mp3");
+		std::fs::write(&path, bytes).unwrap();
+		let chapters = find_chapters(&path);
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(chapters, vec![
+			Chapter {start_ms: 0, end_ms: 60000, title: "Intro".to_string()},
+			Chapter {start_ms: 60000, end_ms: 120000, title: "Chapter 2".to_string()},
+		]);
+	}
+
+	#[test]
+	fn navigates_chapters() {
+		let chapters: Vec<Chapter> = [0, 60000, 120000].iter()
+			.map(|start| Chapter {start_ms: *start, end_ms: start + 60000, title: "".to_string()})
+			.collect();
+		assert_eq!(current_chapter(&chapters, 59999), Some(0));
+		assert_eq!(current_chapter(&chapters, 130000), Some(2));
+		assert_eq!(next_chapter(&chapters, 60000).unwrap().start_ms, 120000);
+		assert!(next_chapter(&chapters, 120000).is_none());
+		assert_eq!(previous_chapter(&chapters, 90000).unwrap().start_ms, 60000);
+		assert_eq!(previous_chapter(&chapters, 60500).unwrap().start_ms, 0);
+		assert!(parse_chap(b"no terminator", 3).is_none());
+	}
+}
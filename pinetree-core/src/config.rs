@@ -0,0 +1,735 @@
+/*
+ * Pinetree settings and install locations, stored in internal_pinetree_data.txt
+ */
+
+use std::io::BufRead;
+
+use crate::audio_frontend;
+use crate::files::build_full_filepath;
+use crate::playlist::{Playlist, init_playlist_from_filepath};
+use crate::scrobble;
+use crate::shortcuts;
+use crate::record;
+use crate::resume;
+use crate::roots;
+use crate::track_table;
+use crate::visualiser;
+
+pub const CURRENT_VERSION: &str = "OPEN BETA 5";
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+pub enum ThemePref {
+	DARK,
+	LIGHT,
+}
+
+pub fn theme_to_str(theme: &ThemePref) -> String {
+	return match theme {
+		ThemePref::DARK => "Dark".to_string(),
+		ThemePref::LIGHT => "Light".to_string(),
+	};
+}
+
+pub fn str_to_theme_preference(string: &str) -> ThemePref {
+	return match string {
+		"Dark" => ThemePref::DARK,
+		"Light" => ThemePref::LIGHT,
+
+		/* Default is dark */
+		&_ => ThemePref::DARK,
+	};
+}
+
+pub struct PersistentData {
+	pub data_file_exists: bool,
+	/* Set by --no-install. Nothing gets read from or written to disk */
+	pub no_install: bool,
+	pub hide_directories_by_default: bool,
+	pub data_file_version: String,
+	pub default_directory: String,
+	/* The folders scanned into the library. Empty means the default directory is the only one (see roots::library_roots) */
+	pub library_roots: Vec<roots::LibraryRoot>,
+	pub theme: ThemePref,
+	pub playlists: Vec<Playlist>,
+	pub default_on_finish: audio_frontend::LoopBehavior,
+	pub default_volume: f32,
+	pub shuffle_memory: usize,
+	pub prev_behavior: audio_frontend::PrevBehavior,
+	pub scrobble_log_format: scrobble::ScrobbleLogFormat,
+	pub scrobble_endpoint: String,
+	pub scrobble_token: String,
+	pub shortcuts: Vec<shortcuts::Shortcut>,
+	pub seek_step_secs: u64,
+	pub long_seek_step_secs: u64,
+	/* Grab the keyboard's play/pause/next/previous keys system-wide */
+	pub media_keys: bool,
+	pub visualiser: visualiser::VisualiserSettings,
+	pub resume: resume::ResumeSettings,
+	/* How long the sleep timer takes to fade out */
+	pub sleep_fade_secs: u64,
+	/* Recordings and renders go here, named after the template (see record::expand_template) */
+	pub record_folder: String,
+	pub record_template: String,
+	pub record_format: record::RecordFormat,
+	pub table: track_table::TableSettings,
+}
+
+pub fn default_persistent_data() -> PersistentData {
+	PersistentData {
+		data_file_exists: false,
+		no_install: false,
+		hide_directories_by_default: false,
+		data_file_version: CURRENT_VERSION.to_string(),
+		default_directory: "".to_string(),
+		library_roots: Vec::new(),
+		theme: ThemePref::DARK,
+		playlists: init_playlist_from_filepath(""),
+		default_on_finish: audio_frontend::LoopBehavior::Stop,
+		default_volume: audio_frontend::DEFAULT_VOLUME,
+		shuffle_memory: 3,
+		prev_behavior: audio_frontend::PrevBehavior::History,
+		scrobble_log_format: scrobble::ScrobbleLogFormat::Off,
+		scrobble_endpoint: "".to_string(),
+		scrobble_token: "".to_string(),
+		shortcuts: shortcuts::default_shortcuts(),
+		seek_step_secs: shortcuts::DEFAULT_SEEK_STEP_SECS,
+		long_seek_step_secs: shortcuts::DEFAULT_LONG_SEEK_STEP_SECS,
+		media_keys: false,
+		visualiser: visualiser::default_visualiser_settings(),
+		resume: resume::default_resume_settings(),
+		sleep_fade_secs: audio_frontend::DEFAULT_SLEEP_FADE_SECS,
+		record_folder: default_song_path(),
+		record_template: record::DEFAULT_RECORD_TEMPLATE.to_string(),
+		record_format: record::RecordFormat::Wav,
+		table: track_table::default_table_settings(),
+	}
+}
+
+/**
+ * This code was mostly lifted from the dirs-sys with changes
+ * https://github.com/dirs-dev/dirs-sys-rs/
+ * 
+ * It felt irresponsible adding a dependency for this when the code is simple,
+ * and I don't need the entirety of the dependency
+ */
+#[cfg(target_os = "windows")]
+mod windows_folders {
+	use std::ffi::OsString;
+	use std::os::windows::ffi::OsStringExt;
+	use windows_sys::Win32::UI::Shell;
+
+	pub fn try_get_winfolder(folder_id: windows_sys::core::GUID) -> Option<std::path::PathBuf> {
+		unsafe {
+			let mut path_ptr: windows_sys::core::PWSTR = std::ptr::null_mut();
+			let result = Shell::SHGetKnownFolderPath(&folder_id, 0,std::ptr::null_mut(),&mut path_ptr);
+			if result == 0 {
+				let len = windows_sys::Win32::Globalization::lstrlenW(path_ptr) as usize;
+				let path = std::slice::from_raw_parts(path_ptr, len);
+				let ostr: OsString = OsStringExt::from_wide(path);
+				windows_sys::Win32::System::Com::CoTaskMemFree(path_ptr as *const std::ffi::c_void);
+				Some(std::path::PathBuf::from(ostr))
+			} else {
+				windows_sys::Win32::System::Com::CoTaskMemFree(path_ptr as *const std::ffi::c_void);
+				None
+			}
+		}
+	}
+
+	pub fn roaming_app_data() -> Option<std::path::PathBuf> {
+		try_get_winfolder(Shell::FOLDERID_RoamingAppData)
+	}
+
+	// pub fn local_app_data() -> Option<std::path::PathBuf> {
+	// 	try_get_winfolder(Shell::FOLDERID_LocalAppData)
+	// }
+
+	pub fn music() -> Option<std::path::PathBuf> {
+		try_get_winfolder(Shell::FOLDERID_Music)
+	}
+
+	// pub fn desktop() -> Option<std::path::PathBuf> {
+	// 	try_get_winfolder(Shell::FOLDERID_Desktop)
+	// }
+}
+
+#[cfg(target_family = "unix")]
+mod unix_folders {
+	use std::env;
+
+	/**
+	* Returns ~/
+	*/
+	pub fn home() -> Option<String> {
+		let mut r_str = None;
+		if let Some(home) = env::home_dir() && let Some(path_string) = home.as_os_str().to_str() {
+			r_str = Some(path_string.to_string());
+		}
+		return r_str;
+	}
+}
+
+pub fn default_install_path() -> String {
+	#[cfg(target_os = "windows")] {
+		let appdata = windows_folders::roaming_app_data();
+		if let Some(appdata_folder) = appdata
+		&& let Some(path_string) = appdata_folder.as_os_str().to_str() {
+			return path_string.to_string();
+		}
+	}
+	#[cfg(target_os = "linux")] {
+		let home = unix_folders::home();
+		if let Some(home_folder) = home {
+			let local_share = build_full_filepath(&home_folder, ".local/share");
+			return local_share.to_string();
+		}
+	}
+	#[cfg(target_os = "macos")] {
+		let home = unix_folders::home();
+		if let Some(home_folder) = home {
+			let local_share = build_full_filepath(&home_folder, "Library/Application Support");
+			return local_share.to_string();
+		}
+	}
+	"".to_string()
+}
+pub fn default_song_path() -> String {
+	#[cfg(target_os = "windows")] {
+		let appdata = windows_folders::music();
+		if let Some(appdata_folder) = appdata
+		&& let Some(path_string) = appdata_folder.as_os_str().to_str() {
+			return path_string.to_string();
+		}
+	}
+	#[cfg(target_os = "linux")] {
+		let home = unix_folders::home();
+		if let Some(home_folder) = home {
+			let local_share = build_full_filepath(&home_folder, "Music");
+			return local_share.to_string();
+		}
+	}
+	#[cfg(target_os = "macos")] {
+		let home = unix_folders::home();
+		if let Some(home_folder) = home {
+			let local_share = build_full_filepath(&home_folder, "Music");
+			return local_share.to_string();
+		}
+	}
+	"".to_string()
+}
+
+/**
+ * With a data folder (--data-dir), only that folder is looked at. It gets set up with default data the first time,
+ * so a new profile doesn't need to go through the installer.
+ */
+pub fn find_persistent_data(data_dir: Option<&str>, no_install: bool) -> (PersistentData, String) {
+	let mut ret_str = "".to_string();
+	if no_install {
+		let mut persistent_data = default_persistent_data();
+		persistent_data.no_install = true;
+		persistent_data.default_directory = default_song_path();
+		return (persistent_data, ret_str);
+	}
+	let data_file = if let Some(data_dir) = data_dir {
+		let internal_data_file = build_full_filepath(data_dir, "internal_pinetree_data.txt");
+		if !std::path::Path::new(&internal_data_file).exists() {
+			let mut new_profile = default_persistent_data();
+			new_profile.default_directory = default_song_path();
+			if std::fs::create_dir_all(data_dir).is_err() || write_internal_data(&internal_data_file, &new_profile).is_err() {
+				println!("Error in creating data folder");
+				return (default_persistent_data(), ret_str);
+			}
+		}
+		if let Ok(data_file) = std::fs::File::open(internal_data_file) {
+			ret_str = data_dir.to_string();
+			data_file
+		} else {
+			return (default_persistent_data(), ret_str);
+		}
+	} else if let Ok(same_dir) = std::fs::File::open("internal_pinetree_data.txt") {
+		ret_str = "./".to_string();
+		same_dir
+	} else {
+		let pinetree_folder = &build_full_filepath(&default_install_path(), "Pinetree");
+		let internal_data_file = build_full_filepath(pinetree_folder, "internal_pinetree_data.txt");
+
+		if let Ok(default_install_location) = std::fs::File::open(internal_data_file) {
+			ret_str = build_full_filepath(&default_install_path(), "Pinetree");
+			default_install_location
+		} else {
+			return (default_persistent_data(), ret_str);
+		}
+	} ;
+	let mut persistent_data = parse_persistent_data(std::io::BufReader::new(data_file));
+	persistent_data.data_file_exists = true;
+
+	(persistent_data, ret_str)
+}
+
+/**
+ * Reads the contents of internal_pinetree_data.txt. Anything missing or unreadable keeps its default value.
+ */
+pub fn parse_persistent_data(reader: impl BufRead) -> PersistentData {
+	let mut persistent_data = default_persistent_data();
+
+	let mut current_songs = Vec::<String>::new();
+	let mut playlist_name: Option<String> = None;
+
+	#[derive(PartialEq)]
+	enum State {
+		Version,
+		Settings,
+		Playlists,
+	}
+
+	let mut current_state = State::Version;
+	
+	for line in reader.lines() {
+		if let Ok(line) = line {
+			let version_header = "VERSION: ";
+			let settings_header = "SETTINGS";
+			let playlists_header = "PLAYLISTS";
+
+			if line.starts_with(version_header) {
+				persistent_data.data_file_version = line[version_header.len()..].to_string();
+			}
+			else if line.starts_with(settings_header) {
+				current_state = State::Settings;
+			}
+			else if line.starts_with(playlists_header) {
+				current_state = State::Playlists;
+			}
+			else if current_state == State::Settings {
+				let theme_identifier = "Theme: ";
+				let default_directory_identifier = "Default Directory: ";
+				/* A root starts with its path, the lines after it up to the next root are its settings */
+				let library_root_identifier = "Library Root: ";
+				let root_name_identifier = "Root Name: ";
+				let root_depth_identifier = "Root Depth: ";
+				let root_exclude_identifier = "Root Exclude: ";
+				let root_glob_identifier = "Root Glob: ";
+				let hide_dirs_identifier = "Hide Directories: "; 
+				let default_end_behavior_identifier = "Default End Behavior: "; 
+				let default_volume_identifier = "Default Volume: ";
+				let shuffle_memory_identifier = "Shuffle Memory: ";
+				let prev_behavior_identifier= "Default Prev Behavior: ";
+				let scrobble_log_identifier = "Scrobble Log: ";
+				let scrobble_endpoint_identifier = "Scrobble Endpoint: ";
+				let scrobble_token_identifier = "Scrobble Token: ";
+				let seek_step_identifier = "Seek Step: ";
+				let long_seek_step_identifier = "Long Seek Step: ";
+				let shortcut_identifier = "Shortcut ";
+				let global_shortcut_identifier = "Global Shortcut ";
+				let media_keys_identifier = "Media Keys: ";
+				let visualiser_sensitivity_identifier = "Visualiser Sensitivity: ";
+				let visualiser_bands_identifier = "Visualiser Bands: ";
+				let visualiser_decay_identifier = "Visualiser Decay: ";
+				let visualiser_oscilloscope_identifier = "Visualiser Oscilloscope: ";
+				let resume_threshold_identifier = "Resume Threshold: ";
+				let audiobook_folder_identifier = "Audiobook Folder: ";
+				let sleep_fade_identifier = "Sleep Fade: ";
+				let record_folder_identifier = "Record Folder: ";
+				let record_template_identifier = "Record Template: ";
+				let record_format_identifier = "Record Format: ";
+				let table_columns_identifier = "Table Columns: ";
+				let table_sort_identifier = "Table Sort ";
+				if line.starts_with(theme_identifier) {
+					persistent_data.theme = str_to_theme_preference(&line[theme_identifier.len()..]);
+				} else if line.starts_with(default_directory_identifier) {
+					persistent_data.default_directory = (line[default_directory_identifier.len()..]).to_string();
+				} else if let Some(value) = line.strip_prefix(library_root_identifier) {
+					persistent_data.library_roots.push(roots::new_root(value));
+				} else if let Some(value) = line.strip_prefix(root_name_identifier) && let Some(root) = persistent_data.library_roots.last_mut() {
+					root.name = value.to_string();
+				} else if let Some(value) = line.strip_prefix(root_depth_identifier) && let Some(root) = persistent_data.library_roots.last_mut() {
+					root.max_depth = value.parse().ok();
+				} else if let Some(value) = line.strip_prefix(root_exclude_identifier) && let Some(root) = persistent_data.library_roots.last_mut() {
+					root.excludes.push(value.to_string());
+				} else if let Some(value) = line.strip_prefix(root_glob_identifier) && let Some(root) = persistent_data.library_roots.last_mut() {
+					root.globs.push(value.to_string());
+				} else if line.starts_with(hide_dirs_identifier) {
+					match &line[hide_dirs_identifier.len()..] {
+						"true" => {
+							persistent_data.hide_directories_by_default = true;
+						},
+						"false" => {
+							persistent_data.hide_directories_by_default = false;
+						},
+						_ => {},
+					}
+				} else if line.starts_with(default_end_behavior_identifier) {
+					match &line[default_end_behavior_identifier.len()..] {
+						"Loop" => {
+							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Loop;
+						},
+						"Stop" => {
+							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Stop;
+						},
+						"Shuffle" => {
+							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Shuffle;
+						},
+						"Next" => {
+							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Next;
+						},
+						_ => {},
+					}
+				} else if line.starts_with(default_volume_identifier) {
+					persistent_data.default_volume = line[default_volume_identifier.len()..].parse().unwrap_or(audio_frontend::DEFAULT_VOLUME);
+				} else if line.starts_with(shuffle_memory_identifier) {
+					persistent_data.shuffle_memory = line[shuffle_memory_identifier.len()..].parse().unwrap_or(3);
+				} else if line.starts_with(prev_behavior_identifier) {
+					match &line[prev_behavior_identifier.len()..] {
+						"Above" => {
+							persistent_data.prev_behavior = audio_frontend::PrevBehavior::Above;
+						},
+						"History" => {
+							persistent_data.prev_behavior = audio_frontend::PrevBehavior::History;
+						},
+						_ => {},
+					}
+				} else if let Some(value) = line.strip_prefix(scrobble_log_identifier) {
+					persistent_data.scrobble_log_format = scrobble::str_to_log_format(value);
+				} else if let Some(value) = line.strip_prefix(scrobble_endpoint_identifier) {
+					persistent_data.scrobble_endpoint = value.to_string();
+				} else if let Some(value) = line.strip_prefix(scrobble_token_identifier) {
+					persistent_data.scrobble_token = value.to_string();
+				} else if let Some(value) = line.strip_prefix(seek_step_identifier) {
+					persistent_data.seek_step_secs = value.parse().unwrap_or(shortcuts::DEFAULT_SEEK_STEP_SECS);
+				} else if let Some(value) = line.strip_prefix(long_seek_step_identifier) {
+					persistent_data.long_seek_step_secs = value.parse().unwrap_or(shortcuts::DEFAULT_LONG_SEEK_STEP_SECS);
+				} else if let Some(value) = line.strip_prefix(media_keys_identifier) {
+					persistent_data.media_keys = value == "true";
+				} else if let Some(value) = line.strip_prefix(visualiser_sensitivity_identifier)
+				&& let Ok(sensitivity) = value.parse::<f32>() {
+					persistent_data.visualiser.sensitivity_db = sensitivity;
+				} else if let Some(value) = line.strip_prefix(visualiser_bands_identifier)
+				&& let Ok(band_count) = value.parse::<usize>() {
+					persistent_data.visualiser.band_count = band_count.clamp(visualiser::MIN_BAND_COUNT, visualiser::MAX_BAND_COUNT);
+				} else if let Some(value) = line.strip_prefix(visualiser_decay_identifier)
+				&& let Ok(decay) = value.parse::<f32>() {
+					persistent_data.visualiser.decay_secs = decay.max(0.0);
+				} else if let Some(value) = line.strip_prefix(visualiser_oscilloscope_identifier) {
+					persistent_data.visualiser.oscilloscope = value == "true";
+				} else if let Some(value) = line.strip_prefix(resume_threshold_identifier) {
+					persistent_data.resume.threshold_mins = value.parse().unwrap_or(resume::DEFAULT_RESUME_THRESHOLD_MINS);
+				} else if let Some(value) = line.strip_prefix(audiobook_folder_identifier) {
+					persistent_data.resume.audiobook_folders.push(value.to_string());
+				} else if let Some(value) = line.strip_prefix(sleep_fade_identifier) {
+					persistent_data.sleep_fade_secs = value.parse().unwrap_or(audio_frontend::DEFAULT_SLEEP_FADE_SECS);
+				} else if let Some(value) = line.strip_prefix(record_folder_identifier) {
+					persistent_data.record_folder = value.to_string();
+				} else if let Some(value) = line.strip_prefix(record_template_identifier) {
+					persistent_data.record_template = value.to_string();
+				} else if let Some(value) = line.strip_prefix(record_format_identifier) {
+					persistent_data.record_format = record::str_to_format(value);
+				} else if let Some(value) = line.strip_prefix(table_columns_identifier) {
+					persistent_data.table.columns = value.split(", ").filter_map(track_table::str_to_column).collect();
+				} else if let Some(value) = line.strip_prefix(table_sort_identifier)
+				&& let Some((id, keys)) = value.rsplit_once(": ") {
+					persistent_data.table.sorts.insert(id.to_string(), track_table::str_to_sort_keys(keys));
+				} else if let Some(value) = line.strip_prefix(global_shortcut_identifier)
+				&& let Some((action, binding)) = value.split_once(": ")
+				&& let Some(action) = shortcuts::str_to_action(action) {
+					shortcuts::set_global_binding(&mut persistent_data.shortcuts, action, shortcuts::str_to_binding(binding));
+				} else if let Some(value) = line.strip_prefix(shortcut_identifier)
+				&& let Some((action, binding)) = value.split_once(": ")
+				&& let Some(action) = shortcuts::str_to_action(action) {
+					shortcuts::set_binding(&mut persistent_data.shortcuts, action, shortcuts::str_to_binding(binding));
+				}
+			}
+			else if current_state == State::Playlists {
+				/* Prevents trailing newlines from causing problems */
+				if line.len() < 1 {continue;}
+
+				if line.starts_with("Playlist: ") {
+					if let Some(name) = playlist_name {
+						persistent_data.playlists.push(Playlist {
+							name: name,
+							songs: current_songs,
+							is_open: false,
+						});
+						current_songs = Vec::<String>::new();
+					}
+					playlist_name = Some((&line[10..]).to_string());
+				} else {
+					current_songs.push(line);
+				}
+			}
+		}
+	}
+	/* Playlists are currently the last thing in the internal data */
+	if let Some(name) = playlist_name {
+		persistent_data.playlists.push(Playlist {
+			name: name,
+			songs: current_songs,
+			is_open: false,
+		});
+	}
+
+	return persistent_data;
+}
+
+pub fn default_on_finish_to_str(behavior: &audio_frontend::LoopBehavior) -> &'static str {
+	match behavior {
+		audio_frontend::LoopBehavior::Stop => {
+			"Stop"
+		},
+		audio_frontend::LoopBehavior::Loop => {
+			"Loop"
+		},
+		audio_frontend::LoopBehavior::Next => {
+			"Next"
+		},
+		audio_frontend::LoopBehavior::Shuffle => {
+			"Shuffle"
+		},
+	}
+}
+
+pub fn prev_behavior_to_str(p: &audio_frontend::PrevBehavior) -> &str {
+	return match *p {
+		audio_frontend::PrevBehavior::Above => {
+			"Above"
+		},
+		audio_frontend::PrevBehavior::History => {
+			"History"
+		},
+	};
+}
+
+pub fn write_internal_data(path: &str, persistent_data: &PersistentData) -> Result<(), Box<dyn std::error::Error>>{
+	use std::io::prelude::*;
+	if persistent_data.no_install {
+		return Ok(());
+	}
+	let file = std::fs::File::create(path);
+	if let Ok(mut file) = file {
+		let mut data_to_write = format!("VERSION: {}\n", CURRENT_VERSION);
+
+		data_to_write = format!("{}{}", data_to_write, "SETTINGS\n");
+		data_to_write = format!("{}Theme: {}\n", data_to_write, theme_to_str(&persistent_data.theme));
+		data_to_write = format!("{}Default Directory: {}\n", data_to_write, persistent_data.default_directory);
+		for root in &persistent_data.library_roots {
+			data_to_write = format!("{}Library Root: {}\n", data_to_write, root.path);
+			data_to_write = format!("{}Root Name: {}\n", data_to_write, root.name);
+			if let Some(max_depth) = root.max_depth {
+				data_to_write = format!("{}Root Depth: {}\n", data_to_write, max_depth);
+			}
+			for exclude in root.excludes.iter().map(|exclude| exclude.trim()).filter(|exclude| !exclude.is_empty()) {
+				data_to_write = format!("{}Root Exclude: {}\n", data_to_write, exclude);
+			}
+			for glob in root.globs.iter().map(|glob| glob.trim()).filter(|glob| !glob.is_empty()) {
+				data_to_write = format!("{}Root Glob: {}\n", data_to_write, glob);
+			}
+		}
+		data_to_write = format!("{}Default End Behavior: {}\n", data_to_write, default_on_finish_to_str(&persistent_data.default_on_finish));
+		data_to_write = format!("{}Default Prev Behavior: {}\n", data_to_write, prev_behavior_to_str(&persistent_data.prev_behavior));
+		data_to_write = format!("{}Default Volume: {}\n", data_to_write, persistent_data.default_volume.to_string());
+		data_to_write = format!("{}Hide Directories: {}\n", data_to_write, 
+			if persistent_data.hide_directories_by_default {"true"}
+			else {"false"}
+		);
+		data_to_write = format!("{}Shuffle Memory: {}\n", data_to_write, persistent_data.shuffle_memory);
+		data_to_write = format!("{}Scrobble Log: {}\n", data_to_write, scrobble::log_format_to_str(&persistent_data.scrobble_log_format));
+		data_to_write = format!("{}Scrobble Endpoint: {}\n", data_to_write, persistent_data.scrobble_endpoint);
+		data_to_write = format!("{}Scrobble Token: {}\n", data_to_write, persistent_data.scrobble_token);
+		data_to_write = format!("{}Seek Step: {}\n", data_to_write, persistent_data.seek_step_secs);
+		data_to_write = format!("{}Long Seek Step: {}\n", data_to_write, persistent_data.long_seek_step_secs);
+		for shortcut in &persistent_data.shortcuts {
+			data_to_write = format!("{}Shortcut {}: {}\n", data_to_write, shortcuts::action_to_str(shortcut.action), shortcuts::binding_to_str(&shortcut.binding));
+		}
+		for shortcut in &persistent_data.shortcuts {
+			if shortcut.global_binding.is_some() {
+				data_to_write = format!("{}Global Shortcut {}: {}\n", data_to_write, shortcuts::action_to_str(shortcut.action), shortcuts::binding_to_str(&shortcut.global_binding));
+			}
+		}
+		data_to_write = format!("{}Media Keys: {}\n", data_to_write, if persistent_data.media_keys {"true"} else {"false"});
+		data_to_write = format!("{}Visualiser Sensitivity: {}\n", data_to_write, persistent_data.visualiser.sensitivity_db);
+		data_to_write = format!("{}Visualiser Bands: {}\n", data_to_write, persistent_data.visualiser.band_count);
+		data_to_write = format!("{}Visualiser Decay: {}\n", data_to_write, persistent_data.visualiser.decay_secs);
+		data_to_write = format!("{}Visualiser Oscilloscope: {}\n", data_to_write, if persistent_data.visualiser.oscilloscope {"true"} else {"false"});
+		data_to_write = format!("{}Resume Threshold: {}\n", data_to_write, persistent_data.resume.threshold_mins);
+		for folder in &persistent_data.resume.audiobook_folders {
+			data_to_write = format!("{}Audiobook Folder: {}\n", data_to_write, folder);
+		}
+		data_to_write = format!("{}Sleep Fade: {}\n", data_to_write, persistent_data.sleep_fade_secs);
+		data_to_write = format!("{}Record Folder: {}\n", data_to_write, persistent_data.record_folder);
+		data_to_write = format!("{}Record Template: {}\n", data_to_write, persistent_data.record_template);
+		data_to_write = format!("{}Record Format: {}\n", data_to_write, record::format_to_str(persistent_data.record_format));
+		let columns: Vec<&str> = persistent_data.table.columns.iter().map(|column| track_table::column_to_str(*column)).collect();
+		data_to_write = format!("{}Table Columns: {}\n", data_to_write, columns.join(", "));
+		for (id, keys) in &persistent_data.table.sorts {
+			if !keys.is_empty() {
+				data_to_write = format!("{}Table Sort {}: {}\n", data_to_write, id, track_table::sort_keys_to_str(keys));
+			}
+		}
+
+		data_to_write = format!("{}{}", data_to_write, "PLAYLISTS\n");
+
+		for playlist in &persistent_data.playlists {
+			data_to_write = format!("{}Playlist: {}\n", data_to_write, playlist.name);
+			
+			for song in &playlist.songs {
+				data_to_write = format!("{}{}\n", data_to_write, song);
+			}
+		}
+		file.write_all(data_to_write.as_bytes())?;
+	}
+	return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	const DATA_FILE: &str = "VERSION: OPEN BETA 4
+SETTINGS
+Theme: Light
+Default Directory: /home/user/Music
+Library Root: /home/user/Music
+Root Exclude: Samples
+Library Root: /mnt/nas/music/
+Root Name: NAS
+Root Depth: 2
+Root Glob: *.mp3
+Root Glob: * - *
+Default End Behavior: Shuffle
+Default Prev Behavior: Above
+Default Volume: 0.5
+Hide Directories: true
+Shuffle Memory: 7
+Scrobble Log: Both
+Scrobble Endpoint: http://localhost:8100
+Scrobble Token: abc
+Seek Step: 10
+Shortcut Pause: Space
+Shortcut Refresh: None
+Shortcut Made Up: Ctrl+M
+Global Shortcut Next Song: Ctrl+Alt+Right
+Media Keys: true
+Visualiser Sensitivity: 6.5
+Visualiser Bands: 500
+Visualiser Oscilloscope: false
+Resume Threshold: 45
+Audiobook Folder: /home/user/Audiobooks
+Audiobook Folder: /home/user/Podcasts
+Sleep Fade: 30
+Record Template: {song} live
+Record Format: FLAC
+Table Columns: Title, Duration, Nonsense
+Table Sort /home/user/Music: Artist+, Track-
+Table Sort Playlist: Morning: Play Count-
+PLAYLISTS
+Playlist: Morning
+/home/user/Music/a.mp3
+/home/user/Music/b.mp3
+Playlist: Empty
+Playlist: Evening
+/home/user/Music/c.mp3
+
+";
+
+	#[test]
+	fn parses_settings_and_playlists() {
+		let data = parse_persistent_data(DATA_FILE.as_bytes());
+		assert_eq!(data.data_file_version, "OPEN BETA 4");
+		assert!(data.theme == ThemePref::LIGHT);
+		assert_eq!(data.default_directory, "/home/user/Music");
+		assert_eq!(data.library_roots.len(), 2);
+		assert_eq!(data.library_roots[0].name, "Music");
+		assert_eq!(data.library_roots[0].max_depth, None);
+		assert_eq!(data.library_roots[0].excludes, vec!["Samples"]);
+		assert_eq!(data.library_roots[1].name, "NAS");
+		assert_eq!(data.library_roots[1].max_depth, Some(2));
+		assert_eq!(data.library_roots[1].globs, vec!["*.mp3", "* - *"]);
+		assert!(data.default_on_finish == audio_frontend::LoopBehavior::Shuffle);
+		assert!(data.prev_behavior == audio_frontend::PrevBehavior::Above);
+		assert_eq!(data.default_volume, 0.5);
+		assert!(data.hide_directories_by_default);
+		assert_eq!(data.shuffle_memory, 7);
+		assert!(data.scrobble_log_format == scrobble::ScrobbleLogFormat::Both);
+		assert_eq!(data.scrobble_endpoint, "http://localhost:8100");
+		assert_eq!(data.scrobble_token, "abc");
+		assert_eq!(data.seek_step_secs, 10);
+		assert_eq!(data.long_seek_step_secs, shortcuts::DEFAULT_LONG_SEEK_STEP_SECS);
+		assert_eq!(shortcuts::binding_to_str(&shortcuts::find_binding(&data.shortcuts, shortcuts::ShortcutAction::TogglePause).cloned()), "Space");
+		assert!(shortcuts::find_binding(&data.shortcuts, shortcuts::ShortcutAction::Refresh).is_none());
+		/* Anything not in the file keeps its default */
+		assert_eq!(shortcuts::find_binding(&data.shortcuts, shortcuts::ShortcutAction::NextSong), shortcuts::default_binding(shortcuts::ShortcutAction::NextSong).as_ref());
+		assert_eq!(data.shortcuts.len(), shortcuts::ALL_ACTIONS.len());
+		assert_eq!(shortcuts::find_global_binding(&data.shortcuts, shortcuts::ShortcutAction::NextSong), shortcuts::str_to_binding("Ctrl+Alt+Right").as_ref());
+		assert!(shortcuts::find_global_binding(&data.shortcuts, shortcuts::ShortcutAction::TogglePause).is_none());
+		assert!(data.media_keys);
+		assert_eq!(data.visualiser.sensitivity_db, 6.5);
+		assert_eq!(data.visualiser.band_count, visualiser::MAX_BAND_COUNT);
+		assert_eq!(data.visualiser.decay_secs, visualiser::default_visualiser_settings().decay_secs);
+		assert!(!data.visualiser.oscilloscope);
+		assert_eq!(data.resume.threshold_mins, 45);
+		assert_eq!(data.resume.audiobook_folders, vec!["/home/user/Audiobooks", "/home/user/Podcasts"]);
+		assert_eq!(data.sleep_fade_secs, 30);
+		assert_eq!(data.record_template, "{song} live");
+		assert!(data.record_format == record::RecordFormat::Flac);
+		assert_eq!(data.table.columns, vec![track_table::TableColumn::Title, track_table::TableColumn::Duration]);
+		assert_eq!(track_table::sort_keys_to_str(&data.table.sorts["/home/user/Music"]), "Artist+, Track-");
+		assert_eq!(track_table::sort_keys_to_str(&data.table.sorts[&track_table::playlist_sort_id("Morning")]), "Play Count-");
+
+		let names: Vec<&str> = data.playlists.iter().map(|playlist| playlist.name.as_str()).collect();
+		assert_eq!(names, vec!["Morning", "Empty", "Evening"]);
+		assert_eq!(data.playlists[0].songs, vec!["/home/user/Music/a.mp3", "/home/user/Music/b.mp3"]);
+		assert!(data.playlists[1].songs.is_empty());
+		assert_eq!(data.playlists[2].songs, vec!["/home/user/Music/c.mp3"]);
+	}
+
+	#[test]
+	fn unknown_values_keep_defaults() {
+		let data = parse_persistent_data("SETTINGS\nTheme: Purple\nDefault Volume: loud\nDefault End Behavior: Sometimes\nShuffle Memory: -1\nSomething New: 1\n".as_bytes());
+		assert!(data.theme == ThemePref::DARK);
+		assert_eq!(data.default_volume, audio_frontend::DEFAULT_VOLUME);
+		assert!(data.default_on_finish == audio_frontend::LoopBehavior::Stop);
+		assert_eq!(data.shuffle_memory, 3);
+		assert!(data.playlists.is_empty());
+	}
+
+	#[test]
+	fn written_data_reads_back() {
+		let mut data = parse_persistent_data(DATA_FILE.as_bytes());
+		data.default_volume = 0.25;
+		data.playlists[1].songs.push("/home/user/Music/d.mp3".to_string());
+
+		let path = temp_file_path("internal_pinetree_data.txt");
+		write_internal_data(&path, &data).unwrap();
+		let file = std::fs::File::open(&path).unwrap();
+		let read_back = parse_persistent_data(std::io::BufReader::new(file));
+		let _ = std::fs::remove_file(&path);
+
+		assert_eq!(read_back.data_file_version, CURRENT_VERSION);
+		assert!(read_back.theme == ThemePref::LIGHT);
+		assert_eq!(read_back.default_volume, 0.25);
+		assert_eq!(read_back.shuffle_memory, 7);
+		assert_eq!(read_back.seek_step_secs, 10);
+		assert!(shortcuts::find_binding(&read_back.shortcuts, shortcuts::ShortcutAction::Refresh).is_none());
+		assert_eq!(shortcuts::binding_to_str(&shortcuts::find_binding(&read_back.shortcuts, shortcuts::ShortcutAction::TogglePause).cloned()), "Space");
+		assert_eq!(shortcuts::find_global_binding(&read_back.shortcuts, shortcuts::ShortcutAction::NextSong), shortcuts::str_to_binding("Ctrl+Alt+Right").as_ref());
+		assert!(read_back.media_keys);
+		assert!(read_back.visualiser == data.visualiser);
+		assert!(read_back.resume == data.resume);
+		assert_eq!(read_back.sleep_fade_secs, 30);
+		assert_eq!(read_back.record_template, "{song} live");
+		assert!(read_back.record_format == record::RecordFormat::Flac);
+		assert!(read_back.table == data.table);
+		assert_eq!(read_back.library_roots, data.library_roots);
+		assert!(read_back.scrobble_log_format == scrobble::ScrobbleLogFormat::Both);
+		assert_eq!(read_back.playlists.len(), 3);
+		assert_eq!(read_back.playlists[1].songs, vec!["/home/user/Music/d.mp3"]);
+	}
+
+	#[test]
+	fn no_install_writes_nothing() {
+		let mut data = default_persistent_data();
+		data.no_install = true;
+		let path = temp_file_path("no_install_data.txt");
+		write_internal_data(&path, &data).unwrap();
+		assert!(!std::path::Path::new(&path).exists());
+	}
+}
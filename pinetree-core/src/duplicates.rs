@@ -0,0 +1,524 @@
+/*
+ * Pinetree duplicate finder. Finds songs that are in the library more than once, in three ways:
+ * - the same file: byte for byte identical, found by size and then by a hash of the contents
+ * - the same recording: a copy that was encoded or tagged differently. Songs of about the same length whose
+ *   loudness envelopes (the ones the waveform seek bar shows) follow each other closely
+ * - the same song: artist and title match once case, spaces and punctuation are ignored
+ *
+ * Each group suggests keeping the copy with the highest bitrate, then the one with the most tags. Removing the
+ * other copies deletes their files, points playlists at the copy that is kept and adds their play history to it.
+ * Paths that lead to the same file (e.g. through a symlink) are never copies of each other, and identical files are
+ * compared byte for byte before one gets deleted, the hash alone isn't proof enough.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hasher;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::audio_frontend;
+use crate::index;
+use crate::library;
+use crate::playlist::Playlist;
+use crate::tags;
+use crate::waveform;
+
+/* Copies of the same recording can be this much longer or shorter, encoders pad the ends differently */
+const LENGTH_TOLERANCE_MS: u64 = 2000;
+/* How closely two loudness envelopes have to follow each other (correlation, 1.0 is identical) */
+const MIN_ENVELOPE_CORRELATION: f32 = 0.95;
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum DuplicateKind {
+	SameFile,
+	SameRecording,
+	SameTags,
+}
+
+pub fn kind_to_str(kind: DuplicateKind) -> &'static str {
+	match kind {
+		DuplicateKind::SameFile => "Identical files",
+		DuplicateKind::SameRecording => "Same recording",
+		DuplicateKind::SameTags => "Same artist and title",
+	}
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SongCopy {
+	pub path: String,
+	pub size: u64,
+	pub length_ms: u64,
+	/* Average, worked out from the size of the audio and the length */
+	pub bitrate_kbps: u64,
+	/* How many of title, artist, album, album artist, year and track are filled in */
+	pub tag_count: usize,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct DuplicateGroup {
+	pub kind: DuplicateKind,
+	pub copies: Vec<SongCopy>,
+	/* Position in copies of the one suggested to keep */
+	pub best: usize,
+	/* Position in copies of the one that will be kept, the best one unless picked otherwise */
+	pub keep: usize,
+}
+
+pub fn content_hash(song: &str) -> Option<u64> {
+	let mut file = std::fs::File::open(song).ok()?;
+	let mut hasher = std::hash::DefaultHasher::new();
+	let mut buffer = vec![0u8; 64 * 1024];
+	loop {
+		let read = file.read(&mut buffer).ok()?;
+		if read == 0 {
+			break;
+		}
+		hasher.write(&buffer[..read]);
+	}
+	Some(hasher.finish())
+}
+
+/* Whether both paths lead to the same file on disk, e.g. one is a symlink to the other or to its folder */
+pub fn same_file_on_disk(a: &str, b: &str) -> bool {
+	match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+		(Ok(a), Ok(b)) => a == b,
+		/* Can't tell, so don't treat them as different files */
+		_ => true,
+	}
+}
+
+/* Whether the two files have exactly the same contents */
+pub fn same_contents(a: &str, b: &str) -> bool {
+	let (Ok(mut file_a), Ok(mut file_b)) = (std::fs::File::open(a), std::fs::File::open(b)) else {return false;};
+	let mut buffer_a = vec![0u8; 64 * 1024];
+	let mut buffer_b = vec![0u8; 64 * 1024];
+	loop {
+		let Ok(read) = file_a.read(&mut buffer_a) else {return false;};
+		if read == 0 {
+			return file_b.read(&mut buffer_b[..1]).is_ok_and(|read| read == 0);
+		}
+		if file_b.read_exact(&mut buffer_b[..read]).is_err() || buffer_a[..read] != buffer_b[..read] {
+			return false;
+		}
+	}
+}
+
+/**
+ * Groups of identical files. Only files of the same size get hashed. A path that leads to a file already seen under
+ * another path is left out, it's the same file and not a copy.
+ */
+pub fn same_files(songs: &[String], done: &AtomicU32) -> Vec<Vec<String>> {
+	let mut by_size = HashMap::<u64, Vec<&String>>::new();
+	let mut seen = HashSet::<std::path::PathBuf>::new();
+	for song in songs {
+		if let Ok(real_path) = std::fs::canonicalize(song) && !seen.insert(real_path) {
+			done.fetch_add(1, Ordering::Relaxed);
+			continue;
+		}
+		if let Ok(metadata) = std::fs::metadata(song) {
+			by_size.entry(metadata.len()).or_default().push(song);
+		}
+	}
+	let mut groups = Vec::new();
+	for same_size in by_size.into_values() {
+		if same_size.len() < 2 {
+			done.fetch_add(same_size.len() as u32, Ordering::Relaxed);
+			continue;
+		}
+		let mut by_hash = HashMap::<u64, Vec<String>>::new();
+		for song in same_size {
+			if let Some(hash) = content_hash(song) {
+				by_hash.entry(hash).or_default().push(song.clone());
+			}
+			done.fetch_add(1, Ordering::Relaxed);
+		}
+		groups.extend(by_hash.into_values().filter(|group| group.len() > 1));
+	}
+	groups
+}
+
+/**
+ * How closely two envelopes follow each other, from -1.0 to 1.0. None if either is flat (e.g. silence), since that
+ * says nothing about the recording.
+ */
+pub fn envelope_correlation(a: &[f32], b: &[f32]) -> Option<f32> {
+	if a.len() != b.len() || a.is_empty() {
+		return None;
+	}
+	let mean = |levels: &[f32]| levels.iter().sum::<f32>() / levels.len() as f32;
+	let (mean_a, mean_b) = (mean(a), mean(b));
+	let (mut covariance, mut variance_a, mut variance_b) = (0.0, 0.0, 0.0);
+	for (level_a, level_b) in a.iter().zip(b) {
+		covariance += (level_a - mean_a) * (level_b - mean_b);
+		variance_a += (level_a - mean_a) * (level_a - mean_a);
+		variance_b += (level_b - mean_b) * (level_b - mean_b);
+	}
+	if variance_a < 1e-6 || variance_b < 1e-6 {
+		return None;
+	}
+	Some(covariance / (variance_a * variance_b).sqrt())
+}
+
+fn find_set(sets: &mut [usize], i: usize) -> usize {
+	let mut root = i;
+	while sets[root] != root {
+		root = sets[root];
+	}
+	sets[i] = root;
+	root
+}
+
+/**
+ * Groups of the same recording among songs with a length and a loudness envelope. Only songs of about the same
+ * length get compared, so this doesn't compare every song with every other one.
+ */
+pub fn same_recordings(songs: &[(String, u64, Vec<f32>)]) -> Vec<Vec<String>> {
+	let mut order: Vec<usize> = (0..songs.len()).collect();
+	order.sort_by_key(|i| songs[*i].1);
+	let mut sets: Vec<usize> = (0..songs.len()).collect();
+	for (position, i) in order.iter().enumerate() {
+		for j in order[position + 1..].iter().take_while(|j| songs[**j].1 - songs[*i].1 <= LENGTH_TOLERANCE_MS) {
+			if envelope_correlation(&songs[*i].2, &songs[*j].2).is_some_and(|correlation| correlation >= MIN_ENVELOPE_CORRELATION) {
+				let (set_i, set_j) = (find_set(&mut sets, *i), find_set(&mut sets, *j));
+				sets[set_j] = set_i;
+			}
+		}
+	}
+	let mut groups = HashMap::<usize, Vec<String>>::new();
+	for (i, (song, _, _)) in songs.iter().enumerate() {
+		let set = find_set(&mut sets, i);
+		groups.entry(set).or_default().push(song.clone());
+	}
+	groups.into_values().filter(|group| group.len() > 1).collect()
+}
+
+fn normalize_tag(tag: &str) -> String {
+	tag.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+/* Artist and title with everything but letters and digits left out, None without both */
+pub fn tag_key(song: &index::IndexedSong) -> Option<(String, String)> {
+	let artist = normalize_tag(if song.artist.is_empty() {&song.album_artist} else {&song.artist});
+	let title = normalize_tag(&song.title);
+	if artist.is_empty() || title.is_empty() {None} else {Some((artist, title))}
+}
+
+pub fn same_tags(songs: &[index::IndexedSong]) -> Vec<Vec<String>> {
+	let mut by_key = HashMap::<(String, String), Vec<String>>::new();
+	for song in songs {
+		if let Some(key) = tag_key(song) {
+			by_key.entry(key).or_default().push(song.path.clone());
+		}
+	}
+	by_key.into_values().filter(|group| group.len() > 1).collect()
+}
+
+pub fn song_copy(song: &index::IndexedSong, length_ms: u64) -> SongCopy {
+	let size = std::fs::metadata(&song.path).map(|metadata| metadata.len()).unwrap_or(0);
+	let mut header = [0u8; 10];
+	let tag_len = std::fs::File::open(&song.path).and_then(|mut file| file.read_exact(&mut header)).map(|_| tags::id3_tag_len(&header)).unwrap_or(0);
+	/* Bits per millisecond is kilobits per second */
+	let bitrate_kbps = (size.saturating_sub(tag_len as u64) * 8).checked_div(length_ms).unwrap_or(0);
+	let tag_count = [&song.title, &song.artist, &song.album, &song.album_artist, &song.year].iter().filter(|tag| !tag.is_empty()).count()
+		+ if song.track > 0 {1} else {0};
+	SongCopy {path: song.path.clone(), size, length_ms, bitrate_kbps, tag_count}
+}
+
+/**
+ * The copy to keep: the highest bitrate, then the most tags, then the shortest path.
+ */
+pub fn best_copy(copies: &[SongCopy]) -> usize {
+	copies.iter().enumerate()
+		.max_by(|(_, a), (_, b)| (a.bitrate_kbps, a.tag_count).cmp(&(b.bitrate_kbps, b.tag_count)).then(b.path.len().cmp(&a.path.len())).then(b.path.cmp(&a.path)))
+		.map(|(i, _)| i)
+		.unwrap_or(0)
+}
+
+/**
+ * Adds the groups found one way, leaving out any whose songs were all found together already.
+ */
+fn add_groups(groups: &mut Vec<DuplicateGroup>, kind: DuplicateKind, found: Vec<Vec<String>>, copies: &HashMap<String, SongCopy>) {
+	for mut songs in found {
+		if groups.iter().any(|group| songs.iter().all(|song| group.copies.iter().any(|copy| copy.path == *song))) {
+			continue;
+		}
+		songs.sort();
+		let copies: Vec<SongCopy> = songs.iter().filter_map(|song| copies.get(song).cloned()).collect();
+		let best = best_copy(&copies);
+		groups.push(DuplicateGroup {kind, copies, best, keep: best});
+	}
+}
+
+/**
+ * Finds the duplicates among the songs. Lengths and envelopes come from the library where it has them, the rest
+ * get worked out here and stored in it. Counts two steps per song in done.
+ */
+pub fn find_duplicates(songs: &[index::IndexedSong], library: &Mutex<library::Library>, done: &AtomicU32) -> Vec<DuplicateGroup> {
+	let paths: Vec<String> = songs.iter().map(|song| song.path.clone()).collect();
+	let identical = same_files(&paths, done);
+	done.store(songs.len() as u32, Ordering::Relaxed);
+
+	let lengths: Vec<u64> = songs.iter().map(|song| {
+		let known = library.lock().ok().and_then(|library| library.songs.get(&song.path).map(|record| record.length_ms)).unwrap_or(0);
+		if known > 0 {known} else {audio_frontend::get_song_len_ms(&song.path) as u64}
+	}).collect();
+	let copies: HashMap<String, SongCopy> = songs.iter().zip(&lengths).map(|(song, length)| (song.path.clone(), song_copy(song, *length))).collect();
+
+	/* Only songs with another one of about the same length are worth decoding */
+	let mut sorted_lengths: Vec<u64> = lengths.iter().copied().filter(|length| *length > 0).collect();
+	sorted_lengths.sort_unstable();
+	let has_neighbour = |length: u64| {
+		let start = sorted_lengths.partition_point(|other| *other + LENGTH_TOLERANCE_MS < length);
+		sorted_lengths[start..].iter().take_while(|other| **other <= length + LENGTH_TOLERANCE_MS).count() > 1
+	};
+	let identical_songs: HashSet<&String> = identical.iter().flat_map(|group| group.iter().skip(1)).collect();
+	let mut envelopes = Vec::<(String, u64, Vec<f32>)>::new();
+	for (song, length) in songs.iter().zip(&lengths) {
+		/* One file of a group of identical ones stands in for the others */
+		if *length > 0 && has_neighbour(*length) && !identical_songs.contains(&song.path) {
+			let cached = library.lock().ok().and_then(|library| library.songs.get(&song.path).and_then(|record| record.waveform.clone()));
+			let waveform = cached.or_else(|| {
+				let analysed = waveform::analyse_file(&song.path)?;
+				if let Ok(mut library) = library.lock() {
+					library::get_or_create_record(&mut library, &song.path).waveform = Some(analysed.clone());
+				}
+				Some(analysed)
+			});
+			if let Some(waveform) = waveform {
+				envelopes.push((song.path.clone(), *length, waveform.rms));
+			}
+		}
+		done.fetch_add(1, Ordering::Relaxed);
+	}
+
+	let mut groups = Vec::<DuplicateGroup>::new();
+	add_groups(&mut groups, DuplicateKind::SameFile, identical, &copies);
+	add_groups(&mut groups, DuplicateKind::SameRecording, same_recordings(&envelopes), &copies);
+	add_groups(&mut groups, DuplicateKind::SameTags, same_tags(songs), &copies);
+	groups
+}
+
+/**
+ * A duplicate search running in the background, it reads every song in the library.
+ */
+pub struct DuplicateScan {
+	done: AtomicU32,
+	pub total: usize,
+	result: Mutex<Option<Vec<DuplicateGroup>>>,
+}
+
+impl DuplicateScan {
+	pub fn progress(&self) -> f32 {
+		self.done.load(Ordering::Relaxed) as f32 / self.total.max(1) as f32
+	}
+
+	pub fn result(&self) -> Option<Vec<DuplicateGroup>> {
+		self.result.lock().ok().and_then(|result| result.clone())
+	}
+}
+
+pub fn start_duplicate_scan(songs: Vec<index::IndexedSong>, library: Arc<Mutex<library::Library>>) -> Arc<DuplicateScan> {
+	let scan = Arc::new(DuplicateScan {done: AtomicU32::new(0), total: songs.len() * 2, result: Mutex::new(None)});
+	let thread_scan = Arc::clone(&scan);
+	std::thread::spawn(move || {
+		let groups = find_duplicates(&songs, &library, &thread_scan.done);
+		if let Ok(mut result) = thread_scan.result.lock() {
+			*result = Some(groups);
+		}
+	});
+	scan
+}
+
+/**
+ * Points playlists at the kept copy instead of the removed ones. A playlist that already has the kept copy just
+ * loses the removed ones. Returns whether any playlist changed.
+ */
+pub fn replace_in_playlists(playlists: &mut [Playlist], removed: &[String], kept: &str) -> bool {
+	let mut changed = false;
+	for playlist in playlists {
+		if !playlist.songs.iter().any(|song| removed.contains(song)) {
+			continue;
+		}
+		let mut has_kept = playlist.songs.iter().any(|song| song == kept);
+		playlist.songs = std::mem::take(&mut playlist.songs).into_iter()
+			.filter_map(|song| {
+				if !removed.contains(&song) {
+					Some(song)
+				} else if has_kept {
+					None
+				} else {
+					has_kept = true;
+					Some(kept.to_string())
+				}
+			})
+			.collect();
+		changed = true;
+	}
+	changed
+}
+
+/* Adds the play history of a removed copy to the kept one */
+fn merge_record(library: &mut library::Library, removed: &str, kept: &str) {
+	let Some(removed) = library.songs.remove(removed) else {return;};
+	let record = library::get_or_create_record(library, kept);
+	record.play_count += removed.play_count;
+	record.completion_count += removed.completion_count;
+	record.skip_count += removed.skip_count;
+	record.time_listened_ms += removed.time_listened_ms;
+	record.last_played = record.last_played.max(removed.last_played);
+	if record.bookmarks.is_empty() {
+		record.bookmarks = removed.bookmarks;
+	}
+}
+
+/**
+ * Deletes every copy in the group except the one kept, then updates playlists and play history. Copies that are
+ * really the kept file, or of identical files that turn out not to be, are left alone.
+ * Returns the songs that were removed and what went wrong.
+ */
+pub fn remove_copies(group: &DuplicateGroup, playlists: &mut [Playlist], library: &mut library::Library) -> (Vec<String>, Vec<String>) {
+	let Some(kept) = group.copies.get(group.keep) else {return (Vec::new(), Vec::new());};
+	let mut removed = Vec::new();
+	let mut errors = Vec::new();
+	for copy in group.copies.iter().filter(|copy| copy.path != kept.path) {
+		if same_file_on_disk(&copy.path, &kept.path) {
+			errors.push(format!("Left {} alone, it is the same file as {}", copy.path, kept.path));
+			continue;
+		}
+		if group.kind == DuplicateKind::SameFile && !same_contents(&copy.path, &kept.path) {
+			errors.push(format!("Left {} alone, it isn't identical to {}", copy.path, kept.path));
+			continue;
+		}
+		match std::fs::remove_file(&copy.path) {
+			Ok(()) => {
+				merge_record(library, &copy.path, &kept.path);
+				removed.push(copy.path.clone());
+			},
+			Err(err) => errors.push(format!("Could not delete {}: {}", copy.path, err)),
+		}
+	}
+	replace_in_playlists(playlists, &removed, &kept.path);
+	(removed, errors)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn indexed(path: &str, artist: &str, title: &str) -> index::IndexedSong {
+		index::IndexedSong {path: path.to_string(), artist: artist.to_string(), title: title.to_string(), ..Default::default()}
+	}
+
+	fn copy(path: &str, bitrate_kbps: u64, tag_count: usize) -> SongCopy {
+		SongCopy {path: path.to_string(), size: 0, length_ms: 0, bitrate_kbps, tag_count}
+	}
+
+	#[test]
+	fn matches_recordings_and_tags() {
+		let envelope: Vec<f32> = (0..waveform::WAVEFORM_POINTS).map(|i| ((i as f32) / 10.0).sin().abs()).collect();
+		let reencoded: Vec<f32> = envelope.iter().enumerate().map(|(i, level)| level * 0.9 + if i % 2 == 0 {0.01} else {0.0}).collect();
+		let other: Vec<f32> = (0..waveform::WAVEFORM_POINTS).map(|i| ((i as f32) / 3.0).cos().abs()).collect();
+		assert!(envelope_correlation(&envelope, &reencoded).unwrap() > MIN_ENVELOPE_CORRELATION);
+		assert!(envelope_correlation(&envelope, &other).unwrap() < MIN_ENVELOPE_CORRELATION);
+		assert!(envelope_correlation(&envelope, &vec![0.0; waveform::WAVEFORM_POINTS]).is_none());
+
+		let songs = vec![
+			("a.mp3".to_string(), 200_000, envelope.clone()),
+			("b.mp3".to_string(), 201_000, reencoded),
+			/* Same envelope, but far too long to be the same recording */
+			("c.mp3".to_string(), 260_000, envelope),
+			("d.mp3".to_string(), 200_500, other),
+		];
+		let mut groups = same_recordings(&songs);
+		groups[0].sort();
+		assert_eq!(groups, vec![vec!["a.mp3", "b.mp3"]]);
+
+		let songs = vec![indexed("1.mp3", "The Band", "Song (Remastered)"), indexed("2.mp3", "the band", "song remastered"), indexed("3.mp3", "", "Song (Remastered)")];
+		let mut groups = same_tags(&songs);
+		groups[0].sort();
+		assert_eq!(groups, vec![vec!["1.mp3", "2.mp3"]]);
+
+		assert_eq!(best_copy(&[copy("/a/long/path.mp3", 128, 6), copy("/b.mp3", 320, 2), copy("/c.mp3", 320, 5)]), 2);
+		assert_eq!(best_copy(&[copy("/music/x.mp3", 320, 5), copy("/x.mp3", 320, 5)]), 1);
+	}
+
+	#[test]
+	fn removes_copies_and_updates_playlists() {
+		let kept = temp_file_path("dup-kept.mp3");
+		let extra = temp_file_path("dup-extra.mp3");
+		for path in [&kept, &extra] {
+			std::fs::write(path, [0xFF, 0xFB, 0x90, 0x00, 1, 2, 3]).unwrap();
+		}
+		let done = AtomicU32::new(0);
+		let mut found = same_files(&[kept.clone(), extra.clone()], &done);
+		found[0].sort();
+		assert_eq!(found, vec![{let mut both = vec![kept.clone(), extra.clone()]; both.sort(); both}]);
+
+		let group = DuplicateGroup {kind: DuplicateKind::SameFile, copies: vec![copy(&kept, 0, 0), copy(&extra, 0, 0)], best: 0, keep: 0};
+		let mut playlists = vec![
+			Playlist {name: "Only extra".to_string(), songs: vec!["x".to_string(), extra.clone(), "y".to_string()], is_open: false},
+			Playlist {name: "Both".to_string(), songs: vec![kept.clone(), extra.clone()], is_open: false},
+			Playlist {name: "Neither".to_string(), songs: vec!["x".to_string()], is_open: false},
+		];
+		let mut library = library::new_library(None);
+		library::get_or_create_record(&mut library, &extra).play_count = 3;
+		library::get_or_create_record(&mut library, &kept).play_count = 2;
+		let (removed, errors) = remove_copies(&group, &mut playlists, &mut library);
+		let extra_exists = std::path::Path::new(&extra).exists();
+		let _ = std::fs::remove_file(&kept);
+		let _ = std::fs::remove_file(&extra);
+
+		assert_eq!(removed, vec![extra.clone()]);
+		assert!(errors.is_empty());
+		assert!(!extra_exists);
+		assert_eq!(playlists[0].songs, vec!["x".to_string(), kept.clone(), "y".to_string()]);
+		assert_eq!(playlists[1].songs, vec![kept.clone()]);
+		assert_eq!(playlists[2].songs, vec!["x"]);
+		assert_eq!(library.songs[&kept].play_count, 5);
+		assert!(!library.songs.contains_key(&extra));
+	}
+
+	#[test]
+	fn leaves_the_kept_file_and_changed_files_alone() {
+		let kept = temp_file_path("dup-real.mp3");
+		let changed = temp_file_path("dup-changed.mp3");
+		std::fs::write(&kept, [0xFF, 0xFB, 0x90, 0x00, 1, 2, 3]).unwrap();
+		std::fs::write(&changed, [0xFF, 0xFB, 0x90, 0x00, 1, 2, 4]).unwrap();
+		assert!(same_contents(&kept, &kept));
+		assert!(!same_contents(&kept, &changed));
+
+		/* Say the hashes matched by chance */
+		let group = DuplicateGroup {kind: DuplicateKind::SameFile, copies: vec![copy(&kept, 0, 0), copy(&changed, 0, 0)], best: 0, keep: 0};
+		let mut library = library::new_library(None);
+		let (removed, errors) = remove_copies(&group, &mut [], &mut library);
+		assert!(removed.is_empty());
+		assert_eq!(errors.len(), 1);
+		assert!(std::path::Path::new(&changed).exists());
+
+		#[cfg(unix)]
+		{
+			let link = temp_file_path("dup-link.mp3");
+			let _ = std::fs::remove_file(&link);
+			std::os::unix::fs::symlink(&kept, &link).unwrap();
+			let done = AtomicU32::new(0);
+			assert!(same_files(&[kept.clone(), link.clone()], &done).is_empty());
+
+			/* Keeping the link must not delete the file it points to */
+			let group = DuplicateGroup {kind: DuplicateKind::SameFile, copies: vec![copy(&kept, 0, 0), copy(&link, 0, 0)], best: 1, keep: 1};
+			let (removed, errors) = remove_copies(&group, &mut [], &mut library);
+			assert!(removed.is_empty());
+			assert_eq!(errors.len(), 1);
+			assert!(std::path::Path::new(&kept).exists());
+			let _ = std::fs::remove_file(&link);
+		}
+		let _ = std::fs::remove_file(&kept);
+		let _ = std::fs::remove_file(&changed);
+	}
+}
@@ -0,0 +1,142 @@
+/*
+ * Pinetree engine. Starts the audio thread and everything around it that isn't a front-end.
+ */
+
+use std::sync::{Arc, Mutex, Condvar};
+use std::thread;
+
+use crate::args;
+use crate::audio_frontend::*;
+use crate::config::PersistentData;
+use crate::files::build_full_filepath;
+use crate::index;
+use crate::library;
+#[cfg(target_os = "linux")]
+use crate::mpris;
+use crate::record;
+use crate::remote;
+use crate::roots;
+use crate::scrobble;
+use crate::visualiser;
+use crate::watch;
+use crate::waveform;
+
+/**
+ * Everything a front-end (the GUI or the terminal interface) needs to talk to the audio thread.
+ */
+pub struct Engine {
+	pub audio_message_channel: Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>,
+	pub audio_receive_channel: Arc<(Mutex<Vec<RodioData>>, Condvar)>,
+	pub library: Arc<Mutex<library::Library>>,
+	pub scrobbler: Arc<Mutex<scrobble::Scrobbler>>,
+	/* Songs to analyse for the waveform seek bar, see waveform::request_waveform */
+	pub waveform_request_channel: Arc<(Mutex<Vec<String>>, Condvar)>,
+	/* Copy of the samples being played, for the visualiser */
+	pub sample_tap: Arc<visualiser::SampleTap>,
+	/* Starts and stops recordings of what's playing, see record::start_recording */
+	pub record_tap: Arc<record::RecordTap>,
+	/* Tags of every song in the music folder, filled in by a background scan */
+	pub song_index: Arc<Mutex<index::SongIndex>>,
+	/* Keeps the index and the open folders up to date when files change, see watch::watch_folders */
+	pub folder_watcher: Arc<watch::FolderWatcher>,
+}
+
+/**
+ * Loads the library and starts the audio thread along with everything that controls it from outside the front-end
+ * (scrobble submission, remote control, MPRIS).
+ */
+pub fn start_engine(startup_args: &args::StartupArgs, persistent_data: &PersistentData, installed_location: &str) -> Engine {
+	let message_param: Vec<MessageToAudio> = Vec::<MessageToAudio>::new();
+	let gui_message_param: Vec<RodioData> = Vec::<RodioData>::new();
+	
+	let audio_thread_recieve = Arc::new((Mutex::new(message_param), Condvar::new()));
+	let gui_thread_send = Arc::clone(&audio_thread_recieve);
+	
+	let audio_thread_send = Arc::new((Mutex::new(gui_message_param), Condvar::new()));
+	let gui_thread_recieve = Arc::clone(&audio_thread_send);
+	
+	let library = if persistent_data.data_file_exists {
+		library::init_library_from_filepath(&build_full_filepath(installed_location, library::LIBRARY_FILE_NAME))
+	} else {
+		library::new_library(None)
+	};
+	let library = Arc::new(Mutex::new(library));
+	let audio_thread_library = Arc::clone(&library);
+
+	let mut scrobbler = scrobble::init_scrobbler(if persistent_data.data_file_exists {Some(installed_location.to_string())} else {None});
+	scrobbler.log_format = persistent_data.scrobble_log_format;
+	scrobbler.endpoint = persistent_data.scrobble_endpoint.clone();
+	scrobbler.token = persistent_data.scrobble_token.clone();
+	let scrobbler = Arc::new(Mutex::new(scrobbler));
+	let audio_thread_scrobbler = Arc::clone(&scrobbler);
+	let submitter_scrobbler = Arc::clone(&scrobbler);
+
+	let sample_tap = visualiser::new_sample_tap();
+	let audio_thread_tap = Arc::clone(&sample_tap);
+	let record_tap = record::new_record_tap();
+	let audio_thread_record_tap = Arc::clone(&record_tap);
+
+	thread::spawn(move || {
+		audio_thread_loop(audio_thread_recieve, audio_thread_send, audio_thread_library, audio_thread_scrobbler, audio_thread_tap, audio_thread_record_tap);
+	});
+	thread::spawn(move || {
+		scrobble::submitter_loop(submitter_scrobbler);
+	});
+	let remote_send = Arc::clone(&gui_thread_send);
+	let remote_recieve = Arc::clone(&gui_thread_recieve);
+	let remote_library = Arc::clone(&library);
+	let remote_data_dir = startup_args.data_dir.clone();
+	thread::spawn(move || {
+		remote::remote_server_loop(remote_send, remote_recieve, remote_library, remote_data_dir);
+	});
+	let waveform_request_channel = Arc::new((Mutex::new(Vec::<String>::new()), Condvar::new()));
+	let analyser_requests = Arc::clone(&waveform_request_channel);
+	let analyser_library = Arc::clone(&library);
+	thread::spawn(move || {
+		waveform::analyser_loop(analyser_requests, analyser_library);
+	});
+	let song_index = index::new_song_index();
+	let library_roots = roots::library_roots(persistent_data);
+	if !library_roots.is_empty() {
+		index::start_scan(Arc::clone(&song_index), library_roots.clone());
+	}
+	let folder_watcher = watch::start_watcher(Arc::clone(&song_index), library_roots);
+	#[cfg(target_os = "linux")] {
+		let mpris_send = Arc::clone(&gui_thread_send);
+		let mpris_recieve = Arc::clone(&gui_thread_recieve);
+		let mpris_library = Arc::clone(&library);
+		thread::spawn(move || {
+			mpris::mpris_thread_loop(mpris_send, mpris_recieve, mpris_library);
+		});
+	}
+
+	Engine {
+		audio_message_channel: gui_thread_send,
+		audio_receive_channel: gui_thread_recieve,
+		library,
+		scrobbler,
+		waveform_request_channel,
+		sample_tap,
+		record_tap,
+		song_index,
+		folder_watcher,
+	}
+}
+
+/**
+ * The folder to show at startup. A song given on the command line opens the folder it is in and starts playing,
+ * a folder just gets opened.
+ */
+pub fn find_start_folder(startup_args: &args::StartupArgs, persistent_data: &PersistentData, audio_message_channel: &Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>) -> String {
+	let mut start_folder = persistent_data.default_directory.clone();
+	if let Some(open_path) = &startup_args.open_path {
+		let path = std::path::Path::new(open_path);
+		if path.is_dir() {
+			start_folder = open_path.clone();
+		} else if let Some(parent) = path.parent() && let Some(parent) = parent.to_str() {
+			start_folder = parent.to_string();
+			play_song_in_folder(audio_message_channel, open_path);
+		}
+	}
+	start_folder
+}
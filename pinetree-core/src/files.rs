@@ -0,0 +1,239 @@
+/*
+ * Pinetree file browsing. Filepaths are kept as strings and use the platform separator.
+ */
+
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct Directory {
+	pub filepath_identifier: String,
+	pub subdirectories: Vec<String>,
+	pub songs: Vec<String>
+}
+
+pub fn trim_slash_if_exists(to_trim: &str) -> &str {
+	if to_trim.ends_with('/') || to_trim.ends_with('\\') {
+		return &to_trim[..to_trim.len() - 1];
+	} else {
+		return &to_trim;
+	}
+}
+
+pub fn build_full_filepath(input_first_half: &str, input_second_half: &str) -> String {
+	let mergeable_first_half = trim_slash_if_exists(input_first_half);
+	#[cfg(target_family = "windows")]
+	return format!("{}\\{}", mergeable_first_half, input_second_half);
+	#[cfg(target_family = "unix")]
+	return format!("{}/{}", mergeable_first_half, input_second_half);
+}
+
+pub fn init_directory_at_filepath(directory_filepath: &str, dir_map: &mut HashMap<String, Directory>) -> bool {
+	let read_result = std::fs::read_dir(directory_filepath);
+	if let Ok(paths) = read_result {
+		let mut songs_vec = Vec::<String>::new();
+		let mut subdirectory_vec = Vec::<String>::new();
+
+		for path in paths {
+			if let Ok(valid_path) = path {
+				if let Ok(file_name) = valid_path.file_name().into_string() {
+
+					if let Ok(file_type) = valid_path.file_type() && file_type.is_dir() {
+						subdirectory_vec.push(build_full_filepath(directory_filepath, &file_name));
+					} else if file_name.ends_with(".mp3") {
+						songs_vec.push(build_full_filepath(directory_filepath, &file_name));
+					}
+
+				}
+			}
+		}
+		/* Is there a more efficient way? This makes copies */
+		songs_vec.sort_by_key(|name| name.to_lowercase());
+		subdirectory_vec.sort_by_key(|name| name.to_lowercase());
+
+		dir_map.insert(directory_filepath.to_string(), Directory {filepath_identifier: directory_filepath.to_string(), subdirectories: subdirectory_vec, songs: songs_vec});
+		return true;
+	} else {
+		return false;
+	}
+}
+
+pub fn init_directory_at_filepath_recursive(directory_filepath: &str, dir_map: &mut HashMap<String, Directory>) -> bool {
+	let read_result = std::fs::read_dir(directory_filepath);
+	if let Ok(paths) = read_result {
+		let mut songs_vec = Vec::<String>::new();
+		let mut subdirectory_vec = Vec::<String>::new();
+		for path in paths {
+			if let Ok(valid_path) = path {
+				if let Ok(file_name) = valid_path.file_name().into_string() {
+					if let Ok(file_type) = valid_path.file_type() && file_type.is_dir() {
+						subdirectory_vec.push(build_full_filepath(directory_filepath, &file_name));
+					} else if file_name.ends_with(".mp3") {
+						songs_vec.push(build_full_filepath(directory_filepath, &file_name));
+					} 
+				}
+			}
+		}
+		/* Is there a more efficient way? This makes copies */
+		songs_vec.sort_by_key(|name| name.to_lowercase());
+		subdirectory_vec.sort_by_key(|name| name.to_lowercase());
+
+		for directory in &subdirectory_vec {
+			init_directory_at_filepath_recursive(directory, dir_map);
+		}
+
+		dir_map.insert(directory_filepath.to_string(), Directory {filepath_identifier: directory_filepath.to_string(), subdirectories: subdirectory_vec, songs: songs_vec});
+
+		return true;
+	} else {
+		return false;
+	}
+}
+
+/**
+* Backs up from e.g. ~/Music/ to ~/
+*/
+pub fn song_folder_go_up(input_string: &str) -> String {
+	let last_char = {
+		// This is here because otherwise with an extra slash it will back up to the same folder and just delete the slash.
+		let operated_str = trim_slash_if_exists(input_string);
+		let last_bslash = operated_str.rfind('\\');
+		let last_slash = operated_str.rfind('/');
+
+		match (last_bslash, last_slash) {
+			(Some(bslash), Some(slash)) => {
+				if bslash > slash {bslash} else {slash}
+			}
+			(Some(bslash), None) => bslash,
+			(None, Some(slash)) => slash,
+			(None, None) => 0,
+		}
+	};
+	format!("{}", if last_char == 0 {""} else {&input_string[0..last_char]})
+}
+
+pub fn extract_file_name(input_string: &str) -> &str {
+	let last_bslash = input_string.rfind('\\');
+	let last_slash = input_string.rfind('/');
+
+	let start = match (last_bslash, last_slash) {
+		(Some(bslash), Some(slash)) => {
+			if bslash > slash {bslash} else {slash}
+		}
+		(Some(bslash), None) => bslash,
+		(None, Some(slash)) => slash,
+		(None, None) => 0,
+	};
+	return if start == 0 {input_string} else {&input_string[start + 1..]};
+}
+
+pub fn extract_folder_name(input_string: &str) -> &str {
+	let input_string = if input_string.ends_with('\\') {
+		&input_string[..input_string.len() - 1]
+	} else {
+		input_string
+	};
+	let last_bslash = input_string.rfind('\\');
+	let last_slash = input_string.rfind('/');
+
+	let start = match (last_bslash, last_slash) {
+		(Some(bslash), Some(slash)) => {
+			if bslash > slash {bslash} else {slash}
+		}
+		(Some(bslash), None) => bslash,
+		(None, Some(slash)) => slash,
+		(None, None) => 0,
+	};
+	return if start == 0 {input_string} else {&input_string[start + 1..]};
+}
+
+pub struct DirTreeElement {
+	pub name: String,
+	pub depth: usize,
+	pub is_dir: bool,
+	/* For directories only, indicating whether it is active or not */
+	pub is_active: bool,
+}
+
+pub fn get_dir_tree_elements(output_vec: &mut Vec<DirTreeElement>, directory_string: &str, map: &HashMap<String, Directory>, depth: usize) {
+	let map_result = map.get(directory_string);
+	if let Some(directory) = map_result {
+		for subdirectory_string in &directory.subdirectories {
+			let is_active = match map.get(subdirectory_string) {Some(_) => true, None => false};
+			output_vec.push(DirTreeElement {
+				name: subdirectory_string.to_string(),
+				depth: depth,
+				is_dir: true,
+				is_active: is_active
+			});
+			if is_active {get_dir_tree_elements(output_vec, subdirectory_string, map, depth + 1)};
+		}
+
+		for song_string in &directory.songs {
+			output_vec.push(DirTreeElement {
+				name: song_string.to_string(),
+				depth: depth,
+				is_dir: false,
+				is_active: false,
+			});
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	#[test]
+	fn goes_up_a_folder() {
+		assert_eq!(song_folder_go_up("/home/user/Music"), "/home/user");
+		assert_eq!(song_folder_go_up("/home/user/Music/"), "/home/user");
+		assert_eq!(song_folder_go_up("C:\\Users\\user\\Music"), "C:\\Users\\user");
+		assert_eq!(song_folder_go_up("Music"), "");
+	}
+
+	#[test]
+	fn extracts_names() {
+		assert_eq!(extract_file_name("/home/user/Music/song.mp3"), "song.mp3");
+		assert_eq!(extract_file_name("C:\\Music/mixed\\song.mp3"), "song.mp3");
+		assert_eq!(extract_file_name("song.mp3"), "song.mp3");
+		assert_eq!(extract_folder_name("/home/user/Music"), "Music");
+		assert_eq!(extract_folder_name("C:\\Users\\Music\\"), "Music");
+	}
+
+	#[test]
+	fn builds_filepaths_without_doubled_separators() {
+		let expected = build_full_filepath("folder", "song.mp3");
+		assert_eq!(build_full_filepath("folder/", "song.mp3"), expected);
+		assert_eq!(build_full_filepath("folder\\", "song.mp3"), expected);
+		assert!(expected.starts_with("folder") && expected.ends_with("song.mp3"));
+		assert_eq!(expected.len(), "folder/song.mp3".len());
+	}
+
+	#[test]
+	fn directory_tree_lists_folders_before_songs() {
+		let root = temp_file_path("dir_tree");
+		let sub = build_full_filepath(&root, "Sub");
+		std::fs::create_dir_all(&sub).unwrap();
+		for file in ["b.mp3", "A.mp3", "notes.txt"] {
+			std::fs::write(build_full_filepath(&root, file), "").unwrap();
+		}
+		std::fs::write(build_full_filepath(&sub, "c.mp3"), "").unwrap();
+
+		let mut map = HashMap::<String, Directory>::new();
+		assert!(init_directory_at_filepath(&root, &mut map));
+		let mut tree = Vec::<DirTreeElement>::new();
+		get_dir_tree_elements(&mut tree, &root, &map, 0);
+		let rows: Vec<(&str, usize, bool)> = tree.iter().map(|element| (extract_file_name(&element.name), element.depth, element.is_dir)).collect();
+		assert_eq!(rows, vec![("Sub", 0, true), ("A.mp3", 0, false), ("b.mp3", 0, false)]);
+
+		assert!(init_directory_at_filepath_recursive(&root, &mut map));
+		tree.clear();
+		get_dir_tree_elements(&mut tree, &root, &map, 0);
+		let rows: Vec<(&str, usize)> = tree.iter().map(|element| (extract_file_name(&element.name), element.depth)).collect();
+		assert_eq!(rows, vec![("Sub", 0), ("c.mp3", 1), ("A.mp3", 0), ("b.mp3", 0)]);
+
+		let _ = std::fs::remove_dir_all(&root);
+		assert!(!init_directory_at_filepath(&root, &mut map));
+	}
+}
@@ -0,0 +1,393 @@
+/*
+ * Pinetree FLAC encoder, for recordings and exports. There's no FLAC encoder among the dependencies (claxon
+ * only decodes), and the format is simple enough to write by hand.
+ *
+ * It only does what the format needs to be useful here: fixed size blocks, independent channels, and for every
+ * channel of every block whichever of a constant, a verbatim or a fixed predictor subframe comes out smallest.
+ * Residuals are rice coded in a single partition. That gets most of the way to what the reference encoder's
+ * fastest setting does. Samples are 16 bit.
+ *
+ * STREAMINFO gets filled in at the end, so the writer has to be able to seek. The MD5 signature is left as zeros,
+ * which the format allows and means that it wasn't calculated.
+ *
+ * The tests decode the output with claxon, which checks every frame's CRCs, so the encoder is held to what an
+ * independent decoder accepts.
+ */
+
+use std::io::{Seek, SeekFrom, Write};
+
+pub const BLOCK_SIZE: usize = 4096;
+const BITS_PER_SAMPLE: u32 = 16;
+const MAX_FIXED_ORDER: usize = 4;
+/* Parameter 15 is the escape code with 4 bit parameters */
+const MAX_RICE_PARAMETER: u32 = 14;
+/* "fLaC" plus the metadata block header */
+const STREAMINFO_OFFSET: u64 = 8;
+
+struct BitWriter {
+	bytes: Vec<u8>,
+	accumulator: u64,
+	bit_count: u32,
+}
+
+impl BitWriter {
+	fn new() -> BitWriter {
+		BitWriter {bytes: Vec::new(), accumulator: 0, bit_count: 0}
+	}
+
+	/* Writes the lowest `bits` bits of value, at most 32 */
+	fn write(&mut self, value: u64, bits: u32) {
+		if bits == 0 {
+			return;
+		}
+		self.accumulator = (self.accumulator << bits) | (value & ((1 << bits) - 1));
+		self.bit_count += bits;
+		while self.bit_count >= 8 {
+			self.bit_count -= 8;
+			self.bytes.push((self.accumulator >> self.bit_count) as u8);
+		}
+		self.accumulator &= (1 << self.bit_count) - 1;
+	}
+
+	fn write_unary(&mut self, zeros: u32) {
+		let mut zeros = zeros;
+		while zeros >= 32 {
+			self.write(0, 32);
+			zeros -= 32;
+		}
+		self.write(1, zeros + 1);
+	}
+
+	/* Pads with zeros up to the next byte */
+	fn into_bytes(mut self) -> Vec<u8> {
+		if self.bit_count > 0 {
+			self.write(0, 8 - self.bit_count);
+		}
+		self.bytes
+	}
+}
+
+fn crc8(data: &[u8]) -> u8 {
+	let mut crc: u8 = 0;
+	for byte in data {
+		crc ^= byte;
+		for _ in 0..8 {
+			crc = if crc & 0x80 != 0 {(crc << 1) ^ 0x07} else {crc << 1};
+		}
+	}
+	crc
+}
+
+fn crc16(data: &[u8]) -> u16 {
+	let mut crc: u16 = 0;
+	for byte in data {
+		crc ^= (*byte as u16) << 8;
+		for _ in 0..8 {
+			crc = if crc & 0x8000 != 0 {(crc << 1) ^ 0x8005} else {crc << 1};
+		}
+	}
+	crc
+}
+
+/* Frame numbers are coded like UTF-8, extended up to 36 bits */
+fn write_utf8_number(bits: &mut BitWriter, number: u64) {
+	if number < 0x80 {
+		bits.write(number, 8);
+		return;
+	}
+	let continuation_bytes = match number {
+		0..0x800 => 1,
+		0x800..0x10000 => 2,
+		0x10000..0x200000 => 3,
+		0x200000..0x4000000 => 4,
+		0x4000000..0x80000000 => 5,
+		_ => 6,
+	};
+	/* As many leading ones as there are bytes, then a zero and the top bits of the number */
+	let marker = (0xFFu64 << (7 - continuation_bytes)) & 0xFF;
+	bits.write(marker | (number >> (6 * continuation_bytes)), 8);
+	for i in (0..continuation_bytes).rev() {
+		bits.write(0x80 | ((number >> (6 * i)) & 0x3F), 8);
+	}
+}
+
+fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i32> {
+	(order..samples.len()).map(|i| {
+		let s = |back: usize| samples[i - back];
+		match order {
+			0 => s(0),
+			1 => s(0) - s(1),
+			2 => s(0) - 2 * s(1) + s(2),
+			3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+			_ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+		}
+	}).collect()
+}
+
+fn fold(residual: i32) -> u64 {
+	((residual << 1) ^ (residual >> 31)) as u32 as u64
+}
+
+/* The cheapest rice parameter for the residuals, and how many bits they take with it */
+fn best_rice_parameter(residuals: &[i32]) -> (u32, u64) {
+	let folded: Vec<u64> = residuals.iter().map(|residual| fold(*residual)).collect();
+	(0..=MAX_RICE_PARAMETER)
+		.map(|parameter| (parameter, folded.iter().map(|value| (value >> parameter) + 1 + parameter as u64).sum::<u64>()))
+		.min_by_key(|(_, bits)| *bits)
+		.unwrap_or((0, 0))
+}
+
+fn write_subframe(bits: &mut BitWriter, samples: &[i32]) {
+	if samples.iter().all(|sample| *sample == samples[0]) {
+		bits.write(0b0000_0000, 8);
+		bits.write(samples[0] as u64, BITS_PER_SAMPLE);
+		return;
+	}
+	let verbatim_bits = samples.len() as u64 * BITS_PER_SAMPLE as u64;
+	let best_fixed = (0..=MAX_FIXED_ORDER.min(samples.len().saturating_sub(1)))
+		.map(|order| {
+			let residuals = fixed_residuals(samples, order);
+			let (parameter, residual_bits) = best_rice_parameter(&residuals);
+			let total_bits = order as u64 * BITS_PER_SAMPLE as u64 + 10 + residual_bits;
+			(order, residuals, parameter, total_bits)
+		})
+		.min_by_key(|(_, _, _, total_bits)| *total_bits);
+	match best_fixed {
+		Some((order, residuals, parameter, total_bits)) if total_bits < verbatim_bits => {
+			bits.write(0b0001_0000 | (order as u64) << 1, 8);
+			for sample in &samples[..order] {
+				bits.write(*sample as u64, BITS_PER_SAMPLE);
+			}
+			/* Rice coding with 4 bit parameters, partition order 0 */
+			bits.write(0, 2);
+			bits.write(0, 4);
+			bits.write(parameter as u64, 4);
+			for residual in residuals {
+				let value = fold(residual);
+				bits.write_unary((value >> parameter) as u32);
+				bits.write(value, parameter);
+			}
+		},
+		_ => {
+			bits.write(0b0000_0010, 8);
+			for sample in samples {
+				bits.write(*sample as u64, BITS_PER_SAMPLE);
+			}
+		},
+	}
+}
+
+/**
+ * Encodes one frame. `channels` holds the samples of every channel, all the same length.
+ */
+pub fn encode_frame(frame_number: u64, channels: &[Vec<i32>]) -> Vec<u8> {
+	let block_size = channels.first().map(|samples| samples.len()).unwrap_or(0);
+	let mut header = BitWriter::new();
+	/* Sync code, fixed block size */
+	header.write(0xFFF8, 16);
+	/* Block size as a 16 bit number at the end of the header, sample rate from STREAMINFO */
+	header.write(0b0111, 4);
+	header.write(0b0000, 4);
+	/* Independent channels, 16 bits per sample */
+	header.write(channels.len() as u64 - 1, 4);
+	header.write(0b100, 3);
+	header.write(0, 1);
+	write_utf8_number(&mut header, frame_number);
+	header.write(block_size as u64 - 1, 16);
+	let mut bytes = header.into_bytes();
+	bytes.push(crc8(&bytes));
+
+	let mut body = BitWriter::new();
+	for samples in channels {
+		write_subframe(&mut body, samples);
+	}
+	bytes.extend_from_slice(&body.into_bytes());
+	let crc = crc16(&bytes);
+	bytes.extend_from_slice(&crc.to_be_bytes());
+	bytes
+}
+
+pub struct FlacWriter<W: Write + Seek> {
+	writer: W,
+	channels: u16,
+	sample_rate: u32,
+	/* Interleaved samples that don't make up a full block yet */
+	pending: Vec<i32>,
+	frame_number: u64,
+	total_frames: u64,
+	min_frame_bytes: usize,
+	max_frame_bytes: usize,
+}
+
+fn streaminfo(flac: &FlacWriter<impl Write + Seek>) -> Vec<u8> {
+	let mut bits = BitWriter::new();
+	bits.write(BLOCK_SIZE as u64, 16);
+	bits.write(BLOCK_SIZE as u64, 16);
+	bits.write(flac.min_frame_bytes as u64, 24);
+	bits.write(flac.max_frame_bytes as u64, 24);
+	bits.write(flac.sample_rate as u64, 20);
+	bits.write(flac.channels as u64 - 1, 3);
+	bits.write(BITS_PER_SAMPLE as u64 - 1, 5);
+	bits.write(flac.total_frames >> 32, 4);
+	bits.write(flac.total_frames, 32);
+	let mut bytes = bits.into_bytes();
+	/* Unknown MD5 */
+	bytes.extend_from_slice(&[0; 16]);
+	bytes
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+	pub fn new(writer: W, channels: u16, sample_rate: u32) -> std::io::Result<FlacWriter<W>> {
+		let flac = FlacWriter {
+			writer,
+			channels: channels.clamp(1, 8),
+			sample_rate,
+			pending: Vec::new(),
+			frame_number: 0,
+			total_frames: 0,
+			min_frame_bytes: 0,
+			max_frame_bytes: 0,
+		};
+		let mut header = b"fLaC".to_vec();
+		/* The only metadata block, so it's the last one. Type 0 is STREAMINFO */
+		header.extend_from_slice(&[0x80, 0, 0, 34]);
+		header.extend_from_slice(&streaminfo(&flac));
+		let mut flac = flac;
+		flac.writer.write_all(&header)?;
+		Ok(flac)
+	}
+
+	/* Takes interleaved samples */
+	pub fn write_samples(&mut self, samples: &[i16]) -> std::io::Result<()> {
+		self.pending.extend(samples.iter().map(|sample| *sample as i32));
+		let block_samples = BLOCK_SIZE * self.channels as usize;
+		while self.pending.len() >= block_samples {
+			let block: Vec<i32> = self.pending.drain(..block_samples).collect();
+			self.write_block(&block)?;
+		}
+		Ok(())
+	}
+
+	fn write_block(&mut self, interleaved: &[i32]) -> std::io::Result<()> {
+		let channel_count = self.channels as usize;
+		let channels: Vec<Vec<i32>> = (0..channel_count)
+			.map(|channel| interleaved.iter().skip(channel).step_by(channel_count).copied().collect())
+			.collect();
+		let frame = encode_frame(self.frame_number, &channels);
+		self.writer.write_all(&frame)?;
+		self.frame_number += 1;
+		self.total_frames += (interleaved.len() / channel_count) as u64;
+		self.min_frame_bytes = if self.min_frame_bytes == 0 {frame.len()} else {self.min_frame_bytes.min(frame.len())};
+		self.max_frame_bytes = self.max_frame_bytes.max(frame.len());
+		Ok(())
+	}
+
+	/* Writes whatever is left as a last, shorter block and fills in STREAMINFO */
+	pub fn finalize(mut self) -> std::io::Result<W> {
+		/* A partial frame at the end (from a recording stopped mid-frame) gets dropped */
+		let channel_count = self.channels as usize;
+		let usable = self.pending.len() - self.pending.len() % channel_count;
+		if usable > 0 {
+			let block: Vec<i32> = self.pending.drain(..usable).collect();
+			self.write_block(&block)?;
+		}
+		let info = streaminfo(&self);
+		self.writer.seek(SeekFrom::Start(STREAMINFO_OFFSET))?;
+		self.writer.write_all(&info)?;
+		self.writer.seek(SeekFrom::End(0))?;
+		self.writer.flush()?;
+		Ok(self.writer)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use rodio::Source;
+
+	fn decode(bytes: Vec<u8>) -> (u16, u32, Vec<i16>) {
+		let decoder = rodio::Decoder::new_flac(std::io::Cursor::new(bytes)).unwrap();
+		(decoder.channels(), decoder.sample_rate(), decoder.collect())
+	}
+
+	#[test]
+	fn encoded_audio_decodes_to_the_same_samples() {
+		/* A bit over two blocks of a stereo tone with some noise, so every kind of subframe gets a chance */
+		let mut seed: u32 = 1;
+		let mut samples = Vec::<i16>::new();
+		for i in 0..(BLOCK_SIZE * 2 + 1234) {
+			seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+			let tone = ((i as f32 * 0.05).sin() * 12000.0) as i16;
+			samples.push(tone);
+			samples.push(tone / 2 + (seed >> 20) as i16 - 2048);
+		}
+		/* And a silent stretch */
+		samples.extend(std::iter::repeat_n(0, BLOCK_SIZE * 2));
+
+		let mut flac = FlacWriter::new(std::io::Cursor::new(Vec::new()), 2, 44100).unwrap();
+		for chunk in samples.chunks(1000) {
+			flac.write_samples(chunk).unwrap();
+		}
+		let bytes = flac.finalize().unwrap().into_inner();
+		assert!(bytes.len() < samples.len() * 2, "{} bytes", bytes.len());
+		let (channels, sample_rate, decoded) = decode(bytes);
+		assert_eq!(channels, 2);
+		assert_eq!(sample_rate, 44100);
+		assert!(decoded == samples);
+	}
+
+	/* Decodes with claxon directly, which also checks the frame checksums and reads STREAMINFO */
+	fn decode_checked(bytes: Vec<u8>) -> (claxon::metadata::StreamInfo, Vec<Vec<i32>>) {
+		let mut reader = claxon::FlacReader::new(std::io::Cursor::new(bytes)).unwrap();
+		let info = reader.streaminfo();
+		let mut channels = vec![Vec::new(); info.channels as usize];
+		let mut blocks = reader.blocks();
+		let mut buffer = Vec::new();
+		while let Some(block) = blocks.read_next_or_eof(buffer).unwrap() {
+			for (channel, samples) in channels.iter_mut().enumerate() {
+				samples.extend_from_slice(block.channel(channel as u32));
+			}
+			buffer = block.into_buffer();
+		}
+		(info, channels)
+	}
+
+	#[test]
+	fn hard_to_code_audio_round_trips() {
+		/* Full scale square waves, the worst case for the predictors, then a single sample in the last block */
+		let mut samples = Vec::<i16>::new();
+		for i in 0..BLOCK_SIZE {
+			samples.push(if i % 2 == 0 { i16::MAX } else { i16::MIN });
+		}
+		for i in 0..BLOCK_SIZE {
+			samples.push(if (i / 7) % 2 == 0 { i16::MIN } else { i16::MAX });
+		}
+		samples.push(-1);
+
+		let mut flac = FlacWriter::new(std::io::Cursor::new(Vec::new()), 1, 48000).unwrap();
+		flac.write_samples(&samples).unwrap();
+		let (info, channels) = decode_checked(flac.finalize().unwrap().into_inner());
+		assert_eq!((info.channels, info.sample_rate, info.bits_per_sample), (1, 48000, 16));
+		assert_eq!(info.samples, Some(samples.len() as u64));
+		assert_eq!(info.max_block_size, BLOCK_SIZE as u16);
+		assert_eq!(channels[0], samples.iter().map(|sample| *sample as i32).collect::<Vec<i32>>());
+	}
+
+	#[test]
+	fn empty_recordings_are_valid() {
+		let flac = FlacWriter::new(std::io::Cursor::new(Vec::new()), 2, 44100).unwrap();
+		let (info, channels) = decode_checked(flac.finalize().unwrap().into_inner());
+		/* A total of 0 in STREAMINFO reads as unknown */
+		assert!(info.samples.unwrap_or(0) == 0);
+		assert!(channels.iter().all(|samples| samples.is_empty()));
+	}
+
+	#[test]
+	fn codes_large_frame_numbers() {
+		for (number, expected) in [(0x7Fu64, vec![0x7F]), (0x80, vec![0xC2, 0x80]), (0x10FFFF, vec![0xF4, 0x8F, 0xBF, 0xBF])] {
+			let mut bits = BitWriter::new();
+			write_utf8_number(&mut bits, number);
+			assert_eq!(bits.into_bytes(), expected);
+		}
+	}
+}
@@ -0,0 +1,250 @@
+/*
+ * Pinetree song index. The tags of every song in the library roots, so songs can be browsed by artist and album
+ * instead of by folder.
+ *
+ * Reading the tags of a whole music collection takes a while, so the scan runs on its own thread and the index
+ * fills up while it goes. Songs that were already indexed aren't read again when the roots get rescanned.
+ */
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::roots;
+use crate::tags;
+use crate::track_table;
+
+/* How many newly read songs to collect before handing them to the index */
+const SCAN_BATCH_SIZE: usize = 50;
+
+#[derive(Clone)]
+#[derive(Default)]
+#[derive(Debug)]
+pub struct IndexedSong {
+	pub path: String,
+	pub title: String,
+	pub artist: String,
+	pub album_artist: String,
+	pub album: String,
+	pub year: String,
+	/* 0 when the song has no disc or track number */
+	pub disc: u32,
+	pub track: u32,
+}
+
+pub struct SongIndex {
+	/* Keyed by path */
+	pub songs: BTreeMap<String, IndexedSong>,
+	pub scanning: bool,
+	/* Goes up every time songs are added or removed, so views know when to rebuild */
+	pub generation: u64,
+}
+
+pub fn new_song_index() -> Arc<Mutex<SongIndex>> {
+	Arc::new(Mutex::new(SongIndex {songs: BTreeMap::new(), scanning: false, generation: 0}))
+}
+
+pub fn index_song(song: &str) -> IndexedSong {
+	let song_tags = tags::read_tags(song);
+	IndexedSong {
+		path: song.to_string(),
+		title: song_tags.title,
+		artist: song_tags.artist,
+		album_artist: song_tags.album_artist,
+		album: song_tags.album,
+		year: song_tags.year,
+		disc: track_table::parse_track_number(&song_tags.disc),
+		track: track_table::parse_track_number(&song_tags.track),
+	}
+}
+
+/**
+ * Indexes every song in the library roots and drops songs that are gone or no longer in any root. Songs of a root
+ * that can't be read (e.g. an unmounted NAS) are kept until it can be read again.
+ */
+pub fn scan_roots(index: &Mutex<SongIndex>, library_roots: &[roots::LibraryRoot]) {
+	let mut found = HashSet::<String>::new();
+	let mut unreadable = Vec::<&str>::new();
+	for root in library_roots {
+		match roots::root_songs(library_roots, root) {
+			Ok(songs) => found.extend(songs),
+			Err(err) => {
+				println!("Error in scan_roots: {}", err);
+				unreadable.push(&root.path);
+			},
+		}
+	}
+	let to_read: Vec<String> = {
+		let mut index = index.lock().unwrap();
+		index.scanning = true;
+		let before = index.songs.len();
+		index.songs.retain(|path, _| found.contains(path) || roots::root_of(library_roots, path).is_some_and(|root| unreadable.contains(&root.path.as_str())));
+		if index.songs.len() != before {
+			index.generation += 1;
+		}
+		let mut to_read: Vec<String> = found.into_iter().filter(|song| !index.songs.contains_key(song)).collect();
+		to_read.sort();
+		to_read
+	};
+	for batch in to_read.chunks(SCAN_BATCH_SIZE) {
+		let songs: Vec<IndexedSong> = batch.iter().map(|song| index_song(song)).collect();
+		let mut index = index.lock().unwrap();
+		for song in songs {
+			index.songs.insert(song.path.clone(), song);
+		}
+		index.generation += 1;
+	}
+	index.lock().unwrap().scanning = false;
+}
+
+/**
+ * Reads the tags of songs that are in the index again, after they were edited.
+ */
+pub fn reindex_songs(index: &Mutex<SongIndex>, songs: &[String]) {
+	let mut index = index.lock().unwrap();
+	for song in songs {
+		if index.songs.contains_key(song) {
+			index.songs.insert(song.clone(), index_song(song));
+		}
+	}
+	index.generation += 1;
+}
+
+/**
+ * Applies what a folder watcher saw: drops the removed songs and reads the tags of new or rewritten ones.
+ */
+pub fn update_songs(index: &Mutex<SongIndex>, removed: &[String], to_read: &[String]) {
+	let songs: Vec<IndexedSong> = to_read.iter().map(|song| index_song(song)).collect();
+	let mut index = index.lock().unwrap();
+	for song in removed {
+		index.songs.remove(song);
+	}
+	for song in songs {
+		index.songs.insert(song.path.clone(), song);
+	}
+	index.generation += 1;
+}
+
+pub fn songs_under(index: &SongIndex, folder: &str) -> Vec<String> {
+	index.songs.keys().filter(|song| roots::is_under(song, folder)).cloned().collect()
+}
+
+pub fn start_scan(index: Arc<Mutex<SongIndex>>, library_roots: Vec<roots::LibraryRoot>) {
+	thread::spawn(move || {
+		scan_roots(&index, &library_roots);
+	});
+}
+
+/**
+ * Who an album is listed under: the album artist if there is one, so compilations stay together.
+ */
+pub fn album_artist(song: &IndexedSong) -> &str {
+	if !song.album_artist.trim().is_empty() {
+		&song.album_artist
+	} else if !song.artist.trim().is_empty() {
+		&song.artist
+	} else {
+		"Unknown Artist"
+	}
+}
+
+pub fn album_name(song: &IndexedSong) -> &str {
+	if song.album.trim().is_empty() {"Unknown Album"} else {&song.album}
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct Album {
+	pub artist: String,
+	pub name: String,
+	pub year: String,
+	/* In play order: by disc, then by track number */
+	pub songs: Vec<IndexedSong>,
+}
+
+pub fn album_paths(album: &Album) -> Vec<String> {
+	album.songs.iter().map(|song| song.path.clone()).collect()
+}
+
+/* Songs without a title show their file name */
+pub fn display_title(song: &IndexedSong) -> &str {
+	if song.title.trim().is_empty() {
+		std::path::Path::new(&song.path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(&song.path)
+	} else {
+		&song.title
+	}
+}
+
+/**
+ * Artists with how many albums they have, sorted by name.
+ */
+pub fn artists(index: &SongIndex) -> Vec<(String, usize)> {
+	let mut album_counts = BTreeMap::<String, (String, HashSet<String>)>::new();
+	for song in index.songs.values() {
+		let artist = album_artist(song);
+		album_counts.entry(artist.to_lowercase())
+			.or_insert_with(|| (artist.to_string(), HashSet::new()))
+			.1.insert(album_name(song).to_lowercase());
+	}
+	album_counts.into_values().map(|(artist, albums)| (artist, albums.len())).collect()
+}
+
+/**
+ * Albums of one artist sorted by year, or of every artist sorted by artist and then year.
+ * Album and artist names are matched without caring about case.
+ */
+pub fn albums(index: &SongIndex, artist: Option<&str>) -> Vec<Album> {
+	let mut grouped = BTreeMap::<(String, String), Vec<&IndexedSong>>::new();
+	for song in index.songs.values() {
+		if artist.is_some_and(|artist| album_artist(song).to_lowercase() != artist.to_lowercase()) {
+			continue;
+		}
+		grouped.entry((album_artist(song).to_lowercase(), album_name(song).to_lowercase())).or_default().push(song);
+	}
+	let mut albums: Vec<Album> = grouped.into_values().map(|mut songs| {
+		songs.sort_by_key(|song| (song.disc, song.track, song.path.to_lowercase()));
+		Album {
+			artist: album_artist(songs[0]).to_string(),
+			name: album_name(songs[0]).to_string(),
+			year: songs.iter().map(|song| song.year.clone()).find(|year| !year.is_empty()).unwrap_or_default(),
+			songs: songs.into_iter().cloned().collect(),
+		}
+	}).collect();
+	albums.sort_by(|a, b| (a.artist.to_lowercase(), &a.year, a.name.to_lowercase()).cmp(&(b.artist.to_lowercase(), &b.year, b.name.to_lowercase())));
+	albums
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn song(path: &str, artist: &str, album_artist: &str, album: &str, disc: u32, track: u32) -> IndexedSong {
+		IndexedSong {path: path.to_string(), artist: artist.to_string(), album_artist: album_artist.to_string(), album: album.to_string(), disc, track, ..Default::default()}
+	}
+
+	fn index_of(songs: Vec<IndexedSong>) -> SongIndex {
+		SongIndex {songs: songs.into_iter().map(|song| (song.path.clone(), song)).collect(), scanning: false, generation: 0}
+	}
+
+	#[test]
+	fn groups_albums_in_play_order() {
+		let index = index_of(vec![
+			song("/m/b/2-01.mp3", "Band", "", "Double", 2, 1),
+			song("/m/b/1-02.mp3", "band", "", "double", 1, 2),
+			song("/m/b/1-01.mp3", "Band", "", "Double", 1, 1),
+			song("/m/v/01.mp3", "Someone", "Various Artists", "Hits", 0, 1),
+			song("/m/v/02.mp3", "Band", "Various Artists", "Hits", 0, 2),
+			song("/m/loose.mp3", "", "", "", 0, 0),
+		]);
+		assert_eq!(artists(&index), vec![("Band".to_string(), 1), ("Unknown Artist".to_string(), 1), ("Various Artists".to_string(), 1)]);
+
+		let band_albums = albums(&index, Some("BAND"));
+		assert_eq!(band_albums.len(), 1);
+		assert_eq!(album_paths(&band_albums[0]), vec!["/m/b/1-01.mp3", "/m/b/1-02.mp3", "/m/b/2-01.mp3"]);
+
+		let all_albums = albums(&index, None);
+		let names: Vec<&str> = all_albums.iter().map(|album| album.name.as_str()).collect();
+		assert_eq!(names, vec!["Double", "Unknown Album", "Hits"]);
+		assert_eq!(all_albums[2].songs.len(), 2);
+	}
+}
@@ -0,0 +1,37 @@
+/*
+ * Pinetree core. Everything that isn't tied to a front-end: the audio thread, the library,
+ * playlists, settings and the ways of controlling playback from outside (remote control, MPRIS).
+ *
+ * by Katelyn Doucette
+ */
+
+pub mod args;
+pub mod art;
+pub mod audio_frontend;
+pub mod chapters;
+pub mod config;
+pub mod duplicates;
+pub mod engine;
+pub mod files;
+pub mod flac;
+pub mod index;
+pub mod library;
+pub mod lyrics;
+#[cfg(target_os = "linux")]
+pub mod mpris;
+pub mod playlist;
+pub mod record;
+pub mod remote;
+pub mod resume;
+pub mod roots;
+pub mod search;
+pub mod scrobble;
+pub mod shortcuts;
+pub mod tag_editor;
+pub mod tags;
+pub mod track_table;
+pub mod visualiser;
+pub mod watch;
+pub mod waveform;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,567 @@
+/*
+ * Pinetree library database. Keeps track of everything Pinetree knows about individual songs
+ * (currently tags, listening statistics, bookmarks and the waveform of the seek bar).
+ *
+ * The database is a plain text file next to the internal data file, following the same format:
+ *
+ * VERSION: version
+ * SONGS
+ * Song: song_filepath
+ * Title: title
+ * Play Count: 3
+ * ...
+ * Song: song_filepath_2
+ * ...
+ * LISTENING
+ * Day 20000: 3600000
+ * ...
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+
+use crate::tags;
+use crate::waveform;
+
+pub const LIBRARY_FILE_NAME: &str = "pinetree_library.txt";
+
+/* Going to the previous bookmark skips one that was passed less than this long ago, like going to the previous song does */
+pub const BOOKMARK_GRACE_MS: usize = 1000;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Bookmark {
+	pub position_ms: usize,
+	pub name: String,
+}
+
+#[derive(Clone)]
+#[derive(Default)]
+pub struct SongRecord {
+	pub title: String,
+	pub artist: String,
+	pub album: String,
+	pub genre: String,
+	pub track: String,
+
+	/* Number of times the song was started */
+	pub play_count: u64,
+	/* Number of times more than half the song was listened to */
+	pub completion_count: u64,
+	/* Number of times the song was moved away from before reaching half way */
+	pub skip_count: u64,
+	/* Seconds since the unix epoch */
+	pub last_played: u64,
+	pub time_listened_ms: u64,
+	/* None until the waveform analyser got to the song */
+	pub waveform: Option<waveform::Waveform>,
+	/* Per-song adjustment of the lyric timestamps, positive shows them later */
+	pub lyrics_offset_ms: i64,
+	/* Sorted by position */
+	pub bookmarks: Vec<Bookmark>,
+	/* 0 until the song was played once */
+	pub length_ms: u64,
+	/* Where a long file was left off, 0 to start from the beginning */
+	pub resume_position_ms: usize,
+	/* Set when a resumed file gets listened to the end, which can take more than one sitting */
+	pub finished: bool,
+}
+
+pub struct Library {
+	pub songs: HashMap<String, SongRecord>,
+	/* Listening time in milliseconds, keyed by the number of days since the unix epoch (UTC) */
+	pub listening_days: BTreeMap<u64, u64>,
+	/* None means the library only lives in memory (e.g. Pinetree isn't installed) */
+	pub file_path: Option<String>,
+}
+
+pub fn new_library(file_path: Option<String>) -> Library {
+	Library {
+		songs: HashMap::<String, SongRecord>::new(),
+		listening_days: BTreeMap::<u64, u64>::new(),
+		file_path,
+	}
+}
+
+pub fn unix_time_now() -> u64 {
+	if let Ok(since_the_epoch) = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+		return since_the_epoch.as_secs();
+	}
+	0
+}
+
+pub fn unix_time_to_day(unix_time: u64) -> u64 {
+	unix_time / 86400
+}
+
+/**
+ * Loads the library at the given path. A missing or unreadable file gives an empty library
+ * that will still be saved to that path.
+ */
+pub fn init_library_from_filepath(fp: &str) -> Library {
+	let mut library = new_library(Some(fp.to_string()));
+	let file = if let Ok(file) = std::fs::File::open(fp) {
+		file
+	} else {
+		return library;
+	};
+
+	#[derive(PartialEq)]
+	enum State {
+		Version,
+		Songs,
+		Listening,
+	}
+
+	let mut current_state = State::Version;
+	let mut current_song: Option<(String, SongRecord)> = None;
+
+	let reader = std::io::BufReader::new(file);
+	for line in reader.lines().map_while(Result::ok) {
+		if line.starts_with("VERSION: ") {
+			continue;
+		} else if line == "SONGS" {
+			current_state = State::Songs;
+		} else if line == "LISTENING" {
+			current_state = State::Listening;
+		} else if current_state == State::Songs {
+			if let Some(path) = line.strip_prefix("Song: ") {
+				if let Some((path, record)) = current_song.take() {
+					library.songs.insert(path, record);
+				}
+				current_song = Some((path.to_string(), SongRecord::default()));
+			} else if let Some((_, record)) = &mut current_song {
+				if let Some(value) = line.strip_prefix("Title: ") {
+					record.title = value.to_string();
+				} else if let Some(value) = line.strip_prefix("Artist: ") {
+					record.artist = value.to_string();
+				} else if let Some(value) = line.strip_prefix("Album: ") {
+					record.album = value.to_string();
+				} else if let Some(value) = line.strip_prefix("Genre: ") {
+					record.genre = value.to_string();
+				} else if let Some(value) = line.strip_prefix("Track: ") {
+					record.track = value.to_string();
+				} else if let Some(value) = line.strip_prefix("Play Count: ") {
+					record.play_count = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Completions: ") {
+					record.completion_count = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Skips: ") {
+					record.skip_count = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Last Played: ") {
+					record.last_played = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Time Listened: ") {
+					record.time_listened_ms = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Lyrics Offset: ") {
+					record.lyrics_offset_ms = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Length: ") {
+					record.length_ms = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Resume Position: ") {
+					record.resume_position_ms = value.parse().unwrap_or(0);
+				} else if let Some(value) = line.strip_prefix("Finished: ") {
+					record.finished = value == "true";
+				} else if let Some(value) = line.strip_prefix("Bookmark: ") {
+					let (position, name) = value.split_once(' ').unwrap_or((value, ""));
+					if let Ok(position_ms) = position.parse() {
+						add_bookmark(record, position_ms, name);
+					}
+				} else if let Some(value) = line.strip_prefix("Peaks: ")
+				&& let Some(peaks) = waveform::str_to_levels(value) {
+					record.waveform = Some(waveform::Waveform {peaks, rms: Vec::new()});
+				} else if let Some(value) = line.strip_prefix("RMS: ")
+				&& let Some(song_waveform) = &mut record.waveform
+				&& let Some(rms) = waveform::str_to_levels(value) {
+					song_waveform.rms = rms;
+				}
+			}
+		} else if current_state == State::Listening
+		&& let Some(entry) = line.strip_prefix("Day ")
+		&& let Some((day, listened)) = entry.split_once(": ")
+		&& let Ok(day) = day.parse::<u64>()
+		&& let Ok(listened) = listened.parse::<u64>() {
+			library.listening_days.insert(day, listened);
+		}
+	}
+	if let Some((path, record)) = current_song.take() {
+		library.songs.insert(path, record);
+	}
+	library
+}
+
+pub fn write_library(library: &Library) -> Result<(), Box<dyn std::error::Error>> {
+	if let Some(path) = &library.file_path {
+		write_library_contents(path, &library_to_string(library)?)?;
+	}
+	Ok(())
+}
+
+/**
+ * Writes what library_to_string made. Split from it so the library doesn't have to stay locked while the file gets
+ * written.
+ */
+pub fn write_library_contents(path: &str, contents: &str) -> Result<(), Box<dyn std::error::Error>> {
+	use std::io::Write as _;
+
+	let mut file = std::fs::File::create(path)?;
+	file.write_all(contents.as_bytes())?;
+	Ok(())
+}
+
+pub fn library_to_string(library: &Library) -> Result<String, std::fmt::Error> {
+	use std::fmt::Write as _;
+
+	let mut data_to_write = format!("VERSION: {}\n", crate::config::CURRENT_VERSION);
+	data_to_write.push_str("SONGS\n");
+	for (song_path, record) in &library.songs {
+		writeln!(data_to_write, "Song: {}", song_path)?;
+		writeln!(data_to_write, "Title: {}", record.title)?;
+		writeln!(data_to_write, "Artist: {}", record.artist)?;
+		writeln!(data_to_write, "Album: {}", record.album)?;
+		writeln!(data_to_write, "Genre: {}", record.genre)?;
+		writeln!(data_to_write, "Track: {}", record.track)?;
+		writeln!(data_to_write, "Play Count: {}", record.play_count)?;
+		writeln!(data_to_write, "Completions: {}", record.completion_count)?;
+		writeln!(data_to_write, "Skips: {}", record.skip_count)?;
+		writeln!(data_to_write, "Last Played: {}", record.last_played)?;
+		writeln!(data_to_write, "Time Listened: {}", record.time_listened_ms)?;
+		if record.lyrics_offset_ms != 0 {
+			writeln!(data_to_write, "Lyrics Offset: {}", record.lyrics_offset_ms)?;
+		}
+		if record.length_ms != 0 {
+			writeln!(data_to_write, "Length: {}", record.length_ms)?;
+		}
+		if record.resume_position_ms != 0 {
+			writeln!(data_to_write, "Resume Position: {}", record.resume_position_ms)?;
+		}
+		if record.finished {
+			writeln!(data_to_write, "Finished: true")?;
+		}
+		for bookmark in &record.bookmarks {
+			writeln!(data_to_write, "Bookmark: {} {}", bookmark.position_ms, bookmark.name)?;
+		}
+		if let Some(song_waveform) = &record.waveform {
+			writeln!(data_to_write, "Peaks: {}", waveform::levels_to_str(&song_waveform.peaks))?;
+			writeln!(data_to_write, "RMS: {}", waveform::levels_to_str(&song_waveform.rms))?;
+		}
+	}
+	data_to_write.push_str("LISTENING\n");
+	for (day, listened) in &library.listening_days {
+		writeln!(data_to_write, "Day {}: {}", day, listened)?;
+	}
+	Ok(data_to_write)
+}
+
+/**
+ * Gets the record for a song, creating it (and reading its tags) if it doesn't exist yet.
+ */
+pub fn get_or_create_record<'a>(library: &'a mut Library, song: &str) -> &'a mut SongRecord {
+	library.songs.entry(song.to_string()).or_insert_with(|| {
+		let song_tags = tags::read_tags(song);
+		SongRecord {
+			title: song_tags.title,
+			artist: song_tags.artist,
+			album: song_tags.album,
+			genre: song_tags.genre,
+			track: song_tags.track,
+			..Default::default()
+		}
+	})
+}
+
+/**
+ * Reads the tags of a record that has none. The audio thread makes records without reading tags, since it can't wait
+ * on the disk.
+ */
+pub fn fill_missing_tags(record: &mut SongRecord, song: &str) {
+	if !record.title.is_empty() || !record.artist.is_empty() {
+		return;
+	}
+	let song_tags = tags::read_tags(song);
+	record.title = song_tags.title;
+	record.artist = song_tags.artist;
+	record.album = song_tags.album;
+	record.genre = song_tags.genre;
+	record.track = song_tags.track;
+}
+
+pub fn record_play_start(library: &mut Library, song: &str) {
+	let record = get_or_create_record(library, song);
+	fill_missing_tags(record, song);
+	record.play_count += 1;
+	record.last_played = unix_time_now();
+}
+
+/**
+ * Called whenever a song stops being the current song, whether it finished or got skipped.
+ * Anything past the half way point counts as a completion, anything before it counts as a skip.
+ * Without a length there is no half way point, so only the time listened counts.
+ */
+pub fn record_play_finish(library: &mut Library, song: &str, listened_ms: u64, song_length_ms: u64) {
+	let record = get_or_create_record(library, song);
+	record.time_listened_ms += listened_ms;
+	if song_length_ms > 0 && listened_ms * 2 >= song_length_ms {
+		record.completion_count += 1;
+	} else if song_length_ms > 0 {
+		record.skip_count += 1;
+	}
+	*library.listening_days.entry(unix_time_to_day(unix_time_now())).or_insert(0) += listened_ms;
+}
+
+/**
+ * Adds a bookmark, keeping them sorted by position.
+ */
+pub fn add_bookmark(record: &mut SongRecord, position_ms: usize, name: &str) {
+	let index = record.bookmarks.partition_point(|bookmark| bookmark.position_ms <= position_ms);
+	record.bookmarks.insert(index, Bookmark {position_ms, name: name.to_string()});
+}
+
+pub fn next_bookmark(bookmarks: &[Bookmark], position_ms: usize) -> Option<&Bookmark> {
+	bookmarks.iter().find(|bookmark| bookmark.position_ms > position_ms)
+}
+
+pub fn previous_bookmark(bookmarks: &[Bookmark], position_ms: usize) -> Option<&Bookmark> {
+	bookmarks.iter().rev().find(|bookmark| bookmark.position_ms + BOOKMARK_GRACE_MS < position_ms)
+}
+
+/**
+ * Human readable name for a song. Uses the title tag if there is one, otherwise the file name.
+ */
+pub fn display_title(song: &str, record: &SongRecord) -> String {
+	if record.title.is_empty() {
+		let file_name = song.rsplit(['/', '\\']).next().unwrap_or(song);
+		file_name.strip_suffix(".mp3").unwrap_or(file_name).to_string()
+	} else {
+		record.title.clone()
+	}
+}
+
+/**
+ * Returns the (path, record) of the songs with the highest play counts, most played first.
+ */
+pub fn top_tracks(library: &Library, count: usize) -> Vec<(&String, &SongRecord)> {
+	let mut tracks: Vec<(&String, &SongRecord)> = library.songs.iter().filter(|(_, record)| record.play_count > 0).collect();
+	tracks.sort_by(|a, b| b.1.play_count.cmp(&a.1.play_count).then(b.1.time_listened_ms.cmp(&a.1.time_listened_ms)));
+	tracks.truncate(count);
+	tracks
+}
+
+/**
+ * Returns (artist, play count, time listened in ms), most played first.
+ * Songs without an artist tag are grouped together under an empty name.
+ */
+pub fn top_artists(library: &Library, count: usize) -> Vec<(String, u64, u64)> {
+	let mut artist_map = HashMap::<&str, (u64, u64)>::new();
+	for record in library.songs.values() {
+		if record.play_count == 0 {continue;}
+		let entry = artist_map.entry(record.artist.as_str()).or_insert((0, 0));
+		entry.0 += record.play_count;
+		entry.1 += record.time_listened_ms;
+	}
+	let mut artists: Vec<(String, u64, u64)> = artist_map.into_iter().map(|(artist, (plays, listened))| (artist.to_string(), plays, listened)).collect();
+	artists.sort_by(|a, b| b.1.cmp(&a.1).then(b.2.cmp(&a.2)));
+	artists.truncate(count);
+	artists
+}
+
+/**
+ * Converts a day number (days since the unix epoch) to (year, month, day).
+ * This is Howard Hinnant's civil_from_days algorithm.
+ */
+pub fn day_to_date(day: u64) -> (i64, u64, u64) {
+	let z = day as i64 + 719468;
+	let era = z.div_euclid(146097);
+	let day_of_era = z.rem_euclid(146097);
+	let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+	let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+	let mp = (5 * day_of_year + 2) / 153;
+	let day_of_month = (day_of_year - (153 * mp + 2) / 5 + 1) as u64;
+	let month = if mp < 10 {mp + 3} else {mp - 9} as u64;
+	let year = year_of_era + era * 400 + if month <= 2 {1} else {0};
+	(year, month, day_of_month)
+}
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum ListeningPeriod {
+	Day,
+	Week,
+	Month,
+}
+
+/**
+ * Sums up the listening time of the last `count` periods, most recent first.
+ * Each element is (label, time listened in ms). Weeks start on Monday.
+ */
+pub fn listening_time_per_period(library: &Library, period: ListeningPeriod, count: usize) -> Vec<(String, u64)> {
+	let today = unix_time_to_day(unix_time_now());
+	let mut result = Vec::<(String, u64)>::new();
+
+	/* Day 0 (1970-01-01) was a Thursday */
+	let start_of_week = |day: u64| day.saturating_sub((day + 3) % 7);
+	let start_of_month = |day: u64| {
+		let (_, _, day_of_month) = day_to_date(day);
+		day.saturating_sub(day_of_month - 1)
+	};
+
+	let mut period_end = today;
+	for _ in 0..count {
+		let period_start = match period {
+			ListeningPeriod::Day => period_end,
+			ListeningPeriod::Week => start_of_week(period_end),
+			ListeningPeriod::Month => start_of_month(period_end),
+		};
+		let listened: u64 = library.listening_days.range(period_start..=period_end).map(|(_, ms)| *ms).sum();
+		let (year, month, day) = day_to_date(period_start);
+		let label = match period {
+			ListeningPeriod::Day => format!("{}-{:02}-{:02}", year, month, day),
+			ListeningPeriod::Week => format!("Week of {}-{:02}-{:02}", year, month, day),
+			ListeningPeriod::Month => format!("{}-{:02}", year, month),
+		};
+		result.push((label, listened));
+
+		if period_start == 0 {
+			break;
+		}
+		period_end = period_start - 1;
+	}
+	result
+}
+
+/**
+ * Formats a duration as e.g. "3h 25m" or "4m 10s"
+ */
+pub fn format_listening_time(ms: u64) -> String {
+	let secs = ms / 1000;
+	let hours = secs / 3600;
+	let minutes = (secs % 3600) / 60;
+	if hours > 0 {
+		format!("{}h {}m", hours, minutes)
+	} else {
+		format!("{}m {}s", minutes, secs % 60)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	#[test]
+	fn written_library_reads_back() {
+		let path = temp_file_path(LIBRARY_FILE_NAME);
+		let mut library = new_library(Some(path.clone()));
+		library.songs.insert("/music/a.mp3".to_string(), SongRecord {
+			title: "A".to_string(),
+			artist: "Someone".to_string(),
+			album: "Album: Deluxe".to_string(),
+			genre: "Rock".to_string(),
+			track: "1/10".to_string(),
+			play_count: 4,
+			completion_count: 3,
+			skip_count: 1,
+			last_played: 1700000000,
+			time_listened_ms: 600000,
+			waveform: Some(waveform::Waveform {peaks: vec![0.0, 1.0], rms: vec![0.0, 0.6]}),
+			lyrics_offset_ms: -250,
+			bookmarks: vec![Bookmark {position_ms: 1500, name: "Solo".to_string()}, Bookmark {position_ms: 90000, name: "Last chorus".to_string()}],
+			length_ms: 180000,
+			resume_position_ms: 65000,
+			finished: true,
+		});
+		library.songs.insert("/music/b.mp3".to_string(), SongRecord::default());
+		library.listening_days.insert(19723, 1234);
+		library.listening_days.insert(19724, 5678);
+		write_library(&library).unwrap();
+
+		let read_back = init_library_from_filepath(&path);
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(read_back.file_path, Some(path));
+		assert_eq!(read_back.songs.len(), 2);
+		let record = &read_back.songs["/music/a.mp3"];
+		assert_eq!(record.title, "A");
+		assert_eq!(record.artist, "Someone");
+		assert_eq!(record.album, "Album: Deluxe");
+		assert_eq!(record.genre, "Rock");
+		assert_eq!(record.track, "1/10");
+		assert_eq!((record.play_count, record.completion_count, record.skip_count), (4, 3, 1));
+		assert_eq!(record.last_played, 1700000000);
+		assert_eq!(record.time_listened_ms, 600000);
+		assert_eq!(record.lyrics_offset_ms, -250);
+		assert_eq!(record.bookmarks, library.songs["/music/a.mp3"].bookmarks);
+		assert_eq!((record.length_ms, record.resume_position_ms, record.finished), (180000, 65000, true));
+		let song_waveform = record.waveform.as_ref().unwrap();
+		assert_eq!(song_waveform.peaks, vec![0.0, 1.0]);
+		assert_eq!(song_waveform.rms.len(), 2);
+		assert!(read_back.songs["/music/b.mp3"].waveform.is_none());
+		assert_eq!(read_back.listening_days, library.listening_days);
+	}
+
+	#[test]
+	fn missing_library_file_is_empty() {
+		let library = init_library_from_filepath(&temp_file_path("missing_library.txt"));
+		assert!(library.songs.is_empty());
+		assert!(library.listening_days.is_empty());
+	}
+
+	#[test]
+	fn finishing_past_half_way_is_a_completion() {
+		let mut library = new_library(None);
+		/* The song doesn't exist, so it just gets empty tags */
+		library.songs.insert("/music/a.mp3".to_string(), SongRecord::default());
+		record_play_finish(&mut library, "/music/a.mp3", 60000, 120000);
+		record_play_finish(&mut library, "/music/a.mp3", 59999, 120000);
+		let record = &library.songs["/music/a.mp3"];
+		assert_eq!((record.completion_count, record.skip_count), (1, 1));
+		assert_eq!(record.time_listened_ms, 119999);
+		assert_eq!(library.listening_days.values().sum::<u64>(), 119999);
+
+		/* A song whose length couldn't be read is neither */
+		record_play_finish(&mut library, "/music/a.mp3", 1000, 0);
+		let record = &library.songs["/music/a.mp3"];
+		assert_eq!((record.completion_count, record.skip_count), (1, 1));
+		assert_eq!(record.time_listened_ms, 120999);
+	}
+
+	#[test]
+	fn finds_bookmarks_around_a_position() {
+		let mut record = SongRecord::default();
+		add_bookmark(&mut record, 30000, "Chorus");
+		add_bookmark(&mut record, 10000, "Verse");
+		add_bookmark(&mut record, 60000, "Outro");
+		let positions: Vec<usize> = record.bookmarks.iter().map(|bookmark| bookmark.position_ms).collect();
+		assert_eq!(positions, vec![10000, 30000, 60000]);
+
+		assert_eq!(next_bookmark(&record.bookmarks, 0).unwrap().name, "Verse");
+		assert_eq!(next_bookmark(&record.bookmarks, 30000).unwrap().name, "Outro");
+		assert!(next_bookmark(&record.bookmarks, 60000).is_none());
+		/* Right after jumping to a bookmark, previous goes to the one before it */
+		assert_eq!(previous_bookmark(&record.bookmarks, 30200).unwrap().name, "Verse");
+		assert_eq!(previous_bookmark(&record.bookmarks, 45000).unwrap().name, "Chorus");
+		assert!(previous_bookmark(&record.bookmarks, 10500).is_none());
+	}
+
+	#[test]
+	fn converts_days_to_dates() {
+		assert_eq!(day_to_date(0), (1970, 1, 1));
+		assert_eq!(day_to_date(19723), (2024, 1, 1));
+		assert_eq!(day_to_date(19782), (2024, 2, 29));
+		assert_eq!(day_to_date(11016), (2000, 2, 29));
+	}
+
+	#[test]
+	fn formats_listening_time() {
+		assert_eq!(format_listening_time(0), "0m 0s");
+		assert_eq!(format_listening_time(250 * 1000), "4m 10s");
+		assert_eq!(format_listening_time((3 * 3600 + 25 * 60 + 59) * 1000), "3h 25m");
+	}
+
+	#[test]
+	fn display_title_falls_back_to_file_name() {
+		let mut record = SongRecord::default();
+		assert_eq!(display_title("/music/Some Song.mp3", &record), "Some Song");
+		assert_eq!(display_title("C:\\music\\Other.mp3", &record), "Other");
+		record.title = "Tagged".to_string();
+		assert_eq!(display_title("/music/Some Song.mp3", &record), "Tagged");
+	}
+}
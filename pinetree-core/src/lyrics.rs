@@ -0,0 +1,294 @@
+/*
+ * Pinetree lyrics. Looks for lyrics in this order:
+ *
+ * - An .lrc file next to the song with the same name (song.mp3 -> song.lrc)
+ * - An embedded SYLT (synchronised lyrics) ID3 frame
+ * - An embedded USLT (unsynchronised lyrics) ID3 frame. These often hold LRC text anyway, so they get parsed as LRC
+ *
+ * LRC lines look like "[01:23.45]Some words", a line can have several timestamps. Lyrics without any timestamps
+ * are still shown, just without highlighting the current line.
+ */
+
+use crate::tags;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct LyricLine {
+	/* None for unsynchronised lyrics */
+	pub time_ms: Option<u64>,
+	pub text: String,
+}
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Lyrics {
+	/* Sorted by time when synced */
+	pub lines: Vec<LyricLine>,
+	pub synced: bool,
+}
+
+/**
+ * Parses "mm:ss", "mm:ss.xx" or "mm:ss.xxx" into milliseconds.
+ */
+fn parse_timestamp(timestamp: &str) -> Option<u64> {
+	let (minutes, seconds) = timestamp.split_once(':')?;
+	let minutes: u64 = minutes.trim().parse().ok()?;
+	let (seconds, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, ""));
+	let seconds: u64 = seconds.trim().parse().ok()?;
+	let fraction_ms = if fraction.is_empty() {
+		0
+	} else {
+		let digits: String = fraction.chars().take(3).collect();
+		let value: u64 = digits.parse().ok()?;
+		match digits.len() {
+			1 => value * 100,
+			2 => value * 10,
+			_ => value,
+		}
+	};
+	Some(minutes * 60000 + seconds * 1000 + fraction_ms)
+}
+
+pub fn parse_lrc(text: &str) -> Lyrics {
+	let mut timed_lines = Vec::<LyricLine>::new();
+	let mut plain_lines = Vec::<LyricLine>::new();
+	let mut offset_ms: i64 = 0;
+	for line in text.lines() {
+		let mut rest = line.trim();
+		let mut times = Vec::<u64>::new();
+		let mut is_tag = false;
+		while let Some(inner) = rest.strip_prefix('[')
+		&& let Some((bracket, after)) = inner.split_once(']') {
+			if let Some(time) = parse_timestamp(bracket) {
+				times.push(time);
+			} else if let Some(offset) = bracket.strip_prefix("offset:") {
+				offset_ms = offset.trim().parse().unwrap_or(0);
+				is_tag = true;
+			} else {
+				/* Other ID tags, e.g. [ar:Artist] */
+				is_tag = true;
+			}
+			rest = after;
+		}
+		if times.is_empty() {
+			if !is_tag {
+				plain_lines.push(LyricLine {time_ms: None, text: rest.trim().to_string()});
+			}
+			continue;
+		}
+		for time in times {
+			timed_lines.push(LyricLine {time_ms: Some(time), text: rest.trim().to_string()});
+		}
+	}
+
+	if timed_lines.is_empty() {
+		/* Leading and trailing empty lines aren't worth showing */
+		while plain_lines.first().is_some_and(|line| line.text.is_empty()) {plain_lines.remove(0);}
+		while plain_lines.last().is_some_and(|line| line.text.is_empty()) {plain_lines.pop();}
+		return Lyrics {lines: plain_lines, synced: false};
+	}
+	/* A positive offset in LRC means the lyrics come earlier */
+	for line in &mut timed_lines {
+		line.time_ms = line.time_ms.map(|time| (time as i64 - offset_ms).max(0) as u64);
+	}
+	timed_lines.sort_by_key(|line| line.time_ms);
+	Lyrics {lines: timed_lines, synced: true}
+}
+
+/**
+ * Splits off a string terminated by a null (two nulls for the UTF-16 encodings). Returns the string and the rest.
+ */
+fn split_terminated(encoding: u8, bytes: &[u8]) -> (&[u8], &[u8]) {
+	if encoding == 1 || encoding == 2 {
+		let mut i = 0;
+		while i + 1 < bytes.len() {
+			if bytes[i] == 0 && bytes[i + 1] == 0 {
+				return (&bytes[..i], &bytes[i + 2..]);
+			}
+			i += 2;
+		}
+	} else if let Some(end) = bytes.iter().position(|byte| *byte == 0) {
+		return (&bytes[..end], &bytes[end + 1..]);
+	}
+	(bytes, &[])
+}
+
+/**
+ * USLT: encoding, language (3 bytes), content descriptor, lyrics text
+ */
+pub fn parse_uslt(data: &[u8]) -> Option<String> {
+	if data.len() < 4 {
+		return None;
+	}
+	let encoding = data[0];
+	let (_, text) = split_terminated(encoding, &data[4..]);
+	let text = tags::decode_id3_string(encoding, text);
+	if text.trim().is_empty() {None} else {Some(text)}
+}
+
+/**
+ * SYLT: encoding, language (3 bytes), timestamp format, content type, content descriptor,
+ * then text + 32 bit timestamp pairs. Only millisecond timestamps are supported, MPEG frame timestamps are rare.
+ */
+pub fn parse_sylt(data: &[u8]) -> Option<Lyrics> {
+	if data.len() < 6 || data[4] != 2 {
+		return None;
+	}
+	let encoding = data[0];
+	let (_, mut rest) = split_terminated(encoding, &data[6..]);
+	let mut lines = Vec::<LyricLine>::new();
+	while !rest.is_empty() {
+		let (text, after) = split_terminated(encoding, rest);
+		if after.len() < 4 {
+			break;
+		}
+		let time = u32::from_be_bytes([after[0], after[1], after[2], after[3]]) as u64;
+		let text = tags::decode_id3_string(encoding, text);
+		/* Lines are often stored with the newline in front of them */
+		lines.push(LyricLine {time_ms: Some(time), text: text.trim_matches(['\n', '\r']).trim().to_string()});
+		rest = &after[4..];
+	}
+	if lines.is_empty() {
+		return None;
+	}
+	lines.sort_by_key(|line| line.time_ms);
+	Some(Lyrics {lines, synced: true})
+}
+
+pub fn lrc_path_for(song_path: &str) -> String {
+	std::path::Path::new(song_path).with_extension("lrc").to_string_lossy().to_string()
+}
+
+/**
+ * Finds the lyrics of a song, None if it has none.
+ */
+pub fn find_lyrics(song_path: &str) -> Option<Lyrics> {
+	if let Ok(bytes) = std::fs::read(lrc_path_for(song_path)) {
+		let lyrics = parse_lrc(&String::from_utf8_lossy(&bytes));
+		if !lyrics.lines.is_empty() {
+			return Some(lyrics);
+		}
+	}
+	let tag = tags::read_id3_frames(song_path)?;
+	if let Some(lyrics) = tag.frames.iter().filter(|frame| frame.id == "SYLT" || frame.id == "SLT").find_map(|frame| parse_sylt(&frame.data)) {
+		return Some(lyrics);
+	}
+	let text = tag.frames.iter().filter(|frame| frame.id == "USLT" || frame.id == "ULT").find_map(|frame| parse_uslt(&frame.data))?;
+	let lyrics = parse_lrc(&text);
+	if lyrics.lines.is_empty() {None} else {Some(lyrics)}
+}
+
+/**
+ * Index of the line being sung at the given position. offset_ms is the per-song adjustment,
+ * a positive offset shows every line later.
+ */
+pub fn current_line(lyrics: &Lyrics, position_ms: u64, offset_ms: i64) -> Option<usize> {
+	if !lyrics.synced {
+		return None;
+	}
+	lyrics.lines.iter().rposition(|line| line.time_ms.is_some_and(|time| time as i64 + offset_ms <= position_ms as i64))
+}
+
+/**
+ * Where to seek to for a line, with the offset applied.
+ */
+pub fn line_position(line: &LyricLine, offset_ms: i64) -> Option<usize> {
+	line.time_ms.map(|time| (time as i64 + offset_ms).max(0) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn line(time_ms: u64, text: &str) -> LyricLine {
+		LyricLine {time_ms: Some(time_ms), text: text.to_string()}
+	}
+
+	#[test]
+	fn parses_lrc() {
+		let lyrics = parse_lrc("[ar:Someone]\n[ti:Song]\n[00:12.50]First\n[00:05]Intro\n[01:02.345][00:20.1]Chorus\n[00:30.00]\n");
+		assert!(lyrics.synced);
+		assert_eq!(lyrics.lines, vec![
+			line(5000, "Intro"),
+			line(12500, "First"),
+			line(20100, "Chorus"),
+			line(30000, ""),
+			line(62345, "Chorus"),
+		]);
+	}
+
+	#[test]
+	fn applies_lrc_offset() {
+		let lyrics = parse_lrc("[offset:+500]\n[00:01.00]A\n[00:00.20]B\n");
+		assert_eq!(lyrics.lines, vec![line(0, "B"), line(500, "A")]);
+	}
+
+	#[test]
+	fn plain_text_is_unsynced() {
+		let lyrics = parse_lrc("\nJust some words\n\nMore words\n\n");
+		assert!(!lyrics.synced);
+		assert_eq!(lyrics.lines.len(), 3);
+		assert_eq!(lyrics.lines[0].text, "Just some words");
+		assert!(lyrics.lines.iter().all(|line| line.time_ms.is_none()));
+		assert_eq!(current_line(&lyrics, 1000, 0), None);
+	}
+
+	#[test]
+	fn finds_the_current_line() {
+		let lyrics = parse_lrc("[00:01.00]A\n[00:03.00]B\n[00:05.00]C\n");
+		assert_eq!(current_line(&lyrics, 500, 0), None);
+		assert_eq!(current_line(&lyrics, 1000, 0), Some(0));
+		assert_eq!(current_line(&lyrics, 4999, 0), Some(1));
+		assert_eq!(current_line(&lyrics, 60000, 0), Some(2));
+		/* Shown a second later */
+		assert_eq!(current_line(&lyrics, 3500, 1000), Some(0));
+		assert_eq!(line_position(&lyrics.lines[1], 1000), Some(4000));
+		assert_eq!(line_position(&lyrics.lines[0], -2000), Some(0));
+	}
+
+	#[test]
+	fn parses_embedded_frames() {
+		let mut uslt = vec![3];
+		uslt.extend_from_slice(b"eng");
+		uslt.extend_from_slice(b"desc\0[00:01.00]Embedded");
+		assert_eq!(parse_uslt(&uslt), Some("[00:01.00]Embedded".to_string()));
+
+		/* UTF-16, where the terminators are two bytes */
+		let mut sylt = vec![1];
+		sylt.extend_from_slice(b"eng");
+		sylt.extend_from_slice(&[2, 1]);
+		sylt.extend_from_slice(&[0, 0]);
+		for (text, time) in [("\nSecond", 2000u32), ("First", 1000u32)] {
+			sylt.extend_from_slice(&[0xFF, 0xFE]);
+			for unit in text.encode_utf16() {
+				sylt.extend_from_slice(&unit.to_le_bytes());
+			}
+			sylt.extend_from_slice(&[0, 0]);
+			sylt.extend_from_slice(&time.to_be_bytes());
+		}
+		let lyrics = parse_sylt(&sylt).unwrap();
+		assert_eq!(lyrics.lines, vec![line(1000, "First"), line(2000, "Second")]);
+
+		/* MPEG frame timestamps */
+		sylt[4] = 1;
+		assert!(parse_sylt(&sylt).is_none());
+	}
+
+	#[test]
+	fn prefers_lrc_files() {
+		let song = temp_file_path("lyrics_song.mp3");
+		std::fs::write(&song, [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+		assert!(find_lyrics(&song).is_none());
+
+		let lrc = lrc_path_for(&song);
+		assert!(lrc.ends_with("lyrics_song.lrc"));
+		std::fs::write(&lrc, "[00:01.00]From the file\n").unwrap();
+		let lyrics = find_lyrics(&song);
+		let _ = std::fs::remove_file(&song);
+		let _ = std::fs::remove_file(&lrc);
+		assert_eq!(lyrics.unwrap().lines, vec![line(1000, "From the file")]);
+	}
+}
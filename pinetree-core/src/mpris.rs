@@ -0,0 +1,470 @@
+/*
+ * Pinetree MPRIS2 support (Linux only). Exposes the org.mpris.MediaPlayer2 and org.mpris.MediaPlayer2.Player
+ * interfaces on the session bus, so media keys, desktop widgets and playerctl can control Pinetree.
+ *
+ * Every request is translated into a MessageToAudio, and every property is read from RodioData,
+ * so this behaves exactly like another GUI talking to the audio thread.
+ *
+ * The bus connection serves requests on its own threads. The loop in mpris_thread_loop only polls the audio
+ * thread to notice changes (new song, pause, volume, speed) and tells listeners about them through PropertiesChanged.
+ * Positions aren't sent that way, so it also watches for the position jumping (seeks from anywhere, A-B loops) and
+ * sends Seeked for those.
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+
+use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+
+use crate::audio_frontend::{self, MessageToAudio, RodioData};
+use crate::library;
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_BUS_NAME: &str = "org.mpris.MediaPlayer2.pinetree";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const NO_TRACK_PATH: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+/* The same range the speed slider has */
+const MIN_RATE: f64 = 0.5;
+const MAX_RATE: f64 = 2.0;
+/* How far the position can be off from where playing on would have put it before it counts as a seek */
+const SEEK_TOLERANCE_MS: i64 = 1000;
+
+type AudioSendChannel = Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>;
+type AudioReceiveChannel = Arc<(Mutex<Vec<RodioData>>, Condvar)>;
+
+struct MediaPlayer2;
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer2 {
+	/* The window is managed by eframe, so neither of these are supported */
+	fn raise(&self) {}
+	fn quit(&self) {}
+
+	#[zbus(property)]
+	fn can_quit(&self) -> bool {false}
+	#[zbus(property)]
+	fn can_raise(&self) -> bool {false}
+	#[zbus(property)]
+	fn has_track_list(&self) -> bool {false}
+	#[zbus(property)]
+	fn identity(&self) -> String {"Pinetree".to_string()}
+	#[zbus(property)]
+	fn supported_uri_schemes(&self) -> Vec<String> {vec!["file".to_string()]}
+	#[zbus(property)]
+	fn supported_mime_types(&self) -> Vec<String> {vec!["audio/mpeg".to_string()]}
+}
+
+struct Player {
+	audio_send: AudioSendChannel,
+	audio_receive: AudioReceiveChannel,
+	library: Arc<Mutex<library::Library>>,
+}
+
+impl Player {
+	fn rodio_data(&self) -> RodioData {
+		audio_frontend::request_rodio_data(&self.audio_send, &self.audio_receive)
+	}
+
+	fn send(&self, message: MessageToAudio) {
+		audio_frontend::send_audio_signal(&self.audio_send, message);
+	}
+
+	/* Moves to the given position (in microseconds, like everything in MPRIS) */
+	fn seek_to(&self, audio_data: &RodioData, position_us: i64) {
+		if audio_data.song_name.is_empty() {
+			return;
+		}
+		let position_ms = (position_us.max(0) / 1000) as usize;
+		self.send(MessageToAudio::SeekOnce(std::cmp::min(position_ms, audio_data.song_length)));
+	}
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+	fn next(&self) {
+		self.send(MessageToAudio::SongEnd);
+	}
+
+	fn previous(&self) {
+		self.send(MessageToAudio::PreviousSong);
+	}
+
+	fn pause(&self) {
+		let audio_data = self.rodio_data();
+		if !audio_data.is_paused && !audio_data.song_name.is_empty() {
+			self.send(MessageToAudio::TogglePause);
+		}
+	}
+
+	fn play_pause(&self) {
+		if !self.rodio_data().song_name.is_empty() {
+			self.send(MessageToAudio::TogglePause);
+		}
+	}
+
+	/* There is no real "stopped" state outside of the end of the queue, so this just pauses */
+	fn stop(&self) {
+		self.pause();
+	}
+
+	fn play(&self) {
+		let audio_data = self.rodio_data();
+		if audio_data.is_paused && !audio_data.song_name.is_empty() {
+			self.send(MessageToAudio::TogglePause);
+		}
+	}
+
+	fn seek(&self, offset: i64) {
+		let audio_data = self.rodio_data();
+		let position_us = audio_data.playback_position as i64 * 1000 + offset;
+		if position_us >= audio_data.song_length as i64 * 1000 {
+			self.next();
+		} else {
+			self.seek_to(&audio_data, position_us);
+		}
+	}
+
+	fn set_position(&self, track_id: ObjectPath<'_>, position: i64) {
+		let audio_data = self.rodio_data();
+		/* The spec says to ignore requests meant for a song that isn't playing anymore */
+		if track_id.as_str() != track_id_for_song(&audio_data.song_name) {
+			return;
+		}
+		if position < 0 || position > audio_data.song_length as i64 * 1000 {
+			return;
+		}
+		self.seek_to(&audio_data, position);
+	}
+
+	fn open_uri(&self, uri: &str) -> zbus::fdo::Result<()> {
+		let path = uri_to_path(uri).ok_or(zbus::fdo::Error::InvalidArgs(format!("Unsupported uri: {}", uri)))?;
+		if !path.ends_with(".mp3") {
+			return Err(zbus::fdo::Error::InvalidArgs(format!("Unsupported file: {}", path)));
+		}
+		self.send(MessageToAudio::PlaySong(path));
+		Ok(())
+	}
+
+	#[zbus(property)]
+	fn playback_status(&self) -> String {
+		playback_status(&self.rodio_data()).to_string()
+	}
+
+	#[zbus(property)]
+	fn rate(&self) -> f64 {
+		self.rodio_data().speed as f64
+	}
+
+	/* The spec says a rate of 0 should pause, anything else out of range gets ignored */
+	#[zbus(property)]
+	fn set_rate(&self, rate: f64) {
+		if rate == 0.0 {
+			self.pause();
+		} else if (MIN_RATE..=MAX_RATE).contains(&rate) {
+			self.send(MessageToAudio::UpdateSpeed(rate as f32));
+		}
+	}
+
+	#[zbus(property)]
+	fn minimum_rate(&self) -> f64 {MIN_RATE}
+	#[zbus(property)]
+	fn maximum_rate(&self) -> f64 {MAX_RATE}
+
+	#[zbus(property)]
+	fn metadata(&self) -> HashMap<String, OwnedValue> {
+		let audio_data = self.rodio_data();
+		song_metadata(&audio_data, &self.library)
+	}
+
+	#[zbus(property)]
+	fn volume(&self) -> f64 {
+		self.rodio_data().volume as f64
+	}
+
+	#[zbus(property)]
+	fn set_volume(&self, volume: f64) {
+		self.send(MessageToAudio::UpdateVolume(volume.clamp(0.0, 1.0) as f32));
+	}
+
+	#[zbus(property(emits_changed_signal = "false"))]
+	fn position(&self) -> i64 {
+		self.rodio_data().playback_position as i64 * 1000
+	}
+
+	#[zbus(property)]
+	fn can_go_next(&self) -> bool {true}
+	#[zbus(property)]
+	fn can_go_previous(&self) -> bool {true}
+	#[zbus(property)]
+	fn can_play(&self) -> bool {true}
+	#[zbus(property)]
+	fn can_pause(&self) -> bool {true}
+	#[zbus(property)]
+	fn can_seek(&self) -> bool {true}
+	#[zbus(property(emits_changed_signal = "const"))]
+	fn can_control(&self) -> bool {true}
+}
+
+fn playback_status(audio_data: &RodioData) -> &'static str {
+	if audio_data.song_name.is_empty() {
+		"Stopped"
+	} else if audio_data.is_paused {
+		"Paused"
+	} else {
+		"Playing"
+	}
+}
+
+/**
+ * Track ids have to be valid object paths, so the path of the song gets hashed into one.
+ */
+fn track_id_for_song(song: &str) -> String {
+	use std::hash::{Hash, Hasher};
+	if song.is_empty() {
+		return NO_TRACK_PATH.to_string();
+	}
+	let mut hasher = std::collections::hash_map::DefaultHasher::new();
+	song.hash(&mut hasher);
+	format!("/org/pinetree/track/{:016x}", hasher.finish())
+}
+
+/**
+ * Turns file:///some/song%20name.mp3 into /some/song name.mp3
+ */
+fn uri_to_path(uri: &str) -> Option<String> {
+	let encoded = if let Some(path) = uri.strip_prefix("file://") {
+		path
+	} else if uri.starts_with('/') {
+		uri
+	} else {
+		return None;
+	};
+	let bytes = encoded.as_bytes();
+	let mut decoded = Vec::<u8>::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		if bytes[i] == b'%' && i + 2 < bytes.len()
+		&& let Ok(hex) = std::str::from_utf8(&bytes[i + 1..i + 3])
+		&& let Ok(byte) = u8::from_str_radix(hex, 16) {
+			decoded.push(byte);
+			i += 3;
+		} else {
+			decoded.push(bytes[i]);
+			i += 1;
+		}
+	}
+	String::from_utf8(decoded).ok()
+}
+
+fn path_to_uri(path: &str) -> String {
+	let mut uri = "file://".to_string();
+	for byte in path.bytes() {
+		if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+			uri.push(byte as char);
+		} else {
+			uri.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	uri
+}
+
+fn to_owned_value<'a>(value: impl Into<Value<'a>>) -> Option<OwnedValue> {
+	value.into().try_to_owned().ok()
+}
+
+fn song_metadata(audio_data: &RodioData, library: &Arc<Mutex<library::Library>>) -> HashMap<String, OwnedValue> {
+	let mut metadata = HashMap::<String, OwnedValue>::new();
+	let track_id = track_id_for_song(&audio_data.song_name);
+	if let Ok(path) = ObjectPath::try_from(track_id.as_str())
+	&& let Some(value) = to_owned_value(path) {
+		metadata.insert("mpris:trackid".to_string(), value);
+	}
+	if audio_data.song_name.is_empty() {
+		return metadata;
+	}
+
+	let mut entries: Vec<(&str, Option<OwnedValue>)> = vec![
+		("mpris:length", to_owned_value(audio_data.song_length as i64 * 1000)),
+		("xesam:url", to_owned_value(path_to_uri(&audio_data.song_name))),
+	];
+	if let Ok(library) = library.lock() {
+		let record = library.songs.get(&audio_data.song_name).cloned().unwrap_or_default();
+		entries.push(("xesam:title", to_owned_value(library::display_title(&audio_data.song_name, &record))));
+		if !record.artist.is_empty() {
+			entries.push(("xesam:artist", to_owned_value(vec![record.artist.clone()])));
+		}
+		if !record.album.is_empty() {
+			entries.push(("xesam:album", to_owned_value(record.album.clone())));
+		}
+		if !record.genre.is_empty() {
+			entries.push(("xesam:genre", to_owned_value(vec![record.genre.clone()])));
+		}
+		if let Ok(track) = record.track.split('/').next().unwrap_or("").trim().parse::<i32>() {
+			entries.push(("xesam:trackNumber", to_owned_value(track)));
+		}
+		if record.play_count > 0 {
+			entries.push(("xesam:useCount", to_owned_value(record.play_count as i32)));
+		}
+	}
+	for (key, value) in entries {
+		if let Some(value) = value {
+			metadata.insert(key.to_string(), value);
+		}
+	}
+	metadata
+}
+
+/**
+ * Sends org.freedesktop.DBus.Properties.PropertiesChanged for the player interface.
+ */
+fn emit_properties_changed(connection: &zbus::blocking::Connection, changed: HashMap<&str, Value<'_>>) {
+	if changed.is_empty() {
+		return;
+	}
+	let invalidated: Vec<&str> = Vec::new();
+	let result = connection.emit_signal(
+		None::<&str>,
+		MPRIS_PATH,
+		"org.freedesktop.DBus.Properties",
+		"PropertiesChanged",
+		&(PLAYER_INTERFACE, changed, invalidated),
+	);
+	if result.is_err() {
+		println!("Error in sending MPRIS update");
+	}
+}
+
+/**
+ * Sends org.mpris.MediaPlayer2.Player.Seeked with the new position.
+ */
+fn emit_seeked(connection: &zbus::blocking::Connection, position_ms: usize) {
+	let result = connection.emit_signal(None::<&str>, MPRIS_PATH, PLAYER_INTERFACE, "Seeked", &(position_ms as i64 * 1000));
+	if result.is_err() {
+		println!("Error in sending MPRIS seek");
+	}
+}
+
+/**
+ * Whether the position moved other than by playing: more than a bit away from where the last position plus the time
+ * since then (at the current speed) would put it.
+ */
+fn position_jumped(last: &RodioData, now: &RodioData, elapsed_ms: u64) -> bool {
+	if last.song_name != now.song_name || now.song_name.is_empty() {
+		return false;
+	}
+	let expected = if last.is_paused {last.playback_position as i64} else {last.playback_position as i64 + (elapsed_ms as f32 * last.speed) as i64};
+	(now.playback_position as i64 - expected).abs() > SEEK_TOLERANCE_MS
+}
+
+/**
+ * Connects to the session bus and keeps the exported properties up to date. Never returns unless
+ * there's no session bus to connect to, in which case Pinetree just runs without MPRIS.
+ */
+pub fn mpris_thread_loop(audio_send: AudioSendChannel, audio_receive: AudioReceiveChannel, library: Arc<Mutex<library::Library>>) {
+	let player = Player {
+		audio_send: Arc::clone(&audio_send),
+		audio_receive: Arc::clone(&audio_receive),
+		library: Arc::clone(&library),
+	};
+
+	/* If another Pinetree already owns the name, fall back to a unique per-process one as the spec suggests */
+	let connection = zbus::blocking::connection::Builder::session()
+		.and_then(|builder| builder.serve_at(MPRIS_PATH, MediaPlayer2))
+		.and_then(|builder| builder.serve_at(MPRIS_PATH, player))
+		.and_then(|builder| builder.build());
+	let connection = match connection {
+		Ok(connection) => connection,
+		Err(_) => {
+			println!("Could not connect to the session bus, MPRIS is disabled");
+			return;
+		},
+	};
+	if connection.request_name(MPRIS_BUS_NAME).is_err() {
+		let fallback_name = format!("{}.instance{}", MPRIS_BUS_NAME, std::process::id());
+		if connection.request_name(fallback_name.as_str()).is_err() {
+			println!("Could not register MPRIS name");
+			return;
+		}
+	}
+
+	let mut last_song = "".to_string();
+	let mut last_status = "";
+	let mut last_volume = -1.0;
+	let mut last_speed = -1.0;
+	let mut last_data: Option<(RodioData, std::time::Instant)> = None;
+	loop {
+		std::thread::sleep(POLL_INTERVAL);
+		let audio_data = audio_frontend::request_rodio_data(&audio_send, &audio_receive);
+		if let Some((last, polled_at)) = &last_data && position_jumped(last, &audio_data, polled_at.elapsed().as_millis() as u64) {
+			emit_seeked(&connection, audio_data.playback_position);
+		}
+
+		let mut changed = HashMap::<&str, Value>::new();
+		let status = playback_status(&audio_data);
+		if status != last_status {
+			changed.insert("PlaybackStatus", Value::from(status));
+			last_status = status;
+		}
+		if audio_data.song_name != last_song {
+			changed.insert("Metadata", Value::from(song_metadata(&audio_data, &library)));
+			last_song = audio_data.song_name.clone();
+		}
+		if audio_data.volume != last_volume {
+			changed.insert("Volume", Value::from(audio_data.volume as f64));
+			last_volume = audio_data.volume;
+		}
+		if audio_data.speed != last_speed {
+			changed.insert("Rate", Value::from(audio_data.speed as f64));
+			last_speed = audio_data.speed;
+		}
+		emit_properties_changed(&connection, changed);
+		last_data = Some((audio_data, std::time::Instant::now()));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn converts_between_uris_and_paths() {
+		assert_eq!(uri_to_path("file:///music/Some%20Song.mp3").as_deref(), Some("/music/Some Song.mp3"));
+		assert_eq!(uri_to_path("/music/plain.mp3").as_deref(), Some("/music/plain.mp3"));
+		assert_eq!(uri_to_path("http://example.com/a.mp3"), None);
+		let path = "/music/Ünïcode & 100%.mp3";
+		assert_eq!(path_to_uri("/music/a b.mp3"), "file:///music/a%20b.mp3");
+		assert_eq!(uri_to_path(&path_to_uri(path)).as_deref(), Some(path));
+	}
+
+	#[test]
+	fn track_ids_are_stable_object_paths() {
+		let id = track_id_for_song("/music/a.mp3");
+		assert_eq!(id, track_id_for_song("/music/a.mp3"));
+		assert_ne!(id, track_id_for_song("/music/b.mp3"));
+		assert!(ObjectPath::try_from(id.as_str()).is_ok());
+		assert_eq!(track_id_for_song(""), NO_TRACK_PATH);
+	}
+
+	#[test]
+	fn notices_position_jumps() {
+		let data = |song: &str, position: usize, speed: f32, is_paused: bool| RodioData {
+			playback_position: position,
+			song_length: 100000,
+			is_paused,
+			song_name: song.to_string(),
+			error_message: None,
+			shuffle_memory: 0,
+			volume: 1.0,
+			speed,
+			ab_loop: None,
+			sleep_timer: None,
+		};
+		assert!(!position_jumped(&data("a", 5000, 1.0, false), &data("a", 5250, 1.0, false), 250));
+		assert!(!position_jumped(&data("a", 5000, 2.0, false), &data("a", 5500, 2.0, false), 250));
+		assert!(position_jumped(&data("a", 5000, 1.0, false), &data("a", 30000, 1.0, false), 250));
+		assert!(position_jumped(&data("a", 5000, 1.0, true), &data("a", 2000, 1.0, true), 250));
+		/* A new song starting over isn't a seek */
+		assert!(!position_jumped(&data("a", 50000, 1.0, false), &data("b", 0, 1.0, false), 250));
+	}
+}
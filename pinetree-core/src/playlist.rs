@@ -0,0 +1,207 @@
+/*
+ * Pinetree playlists. They are saved at the end of the internal data file, see config.rs
+ */
+
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use crate::audio_frontend::PlaylistTreeElement;
+
+pub struct Playlist {
+	pub name: String,
+	pub songs: Vec<String>,
+	pub is_open: bool,
+}
+
+pub fn build_playlist_tree(playlists: &Vec<Playlist>) -> Vec<PlaylistTreeElement> {
+	let mut vec: Vec<PlaylistTreeElement> = Vec::<PlaylistTreeElement>::new();
+	let mut i = 0;
+	for playlist in playlists {
+		vec.push(PlaylistTreeElement {
+			song_name: None,
+			playlist_position: i,
+		});
+		if playlist.is_open {
+			for song in &playlist.songs {
+				vec.push(PlaylistTreeElement {
+					song_name: Some(song.clone()),
+					playlist_position: 0,
+				});
+			}
+		}
+		i += 1;
+	}
+	return vec;
+}
+
+/**
+* The playlist data takes the following form. We can assume that playlist names and file paths don't
+* contain newlines even if they're technically legal on unix systems because egui wouldn't be able to render it anyways.
+* That is the only character type we can assume is illegal though.
+*
+* Playlist: playlist_name
+* song_filepath
+* song_filepath
+* song_filepath
+* ...
+* Playlist: playlist_2_name
+* ...
+*/
+pub fn init_playlist_from_filepath(fp: &str) -> Vec<Playlist> {
+	let file = std::fs::File::open(fp);
+	let mut playlists = Vec::<Playlist>::new();
+	let mut current_songs = Vec::<String>::new();
+	let mut playlist_name: Option<String> = None;
+
+	if let Ok(file) = file {
+		let reader = std::io::BufReader::new(file);
+		for line in reader.lines() {
+			if let Ok(line) = line {
+				// Drop the current playlist (add to vec)
+				if line.starts_with("Playlist: ") {
+					if let Some(name) = playlist_name {
+						playlists.push(Playlist {
+							name: name,
+							songs: current_songs,
+							is_open: false,
+						});
+						current_songs = Vec::<String>::new();
+					}
+					playlist_name = Some((&line[10..]).to_string());
+				} else {
+					current_songs.push(line);
+				}
+			}
+		}
+	}
+	if let Some(name) = playlist_name {
+		playlists.push(Playlist {
+			name: name,
+			songs: current_songs,
+			is_open: false,
+		});
+	}
+	return playlists;
+}
+
+#[derive(Clone)]
+pub enum PlaylistElementType {
+	Song,
+	_Directory,
+}
+
+/**
+ * Starting to not be a big fan of Rust
+ * 
+ * Were this in a slightly looser language I'd just do this with a doubly linked list to make every operation O(1) but I'm stuck using v*ctors for this.
+ * Doubly linked lists are a pain in the ass in Rust
+ * 
+ * Maybe I'll go back and do the un-safe stuff should this become a real performance issue, though I imagine there are more pressing areas in this code.
+ */
+pub fn range_swap(vec: &mut Vec<String>, start: usize, end_inclusive: usize) {
+	if start > end_inclusive {
+		for i in (end_inclusive + 1..=start).rev() {
+            vec.swap(i, i - 1);
+        }
+	} else {
+		for i in start..end_inclusive {
+			vec.swap(i, i + 1);
+		}
+	}
+}
+
+/**
+ * Builds an ordered vec that looks like the following:
+ * - Existing elements preserve their place in the new vec
+ * - Added elements are on the end, sorted alphabetically
+ */
+pub fn rebuild_ordered_vec(existing_ordered_vec: &mut Vec<String>, existence_map: &mut HashMap<String, PlaylistElementType>) -> Vec<String> {
+	let mut new_ordered_vec = Vec::<String>::new();
+	let mut existence_map_new = HashMap::<String, PlaylistElementType>::new();
+	for song in existing_ordered_vec {
+		if existence_map.contains_key(song) {
+			new_ordered_vec.push(song.clone());
+			existence_map_new.insert(song.clone(), existence_map.get(song).unwrap_or(&PlaylistElementType::Song).clone());
+		}
+	}
+
+	let mut keymap = Vec::<String>::new();
+	for (song, _) in existence_map {
+		if !existence_map_new.contains_key(song) {
+			keymap.push(song.clone());
+		}
+	}
+
+	// TODO: Fix this to remove all the copying
+	keymap.sort_by_key(|name| name.to_lowercase());
+
+	/* Combine */
+	for element in keymap {
+		new_ordered_vec.push(element);
+	}
+
+	return new_ordered_vec;
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn strings(values: &[&str]) -> Vec<String> {
+		values.iter().map(|value| value.to_string()).collect()
+	}
+
+	#[test]
+	fn reads_playlist_file() {
+		let path = temp_file_path("playlists.txt");
+		std::fs::write(&path, "Playlist: First\n/a.mp3\n/b.mp3\nPlaylist: Second\n/c.mp3\n").unwrap();
+		let playlists = init_playlist_from_filepath(&path);
+		let _ = std::fs::remove_file(&path);
+
+		assert_eq!(playlists.len(), 2);
+		assert_eq!(playlists[0].name, "First");
+		assert_eq!(playlists[0].songs, strings(&["/a.mp3", "/b.mp3"]));
+		assert_eq!(playlists[1].name, "Second");
+		assert_eq!(playlists[1].songs, strings(&["/c.mp3"]));
+		assert!(init_playlist_from_filepath(&temp_file_path("missing_playlists.txt")).is_empty());
+	}
+
+	#[test]
+	fn playlist_tree_lists_songs_of_open_playlists() {
+		let playlists = vec![
+			Playlist {name: "Open".to_string(), songs: strings(&["/a.mp3", "/b.mp3"]), is_open: true},
+			Playlist {name: "Closed".to_string(), songs: strings(&["/c.mp3"]), is_open: false},
+		];
+		let tree = build_playlist_tree(&playlists);
+		let rows: Vec<(Option<String>, usize)> = tree.into_iter().map(|element| (element.song_name, element.playlist_position)).collect();
+		assert_eq!(rows, vec![
+			(None, 0),
+			(Some("/a.mp3".to_string()), 0),
+			(Some("/b.mp3".to_string()), 0),
+			(None, 1),
+		]);
+	}
+
+	#[test]
+	fn range_swap_moves_one_element() {
+		let mut vec = strings(&["a", "b", "c", "d", "e"]);
+		range_swap(&mut vec, 1, 3);
+		assert_eq!(vec, strings(&["a", "c", "d", "b", "e"]));
+		range_swap(&mut vec, 3, 0);
+		assert_eq!(vec, strings(&["b", "a", "c", "d", "e"]));
+		range_swap(&mut vec, 2, 2);
+		assert_eq!(vec, strings(&["b", "a", "c", "d", "e"]));
+	}
+
+	#[test]
+	fn rebuild_keeps_order_and_appends_new_songs_sorted() {
+		let mut existing = strings(&["/z.mp3", "/removed.mp3", "/m.mp3"]);
+		let mut existence_map = HashMap::<String, PlaylistElementType>::new();
+		for song in ["/m.mp3", "/z.mp3", "/B.mp3", "/a.mp3"] {
+			existence_map.insert(song.to_string(), PlaylistElementType::Song);
+		}
+		let rebuilt = rebuild_ordered_vec(&mut existing, &mut existence_map);
+		assert_eq!(rebuilt, strings(&["/z.mp3", "/m.mp3", "/a.mp3", "/B.mp3"]));
+	}
+}
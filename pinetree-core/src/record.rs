@@ -0,0 +1,593 @@
+/*
+ * Pinetree recording and rendering to files.
+ *
+ * Recording copies what comes out of the sink, after speed and volume, on its way to the sound card. The samples
+ * pass through RecordSource untouched, so playback isn't converted to anything while nothing is recorded. While a
+ * recording runs, the samples are handed in chunks (with the channels and sample rate they came in) over a channel
+ * to a writer thread, so the audio output thread never waits on the disk. The writer converts them to 44.1kHz stereo.
+ * Speed is applied by rodio as a change in the sample rate the sink reports, so this conversion is also what bakes
+ * the speed into the recording.
+ *
+ * Rendering plays songs into a sink made with Sink::new_idle and pulls the samples out of it as fast as they can
+ * be written. It runs faster than real time and doesn't need a sound card. The sink does speed and volume the same
+ * way it does for playback (speed changes the pitch too, there is no time-stretching), and fades and normalization
+ * are rodio sources wrapped around every song before it goes in.
+ */
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::Duration;
+
+use rodio::Source;
+use rodio::source::UniformSourceIterator;
+
+use crate::audio_frontend;
+use crate::files::build_full_filepath;
+use crate::flac;
+use crate::library;
+
+pub const RECORD_CHANNELS: u16 = 2;
+pub const RECORD_SAMPLE_RATE: u32 = 44100;
+/* About 23ms, sent to the writer thread at a time */
+const CHUNK_SAMPLES: usize = 2048;
+pub const DEFAULT_RECORD_TEMPLATE: &str = "{date} {time} {song}";
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum RecordFormat {
+	Wav,
+	Flac,
+}
+
+pub fn format_to_str(format: RecordFormat) -> &'static str {
+	match format {
+		RecordFormat::Wav => "WAV",
+		RecordFormat::Flac => "FLAC",
+	}
+}
+
+pub fn str_to_format(string: &str) -> RecordFormat {
+	match string {
+		"FLAC" => RecordFormat::Flac,
+		_ => RecordFormat::Wav,
+	}
+}
+
+pub fn format_extension(format: RecordFormat) -> &'static str {
+	match format {
+		RecordFormat::Wav => "wav",
+		RecordFormat::Flac => "flac",
+	}
+}
+
+pub enum AudioFileWriter {
+	Wav(hound::WavWriter<BufWriter<File>>),
+	Flac(flac::FlacWriter<BufWriter<File>>),
+}
+
+/**
+ * Creates a 16 bit, 44.1kHz stereo file.
+ */
+pub fn create_audio_file(path: &str, format: RecordFormat) -> Result<AudioFileWriter, String> {
+	match format {
+		RecordFormat::Wav => {
+			let spec = hound::WavSpec {
+				channels: RECORD_CHANNELS,
+				sample_rate: RECORD_SAMPLE_RATE,
+				bits_per_sample: 16,
+				sample_format: hound::SampleFormat::Int,
+			};
+			hound::WavWriter::create(path, spec).map(AudioFileWriter::Wav).map_err(|err| format!("Couldn't create {}: {}", path, err))
+		},
+		RecordFormat::Flac => {
+			let file = File::create(path).map_err(|err| format!("Couldn't create {}: {}", path, err))?;
+			flac::FlacWriter::new(BufWriter::new(file), RECORD_CHANNELS, RECORD_SAMPLE_RATE).map(AudioFileWriter::Flac).map_err(|err| format!("Couldn't write to {}: {}", path, err))
+		},
+	}
+}
+
+fn to_i16(sample: f32) -> i16 {
+	(sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+impl AudioFileWriter {
+	pub fn write_samples(&mut self, samples: &[f32]) -> Result<(), String> {
+		match self {
+			AudioFileWriter::Wav(writer) => {
+				for sample in samples {
+					writer.write_sample(to_i16(*sample)).map_err(|err| err.to_string())?;
+				}
+				Ok(())
+			},
+			AudioFileWriter::Flac(writer) => {
+				let samples: Vec<i16> = samples.iter().map(|sample| to_i16(*sample)).collect();
+				writer.write_samples(&samples).map_err(|err| err.to_string())
+			},
+		}
+	}
+
+	pub fn finish(self) -> Result<(), String> {
+		match self {
+			AudioFileWriter::Wav(writer) => writer.finalize().map_err(|err| err.to_string()),
+			AudioFileWriter::Flac(writer) => writer.finalize().map(|_| ()).map_err(|err| err.to_string()),
+		}
+	}
+}
+
+/**
+ * Fills in a file name template. {song} is the name of the song (or whatever is being rendered),
+ * {date} and {time} are when it started, in UTC. Characters file systems don't like get replaced.
+ */
+pub fn expand_template(template: &str, song: &str, unix_time: u64) -> String {
+	let (year, month, day) = library::day_to_date(library::unix_time_to_day(unix_time));
+	let seconds = unix_time % 86400;
+	let song_name = std::path::Path::new(song).file_stem().and_then(|stem| stem.to_str()).unwrap_or("Pinetree");
+	let name = template
+		.replace("{date}", &format!("{:04}-{:02}-{:02}", year, month, day))
+		.replace("{time}", &format!("{:02}-{:02}-{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60))
+		.replace("{song}", song_name);
+	let name: String = name.chars().map(|c| if "/\\:*?\"<>|".contains(c) {'_'} else {c}).collect();
+	if name.trim().is_empty() {"Pinetree".to_string()} else {name.trim().to_string()}
+}
+
+/**
+ * Where a new file named after the template goes. Never returns a file that already exists.
+ */
+pub fn output_path(folder: &str, template: &str, song: &str, format: RecordFormat) -> String {
+	let name = expand_template(template, song, library::unix_time_now());
+	let extension = format_extension(format);
+	let mut path = build_full_filepath(folder, &format!("{}.{}", name, extension));
+	let mut number = 2;
+	while std::path::Path::new(&path).exists() {
+		path = build_full_filepath(folder, &format!("{} ({}).{}", name, number, extension));
+		number += 1;
+	}
+	path
+}
+
+/**
+ * Samples as they came out of the sink. The format changes between songs and with the speed.
+ */
+struct RecordChunk {
+	channels: u16,
+	sample_rate: u32,
+	samples: Vec<f32>,
+}
+
+/**
+ * Shared between RecordSource on the audio output thread and whoever starts and stops recordings.
+ */
+pub struct RecordTap {
+	sender: Mutex<Option<mpsc::Sender<RecordChunk>>>,
+	recording: AtomicBool,
+	/* Microseconds of audio handed to the writer since the recording started */
+	recorded_us: AtomicU64,
+	path: Mutex<String>,
+}
+
+pub fn new_record_tap() -> Arc<RecordTap> {
+	Arc::new(RecordTap {
+		sender: Mutex::new(None),
+		recording: AtomicBool::new(false),
+		recorded_us: AtomicU64::new(0),
+		path: Mutex::new("".to_string()),
+	})
+}
+
+impl RecordTap {
+	pub fn is_recording(&self) -> bool {
+		self.recording.load(Ordering::Acquire)
+	}
+
+	pub fn recorded_ms(&self) -> u64 {
+		self.recorded_us.load(Ordering::Relaxed) / 1000
+	}
+
+	pub fn path(&self) -> String {
+		self.path.lock().map(|path| path.clone()).unwrap_or_default()
+	}
+}
+
+/**
+ * Starts writing everything that plays to the file. The file is created right away, so errors show up here.
+ */
+pub fn start_recording(tap: &RecordTap, path: &str, format: RecordFormat) -> Result<(), String> {
+	if tap.is_recording() {
+		return Err("Already recording".to_string());
+	}
+	let mut writer = create_audio_file(path, format)?;
+	let (sender, receiver) = mpsc::channel::<RecordChunk>();
+	std::thread::spawn(move || {
+		/* Ends once the sender is dropped by stop_recording */
+		let mut samples = UniformSourceIterator::<_, f32>::new(new_chunk_source(receiver), RECORD_CHANNELS, RECORD_SAMPLE_RATE);
+		let mut converted = Vec::<f32>::with_capacity(CHUNK_SAMPLES);
+		loop {
+			converted.clear();
+			converted.extend(samples.by_ref().take(CHUNK_SAMPLES));
+			if let Err(err) = writer.write_samples(&converted) {
+				println!("Error in recording: {}", err);
+				return;
+			}
+			if converted.len() < CHUNK_SAMPLES {
+				break;
+			}
+		}
+		if let Err(err) = writer.finish() {
+			println!("Error in recording: {}", err);
+		}
+	});
+	if let Ok(mut path_lock) = tap.path.lock() {
+		*path_lock = path.to_string();
+	}
+	if let Ok(mut sender_lock) = tap.sender.lock() {
+		*sender_lock = Some(sender);
+	}
+	tap.recorded_us.store(0, Ordering::Relaxed);
+	tap.recording.store(true, Ordering::Release);
+	Ok(())
+}
+
+/**
+ * Stops the recording and returns the file it went to. The file gets finished off in the background.
+ */
+pub fn stop_recording(tap: &RecordTap) -> Option<String> {
+	if !tap.recording.swap(false, Ordering::AcqRel) {
+		return None;
+	}
+	if let Ok(mut sender_lock) = tap.sender.lock() {
+		*sender_lock = None;
+	}
+	Some(tap.path())
+}
+
+/**
+ * The chunks of a recording as one source, for the writer thread to convert. Reports each chunk as a frame so the
+ * conversion follows the format changes. The next chunk is waited for as soon as one runs out, so the format is
+ * always known when the conversion asks for it.
+ */
+struct ChunkSource {
+	receiver: mpsc::Receiver<RecordChunk>,
+	/* None once the recording has ended */
+	chunk: Option<RecordChunk>,
+	position: usize,
+}
+
+fn new_chunk_source(receiver: mpsc::Receiver<RecordChunk>) -> ChunkSource {
+	let mut source = ChunkSource {receiver, chunk: None, position: 0};
+	source.next_chunk();
+	source
+}
+
+impl ChunkSource {
+	fn next_chunk(&mut self) {
+		self.position = 0;
+		self.chunk = self.receiver.iter().find(|chunk| !chunk.samples.is_empty());
+	}
+}
+
+impl Iterator for ChunkSource {
+	type Item = f32;
+
+	fn next(&mut self) -> Option<f32> {
+		let chunk = self.chunk.as_ref()?;
+		let sample = chunk.samples[self.position];
+		self.position += 1;
+		if self.position >= chunk.samples.len() {
+			self.next_chunk();
+		}
+		Some(sample)
+	}
+}
+
+impl Source for ChunkSource {
+	fn current_frame_len(&self) -> Option<usize> {
+		Some(self.chunk.as_ref().map_or(0, |chunk| chunk.samples.len() - self.position))
+	}
+	fn channels(&self) -> u16 { self.chunk.as_ref().map_or(RECORD_CHANNELS, |chunk| chunk.channels) }
+	fn sample_rate(&self) -> u32 { self.chunk.as_ref().map_or(RECORD_SAMPLE_RATE, |chunk| chunk.sample_rate) }
+	fn total_duration(&self) -> Option<Duration> { None }
+}
+
+/**
+ * Passes a source through as it is, and copies it into the tap while recording.
+ */
+pub struct RecordSource<S: Source<Item = f32>> {
+	inner: S,
+	tap: Arc<RecordTap>,
+	buffer: Vec<f32>,
+	/* Format of the samples in the buffer, and how many more samples are sure to have it */
+	channels: u16,
+	sample_rate: u32,
+	frame_left: usize,
+}
+
+pub fn record_source<S: Source<Item = f32>>(inner: S, tap: Arc<RecordTap>) -> RecordSource<S> {
+	RecordSource {
+		channels: inner.channels(),
+		sample_rate: inner.sample_rate(),
+		inner,
+		tap,
+		buffer: Vec::with_capacity(CHUNK_SAMPLES),
+		frame_left: 0,
+	}
+}
+
+impl<S: Source<Item = f32>> RecordSource<S> {
+	fn send_buffer(&mut self) {
+		/* Never wait for the lock here, it's only held for a moment and the chunk can just be sent later */
+		if let Ok(sender) = self.tap.sender.try_lock() {
+			if let Some(sender) = sender.as_ref() {
+				let duration_us = self.buffer.len() as u64 * 1_000_000 / (self.channels.max(1) as u64 * self.sample_rate.max(1) as u64);
+				let samples = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SAMPLES));
+				if sender.send(RecordChunk {channels: self.channels, sample_rate: self.sample_rate, samples}).is_ok() {
+					self.tap.recorded_us.fetch_add(duration_us, Ordering::Relaxed);
+				}
+			} else {
+				self.buffer.clear();
+			}
+		}
+	}
+
+	/* Checked at the start of every frame, or every chunk if the source doesn't say how long its frames are */
+	fn check_format(&mut self) {
+		let (channels, sample_rate) = (self.inner.channels(), self.inner.sample_rate());
+		if (channels, sample_rate) != (self.channels, self.sample_rate) {
+			if !self.buffer.is_empty() {
+				self.send_buffer();
+				/* Couldn't be sent, rather lose it than label it with the wrong format */
+				self.buffer.clear();
+			}
+			self.channels = channels;
+			self.sample_rate = sample_rate;
+		}
+		self.frame_left = self.inner.current_frame_len().unwrap_or(CHUNK_SAMPLES).clamp(1, CHUNK_SAMPLES);
+	}
+}
+
+impl<S: Source<Item = f32>> Iterator for RecordSource<S> {
+	type Item = f32;
+
+	fn next(&mut self) -> Option<f32> {
+		if !self.tap.recording.load(Ordering::Relaxed) {
+			self.buffer.clear();
+			self.frame_left = 0;
+			return self.inner.next();
+		}
+		if self.frame_left == 0 {
+			self.check_format();
+		}
+		let sample = self.inner.next()?;
+		self.frame_left -= 1;
+		self.buffer.push(sample);
+		if self.buffer.len() >= CHUNK_SAMPLES && self.buffer.len().is_multiple_of(self.channels.max(1) as usize) {
+			self.send_buffer();
+		}
+		Some(sample)
+	}
+}
+
+impl<S: Source<Item = f32>> Source for RecordSource<S> {
+	fn current_frame_len(&self) -> Option<usize> { self.inner.current_frame_len() }
+	fn channels(&self) -> u16 { self.inner.channels() }
+	fn sample_rate(&self) -> u32 { self.inner.sample_rate() }
+	fn total_duration(&self) -> Option<Duration> { self.inner.total_duration() }
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct RenderSettings {
+	pub speed: f32,
+	/* Sink volume, so after the volume curve */
+	pub volume: f32,
+	/* Fades at the start and end of every song, in song time (before the speed is applied) */
+	pub fade_in_ms: u64,
+	pub fade_out_ms: u64,
+	/* Brings every song's peak up (or down) to NORMALIZED_PEAK */
+	pub normalize: bool,
+}
+
+pub fn default_render_settings() -> RenderSettings {
+	RenderSettings {
+		speed: 1.0,
+		volume: 1.0,
+		fade_in_ms: 0,
+		fade_out_ms: 0,
+		normalize: false,
+	}
+}
+
+/* -1 dBFS, a little headroom for the resampling speed changes do */
+pub const NORMALIZED_PEAK: f32 = 0.891;
+
+/**
+ * Gain that brings the song's loudest sample to NORMALIZED_PEAK. Decodes the whole song to find it.
+ */
+pub fn normalization_gain(song: &str) -> Result<f32, String> {
+	let file = File::open(song).map_err(|err| format!("Couldn't open {}: {}", song, err))?;
+	let decoder = rodio::Decoder::new_mp3(std::io::BufReader::new(file)).map_err(|_| format!("Invalid mp3 format {}", song))?;
+	let peak = decoder.map(|sample: i16| sample.unsigned_abs()).max().unwrap_or(0);
+	if peak == 0 {
+		return Ok(1.0);
+	}
+	Ok(NORMALIZED_PEAK * i16::MAX as f32 / peak as f32)
+}
+
+/**
+ * Wraps a song in the fades and gain of the render settings. length_ms is the song's length, for the fade out.
+ */
+pub fn with_effects<S>(source: S, settings: RenderSettings, gain: f32, length_ms: u64) -> impl Source<Item = S::Item> + Send
+where
+	S: Source + Send,
+	S::Item: rodio::Sample + Send,
+{
+	/* A ramp over no time at all would divide by zero, so no fade in is a ramp from 1 to 1 */
+	let fade_in = Duration::from_millis(settings.fade_in_ms.max(1));
+	let start_gain = if settings.fade_in_ms > 0 {0.0} else {1.0};
+	let fade_out_ms = settings.fade_out_ms.min(length_ms);
+	source
+		.linear_gain_ramp(fade_in, start_gain, 1.0, false)
+		.track_position()
+		.amplify(gain)
+		.periodic_access(Duration::from_millis(5), move |src| {
+			let position_ms = src.inner().get_pos().as_millis() as u64;
+			src.set_factor(gain * audio_frontend::fade_gain(length_ms.saturating_sub(position_ms), fade_out_ms));
+		})
+}
+
+/**
+ * A render running in the background.
+ */
+pub struct RenderJob {
+	/* 0.0 to 1.0, stored as the bits of an f32 */
+	progress: AtomicU32,
+	cancelled: AtomicBool,
+	/* Set once it's over: the file that was written, or what went wrong */
+	result: Mutex<Option<Result<String, String>>>,
+}
+
+pub fn new_render_job() -> Arc<RenderJob> {
+	Arc::new(RenderJob {
+		progress: AtomicU32::new(0),
+		cancelled: AtomicBool::new(false),
+		result: Mutex::new(None),
+	})
+}
+
+impl RenderJob {
+	pub fn progress(&self) -> f32 {
+		f32::from_bits(self.progress.load(Ordering::Relaxed))
+	}
+
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	pub fn result(&self) -> Option<Result<String, String>> {
+		self.result.lock().ok().and_then(|result| result.clone())
+	}
+}
+
+/**
+ * Plays the songs one after the other into a file, without a sound card. Blocks until it's done.
+ * A cancelled render deletes what it wrote so far.
+ */
+pub fn render_songs(songs: &[String], path: &str, format: RecordFormat, settings: RenderSettings, job: &RenderJob) -> Result<(), String> {
+	if songs.is_empty() {
+		return Err("Nothing to render".to_string());
+	}
+	let (sink, queue) = rodio::Sink::new_idle();
+	sink.set_speed(settings.speed);
+	sink.set_volume(settings.volume);
+	let mut total_ms = 0;
+	for song in songs {
+		let file = File::open(song).map_err(|err| format!("Couldn't open {}: {}", song, err))?;
+		let decoder = rodio::Decoder::new_mp3(std::io::BufReader::new(file)).map_err(|_| format!("Invalid mp3 format {}", song))?;
+		let gain = if settings.normalize {normalization_gain(song)?} else {1.0};
+		let length_ms = audio_frontend::get_song_len_ms(song);
+		sink.append(with_effects(decoder, settings, gain, length_ms as u64));
+		total_ms += length_ms;
+	}
+	let expected_samples = ((total_ms as f32 / settings.speed) as u64 * RECORD_SAMPLE_RATE as u64 / 1000 * RECORD_CHANNELS as u64).max(1);
+
+	let mut writer = create_audio_file(path, format)?;
+	let mut output = UniformSourceIterator::<_, f32>::new(queue, RECORD_CHANNELS, RECORD_SAMPLE_RATE);
+	let mut chunk = Vec::<f32>::with_capacity(CHUNK_SAMPLES * 16);
+	let mut written: u64 = 0;
+	/* The queue plays silence once it runs out of songs, so the sink being empty is what ends it */
+	while !sink.empty() {
+		if job.cancelled.load(Ordering::Relaxed) {
+			drop(writer);
+			let _ = std::fs::remove_file(path);
+			return Err("Cancelled".to_string());
+		}
+		chunk.clear();
+		/* Stopping only between frames keeps the channels lined up */
+		while chunk.len() < chunk.capacity() && (!chunk.len().is_multiple_of(RECORD_CHANNELS as usize) || !sink.empty()) && let Some(sample) = output.next() {
+			chunk.push(sample);
+		}
+		writer.write_samples(&chunk)?;
+		written += chunk.len() as u64;
+		job.progress.store((written as f32 / expected_samples as f32).min(1.0).to_bits(), Ordering::Relaxed);
+	}
+	writer.finish()
+}
+
+/**
+ * Runs render_songs on its own thread.
+ */
+pub fn start_render(songs: Vec<String>, path: String, format: RecordFormat, settings: RenderSettings) -> Arc<RenderJob> {
+	let job = new_render_job();
+	let thread_job = Arc::clone(&job);
+	std::thread::spawn(move || {
+		let result = render_songs(&songs, &path, format, settings, &thread_job).map(|_| path);
+		thread_job.progress.store(1.0f32.to_bits(), Ordering::Relaxed);
+		if let Ok(mut job_result) = thread_job.result.lock() {
+			*job_result = Some(result);
+		}
+	});
+	job
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expands_templates() {
+		/* 2024-03-05 06:07:08 UTC */
+		let time = 1709618828;
+		assert_eq!(expand_template(DEFAULT_RECORD_TEMPLATE, "/music/Some Song.mp3", time), "2024-03-05 06-07-08 Some Song");
+		assert_eq!(expand_template("{song}: take 2", "", time), "Pinetree_ take 2");
+		assert_eq!(expand_template("  ", "/music/a.mp3", time), "Pinetree");
+	}
+
+	#[test]
+	fn records_only_while_recording() {
+		let tap = new_record_tap();
+		/* Mono 48kHz, which playback gets as it is */
+		let tone = rodio::source::SineWave::new(440.0).take_duration(Duration::from_millis(500));
+		let mut source = record_source(tone, Arc::clone(&tap));
+		assert_eq!((source.channels(), source.sample_rate()), (1, 48000));
+		source.by_ref().take(1000).for_each(drop);
+		assert_eq!(tap.recorded_ms(), 0);
+
+		let (sender, receiver) = mpsc::channel::<RecordChunk>();
+		*tap.sender.lock().unwrap() = Some(sender);
+		tap.recording.store(true, Ordering::Release);
+		source.by_ref().take(CHUNK_SAMPLES * 3).for_each(drop);
+		tap.recording.store(false, Ordering::Release);
+		let chunks: Vec<RecordChunk> = receiver.try_iter().collect();
+		assert_eq!(chunks.iter().map(|chunk| chunk.samples.len()).sum::<usize>(), CHUNK_SAMPLES * 3);
+		assert!(chunks.iter().all(|chunk| (chunk.channels, chunk.sample_rate) == (1, 48000)));
+		/* Each chunk's length is rounded down to whole microseconds */
+		assert!(tap.recorded_ms().abs_diff((CHUNK_SAMPLES * 3 * 1000 / 48000) as u64) <= 1);
+
+		/* The writer's side turns it into 44.1kHz stereo */
+		let (sender, receiver) = mpsc::channel::<RecordChunk>();
+		for chunk in chunks {
+			sender.send(chunk).unwrap();
+		}
+		drop(sender);
+		let converted = UniformSourceIterator::<_, f32>::new(new_chunk_source(receiver), RECORD_CHANNELS, RECORD_SAMPLE_RATE).count();
+		let expected = CHUNK_SAMPLES * 3 * 2 * 44100 / 48000;
+		assert!(converted.abs_diff(expected) < 10, "{} samples", converted);
+	}
+
+	#[test]
+	fn fades_songs_in_and_out() {
+		let settings = RenderSettings {fade_in_ms: 200, fade_out_ms: 500, ..default_render_settings()};
+		let tone = rodio::source::SineWave::new(441.0).take_duration(Duration::from_millis(1000));
+		let samples: Vec<f32> = with_effects(tone, settings, 0.5, 1000).collect();
+		let loudest = |from_ms: usize, to_ms: usize| samples[from_ms * 48..to_ms * 48].iter().fold(0.0f32, |max, sample| max.max(sample.abs()));
+		assert!(loudest(0, 20) < 0.1);
+		assert!((loudest(300, 500) - 0.5).abs() < 0.01);
+		assert!((loudest(740, 760) - 0.25).abs() < 0.03, "{}", loudest(740, 760));
+		assert!(loudest(980, 1000) < 0.05);
+	}
+}
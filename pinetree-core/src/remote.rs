@@ -0,0 +1,391 @@
+/*
+ * Pinetree remote control. A running Pinetree listens for text commands, which is what `pinetree ctl` talks to:
+ *
+ * pinetree ctl play ~/Music/song.mp3
+ * pinetree ctl pause
+ * pinetree ctl seek 1:30
+ * pinetree ctl status --json
+ *
+ * Every connection sends a single command line and gets the response back before the connection is closed.
+ * Commands are translated into MessageToAudio, the same way the GUI does it.
+ *
+ * On unix this is a unix domain socket. Windows uses a loopback TCP socket instead, and writes the port
+ * to a file so the client knows where to connect.
+ */
+
+use std::io::{BufRead, Read, Write};
+use std::sync::{Arc, Condvar, Mutex};
+
+use crate::audio_frontend::{self, MessageToAudio, RodioData};
+use crate::library;
+
+#[cfg(target_family = "unix")]
+type RemoteListener = std::os::unix::net::UnixListener;
+#[cfg(target_family = "unix")]
+type RemoteStream = std::os::unix::net::UnixStream;
+#[cfg(target_family = "windows")]
+type RemoteListener = std::net::TcpListener;
+#[cfg(target_family = "windows")]
+type RemoteStream = std::net::TcpStream;
+
+type AudioSendChannel = Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>;
+type AudioReceiveChannel = Arc<(Mutex<Vec<RodioData>>, Condvar)>;
+
+const CONNECTION_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+pub const CTL_USAGE: &str = "Usage: pinetree ctl <command>
+
+Commands:
+  play [file]          Plays the file, or resumes playback if no file is given
+  pause                Pauses playback
+  toggle               Toggles between playing and paused
+  next                 Skips to the next song
+  prev                 Goes back to the previous song
+  seek <time>          Seeks to a time (90, 1:30) or by an offset (+10, -0:30)
+  volume <0.0-1.0>     Sets the volume, on the same scale as the volume slider
+  status [--json]      Prints what is currently playing";
+
+/**
+ * FNV-1a, used to give every data folder its own socket name.
+ * The std hasher isn't guaranteed to give the same result between builds, which would break talking to
+ * a Pinetree built by a different compiler version.
+ */
+fn hash_string(string: &str) -> u64 {
+	let mut hash: u64 = 0xcbf29ce484222325;
+	for byte in string.bytes() {
+		hash ^= byte as u64;
+		hash = hash.wrapping_mul(0x100000001b3);
+	}
+	hash
+}
+
+/**
+ * Where the socket (or on Windows, the port file) lives.
+ * Each user gets their own so two people on the same machine can't control each other's player,
+ * and each --data-dir profile gets its own so separate profiles don't take each other's commands.
+ */
+fn remote_address(data_dir: Option<&str>) -> String {
+	let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or("user".to_string());
+	let profile = if let Some(data_dir) = data_dir {format!("-{:016x}", hash_string(data_dir))} else {"".to_string()};
+	#[cfg(target_os = "linux")]
+	if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") && !runtime_dir.is_empty() {
+		return crate::files::build_full_filepath(&runtime_dir, &format!("pinetree{}.sock", profile));
+	}
+	let temp_dir = std::env::temp_dir();
+	let temp_dir = temp_dir.to_str().unwrap_or("");
+	#[cfg(target_family = "windows")]
+	return crate::files::build_full_filepath(temp_dir, &format!("pinetree-{}{}.port", user, profile));
+	#[cfg(target_family = "unix")]
+	return crate::files::build_full_filepath(temp_dir, &format!("pinetree-{}{}.sock", user, profile));
+}
+
+#[cfg(target_family = "unix")]
+fn connect(data_dir: Option<&str>) -> std::io::Result<RemoteStream> {
+	RemoteStream::connect(remote_address(data_dir))
+}
+
+#[cfg(target_family = "windows")]
+fn connect(data_dir: Option<&str>) -> std::io::Result<RemoteStream> {
+	let port = std::fs::read_to_string(remote_address(data_dir))?;
+	let port: u16 = port.trim().parse().map_err(|_| std::io::Error::other("Invalid port file"))?;
+	RemoteStream::connect(("127.0.0.1", port))
+}
+
+/**
+ * Starts listening for remote commands. Returns None if another Pinetree is already listening.
+ */
+#[cfg(target_family = "unix")]
+fn bind(data_dir: Option<&str>) -> Option<RemoteListener> {
+	let address = remote_address(data_dir);
+	match RemoteListener::bind(&address) {
+		Ok(listener) => Some(listener),
+		Err(_) => {
+			if connect(data_dir).is_ok() {
+				return None;
+			}
+			/* Nobody is answering, so this is left over from a Pinetree that crashed */
+			let _ = std::fs::remove_file(&address);
+			RemoteListener::bind(&address).ok()
+		},
+	}
+}
+
+#[cfg(target_family = "windows")]
+fn bind(data_dir: Option<&str>) -> Option<RemoteListener> {
+	if connect(data_dir).is_ok() {
+		return None;
+	}
+	let listener = RemoteListener::bind(("127.0.0.1", 0)).ok()?;
+	let port = listener.local_addr().ok()?.port();
+	std::fs::write(remote_address(data_dir), format!("{}", port)).ok()?;
+	Some(listener)
+}
+
+/**
+ * Sends one command to the running Pinetree and returns its response.
+ */
+pub fn send_command(command: &str, data_dir: Option<&str>) -> std::io::Result<String> {
+	let mut stream = connect(data_dir)?;
+	stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+	stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+	stream.write_all(format!("{}\n", command).as_bytes())?;
+	let mut response = String::new();
+	stream.read_to_string(&mut response)?;
+	Ok(response)
+}
+
+/**
+ * Parses "90", "1:30" or "1:02:03" into milliseconds.
+ */
+pub fn parse_time_ms(time: &str) -> Option<u64> {
+	let mut total_secs: f64 = 0.0;
+	for part in time.split(':') {
+		let value: f64 = part.trim().parse().ok()?;
+		if value < 0.0 {
+			return None;
+		}
+		total_secs = total_secs * 60.0 + value;
+	}
+	Some((total_secs * 1000.0) as u64)
+}
+
+fn format_time_ms(ms: usize) -> String {
+	let secs = ms / 1000;
+	format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+fn playback_state(audio_data: &RodioData) -> &'static str {
+	if audio_data.song_name.is_empty() {
+		"stopped"
+	} else if audio_data.is_paused {
+		"paused"
+	} else {
+		"playing"
+	}
+}
+
+fn status_response(audio_data: &RodioData, library: &Arc<Mutex<library::Library>>, json: bool) -> String {
+	let record = if let Ok(library) = library.lock() {
+		library.songs.get(&audio_data.song_name).cloned().unwrap_or_default()
+	} else {
+		library::SongRecord::default()
+	};
+	let title = if audio_data.song_name.is_empty() {"".to_string()} else {library::display_title(&audio_data.song_name, &record)};
+	let volume = audio_frontend::inverse_volume_curve(audio_data.volume).max(0.0);
+
+	if json {
+		use crate::scrobble::escape_json;
+		let error = if let Some(err) = &audio_data.error_message {format!("\"{}\"", escape_json(err))} else {"null".to_string()};
+		format!("{{\"state\":\"{}\",\"file\":\"{}\",\"title\":\"{}\",\"artist\":\"{}\",\"album\":\"{}\",\"position_ms\":{},\"length_ms\":{},\"volume\":{:.2},\"error\":{}}}\n",
+			playback_state(audio_data),
+			escape_json(&audio_data.song_name),
+			escape_json(&title),
+			escape_json(&record.artist),
+			escape_json(&record.album),
+			audio_data.playback_position,
+			audio_data.song_length,
+			volume,
+			error,
+		)
+	} else {
+		let mut response = format!("State: {}\n", playback_state(audio_data));
+		if !audio_data.song_name.is_empty() {
+			response.push_str(&format!("File: {}\n", audio_data.song_name));
+			response.push_str(&format!("Title: {}\n", title));
+			if !record.artist.is_empty() {
+				response.push_str(&format!("Artist: {}\n", record.artist));
+			}
+			if !record.album.is_empty() {
+				response.push_str(&format!("Album: {}\n", record.album));
+			}
+			response.push_str(&format!("Position: {} / {}\n", format_time_ms(audio_data.playback_position), format_time_ms(audio_data.song_length)));
+		}
+		response.push_str(&format!("Volume: {:.2}\n", volume));
+		if let Some(err) = &audio_data.error_message {
+			response.push_str(&format!("Error: {}\n", err));
+		}
+		response
+	}
+}
+
+/**
+ * Runs a single command line and returns the response. Failed commands respond with a line starting with "Error".
+ */
+fn handle_command(line: &str, audio_send: &AudioSendChannel, audio_receive: &AudioReceiveChannel, library: &Arc<Mutex<library::Library>>) -> String {
+	let line = line.trim();
+	let (command, argument) = match line.split_once(' ') {
+		Some((command, argument)) => (command, argument.trim()),
+		None => (line, ""),
+	};
+	let audio_data = audio_frontend::request_rodio_data(audio_send, audio_receive);
+	let has_song = !audio_data.song_name.is_empty();
+
+	match command {
+		"play" | "open" => {
+			if argument.is_empty() {
+				if audio_data.is_paused && has_song {
+					audio_frontend::send_audio_signal(audio_send, MessageToAudio::TogglePause);
+				}
+			} else {
+				audio_frontend::play_song_in_folder(audio_send, argument);
+			}
+		},
+		"pause" => {
+			if !audio_data.is_paused && has_song {
+				audio_frontend::send_audio_signal(audio_send, MessageToAudio::TogglePause);
+			}
+		},
+		"toggle" => {
+			if has_song {
+				audio_frontend::send_audio_signal(audio_send, MessageToAudio::TogglePause);
+			}
+		},
+		"next" => audio_frontend::send_audio_signal(audio_send, MessageToAudio::SongEnd),
+		"prev" | "previous" => audio_frontend::send_audio_signal(audio_send, MessageToAudio::PreviousSong),
+		"seek" => {
+			let (relative, time) = if let Some(time) = argument.strip_prefix('+') {
+				(1, time)
+			} else if let Some(time) = argument.strip_prefix('-') {
+				(-1, time)
+			} else {
+				(0, argument)
+			};
+			let time_ms = if let Some(time_ms) = parse_time_ms(time) {
+				time_ms as i64
+			} else {
+				return format!("Error: invalid time {}\n", argument);
+			};
+			if !has_song {
+				return "Error: no song is playing\n".to_string();
+			}
+			let target_ms = if relative == 0 {time_ms} else {audio_data.playback_position as i64 + relative * time_ms};
+			let target_ms = target_ms.clamp(0, audio_data.song_length as i64);
+			audio_frontend::send_audio_signal(audio_send, MessageToAudio::SeekOnce(target_ms as usize));
+		},
+		"volume" => {
+			let volume: f32 = if let Ok(volume) = argument.parse() {
+				volume
+			} else {
+				return format!("Error: invalid volume {}\n", argument);
+			};
+			let volume = if volume <= 0.0 {0.0} else {audio_frontend::volume_curve(volume.min(1.0))};
+			audio_frontend::send_audio_signal(audio_send, MessageToAudio::UpdateVolume(volume));
+		},
+		"status" => {
+			return status_response(&audio_data, library, argument == "--json");
+		},
+		_ => {
+			return format!("Error: unknown command {}\n", command);
+		},
+	}
+	"OK\n".to_string()
+}
+
+fn handle_connection(stream: RemoteStream, audio_send: &AudioSendChannel, audio_receive: &AudioReceiveChannel, library: &Arc<Mutex<library::Library>>) -> std::io::Result<()> {
+	stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+	stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+	let mut reader = std::io::BufReader::new(&stream);
+	let mut line = String::new();
+	reader.read_line(&mut line)?;
+	/* Other instances connect without sending anything just to check if this one is alive */
+	if line.trim().is_empty() {
+		return Ok(());
+	}
+	let response = handle_command(&line, audio_send, audio_receive, library);
+	(&stream).write_all(response.as_bytes())
+}
+
+/**
+ * Accepts remote commands until Pinetree closes. Returns straight away if another Pinetree is already listening.
+ */
+pub fn remote_server_loop(audio_send: AudioSendChannel, audio_receive: AudioReceiveChannel, library: Arc<Mutex<library::Library>>, data_dir: Option<String>) {
+	let listener = if let Some(listener) = bind(data_dir.as_deref()) {
+		listener
+	} else {
+		println!("Remote control is already used by another Pinetree");
+		return;
+	};
+	for stream in listener.incoming() {
+		match stream {
+			Ok(stream) => {
+				if handle_connection(stream, &audio_send, &audio_receive, &library).is_err() {
+					println!("Error in handling remote command");
+				}
+			},
+			Err(_) => {
+				println!("Error in accepting remote connection");
+			},
+		}
+	}
+}
+
+/**
+ * Relative paths are relative to wherever the client was started, not to the running Pinetree.
+ */
+pub fn absolute_path(path: &str) -> String {
+	if let Ok(absolute) = std::fs::canonicalize(path) && let Some(absolute) = absolute.to_str() {
+		#[cfg(target_os = "windows")]
+		return absolute.strip_prefix("\\\\?\\").unwrap_or(absolute).to_string();
+		#[cfg(not(target_os = "windows"))]
+		return absolute.to_string();
+	}
+	path.to_string()
+}
+
+/**
+ * Entry point for `pinetree ctl ...`. Returns the exit code.
+ */
+pub fn run_ctl(args: &[String], data_dir: Option<&str>) -> i32 {
+	if args.is_empty() || args[0] == "help" || args[0] == "--help" {
+		println!("{}", CTL_USAGE);
+		return if args.is_empty() {1} else {0};
+	}
+	let mut command = args[0].clone();
+	if args.len() > 1 {
+		let argument = args[1..].join(" ");
+		let argument = if command == "play" || command == "open" {absolute_path(&argument)} else {argument};
+		command = format!("{} {}", command, argument);
+	}
+	match send_command(&command, data_dir) {
+		Ok(response) => {
+			print!("{}", response);
+			if response.starts_with("Error") {1} else {0}
+		},
+		Err(_) => {
+			eprintln!("Error: Pinetree is not running");
+			1
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_times() {
+		assert_eq!(parse_time_ms("90"), Some(90000));
+		assert_eq!(parse_time_ms("1:30"), Some(90000));
+		assert_eq!(parse_time_ms("1:02:03"), Some(3723000));
+		assert_eq!(parse_time_ms("2.5"), Some(2500));
+		assert_eq!(parse_time_ms("-5"), None);
+		assert_eq!(parse_time_ms("1:xx"), None);
+		assert_eq!(parse_time_ms(""), None);
+	}
+
+	#[test]
+	fn formats_times() {
+		assert_eq!(format_time_ms(0), "0:00");
+		assert_eq!(format_time_ms(90500), "1:30");
+		assert_eq!(format_time_ms(3723000), "62:03");
+	}
+
+	#[test]
+	fn each_data_folder_gets_its_own_address() {
+		let default_address = remote_address(None);
+		let profile_address = remote_address(Some("/home/user/profile"));
+		assert_ne!(default_address, profile_address);
+		assert_eq!(profile_address, remote_address(Some("/home/user/profile")));
+		assert_ne!(profile_address, remote_address(Some("/home/user/other")));
+	}
+}
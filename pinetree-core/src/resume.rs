@@ -0,0 +1,161 @@
+/*
+ * Pinetree resume positions. Long files (podcasts, audiobooks) continue where they were left off instead of
+ * starting over every time. A file gets resumed if it is longer than the threshold, or if it is inside a folder
+ * marked as an audiobook/podcast folder.
+ *
+ * Positions are kept in the library. Stopping close to the end counts as having finished the file,
+ * which clears the position so it starts from the beginning next time.
+ */
+
+use crate::library;
+
+pub const DEFAULT_RESUME_THRESHOLD_MINS: u64 = 20;
+/* Stopping this close to the end counts as having finished the file (or a tenth of it, for short files) */
+pub const FINISHED_MARGIN_MS: usize = 15000;
+/* Resuming goes back a little, to catch up on what was being said */
+pub const RESUME_REWIND_MS: usize = 3000;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct ResumeSettings {
+	/* Files at least this long get resumed, 0 turns it off for files outside audiobook folders */
+	pub threshold_mins: u64,
+	pub audiobook_folders: Vec<String>,
+}
+
+pub fn default_resume_settings() -> ResumeSettings {
+	ResumeSettings {
+		threshold_mins: DEFAULT_RESUME_THRESHOLD_MINS,
+		audiobook_folders: Vec::new(),
+	}
+}
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+pub enum PlayState {
+	Unplayed,
+	InProgress,
+	Played,
+}
+
+fn normalize_path(path: &str) -> String {
+	path.replace('\\', "/").trim_end_matches('/').to_string()
+}
+
+pub fn is_in_folder(song: &str, folder: &str) -> bool {
+	let folder = normalize_path(folder);
+	!folder.is_empty() && normalize_path(song).strip_prefix(&folder).is_some_and(|rest| rest.starts_with('/'))
+}
+
+pub fn is_audiobook(settings: &ResumeSettings, song: &str) -> bool {
+	settings.audiobook_folders.iter().any(|folder| is_in_folder(song, folder))
+}
+
+/**
+ * Whether the song keeps a resume position. length_ms is 0 when the length isn't known yet.
+ */
+pub fn should_resume(settings: &ResumeSettings, song: &str, length_ms: u64) -> bool {
+	is_audiobook(settings, song) || (settings.threshold_mins > 0 && length_ms >= settings.threshold_mins * 60000)
+}
+
+/**
+ * Where to start playing the song, 0 for the beginning.
+ */
+pub fn resume_position(settings: &ResumeSettings, song: &str, record: &library::SongRecord) -> usize {
+	if record.resume_position_ms == 0 || !should_resume(settings, song, record.length_ms) {
+		return 0;
+	}
+	record.resume_position_ms.saturating_sub(RESUME_REWIND_MS)
+}
+
+/**
+ * Saves where the song was left off. Does nothing for songs that don't get resumed.
+ */
+pub fn remember_position(library: &mut library::Library, settings: &ResumeSettings, song: &str, position_ms: usize) {
+	let record = library::get_or_create_record(library, song);
+	if !should_resume(settings, song, record.length_ms) {
+		return;
+	}
+	let length_ms = record.length_ms as usize;
+	if length_ms > 0 && position_ms + FINISHED_MARGIN_MS.min(length_ms / 10) >= length_ms {
+		record.resume_position_ms = 0;
+		record.finished = true;
+	} else {
+		record.resume_position_ms = position_ms;
+	}
+}
+
+pub fn play_state(record: Option<&library::SongRecord>) -> PlayState {
+	match record {
+		Some(record) if record.resume_position_ms > 0 => PlayState::InProgress,
+		Some(record) if record.finished || record.completion_count > 0 => PlayState::Played,
+		_ => PlayState::Unplayed,
+	}
+}
+
+/**
+ * The state to show next to a song in the file list, if any. Files in audiobook folders always show it,
+ * elsewhere only long files that were started show it.
+ */
+pub fn displayed_play_state(settings: &ResumeSettings, song: &str, record: Option<&library::SongRecord>) -> Option<PlayState> {
+	let started_long_file = record.is_some_and(|record| record.resume_position_ms > 0 && should_resume(settings, song, record.length_ms));
+	if is_audiobook(settings, song) || started_long_file {
+		Some(play_state(record))
+	} else {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn long_record(length_ms: u64) -> library::SongRecord {
+		library::SongRecord {length_ms, ..Default::default()}
+	}
+
+	#[test]
+	fn matches_audiobook_folders() {
+		let settings = ResumeSettings {threshold_mins: 0, audiobook_folders: vec!["/books/".to_string(), "C:\\Podcasts".to_string()]};
+		assert!(is_audiobook(&settings, "/books/Some Book/01.mp3"));
+		assert!(is_audiobook(&settings, "C:\\Podcasts\\episode.mp3"));
+		assert!(!is_audiobook(&settings, "/bookshelf/01.mp3"));
+		assert!(!is_audiobook(&settings, "/music/song.mp3"));
+		assert!(!is_in_folder("/music/song.mp3", ""));
+	}
+
+	#[test]
+	fn resumes_long_files() {
+		let settings = default_resume_settings();
+		assert!(!should_resume(&settings, "/music/song.mp3", 4 * 60000));
+		assert!(should_resume(&settings, "/music/mix.mp3", 20 * 60000));
+		assert!(!should_resume(&ResumeSettings {threshold_mins: 0, audiobook_folders: Vec::new()}, "/music/mix.mp3", 600 * 60000));
+
+		let mut library = library::new_library(None);
+		library.songs.insert("/music/mix.mp3".to_string(), long_record(60 * 60000));
+		library.songs.insert("/music/song.mp3".to_string(), long_record(3 * 60000));
+		remember_position(&mut library, &settings, "/music/mix.mp3", 600000);
+		remember_position(&mut library, &settings, "/music/song.mp3", 60000);
+		assert_eq!(resume_position(&settings, "/music/mix.mp3", &library.songs["/music/mix.mp3"]), 600000 - RESUME_REWIND_MS);
+		assert_eq!(library.songs["/music/song.mp3"].resume_position_ms, 0);
+		assert_eq!(play_state(library.songs.get("/music/mix.mp3")), PlayState::InProgress);
+		assert_eq!(play_state(library.songs.get("/music/other.mp3")), PlayState::Unplayed);
+		assert_eq!(displayed_play_state(&settings, "/music/mix.mp3", library.songs.get("/music/mix.mp3")), Some(PlayState::InProgress));
+		assert_eq!(displayed_play_state(&settings, "/music/song.mp3", library.songs.get("/music/song.mp3")), None);
+	}
+
+	#[test]
+	fn finishing_clears_the_position() {
+		let settings = default_resume_settings();
+		let mut library = library::new_library(None);
+		library.songs.insert("/music/mix.mp3".to_string(), long_record(60 * 60000));
+		remember_position(&mut library, &settings, "/music/mix.mp3", 60 * 60000 - 5000);
+		let record = &library.songs["/music/mix.mp3"];
+		assert_eq!(record.resume_position_ms, 0);
+		assert_eq!(resume_position(&settings, "/music/mix.mp3", record), 0);
+		assert_eq!(play_state(Some(record)), PlayState::Played);
+	}
+}
@@ -0,0 +1,216 @@
+/*
+ * Pinetree library roots. The folders the library is made of, e.g. local music, a NAS mount and a podcasts
+ * folder. Every root gets scanned into the song index (see index::start_scan).
+ *
+ * Each root can limit how deep into its subfolders the scan goes, leave out some subfolders, and only pick up
+ * files matching some globs. Without any roots set up the default folder is the only root.
+ */
+
+use std::collections::HashMap;
+
+use crate::config::PersistentData;
+use crate::files;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct LibraryRoot {
+	pub name: String,
+	pub path: String,
+	/* How many levels of subfolders get scanned, None for all of them. 0 only scans the root folder itself */
+	pub max_depth: Option<usize>,
+	/* Subfolders to leave out, relative to the root (or full paths) */
+	pub excludes: Vec<String>,
+	/* Only song files matching one of these are added, e.g. "*.mp3" or "* - *". Empty adds every song file */
+	pub globs: Vec<String>,
+}
+
+pub fn new_root(path: &str) -> LibraryRoot {
+	LibraryRoot {
+		name: folder_name(path),
+		path: path.to_string(),
+		max_depth: None,
+		excludes: Vec::new(),
+		globs: Vec::new(),
+	}
+}
+
+fn normalize_path(path: &str) -> String {
+	path.replace('\\', "/").trim_end_matches('/').to_string()
+}
+
+fn folder_name(path: &str) -> String {
+	normalize_path(path).rsplit('/').next().filter(|name| !name.is_empty()).unwrap_or(path).to_string()
+}
+
+/**
+ * The roots to scan. Data files from before roots existed only have the default folder, which then is the root.
+ */
+pub fn library_roots(persistent_data: &PersistentData) -> Vec<LibraryRoot> {
+	if persistent_data.library_roots.is_empty() && !persistent_data.default_directory.is_empty() {
+		vec![new_root(&persistent_data.default_directory)]
+	} else {
+		persistent_data.library_roots.clone()
+	}
+}
+
+pub fn is_under(path: &str, folder: &str) -> bool {
+	let folder = normalize_path(folder);
+	let path = normalize_path(path);
+	!folder.is_empty() && (path == folder || path.strip_prefix(&folder).is_some_and(|rest| rest.starts_with('/')))
+}
+
+/* The root a song or folder is in, the deepest one if roots are nested */
+pub fn root_of<'a>(roots: &'a [LibraryRoot], path: &str) -> Option<&'a LibraryRoot> {
+	roots.iter().filter(|root| is_under(path, &root.path)).max_by_key(|root| normalize_path(&root.path).len())
+}
+
+pub fn is_excluded(root: &LibraryRoot, folder: &str) -> bool {
+	root.excludes.iter().filter(|exclude| !exclude.trim().is_empty()).any(|exclude| {
+		let exclude = exclude.trim();
+		let is_full_path = exclude.starts_with('/') || exclude.contains(":\\") || exclude.contains(":/");
+		let excluded_folder = if is_full_path {exclude.to_string()} else {files::build_full_filepath(&root.path, exclude)};
+		is_under(folder, &excluded_folder)
+	})
+}
+
+/**
+ * Matches a file name against a glob with * (any run of characters) and ? (any one character), ignoring case.
+ */
+pub fn glob_matches(glob: &str, name: &str) -> bool {
+	let glob: Vec<char> = glob.to_lowercase().chars().collect();
+	let name: Vec<char> = name.to_lowercase().chars().collect();
+	/* Where to go back to after a mismatch: the last * and the name position it is covering up to */
+	let mut backtrack: Option<(usize, usize)> = None;
+	let (mut g, mut n) = (0, 0);
+	while n < name.len() {
+		if g < glob.len() && (glob[g] == '?' || glob[g] == name[n]) {
+			g += 1;
+			n += 1;
+		} else if g < glob.len() && glob[g] == '*' {
+			backtrack = Some((g, n));
+			g += 1;
+		} else if let Some((star, covered)) = backtrack {
+			g = star + 1;
+			n = covered + 1;
+			backtrack = Some((star, covered + 1));
+		} else {
+			return false;
+		}
+	}
+	glob[g..].iter().all(|c| *c == '*')
+}
+
+pub fn matches_globs(root: &LibraryRoot, song: &str) -> bool {
+	let name = std::path::Path::new(song).file_name().and_then(|name| name.to_str()).unwrap_or(song);
+	let globs: Vec<&String> = root.globs.iter().filter(|glob| !glob.trim().is_empty()).collect();
+	globs.is_empty() || globs.iter().any(|glob| glob_matches(glob.trim(), name))
+}
+
+fn depth_below(root: &LibraryRoot, folder: &str) -> usize {
+	normalize_path(folder).matches('/').count().saturating_sub(normalize_path(&root.path).matches('/').count())
+}
+
+/**
+ * Whether the root's depth and excludes let the songs of a folder in it through. Folders in a deeper root belong to
+ * that one, with its own settings.
+ */
+pub fn folder_allowed(library_roots: &[LibraryRoot], root: &LibraryRoot, folder: &str) -> bool {
+	root_of(library_roots, folder).is_some_and(|deepest| normalize_path(&deepest.path) == normalize_path(&root.path))
+	&& root.max_depth.is_none_or(|max_depth| depth_below(root, folder) <= max_depth)
+	&& !is_excluded(root, folder)
+}
+
+/* Whether the song is in a root whose settings let it through */
+pub fn song_allowed(library_roots: &[LibraryRoot], song: &str) -> bool {
+	let folder = std::path::Path::new(song).parent().and_then(|folder| folder.to_str()).unwrap_or("");
+	root_of(library_roots, folder).is_some_and(|root| folder_allowed(library_roots, root, folder) && matches_globs(root, song))
+}
+
+/**
+ * Every song in the root that its settings let through, leaving out the ones in deeper roots.
+ */
+pub fn root_songs(library_roots: &[LibraryRoot], root: &LibraryRoot) -> Result<Vec<String>, String> {
+	let mut dir_map = HashMap::<String, files::Directory>::new();
+	if !files::init_directory_at_filepath_recursive(&root.path, &mut dir_map) {
+		return Err(format!("Could not read {}", root.path));
+	}
+	let mut songs: Vec<String> = dir_map.into_values()
+		.filter(|directory| folder_allowed(library_roots, root, &directory.filepath_identifier))
+		.flat_map(|directory| directory.songs)
+		.filter(|song| matches_globs(root, song))
+		.collect();
+	songs.sort();
+	Ok(songs)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	#[test]
+	fn matches_globs_and_folders() {
+		assert!(glob_matches("*.mp3", "Song.MP3"));
+		assert!(glob_matches("* - *", "Band - Song.mp3"));
+		assert!(!glob_matches("* - *", "Band_Song.mp3"));
+		assert!(glob_matches("track??.mp3", "track01.mp3"));
+		assert!(!glob_matches("track?.mp3", "track01.mp3"));
+		assert!(glob_matches("*a*b*", "xxaxxbxx"));
+
+		let mut root = new_root("/music/");
+		assert_eq!(root.name, "music");
+		root.excludes = vec!["Old".to_string(), "/mnt/nas/skip".to_string()];
+		assert!(is_excluded(&root, "/music/Old/Album"));
+		assert!(!is_excluded(&root, "/music/Oldies"));
+		assert!(is_excluded(&root, "/mnt/nas/skip"));
+
+		let roots = vec![new_root("/music"), new_root("/music/Podcasts")];
+		assert_eq!(root_of(&roots, "/music/Podcasts/ep.mp3").unwrap().name, "Podcasts");
+		assert_eq!(root_of(&roots, "/music/a.mp3").unwrap().name, "music");
+		assert!(root_of(&roots, "/other/a.mp3").is_none());
+
+		root.max_depth = Some(1);
+		root.globs = vec!["*.mp3".to_string()];
+		let mut podcasts = new_root("/music/Podcasts/");
+		podcasts.globs = vec!["*.ogg".to_string()];
+		let roots = vec![root, podcasts];
+		assert!(song_allowed(&roots, "/music/Band/song.mp3"));
+		assert!(!song_allowed(&roots, "/music/Band/Album/song.mp3"));
+		assert!(!song_allowed(&roots, "/music/Old/song.mp3"));
+		assert!(!song_allowed(&roots, "/music/cover.jpg"));
+		assert!(!song_allowed(&roots, "/elsewhere/song.mp3"));
+		/* The nested root's settings win over the outer one's */
+		assert!(song_allowed(&roots, "/music/Podcasts/Show/Season 1/ep.ogg"));
+		assert!(!song_allowed(&roots, "/music/Podcasts/ep.mp3"));
+		assert!(!folder_allowed(&roots, &roots[0], "/music/Podcasts"));
+	}
+
+	#[test]
+	fn scans_with_depth_excludes_and_globs() {
+		let folder = temp_file_path("roots");
+		for sub in ["", "A", "A/Deeper", "Skip"] {
+			let sub_folder = files::build_full_filepath(&folder, sub);
+			std::fs::create_dir_all(&sub_folder).unwrap();
+			for name in ["one.mp3", "two - live.mp3"] {
+				std::fs::write(files::build_full_filepath(&sub_folder, name), [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+			}
+		}
+		let mut root = new_root(&folder);
+		assert_eq!(root_songs(std::slice::from_ref(&root), &root).unwrap().len(), 8);
+		root.max_depth = Some(1);
+		root.excludes = vec!["Skip".to_string()];
+		root.globs = vec!["*live*".to_string()];
+		let songs = root_songs(std::slice::from_ref(&root), &root);
+		/* A nested root takes its folders out of the outer one */
+		let nested = new_root(&files::build_full_filepath(&folder, "A"));
+		let roots = vec![root.clone(), nested.clone()];
+		let outer_songs = root_songs(&roots, &root);
+		let nested_songs = root_songs(&roots, &nested);
+		let _ = std::fs::remove_dir_all(&folder);
+		let relative = |songs: Vec<String>| -> Vec<String> {songs.iter().map(|song| song[folder.len()..].replace('\\', "/")).collect()};
+		assert_eq!(relative(songs.unwrap()), vec!["/A/two - live.mp3", "/two - live.mp3"]);
+		assert_eq!(relative(outer_songs.unwrap()), vec!["/two - live.mp3"]);
+		assert_eq!(relative(nested_songs.unwrap()), vec!["/A/Deeper/one.mp3", "/A/Deeper/two - live.mp3", "/A/one.mp3", "/A/two - live.mp3"]);
+	}
+}
@@ -0,0 +1,569 @@
+/*
+ * Pinetree scrobbling. Writes an offline log of listened songs, and optionally submits them to a server.
+ *
+ * A song counts as listened (scrobbled) once it has been played for half its length or 4 minutes,
+ * whichever comes first. Songs shorter than 30 seconds are never scrobbled. These are the standard rules
+ * Last.fm and ListenBrainz use.
+ *
+ * Two log formats are supported:
+ * - The Rockbox .scrobbler.log format, which most Last.fm uploaders understand
+ * - ListenBrainz JSON lines (one listen per line), which can be imported into ListenBrainz
+ *
+ * Submitting uses the ListenBrainz submit-listens API. Listens that haven't been submitted yet are kept in
+ * a queue file so they aren't lost if the server can't be reached or Pinetree is closed. Listens the server
+ * refuses (e.g. one without an artist) are moved to a file of their own, so they don't hold up the rest.
+ */
+
+use std::io::{BufRead, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::files::build_full_filepath;
+
+pub const ROCKBOX_LOG_FILE_NAME: &str = ".scrobbler.log";
+pub const LISTENBRAINZ_LOG_FILE_NAME: &str = "listenbrainz_listens.jsonl";
+pub const QUEUE_FILE_NAME: &str = "scrobble_queue.jsonl";
+pub const REJECTED_FILE_NAME: &str = "scrobble_rejected.jsonl";
+
+/* Songs have to be at least this long to be scrobbled at all */
+const MIN_SONG_LENGTH_MS: u64 = 30 * 1000;
+/* Listening for this long always counts, even if it is less than half the song */
+const MAX_REQUIRED_LISTEN_MS: u64 = 4 * 60 * 1000;
+/* Maximum number of listens sent in a single request */
+const SUBMIT_BATCH_SIZE: usize = 100;
+const SUBMIT_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+const MAX_RETRY_DELAY_SECS: u64 = 600;
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+pub enum ScrobbleLogFormat {
+	Off,
+	Rockbox,
+	ListenBrainz,
+	Both,
+}
+
+pub fn log_format_to_str(format: &ScrobbleLogFormat) -> &'static str {
+	match format {
+		ScrobbleLogFormat::Off => "Off",
+		ScrobbleLogFormat::Rockbox => "Rockbox",
+		ScrobbleLogFormat::ListenBrainz => "ListenBrainz",
+		ScrobbleLogFormat::Both => "Both",
+	}
+}
+
+pub fn str_to_log_format(string: &str) -> ScrobbleLogFormat {
+	match string {
+		"Rockbox" => ScrobbleLogFormat::Rockbox,
+		"ListenBrainz" => ScrobbleLogFormat::ListenBrainz,
+		"Both" => ScrobbleLogFormat::Both,
+		_ => ScrobbleLogFormat::Off,
+	}
+}
+
+pub struct Listen {
+	pub artist: String,
+	pub album: String,
+	pub title: String,
+	pub track: String,
+	pub duration_ms: u64,
+	/* Unix time the song started playing */
+	pub listened_at: u64,
+}
+
+pub struct Scrobbler {
+	pub log_format: ScrobbleLogFormat,
+	/* Base URL of the server to submit to (e.g. http://localhost:8080). Empty means submitting is off */
+	pub endpoint: String,
+	pub token: String,
+
+	/* Folder the logs and the queue are written to. None means nothing gets written (not installed) */
+	pub folder: Option<String>,
+	/* Listens waiting to be submitted, already serialized as ListenBrainz JSON */
+	pub queue: Vec<String>,
+	pub last_submit_error: Option<String>,
+}
+
+pub fn init_scrobbler(folder: Option<String>) -> Scrobbler {
+	let mut queue = Vec::<String>::new();
+	if let Some(folder) = &folder
+	&& let Ok(file) = std::fs::File::open(build_full_filepath(folder, QUEUE_FILE_NAME)) {
+		let reader = std::io::BufReader::new(file);
+		for line in reader.lines().map_while(Result::ok) {
+			if !line.is_empty() {
+				queue.push(line);
+			}
+		}
+	}
+	Scrobbler {
+		log_format: ScrobbleLogFormat::Off,
+		endpoint: "".to_string(),
+		token: "".to_string(),
+		folder,
+		queue,
+		last_submit_error: None,
+	}
+}
+
+pub fn should_scrobble(listened_ms: u64, song_length_ms: u64) -> bool {
+	if song_length_ms < MIN_SONG_LENGTH_MS {
+		return false;
+	}
+	listened_ms >= std::cmp::min(song_length_ms / 2, MAX_REQUIRED_LISTEN_MS)
+}
+
+pub fn escape_json(string: &str) -> String {
+	let mut escaped = String::with_capacity(string.len());
+	for c in string.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			'\n' => escaped.push_str("\\n"),
+			'\r' => escaped.push_str("\\r"),
+			'\t' => escaped.push_str("\\t"),
+			c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/**
+ * Formats a listen the way ListenBrainz expects it (one "listen" object of the payload).
+ */
+pub fn listen_to_listenbrainz_json(listen: &Listen) -> String {
+	let mut additional_info = format!("\"duration_ms\":{},\"media_player\":\"Pinetree\",\"submission_client\":\"Pinetree\",\"submission_client_version\":\"{}\"",
+		listen.duration_ms, escape_json(crate::config::CURRENT_VERSION));
+	if !listen.track.is_empty() {
+		additional_info = format!("{},\"tracknumber\":\"{}\"", additional_info, escape_json(&listen.track));
+	}
+	let release = if listen.album.is_empty() {"".to_string()} else {format!(",\"release_name\":\"{}\"", escape_json(&listen.album))};
+	format!("{{\"listened_at\":{},\"track_metadata\":{{\"artist_name\":\"{}\",\"track_name\":\"{}\"{},\"additional_info\":{{{}}}}}}}",
+		listen.listened_at,
+		escape_json(&listen.artist),
+		escape_json(&listen.title),
+		release,
+		additional_info,
+	)
+}
+
+/**
+ * One line of a Rockbox .scrobbler.log:
+ * ARTIST <tab> ALBUM <tab> TITLE <tab> TRACKNUM <tab> LENGTH <tab> RATING <tab> TIMESTAMP <tab> MUSICBRAINZ_TRACKID
+ *
+ * The rating is always L (listened) since skipped songs never get logged.
+ */
+pub fn listen_to_rockbox_line(listen: &Listen) -> String {
+	let clean = |field: &str| field.replace(['\t', '\n', '\r'], " ");
+	/* Track numbers are sometimes stored as "3/12" */
+	let track = listen.track.split('/').next().unwrap_or("").trim().to_string();
+	format!("{}\t{}\t{}\t{}\t{}\tL\t{}\t\n",
+		clean(&listen.artist),
+		clean(&listen.album),
+		clean(&listen.title),
+		clean(&track),
+		listen.duration_ms / 1000,
+		listen.listened_at,
+	)
+}
+
+fn append_to_file(path: &str, header: Option<String>, data: &str) -> std::io::Result<()> {
+	let is_new = !std::path::Path::new(path).exists();
+	let mut file = std::fs::OpenOptions::new()
+		.create(true)
+		.append(true)
+		.open(path)?;
+	if is_new && let Some(header) = header {
+		file.write_all(header.as_bytes())?;
+	}
+	file.write_all(data.as_bytes())
+}
+
+fn write_queue(scrobbler: &Scrobbler) -> std::io::Result<()> {
+	if let Some(folder) = &scrobbler.folder {
+		let mut data_to_write = String::new();
+		for listen in &scrobbler.queue {
+			data_to_write.push_str(listen);
+			data_to_write.push('\n');
+		}
+		std::fs::write(build_full_filepath(folder, QUEUE_FILE_NAME), data_to_write)?;
+	}
+	Ok(())
+}
+
+/**
+ * Writes the listen to the enabled logs, and queues it up for submission if submitting is on.
+ */
+pub fn record_listen(scrobbler: &mut Scrobbler, listen: &Listen) -> std::io::Result<()> {
+	let folder = if let Some(folder) = &scrobbler.folder {
+		folder.clone()
+	} else {
+		return Ok(());
+	};
+
+	if scrobbler.log_format == ScrobbleLogFormat::Rockbox || scrobbler.log_format == ScrobbleLogFormat::Both {
+		let header = format!("#AUDIOSCROBBLER/1.1\n#TZ/UTC\n#CLIENT/Pinetree {}\n", crate::config::CURRENT_VERSION);
+		append_to_file(&build_full_filepath(&folder, ROCKBOX_LOG_FILE_NAME), Some(header), &listen_to_rockbox_line(listen))?;
+	}
+	if scrobbler.log_format == ScrobbleLogFormat::ListenBrainz || scrobbler.log_format == ScrobbleLogFormat::Both {
+		let line = format!("{}\n", listen_to_listenbrainz_json(listen));
+		append_to_file(&build_full_filepath(&folder, LISTENBRAINZ_LOG_FILE_NAME), None, &line)?;
+	}
+	if !scrobbler.endpoint.is_empty() {
+		scrobbler.queue.push(listen_to_listenbrainz_json(listen));
+		write_queue(scrobbler)?;
+	}
+	Ok(())
+}
+
+/**
+ * Splits http://host:port/path into ("host:port", "/path").
+ * Only plain http is supported, so this is meant for local servers or relays.
+ */
+fn split_http_url(url: &str) -> Result<(String, String), String> {
+	let rest = if let Some(rest) = url.strip_prefix("http://") {
+		rest
+	} else if url.starts_with("https://") {
+		return Err("Error: https endpoints are not supported, use a local http relay".to_string());
+	} else {
+		return Err(format!("Error: invalid endpoint {}", url));
+	};
+	let (host, path) = match rest.find('/') {
+		Some(slash) => (&rest[..slash], &rest[slash..]),
+		None => (rest, "/"),
+	};
+	if host.is_empty() {
+		return Err(format!("Error: invalid endpoint {}", url));
+	}
+	let host = if host.contains(':') {host.to_string()} else {format!("{}:80", host)};
+	Ok((host, path.to_string()))
+}
+
+/**
+ * Sends a POST request and returns the status code of the response.
+ */
+fn http_post(url: &str, token: &str, body: &str) -> Result<u16, String> {
+	use std::net::ToSocketAddrs;
+
+	let (host, path) = split_http_url(url)?;
+	let timeout = std::time::Duration::from_secs(10);
+	let address = host.to_socket_addrs()
+		.map_err(|e| format!("Error: could not resolve {} ({})", host, e))?
+		.next()
+		.ok_or(format!("Error: could not resolve {}", host))?;
+	let mut stream = std::net::TcpStream::connect_timeout(&address, timeout)
+		.map_err(|e| format!("Error: could not connect to {} ({})", host, e))?;
+	let _ = stream.set_read_timeout(Some(timeout));
+	let _ = stream.set_write_timeout(Some(timeout));
+
+	let request = format!("POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Token {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+		path, host, token, body.len(), body);
+	stream.write_all(request.as_bytes()).map_err(|e| format!("Error: failed to send listens ({})", e))?;
+
+	let mut response = String::new();
+	let _ = stream.read_to_string(&mut response);
+	let status_line = response.lines().next().unwrap_or("");
+	status_line.split(' ').nth(1)
+		.and_then(|code| code.parse::<u16>().ok())
+		.ok_or(format!("Error: invalid response from {}", host))
+}
+
+pub fn submit_url(endpoint: &str) -> String {
+	let endpoint = endpoint.trim_end_matches('/');
+	if endpoint.ends_with("/1/submit-listens") {
+		endpoint.to_string()
+	} else {
+		format!("{}/1/submit-listens", endpoint)
+	}
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum SubmitOutcome {
+	/* Nothing queued, or submitting is off */
+	Idle,
+	/* How many listens were submitted and how many the server refused */
+	Done(usize, usize),
+	/* The server or the connection had a problem, the listens are worth sending again later */
+	Failed,
+}
+
+/* The server found something wrong with the listens themselves, sending them again won't help */
+fn is_refusal(code: u16) -> bool {
+	matches!(code, 400 | 413 | 422)
+}
+
+/**
+ * Sends listens and takes them off the front of the queue once the server accepted them. Ok(false) if the server
+ * refused them, they are left in the queue.
+ */
+fn submit_listens(scrobbler: &Mutex<Scrobbler>, url: &str, token: &str, batch: &[String]) -> Result<bool, ()> {
+	let listen_type = if batch.len() == 1 {"single"} else {"import"};
+	let body = format!("{{\"listen_type\":\"{}\",\"payload\":[{}]}}", listen_type, batch.join(","));
+	let result = http_post(url, token, &body);
+	let mut scrobbler = scrobbler.lock().map_err(|_| ())?;
+	match result {
+		Ok(code) if (200..300).contains(&code) => {},
+		Ok(code) if is_refusal(code) => return Ok(false),
+		/* A wrong or expired token, nothing is wrong with the listens */
+		Ok(code @ (401 | 403)) => {
+			scrobbler.last_submit_error = Some(format!("Error: the server didn't accept the token (status {})", code));
+			return Err(());
+		},
+		Ok(code) => {
+			scrobbler.last_submit_error = Some(format!("Error: server responded with status {}", code));
+			return Err(());
+		},
+		Err(err) => {
+			scrobbler.last_submit_error = Some(err);
+			return Err(());
+		},
+	}
+	/* New listens only ever get pushed to the end, so the batch is still at the front */
+	let submitted = std::cmp::min(batch.len(), scrobbler.queue.len());
+	scrobbler.queue.drain(..submitted);
+	if write_queue(&scrobbler).is_err() {
+		println!("Error in saving scrobble queue");
+	}
+	Ok(true)
+}
+
+/**
+ * Moves a refused listen from the front of the queue to the rejected file. Returns whether it was moved.
+ */
+fn reject_listen(scrobbler: &Mutex<Scrobbler>, folder: &str, listen: &str) -> bool {
+	let Ok(mut scrobbler) = scrobbler.lock() else {return false;};
+	if append_to_file(&build_full_filepath(folder, REJECTED_FILE_NAME), None, &format!("{}\n", listen)).is_err() {
+		scrobbler.last_submit_error = Some(format!("Error: could not save a refused listen to {}", REJECTED_FILE_NAME));
+		return false;
+	}
+	if scrobbler.queue.first().is_some_and(|first| first == listen) {
+		scrobbler.queue.remove(0);
+	}
+	if write_queue(&scrobbler).is_err() {
+		println!("Error in saving scrobble queue");
+	}
+	true
+}
+
+/**
+ * Submits the listens at the front of the queue. If the server refuses a batch, its listens get sent one at a
+ * time, and the ones it refuses on their own are moved to the rejected file. Without a folder to keep that file in
+ * the refused listens stay in the queue.
+ */
+pub fn submit_next(scrobbler: &Mutex<Scrobbler>) -> SubmitOutcome {
+	let (url, token, batch, folder) = if let Ok(scrobbler) = scrobbler.lock() {
+		if scrobbler.endpoint.is_empty() || scrobbler.queue.is_empty() {
+			return SubmitOutcome::Idle;
+		}
+		let batch: Vec<String> = scrobbler.queue.iter().take(SUBMIT_BATCH_SIZE).cloned().collect();
+		(submit_url(&scrobbler.endpoint), scrobbler.token.clone(), batch, scrobbler.folder.clone())
+	} else {
+		return SubmitOutcome::Idle;
+	};
+
+	match submit_listens(scrobbler, &url, &token, &batch) {
+		Ok(true) => {
+			if let Ok(mut scrobbler) = scrobbler.lock() {
+				scrobbler.last_submit_error = None;
+			}
+			return SubmitOutcome::Done(batch.len(), 0);
+		},
+		Ok(false) => {},
+		Err(()) => return SubmitOutcome::Failed,
+	}
+	let Some(folder) = folder else {
+		if let Ok(mut scrobbler) = scrobbler.lock() {
+			scrobbler.last_submit_error = Some("Error: the server refused listens, they were kept in the queue".to_string());
+		}
+		return SubmitOutcome::Failed;
+	};
+	let mut submitted = 0;
+	let mut rejected = 0;
+	for listen in &batch {
+		/* A batch of one was refused on its own already */
+		let accepted = if batch.len() == 1 {Ok(false)} else {submit_listens(scrobbler, &url, &token, std::slice::from_ref(listen))};
+		match accepted {
+			Ok(true) => submitted += 1,
+			Ok(false) if reject_listen(scrobbler, &folder, listen) => rejected += 1,
+			Ok(false) | Err(()) => return SubmitOutcome::Failed,
+		}
+	}
+	if let Ok(mut scrobbler) = scrobbler.lock() {
+		scrobbler.last_submit_error = if rejected == 0 {None} else {
+			Some(format!("Error: the server refused {} listens, they were moved to {}", rejected, REJECTED_FILE_NAME))
+		};
+	}
+	SubmitOutcome::Done(submitted, rejected)
+}
+
+/**
+ * Runs forever, submitting queued listens whenever there are any.
+ * Failed submissions are retried with an increasing delay so a dead server isn't hammered.
+ */
+pub fn submitter_loop(scrobbler: Arc<Mutex<Scrobbler>>) {
+	let mut retry_delay_secs = 0;
+	let mut next_attempt = std::time::SystemTime::now();
+	loop {
+		std::thread::sleep(SUBMIT_CHECK_INTERVAL);
+		if std::time::SystemTime::now() < next_attempt {
+			continue;
+		}
+		match submit_next(&scrobbler) {
+			SubmitOutcome::Idle => {},
+			SubmitOutcome::Done(_, _) => retry_delay_secs = 0,
+			SubmitOutcome::Failed => retry_delay_secs = (retry_delay_secs * 2).clamp(30, MAX_RETRY_DELAY_SECS),
+		}
+		next_attempt = std::time::SystemTime::now() + std::time::Duration::from_secs(retry_delay_secs);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn listen() -> Listen {
+		Listen {
+			artist: "The \"Band\"".to_string(),
+			album: "Live\tAlbum".to_string(),
+			title: "Song".to_string(),
+			track: "3/12".to_string(),
+			duration_ms: 215000,
+			listened_at: 1700000000,
+		}
+	}
+
+	#[test]
+	fn needs_half_the_song_or_four_minutes() {
+		assert!(!should_scrobble(29000, 29000));
+		assert!(!should_scrobble(59999, 120000));
+		assert!(should_scrobble(60000, 120000));
+		assert!(!should_scrobble(239999, 20 * 60 * 1000));
+		assert!(should_scrobble(240000, 20 * 60 * 1000));
+	}
+
+	#[test]
+	fn escapes_json() {
+		assert_eq!(escape_json("a\"b\\c\nd\u{1}"), "a\\\"b\\\\c\\nd\\u0001");
+		assert_eq!(escape_json("Ünïcode"), "Ünïcode");
+	}
+
+	#[test]
+	fn formats_listenbrainz_listen() {
+		let json = listen_to_listenbrainz_json(&listen());
+		assert!(json.starts_with("{\"listened_at\":1700000000,\"track_metadata\":{\"artist_name\":\"The \\\"Band\\\"\",\"track_name\":\"Song\",\"release_name\":\"Live\\tAlbum\""));
+		assert!(json.contains("\"duration_ms\":215000"));
+		assert!(json.contains("\"tracknumber\":\"3/12\""));
+		assert!(json.ends_with("}}}"));
+	}
+
+	#[test]
+	fn formats_rockbox_line() {
+		assert_eq!(listen_to_rockbox_line(&listen()), "The \"Band\"\tLive Album\tSong\t3\t215\tL\t1700000000\t\n");
+	}
+
+	#[test]
+	fn log_formats_round_trip() {
+		for format in [ScrobbleLogFormat::Off, ScrobbleLogFormat::Rockbox, ScrobbleLogFormat::ListenBrainz, ScrobbleLogFormat::Both] {
+			assert!(str_to_log_format(log_format_to_str(&format)) == format);
+		}
+		assert!(str_to_log_format("Something") == ScrobbleLogFormat::Off);
+	}
+
+	#[test]
+	fn splits_http_urls() {
+		assert_eq!(split_http_url("http://localhost:8100/1/submit-listens"), Ok(("localhost:8100".to_string(), "/1/submit-listens".to_string())));
+		assert_eq!(split_http_url("http://example.com"), Ok(("example.com:80".to_string(), "/".to_string())));
+		assert!(split_http_url("https://api.listenbrainz.org").is_err());
+		assert_eq!(submit_url("http://localhost:8100/"), "http://localhost:8100/1/submit-listens");
+		assert_eq!(submit_url("http://localhost:8100/1/submit-listens"), "http://localhost:8100/1/submit-listens");
+	}
+
+	/*
+	 * A server that refuses any request with a listen without an artist, or answers every request with the given
+	 * status. Returns its address and the bodies it got.
+	 */
+	fn mock_server(status: Option<u16>) -> (String, Arc<Mutex<Vec<String>>>) {
+		let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+		let address = format!("http://{}", listener.local_addr().unwrap());
+		let bodies = Arc::new(Mutex::new(Vec::<String>::new()));
+		let server_bodies = Arc::clone(&bodies);
+		std::thread::spawn(move || {
+			for mut stream in listener.incoming().map_while(Result::ok) {
+				let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+				let mut content_length = 0;
+				let mut line = String::new();
+				while reader.read_line(&mut line).is_ok_and(|read| read > 2) {
+					if let Some(length) = line.to_lowercase().strip_prefix("content-length: ") {
+						content_length = length.trim().parse().unwrap();
+					}
+					line.clear();
+				}
+				let mut body = vec![0u8; content_length];
+				reader.read_exact(&mut body).unwrap();
+				let body = String::from_utf8(body).unwrap();
+				let code = status.unwrap_or(if body.contains("\"artist_name\":\"\"") {400} else {200});
+				server_bodies.lock().unwrap().push(body);
+				let _ = stream.write_all(format!("HTTP/1.1 {} Status\r\nContent-Length: 0\r\n\r\n", code).as_bytes());
+			}
+		});
+		(address, bodies)
+	}
+
+	fn queued_scrobbler(folder: Option<&str>, endpoint: &str, artists: &[&str]) -> Mutex<Scrobbler> {
+		let mut scrobbler = init_scrobbler(folder.map(str::to_string));
+		scrobbler.endpoint = endpoint.to_string();
+		for artist in artists {
+			scrobbler.queue.push(listen_to_listenbrainz_json(&Listen {artist: artist.to_string(), ..listen()}));
+		}
+		Mutex::new(scrobbler)
+	}
+
+	#[test]
+	fn refused_listens_dont_hold_up_the_queue() {
+		let folder = crate::test_util::temp_file_path("scrobble_rejected");
+		std::fs::create_dir_all(&folder).unwrap();
+		let (endpoint, bodies) = mock_server(None);
+		let scrobbler = queued_scrobbler(Some(&folder), &endpoint, &["A", "", "B"]);
+		let outcome = submit_next(&scrobbler);
+		let rejected = std::fs::read_to_string(build_full_filepath(&folder, REJECTED_FILE_NAME));
+		let _ = std::fs::remove_dir_all(&folder);
+
+		assert_eq!(outcome, SubmitOutcome::Done(2, 1));
+		/* The whole batch, then each listen on its own */
+		assert_eq!(bodies.lock().unwrap().len(), 4);
+		let scrobbler = scrobbler.lock().unwrap();
+		assert!(scrobbler.queue.is_empty());
+		assert!(scrobbler.last_submit_error.is_some());
+		let rejected = rejected.unwrap();
+		assert_eq!(rejected.lines().count(), 1);
+		assert!(rejected.contains("\"artist_name\":\"\""));
+	}
+
+	#[test]
+	fn server_errors_keep_the_queue() {
+		let folder = crate::test_util::temp_file_path("scrobble_retry");
+		std::fs::create_dir_all(&folder).unwrap();
+		/* Server trouble, and a token the server doesn't take (nothing wrong with the listens either way) */
+		for status in [503, 429, 401, 403] {
+			let (endpoint, bodies) = mock_server(Some(status));
+			let scrobbler = queued_scrobbler(Some(&folder), &endpoint, &["A", ""]);
+			assert_eq!(submit_next(&scrobbler), SubmitOutcome::Failed);
+			assert_eq!(bodies.lock().unwrap().len(), 1);
+			assert_eq!(scrobbler.lock().unwrap().queue.len(), 2);
+		}
+		let rejected_exists = std::path::Path::new(&build_full_filepath(&folder, REJECTED_FILE_NAME)).exists();
+		let _ = std::fs::remove_dir_all(&folder);
+		assert!(!rejected_exists);
+	}
+
+	#[test]
+	fn refused_listens_stay_queued_without_a_folder() {
+		let (endpoint, _) = mock_server(None);
+		let scrobbler = queued_scrobbler(None, &endpoint, &["A", ""]);
+		assert_eq!(submit_next(&scrobbler), SubmitOutcome::Failed);
+		let scrobbler = scrobbler.lock().unwrap();
+		assert_eq!(scrobbler.queue.len(), 2);
+		assert!(scrobbler.last_submit_error.is_some());
+	}
+}
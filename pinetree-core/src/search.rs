@@ -0,0 +1,247 @@
+/*
+ * Pinetree library search. Fuzzy matching over every indexed song and every playlist, ranked the way fzf ranks
+ * things: the query's characters have to show up in order, and matches that are close together, start words or
+ * continue the previous character score higher. Spaces split the query into terms that all have to match.
+ *
+ * Typing more of the same query can only shrink the results, so each keystroke only looks at what matched the
+ * keystroke before instead of going through the whole library again.
+ */
+
+use std::collections::HashSet;
+
+use crate::index;
+use crate::playlist::Playlist;
+
+/* Most matches kept in ranked order, nobody scrolls further than this */
+pub const MAX_RESULTS: usize = 200;
+
+const SCORE_MATCH: i32 = 16;
+const SCORE_GAP_START: i32 = -3;
+const SCORE_GAP_EXTENSION: i32 = -1;
+/* Matching the first character of a word */
+const BONUS_BOUNDARY: i32 = 8;
+/* Matching the character right after the previous match */
+const BONUS_CONSECUTIVE: i32 = 4;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum SearchTarget {
+	Song(String),
+	/* By name, playlists get sorted so their positions change */
+	Playlist(String),
+}
+
+pub struct SearchEntry {
+	pub target: SearchTarget,
+	/* What gets matched and shown */
+	pub text: String,
+	lowercase: Vec<char>,
+}
+
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct SearchMatch {
+	/* Position in the entries */
+	pub entry: usize,
+	pub score: i32,
+	/* Character (not byte) positions of the matched characters in the entry's text, for highlighting */
+	pub positions: Vec<usize>,
+}
+
+pub struct Search {
+	pub query: String,
+	/* Every entry that matches the query, ranked, even the ones past MAX_RESULTS */
+	candidates: Vec<usize>,
+	pub matches: Vec<SearchMatch>,
+}
+
+pub fn new_search() -> Search {
+	Search {query: "".to_string(), candidates: Vec::new(), matches: Vec::new()}
+}
+
+pub fn new_entry(target: SearchTarget, text: String) -> SearchEntry {
+	let lowercase = text.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+	SearchEntry {target, text, lowercase}
+}
+
+/* "Title · Artist · Album", leaving out what the song doesn't have */
+pub fn song_search_text(song: &index::IndexedSong) -> String {
+	[index::display_title(song), &song.artist, &song.album].iter()
+		.filter(|part| !part.trim().is_empty())
+		.copied()
+		.collect::<Vec<&str>>()
+		.join(" · ")
+}
+
+/**
+ * Everything there is to search: the indexed songs, songs in playlists that aren't in the index, and the playlists.
+ */
+pub fn build_entries(song_index: &index::SongIndex, playlists: &[Playlist]) -> Vec<SearchEntry> {
+	let mut entries: Vec<SearchEntry> = song_index.songs.values()
+		.map(|song| new_entry(SearchTarget::Song(song.path.clone()), song_search_text(song)))
+		.collect();
+	let mut seen: HashSet<&str> = song_index.songs.keys().map(|song| song.as_str()).collect();
+	for playlist in playlists {
+		for song in &playlist.songs {
+			if seen.insert(song) {
+				let name = std::path::Path::new(song).file_stem().and_then(|stem| stem.to_str()).unwrap_or(song);
+				entries.push(new_entry(SearchTarget::Song(song.clone()), name.to_string()));
+			}
+		}
+		entries.push(new_entry(SearchTarget::Playlist(playlist.name.clone()), playlist.name.clone()));
+	}
+	entries
+}
+
+fn is_boundary(previous: Option<char>) -> bool {
+	previous.is_none_or(|previous| !previous.is_alphanumeric())
+}
+
+/**
+ * Scores one term against a text (both lowercase), None if the term's characters aren't all in it in order.
+ * Like fzf's first algorithm: find where the earliest full match ends, then walk back from there to the latest
+ * place it can start, which gives a short match without trying every alignment.
+ */
+pub fn fuzzy_match(term: &[char], text: &[char]) -> Option<(i32, Vec<usize>)> {
+	if term.is_empty() {
+		return Some((0, Vec::new()));
+	}
+	let mut matched = 0;
+	let mut end = 0;
+	for (i, c) in text.iter().enumerate() {
+		if *c == term[matched] {
+			matched += 1;
+			if matched == term.len() {
+				end = i;
+				break;
+			}
+		}
+	}
+	if matched < term.len() {
+		return None;
+	}
+	let mut start = end;
+	let mut remaining = term.len();
+	for i in (0..=end).rev() {
+		if text[i] == term[remaining - 1] {
+			remaining -= 1;
+			if remaining == 0 {
+				start = i;
+				break;
+			}
+		}
+	}
+
+	let mut score = 0;
+	let mut positions = Vec::with_capacity(term.len());
+	let mut matched = 0;
+	let mut in_gap = false;
+	for i in start..=end {
+		if matched < term.len() && text[i] == term[matched] {
+			score += SCORE_MATCH;
+			let previous = if i == 0 {None} else {Some(text[i - 1])};
+			if is_boundary(previous) {
+				/* The first character of the term starting a word is what counts most */
+				score += if matched == 0 {BONUS_BOUNDARY * 2} else {BONUS_BOUNDARY};
+			} else if positions.last().is_some_and(|last| last + 1 == i) {
+				score += BONUS_CONSECUTIVE;
+			}
+			positions.push(i);
+			matched += 1;
+			in_gap = false;
+		} else {
+			score += if in_gap {SCORE_GAP_EXTENSION} else {SCORE_GAP_START};
+			in_gap = true;
+		}
+	}
+	Some((score, positions))
+}
+
+/**
+ * Scores a whole query against an entry. Every term has to match.
+ */
+pub fn match_entry(query: &str, entry: &SearchEntry) -> Option<(i32, Vec<usize>)> {
+	let mut score = 0;
+	let mut positions = Vec::new();
+	for term in query.split_whitespace() {
+		let term: Vec<char> = term.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+		let (term_score, term_positions) = fuzzy_match(&term, &entry.lowercase)?;
+		score += term_score;
+		positions.extend(term_positions);
+	}
+	positions.sort_unstable();
+	positions.dedup();
+	Some((score, positions))
+}
+
+/**
+ * Runs the query. If it only adds to the last one, only what matched last time gets looked at.
+ * Call reset_search first when the entries changed.
+ */
+pub fn update_search(search: &mut Search, entries: &[SearchEntry], query: &str) {
+	if query == search.query {
+		return;
+	}
+	let narrowing = !search.query.trim().is_empty() && query.starts_with(&search.query);
+	search.query = query.to_string();
+	if query.trim().is_empty() {
+		search.candidates.clear();
+		search.matches.clear();
+		return;
+	}
+	let candidates: Vec<usize> = if narrowing {std::mem::take(&mut search.candidates)} else {(0..entries.len()).collect()};
+	let mut matches: Vec<SearchMatch> = candidates.into_iter()
+		.filter_map(|entry| match_entry(query, entries.get(entry)?).map(|(score, positions)| SearchMatch {entry, score, positions}))
+		.collect();
+	/* Shorter texts first on a tie, they matched more of themselves */
+	matches.sort_by_key(|found| (-found.score, entries[found.entry].lowercase.len()));
+	search.candidates = matches.iter().map(|found| found.entry).collect();
+	matches.truncate(MAX_RESULTS);
+	search.matches = matches;
+}
+
+pub fn reset_search(search: &mut Search) {
+	*search = new_search();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn lower(text: &str) -> Vec<char> {
+		text.to_lowercase().chars().collect()
+	}
+
+	#[test]
+	fn ranks_fuzzy_matches() {
+		assert!(fuzzy_match(&lower("xyz"), &lower("Yellow Submarine")).is_none());
+		let (_, positions) = fuzzy_match(&lower("ysub"), &lower("Yellow Submarine")).unwrap();
+		assert_eq!(positions, vec![0, 7, 8, 9]);
+		/* Word starts and runs of characters beat scattered ones */
+		let word_start = fuzzy_match(&lower("sub"), &lower("Yellow Submarine")).unwrap().0;
+		let scattered = fuzzy_match(&lower("sub"), &lower("Sea of Umbrellas")).unwrap().0;
+		assert!(word_start > scattered);
+		/* The shortest window is used, not the first s */
+		let (_, positions) = fuzzy_match(&lower("sub"), &lower("s... Sub")).unwrap();
+		assert_eq!(positions, vec![5, 6, 7]);
+	}
+
+	#[test]
+	fn narrows_results_while_typing() {
+		let entries: Vec<SearchEntry> = ["Help! · The Beatles", "Hello · Adele", "Hey Jude · The Beatles", "Bohemian Rhapsody · Queen"].iter()
+			.map(|text| new_entry(SearchTarget::Song(text.to_string()), text.to_string()))
+			.collect();
+		let mut search = new_search();
+		update_search(&mut search, &entries, "he");
+		assert_eq!(search.matches.len(), 4);
+		update_search(&mut search, &entries, "he beat");
+		let found: Vec<&str> = search.matches.iter().map(|found| entries[found.entry].text.as_str()).collect();
+		assert_eq!(found.len(), 2);
+		assert!(found.contains(&"Help! · The Beatles") && found.contains(&"Hey Jude · The Beatles"));
+		update_search(&mut search, &entries, "hel");
+		assert_eq!(entries[search.matches[0].entry].text, "Hello · Adele");
+		update_search(&mut search, &entries, " ");
+		assert!(search.matches.is_empty());
+	}
+}
@@ -0,0 +1,360 @@
+/*
+ * Pinetree keyboard shortcuts.
+ *
+ * Bindings are kept as text like "Ctrl+Shift+Right" so nothing here depends on egui. Key names are the ones
+ * egui uses (egui::Key::name), and Ctrl means Cmd on macOS. The front-end turns them into its own key events.
+ *
+ * Some actions can also have a global binding, which the GUI registers with the OS so it works while Pinetree
+ * isn't focused. Those are kept separate from the normal bindings, since e.g. Ctrl+Right is fine inside Pinetree
+ * but would break word navigation in every other program if it was grabbed system-wide.
+ */
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Debug)]
+pub enum ShortcutAction {
+	TogglePause,
+	NextSong,
+	PreviousSong,
+	SeekForward,
+	SeekBackward,
+	LongSeekForward,
+	LongSeekBackward,
+	VolumeUp,
+	VolumeDown,
+	SpeedUp,
+	SpeedDown,
+	ToggleLoopMode,
+	JumpToCurrentSong,
+	Refresh,
+	SetLoopStart,
+	SetLoopEnd,
+	ClearLoop,
+	AddBookmark,
+	NextBookmark,
+	PreviousBookmark,
+	NextChapter,
+	PreviousChapter,
+}
+
+/* In the order they are shown in the shortcut editor */
+pub const ALL_ACTIONS: [ShortcutAction; 22] = [
+	ShortcutAction::TogglePause,
+	ShortcutAction::NextSong,
+	ShortcutAction::PreviousSong,
+	ShortcutAction::SeekForward,
+	ShortcutAction::SeekBackward,
+	ShortcutAction::LongSeekForward,
+	ShortcutAction::LongSeekBackward,
+	ShortcutAction::VolumeUp,
+	ShortcutAction::VolumeDown,
+	ShortcutAction::SpeedUp,
+	ShortcutAction::SpeedDown,
+	ShortcutAction::ToggleLoopMode,
+	ShortcutAction::JumpToCurrentSong,
+	ShortcutAction::Refresh,
+	ShortcutAction::SetLoopStart,
+	ShortcutAction::SetLoopEnd,
+	ShortcutAction::ClearLoop,
+	ShortcutAction::AddBookmark,
+	ShortcutAction::NextBookmark,
+	ShortcutAction::PreviousBookmark,
+	ShortcutAction::NextChapter,
+	ShortcutAction::PreviousChapter,
+];
+
+pub const DEFAULT_SEEK_STEP_SECS: u64 = 5;
+pub const DEFAULT_LONG_SEEK_STEP_SECS: u64 = 30;
+/* Slider units, the volume slider goes from -0.2 to 1.0 */
+pub const VOLUME_STEP: f32 = 0.05;
+pub const SPEED_STEP: f32 = 0.1;
+
+/**
+ * Name used in the internal data file. These can't change without breaking saved shortcuts.
+ */
+pub fn action_to_str(action: ShortcutAction) -> &'static str {
+	match action {
+		ShortcutAction::TogglePause => "Pause",
+		ShortcutAction::NextSong => "Next Song",
+		ShortcutAction::PreviousSong => "Previous Song",
+		ShortcutAction::SeekForward => "Seek Forward",
+		ShortcutAction::SeekBackward => "Seek Backward",
+		ShortcutAction::LongSeekForward => "Long Seek Forward",
+		ShortcutAction::LongSeekBackward => "Long Seek Backward",
+		ShortcutAction::VolumeUp => "Volume Up",
+		ShortcutAction::VolumeDown => "Volume Down",
+		ShortcutAction::SpeedUp => "Speed Up",
+		ShortcutAction::SpeedDown => "Speed Down",
+		ShortcutAction::ToggleLoopMode => "Toggle On-finish",
+		ShortcutAction::JumpToCurrentSong => "Jump To Current Song",
+		ShortcutAction::Refresh => "Refresh",
+		ShortcutAction::SetLoopStart => "Set Loop Start",
+		ShortcutAction::SetLoopEnd => "Set Loop End",
+		ShortcutAction::ClearLoop => "Clear Loop",
+		ShortcutAction::AddBookmark => "Add Bookmark",
+		ShortcutAction::NextBookmark => "Next Bookmark",
+		ShortcutAction::PreviousBookmark => "Previous Bookmark",
+		ShortcutAction::NextChapter => "Next Chapter",
+		ShortcutAction::PreviousChapter => "Previous Chapter",
+	}
+}
+
+pub fn str_to_action(string: &str) -> Option<ShortcutAction> {
+	ALL_ACTIONS.iter().find(|action| action_to_str(**action) == string).copied()
+}
+
+/**
+ * Human readable description for the shortcut editor
+ */
+pub fn action_description(action: ShortcutAction) -> &'static str {
+	match action {
+		ShortcutAction::TogglePause => "Play / pause",
+		ShortcutAction::NextSong => "Next song",
+		ShortcutAction::PreviousSong => "Previous song",
+		ShortcutAction::SeekForward => "Seek forward",
+		ShortcutAction::SeekBackward => "Seek backward",
+		ShortcutAction::LongSeekForward => "Seek forward (long step)",
+		ShortcutAction::LongSeekBackward => "Seek backward (long step)",
+		ShortcutAction::VolumeUp => "Volume up",
+		ShortcutAction::VolumeDown => "Volume down",
+		ShortcutAction::SpeedUp => "Speed up",
+		ShortcutAction::SpeedDown => "Speed down",
+		ShortcutAction::ToggleLoopMode => "Cycle the on-finish behavior",
+		ShortcutAction::JumpToCurrentSong => "Jump to the current song",
+		ShortcutAction::Refresh => "Refresh the song list",
+		ShortcutAction::SetLoopStart => "Set the A point of the loop",
+		ShortcutAction::SetLoopEnd => "Set the B point of the loop",
+		ShortcutAction::ClearLoop => "Clear the A-B loop",
+		ShortcutAction::AddBookmark => "Bookmark the current position",
+		ShortcutAction::NextBookmark => "Jump to the next bookmark",
+		ShortcutAction::PreviousBookmark => "Jump to the previous bookmark",
+		ShortcutAction::NextChapter => "Jump to the next chapter",
+		ShortcutAction::PreviousChapter => "Jump to the previous chapter",
+	}
+}
+
+#[derive(PartialEq)]
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct KeyBinding {
+	pub ctrl: bool,
+	pub shift: bool,
+	pub alt: bool,
+	pub key: String,
+}
+
+#[derive(Clone)]
+pub struct Shortcut {
+	pub action: ShortcutAction,
+	/* None means the action has no shortcut */
+	pub binding: Option<KeyBinding>,
+	/* System-wide binding, only used for actions where can_be_global is true */
+	pub global_binding: Option<KeyBinding>,
+}
+
+fn binding(ctrl: bool, shift: bool, key: &str) -> Option<KeyBinding> {
+	Some(KeyBinding {ctrl, shift, alt: false, key: key.to_string()})
+}
+
+pub fn default_binding(action: ShortcutAction) -> Option<KeyBinding> {
+	match action {
+		ShortcutAction::TogglePause => binding(true, false, "P"),
+		ShortcutAction::NextSong => binding(true, false, "Right"),
+		ShortcutAction::PreviousSong => binding(true, false, "Left"),
+		ShortcutAction::SeekForward => binding(false, false, "Right"),
+		ShortcutAction::SeekBackward => binding(false, false, "Left"),
+		ShortcutAction::LongSeekForward => binding(false, true, "Right"),
+		ShortcutAction::LongSeekBackward => binding(false, true, "Left"),
+		ShortcutAction::VolumeUp => binding(true, false, "Up"),
+		ShortcutAction::VolumeDown => binding(true, false, "Down"),
+		ShortcutAction::SpeedUp => binding(true, false, "Period"),
+		ShortcutAction::SpeedDown => binding(true, false, "Comma"),
+		ShortcutAction::ToggleLoopMode => binding(true, false, "L"),
+		ShortcutAction::JumpToCurrentSong => binding(true, false, "J"),
+		ShortcutAction::Refresh => binding(true, false, "R"),
+		ShortcutAction::SetLoopStart => binding(true, false, "OpenBracket"),
+		ShortcutAction::SetLoopEnd => binding(true, false, "CloseBracket"),
+		ShortcutAction::ClearLoop => binding(true, false, "Backslash"),
+		ShortcutAction::AddBookmark => binding(true, false, "B"),
+		ShortcutAction::NextBookmark => binding(true, true, "Right"),
+		ShortcutAction::PreviousBookmark => binding(true, true, "Left"),
+		ShortcutAction::NextChapter => Some(KeyBinding {ctrl: false, shift: false, alt: true, key: "Right".to_string()}),
+		ShortcutAction::PreviousChapter => Some(KeyBinding {ctrl: false, shift: false, alt: true, key: "Left".to_string()}),
+	}
+}
+
+pub fn default_shortcuts() -> Vec<Shortcut> {
+	ALL_ACTIONS.iter().map(|action| Shortcut {action: *action, binding: default_binding(*action), global_binding: None}).collect()
+}
+
+/**
+ * Actions that make sense while Pinetree isn't focused. The rest (seeking, jumping to the song, ...) need the window.
+ */
+pub fn can_be_global(action: ShortcutAction) -> bool {
+	matches!(action,
+		ShortcutAction::TogglePause | ShortcutAction::NextSong | ShortcutAction::PreviousSong
+		| ShortcutAction::VolumeUp | ShortcutAction::VolumeDown)
+}
+
+pub fn find_binding(shortcuts: &[Shortcut], action: ShortcutAction) -> Option<&KeyBinding> {
+	shortcuts.iter().find(|shortcut| shortcut.action == action).and_then(|shortcut| shortcut.binding.as_ref())
+}
+
+pub fn find_global_binding(shortcuts: &[Shortcut], action: ShortcutAction) -> Option<&KeyBinding> {
+	shortcuts.iter().find(|shortcut| shortcut.action == action).and_then(|shortcut| shortcut.global_binding.as_ref())
+}
+
+pub fn set_binding(shortcuts: &mut Vec<Shortcut>, action: ShortcutAction, binding: Option<KeyBinding>) {
+	if let Some(shortcut) = shortcuts.iter_mut().find(|shortcut| shortcut.action == action) {
+		shortcut.binding = binding;
+	} else {
+		shortcuts.push(Shortcut {action, binding, global_binding: None});
+	}
+}
+
+pub fn set_global_binding(shortcuts: &mut Vec<Shortcut>, action: ShortcutAction, binding: Option<KeyBinding>) {
+	if !can_be_global(action) {
+		return;
+	}
+	if let Some(shortcut) = shortcuts.iter_mut().find(|shortcut| shortcut.action == action) {
+		shortcut.global_binding = binding;
+	} else {
+		shortcuts.push(Shortcut {action, binding: None, global_binding: binding});
+	}
+}
+
+/**
+ * Formats a binding as e.g. "Ctrl+Shift+Right". Unbound actions are written as "None".
+ */
+pub fn binding_to_str(binding: &Option<KeyBinding>) -> String {
+	if let Some(binding) = binding {
+		let mut string = String::new();
+		if binding.ctrl {string.push_str("Ctrl+");}
+		if binding.alt {string.push_str("Alt+");}
+		if binding.shift {string.push_str("Shift+");}
+		string.push_str(&binding.key);
+		string
+	} else {
+		"None".to_string()
+	}
+}
+
+/**
+ * The other way around from binding_to_str. Returns None for "None" and anything that isn't a binding.
+ */
+pub fn str_to_binding(string: &str) -> Option<KeyBinding> {
+	let string = string.trim();
+	if string.is_empty() || string == "None" {
+		return None;
+	}
+	let mut binding = KeyBinding {ctrl: false, shift: false, alt: false, key: "".to_string()};
+	/* The key itself can be "+" (as "Plus"), but never contains one, so the last part is always the key */
+	let mut parts: Vec<&str> = string.split('+').collect();
+	let key = parts.pop()?.trim();
+	for modifier in parts {
+		match modifier.trim() {
+			"Ctrl" | "Cmd" => binding.ctrl = true,
+			"Shift" => binding.shift = true,
+			"Alt" => binding.alt = true,
+			_ => return None,
+		}
+	}
+	if key.is_empty() {
+		return None;
+	}
+	binding.key = key.to_string();
+	Some(binding)
+}
+
+/**
+ * Returns every pair of actions that share the same binding. Global bindings count too, a global binding
+ * grabs the key before the window sees it, so it would shadow a normal binding of another action.
+ */
+pub fn find_conflicts(shortcuts: &[Shortcut]) -> Vec<(ShortcutAction, ShortcutAction)> {
+	let mut bindings = Vec::<(ShortcutAction, &KeyBinding)>::new();
+	for shortcut in shortcuts {
+		if let Some(binding) = &shortcut.binding {
+			bindings.push((shortcut.action, binding));
+		}
+	}
+	for shortcut in shortcuts {
+		if let Some(binding) = &shortcut.global_binding {
+			bindings.push((shortcut.action, binding));
+		}
+	}
+	let mut conflicts = Vec::<(ShortcutAction, ShortcutAction)>::new();
+	for (i, (first_action, first_binding)) in bindings.iter().enumerate() {
+		for (second_action, second_binding) in &bindings[i + 1..] {
+			if first_action != second_action && first_binding == second_binding && !conflicts.contains(&(*first_action, *second_action)) {
+				conflicts.push((*first_action, *second_action));
+			}
+		}
+	}
+	conflicts
+}
+
+pub fn has_conflict(conflicts: &[(ShortcutAction, ShortcutAction)], action: ShortcutAction) -> bool {
+	conflicts.iter().any(|(first, second)| *first == action || *second == action)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bindings_round_trip() {
+		for action in ALL_ACTIONS {
+			let binding = default_binding(action);
+			assert_eq!(str_to_binding(&binding_to_str(&binding)), binding);
+			assert_eq!(str_to_action(action_to_str(action)), Some(action));
+		}
+		let binding = Some(KeyBinding {ctrl: true, shift: true, alt: true, key: "F5".to_string()});
+		assert_eq!(binding_to_str(&binding), "Ctrl+Alt+Shift+F5");
+		assert_eq!(str_to_binding("Ctrl+Alt+Shift+F5"), binding);
+	}
+
+	#[test]
+	fn parses_bindings() {
+		assert_eq!(str_to_binding("Space"), Some(KeyBinding {ctrl: false, shift: false, alt: false, key: "Space".to_string()}));
+		assert_eq!(str_to_binding("Cmd+P"), str_to_binding("Ctrl+P"));
+		assert_eq!(str_to_binding("None"), None);
+		assert_eq!(str_to_binding(""), None);
+		assert_eq!(str_to_binding("Ctrl+"), None);
+		assert_eq!(str_to_binding("Hyper+P"), None);
+	}
+
+	#[test]
+	fn defaults_dont_conflict() {
+		assert!(find_conflicts(&default_shortcuts()).is_empty());
+		assert_eq!(default_shortcuts().len(), ALL_ACTIONS.len());
+	}
+
+	#[test]
+	fn finds_conflicts() {
+		let mut shortcuts = default_shortcuts();
+		set_binding(&mut shortcuts, ShortcutAction::Refresh, str_to_binding("Ctrl+P"));
+		set_binding(&mut shortcuts, ShortcutAction::SpeedUp, None);
+		set_binding(&mut shortcuts, ShortcutAction::SpeedDown, None);
+		let conflicts = find_conflicts(&shortcuts);
+		assert_eq!(conflicts, vec![(ShortcutAction::TogglePause, ShortcutAction::Refresh)]);
+		assert!(has_conflict(&conflicts, ShortcutAction::Refresh));
+		assert!(!has_conflict(&conflicts, ShortcutAction::SpeedUp));
+		assert_eq!(find_binding(&shortcuts, ShortcutAction::Refresh), str_to_binding("Ctrl+P").as_ref());
+	}
+
+	#[test]
+	fn global_bindings() {
+		let mut shortcuts = default_shortcuts();
+		set_global_binding(&mut shortcuts, ShortcutAction::Refresh, str_to_binding("Ctrl+Alt+R"));
+		assert!(find_global_binding(&shortcuts, ShortcutAction::Refresh).is_none());
+
+		/* The same key as the action's own normal binding is fine */
+		set_global_binding(&mut shortcuts, ShortcutAction::TogglePause, str_to_binding("Ctrl+P"));
+		assert!(find_conflicts(&shortcuts).is_empty());
+
+		set_global_binding(&mut shortcuts, ShortcutAction::NextSong, str_to_binding("Ctrl+J"));
+		assert_eq!(find_conflicts(&shortcuts), vec![(ShortcutAction::JumpToCurrentSong, ShortcutAction::NextSong)]);
+		assert_eq!(find_global_binding(&shortcuts, ShortcutAction::NextSong), str_to_binding("Ctrl+J").as_ref());
+	}
+}
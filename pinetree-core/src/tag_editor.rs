@@ -0,0 +1,660 @@
+/*
+ * Pinetree tag editing. Writes title, artist, album, genre, track number and year into the ID3v2 tag of a song.
+ *
+ * The rest of the tag (cover art, lyrics, chapters, ...) is copied over byte for byte, flags included, so frames
+ * that are compressed, encrypted or grouped survive. Tags that can't be copied that faithfully, e.g. with a frame
+ * cut off by the end of the tag, are left alone and the edit fails. The new file is written next to the old one,
+ * read back to check it came out as intended, and only then renamed over it, so a crash or a full disk halfway
+ * through leaves the song untouched. Tags are written as ID3v2.4 if the song already had one, otherwise as ID3v2.3
+ * which more players understand. Converting an ID3v2.2 tag only keeps the frames that have a newer equivalent here.
+ */
+
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::library;
+use crate::tags;
+
+/* Empty space left at the end of the tag, so a later edit can grow it without moving the audio (in other programs) */
+const TAG_PADDING: usize = 1024;
+const TEMP_SUFFIX: &str = ".pinetree-tmp";
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(Hash)]
+#[derive(Debug)]
+pub enum TagField {
+	Title,
+	Artist,
+	Album,
+	Genre,
+	Track,
+	Year,
+}
+
+pub const ALL_TAG_FIELDS: [TagField; 6] = [TagField::Title, TagField::Artist, TagField::Album, TagField::Genre, TagField::Track, TagField::Year];
+
+pub fn field_to_str(field: TagField) -> &'static str {
+	match field {
+		TagField::Title => "Title",
+		TagField::Artist => "Artist",
+		TagField::Album => "Album",
+		TagField::Genre => "Genre",
+		TagField::Track => "Track",
+		TagField::Year => "Year",
+	}
+}
+
+pub fn field_value(song_tags: &tags::SongTags, field: TagField) -> &str {
+	match field {
+		TagField::Title => &song_tags.title,
+		TagField::Artist => &song_tags.artist,
+		TagField::Album => &song_tags.album,
+		TagField::Genre => &song_tags.genre,
+		TagField::Track => &song_tags.track,
+		TagField::Year => &song_tags.year,
+	}
+}
+
+pub fn set_field(song_tags: &mut tags::SongTags, field: TagField, value: &str) {
+	let target = match field {
+		TagField::Title => &mut song_tags.title,
+		TagField::Artist => &mut song_tags.artist,
+		TagField::Album => &mut song_tags.album,
+		TagField::Genre => &mut song_tags.genre,
+		TagField::Track => &mut song_tags.track,
+		TagField::Year => &mut song_tags.year,
+	};
+	*target = value.trim().to_string();
+}
+
+/* Fields to change and their new values. An empty value removes the field */
+pub type TagChanges = Vec<(TagField, String)>;
+
+pub fn apply_changes(song_tags: &tags::SongTags, changes: &[(TagField, String)]) -> tags::SongTags {
+	let mut new_tags = song_tags.clone();
+	for (field, value) in changes {
+		set_field(&mut new_tags, *field, value);
+	}
+	new_tags
+}
+
+fn str_to_field(name: &str) -> Option<TagField> {
+	ALL_TAG_FIELDS.iter().find(|field| field_to_str(**field).eq_ignore_ascii_case(name)).copied()
+}
+
+/**
+ * Reads tags out of a file name with a pattern like "%track% - %artist% - %title%". Each field takes everything up to
+ * the text that follows it in the pattern, the last one takes the rest. None if the name doesn't fit the pattern,
+ * or the pattern has a field that doesn't exist.
+ */
+pub fn tags_from_file_name(pattern: &str, song: &str) -> Option<TagChanges> {
+	let mut name = std::path::Path::new(song).file_stem()?.to_str()?;
+	let mut parts = pattern.split('%');
+	let mut changes = TagChanges::new();
+	/* Split on %, the parts alternate between text and field names */
+	let leading = parts.next().unwrap_or("");
+	name = name.strip_prefix(leading)?;
+	let parts: Vec<&str> = parts.collect();
+	for pair in parts.chunks(2) {
+		let field = str_to_field(pair[0])?;
+		let following = pair.get(1).copied().unwrap_or("");
+		let value = if following.is_empty() {
+			std::mem::take(&mut name)
+		} else {
+			let end = name.find(following)?;
+			let value = &name[..end];
+			name = &name[end + following.len()..];
+			value
+		};
+		changes.push((field, value.trim().to_string()));
+	}
+	if name.is_empty() && !changes.is_empty() {Some(changes)} else {None}
+}
+
+fn frame_id(field: TagField, version: u8) -> &'static str {
+	match field {
+		TagField::Title => "TIT2",
+		TagField::Artist => "TPE1",
+		TagField::Album => "TALB",
+		TagField::Genre => "TCON",
+		TagField::Track => "TRCK",
+		TagField::Year => if version >= 4 {"TDRC"} else {"TYER"},
+	}
+}
+
+/* Every frame that holds the field in some version, so old ones don't stay around next to the new one */
+fn frame_ids_of(field: TagField) -> &'static [&'static str] {
+	match field {
+		TagField::Title => &["TIT2"],
+		TagField::Artist => &["TPE1"],
+		TagField::Album => &["TALB"],
+		TagField::Genre => &["TCON"],
+		TagField::Track => &["TRCK"],
+		TagField::Year => &["TYER", "TDRC"],
+	}
+}
+
+/**
+ * The ID3v2.3 frame matching an ID3v2.2 one, None for frames that get dropped.
+ */
+fn convert_v22_frame(frame: &tags::Id3Frame) -> Option<tags::Id3Frame> {
+	let id = match frame.id.as_str() {
+		"TT2" => "TIT2",
+		"TP1" => "TPE1",
+		"TP2" => "TPE2",
+		"TAL" => "TALB",
+		"TCO" => "TCON",
+		"TRK" => "TRCK",
+		"TPA" => "TPOS",
+		"TYE" => "TYER",
+		"COM" => "COMM",
+		"ULT" => "USLT",
+		"PIC" => {
+			/* A three letter image format instead of a MIME type */
+			let format = frame.data.get(1..4)?;
+			let mime: &[u8] = if format.eq_ignore_ascii_case(b"PNG") {b"image/png"} else {b"image/jpeg"};
+			let mut data = vec![frame.data[0]];
+			data.extend_from_slice(mime);
+			data.push(0);
+			data.extend_from_slice(&frame.data[4..]);
+			return Some(tags::Id3Frame {id: "APIC".to_string(), data});
+		},
+		_ => return None,
+	};
+	Some(tags::Id3Frame {id: id.to_string(), data: frame.data.clone()})
+}
+
+fn encode_text(text: &str, version: u8) -> Vec<u8> {
+	if text.chars().all(|c| (c as u32) < 0x100) {
+		let mut data = vec![0];
+		data.extend(text.chars().map(|c| c as u8));
+		data
+	} else if version >= 4 {
+		let mut data = vec![3];
+		data.extend_from_slice(text.as_bytes());
+		data
+	} else {
+		let mut data = vec![1, 0xFF, 0xFE];
+		for unit in text.encode_utf16() {
+			data.extend_from_slice(&unit.to_le_bytes());
+		}
+		data
+	}
+}
+
+fn synchsafe(value: usize) -> [u8; 4] {
+	[(value >> 21) as u8 & 0x7F, (value >> 14) as u8 & 0x7F, (value >> 7) as u8 & 0x7F, value as u8 & 0x7F]
+}
+
+/* Only for frames written here, which need no flags */
+fn encode_frame(out: &mut Vec<u8>, frame: &tags::Id3Frame, version: u8) {
+	out.extend_from_slice(frame.id.as_bytes());
+	if version >= 4 {
+		out.extend_from_slice(&synchsafe(frame.data.len()));
+	} else {
+		out.extend_from_slice(&(frame.data.len() as u32).to_be_bytes());
+	}
+	out.extend_from_slice(&[0, 0]);
+	out.extend_from_slice(&frame.data);
+}
+
+/**
+ * A frame as it is in the file, header included.
+ */
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct RawFrame {
+	pub id: String,
+	pub bytes: Vec<u8>,
+}
+
+pub struct RawTag {
+	pub version: u8,
+	pub frames: Vec<RawFrame>,
+}
+
+/**
+ * What it takes to undo an edit of a song's tags: the fields the edit wrote, the frames they had before (byte for
+ * byte) and the ID3v1 tag from before.
+ */
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct TagUndo {
+	/* Version of the tag the frames belong in */
+	pub version: u8,
+	pub fields: Vec<TagField>,
+	pub frames: Vec<RawFrame>,
+	pub id3v1: Option<Vec<u8>>,
+}
+
+/**
+ * Splits the ID3v2 tag a file starts with into its frames, without decoding them. None if there is no tag, an error
+ * for tags that can't be copied faithfully: unknown versions, compressed ID3v2.2 tags and frames that are cut off or
+ * unreadable. A tag-wide unsynchronisation is undone, since the new tag is written without it.
+ */
+pub fn read_raw_tag(bytes: &[u8]) -> Result<Option<RawTag>, String> {
+	if bytes.len() < 10 || &bytes[0..3] != b"ID3" {
+		return Ok(None);
+	}
+	let version = bytes[3];
+	let flags = bytes[5];
+	if !(2..=4).contains(&version) {
+		return Err(format!("ID3v2.{} tags aren't supported", version));
+	}
+	if version == 2 && flags & 0x40 != 0 {
+		return Err("the ID3v2.2 tag is compressed".to_string());
+	}
+	let tag_end = 10 + tags::synchsafe_to_usize(&bytes[6..10]);
+	if tag_end > bytes.len() {
+		return Err("the tag is cut off".to_string());
+	}
+	let mut tag_data = bytes[10..tag_end].to_vec();
+	if flags & 0x80 != 0 && version < 4 {
+		tag_data = tags::remove_unsynchronisation(&tag_data);
+	}
+	let mut position = 0;
+	if flags & 0x40 != 0 && version > 2 {
+		if tag_data.len() < 4 {
+			return Err("the extended header is cut off".to_string());
+		}
+		position = if version == 4 {tags::synchsafe_to_usize(&tag_data[0..4])} else {tags::be_to_usize(&tag_data[0..4]) + 4};
+	}
+
+	let (id_len, header_len) = if version == 2 {(3, 6)} else {(4, 10)};
+	let mut frames = Vec::<RawFrame>::new();
+	while position + header_len <= tag_data.len() && tag_data[position] != 0 {
+		let frame_header = &tag_data[position..position + header_len];
+		let id = &frame_header[0..id_len];
+		if !id.iter().all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit()) {
+			return Err(format!("unreadable frame at byte {} of the tag", position));
+		}
+		let frame_size = match version {
+			2 => tags::be_to_usize(&frame_header[3..6]),
+			3 => tags::be_to_usize(&frame_header[4..8]),
+			_ => tags::synchsafe_to_usize(&frame_header[4..8]),
+		};
+		let frame_end = position + header_len + frame_size;
+		if frame_end > tag_data.len() {
+			return Err(format!("the {} frame is cut off", String::from_utf8_lossy(id)));
+		}
+		frames.push(RawFrame {id: String::from_utf8_lossy(id).to_string(), bytes: tag_data[position..frame_end].to_vec()});
+		position = frame_end;
+	}
+	if tag_data[position.min(tag_data.len())..].iter().any(|byte| *byte != 0) {
+		return Err("the end of the tag is unreadable".to_string());
+	}
+	Ok(Some(RawTag {version, frames}))
+}
+
+/**
+ * Builds the frames of the new tag from the old one (if any). Fields that were changed, or that the old tag doesn't
+ * have (e.g. they only were in an ID3v1 tag), are written from the new tags. Returns the frames and what it takes
+ * to put the old ones back, which also has the version of the new tag.
+ */
+pub fn build_tag(old_tag: Option<&RawTag>, new_tags: &tags::SongTags, changes: &[(TagField, String)]) -> (Vec<RawFrame>, TagUndo) {
+	let version = if old_tag.is_some_and(|tag| tag.version == 4) {4} else {3};
+	let new_frame = |frame: tags::Id3Frame| {
+		let mut bytes = Vec::new();
+		encode_frame(&mut bytes, &frame, version);
+		RawFrame {id: frame.id, bytes}
+	};
+	let mut frames: Vec<RawFrame> = match old_tag {
+		Some(tag) if tag.version == 2 => tag.frames.iter()
+			.filter_map(|frame| convert_v22_frame(&tags::Id3Frame {id: frame.id.clone(), data: frame.bytes[6..].to_vec()}))
+			.map(new_frame)
+			.collect(),
+		Some(tag) => tag.frames.clone(),
+		None => Vec::new(),
+	};
+	let mut undo = TagUndo {version, fields: Vec::new(), frames: Vec::new(), id3v1: None};
+	for field in ALL_TAG_FIELDS {
+		let ids = frame_ids_of(field);
+		let changed = changes.iter().any(|(changed, _)| *changed == field);
+		let value = field_value(new_tags, field);
+		if !changed && (value.is_empty() || frames.iter().any(|frame| ids.contains(&frame.id.as_str()))) {
+			continue;
+		}
+		undo.fields.push(field);
+		undo.frames.extend(frames.iter().filter(|frame| ids.contains(&frame.id.as_str())).cloned());
+		frames.retain(|frame| !ids.contains(&frame.id.as_str()));
+		if !value.is_empty() {
+			frames.push(new_frame(tags::Id3Frame {id: frame_id(field, version).to_string(), data: encode_text(value, version)}));
+		}
+	}
+	(frames, undo)
+}
+
+fn encode_tag(version: u8, frames: &[RawFrame]) -> Vec<u8> {
+	let mut body: Vec<u8> = frames.iter().flat_map(|frame| frame.bytes.iter().copied()).collect();
+	body.resize(body.len() + TAG_PADDING, 0);
+	let mut tag = b"ID3".to_vec();
+	tag.extend_from_slice(&[version, 0, 0]);
+	tag.extend_from_slice(&synchsafe(body.len()));
+	tag.extend_from_slice(&body);
+	tag
+}
+
+/**
+ * Where the audio starts: after the ID3v2 tag (and its footer) if there is one.
+ */
+fn audio_start(bytes: &[u8]) -> usize {
+	tags::id3_tag_len(bytes).min(bytes.len())
+}
+
+/* Where the ID3v1 tag at the end of the audio starts, if there is one */
+fn id3v1_start(audio: &[u8]) -> Option<usize> {
+	let start = audio.len().checked_sub(128)?;
+	if &audio[start..start + 3] == b"TAG" {Some(start)} else {None}
+}
+
+fn read_id3v1(bytes: &[u8]) -> Option<Vec<u8>> {
+	let audio = &bytes[audio_start(bytes)..];
+	id3v1_start(audio).map(|start| audio[start..].to_vec())
+}
+
+/**
+ * Keeps an ID3v1 tag at the end of the file in line with the new tags, for players that only read that one.
+ * The genre is left alone since ID3v1 can only hold a fixed list of them.
+ */
+fn update_id3v1(block: &mut [u8], new_tags: &tags::SongTags) {
+	let mut put = |start: usize, len: usize, value: &str| {
+		let bytes: Vec<u8> = value.chars().map(|c| if (c as u32) < 0x100 {c as u8} else {b'?'}).take(len).collect();
+		block[start..start + len].fill(0);
+		block[start..start + bytes.len()].copy_from_slice(&bytes);
+	};
+	put(3, 30, &new_tags.title);
+	put(33, 30, &new_tags.artist);
+	put(63, 30, &new_tags.album);
+	put(93, 4, &new_tags.year);
+	/* ID3v1.1 keeps the track number in the last byte of the comment */
+	if block[125] == 0 {
+		block[126] = new_tags.track.split('/').next().and_then(|track| track.trim().parse().ok()).unwrap_or(0);
+	}
+}
+
+/**
+ * Writes the bytes to a temp file next to the song, checks them and then moves them over the song.
+ * `check` gets the temp file's path and says whether it came out right.
+ */
+fn replace_safely(song: &str, bytes: &[u8], check: impl Fn(&str) -> bool) -> Result<(), String> {
+	let temp_path = format!("{}{}", song, TEMP_SUFFIX);
+	let write = || -> std::io::Result<()> {
+		let mut file = std::fs::File::create(&temp_path)?;
+		file.write_all(bytes)?;
+		file.sync_all()?;
+		if let Ok(metadata) = std::fs::metadata(song) {
+			std::fs::set_permissions(&temp_path, metadata.permissions())?;
+		}
+		Ok(())
+	};
+	let result = match write() {
+		Err(err) => Err(format!("Couldn't write {}: {}", temp_path, err)),
+		Ok(()) if std::fs::read(&temp_path).ok().as_deref() != Some(bytes) || !check(&temp_path) => {
+			Err(format!("{} didn't read back correctly", temp_path))
+		},
+		Ok(()) => std::fs::rename(&temp_path, song).map_err(|err| format!("Couldn't replace {}: {}", song, err)),
+	};
+	if result.is_err() {
+		let _ = std::fs::remove_file(&temp_path);
+	}
+	result
+}
+
+/**
+ * Puts a tag with the frames in front of the song's audio and replaces the song with the result. `change_id3v1` gets
+ * the ID3v1 tag at the end, if there is one.
+ */
+fn replace_tag(song: &str, bytes: &[u8], version: u8, frames: &[RawFrame], change_id3v1: impl FnOnce(&mut [u8])) -> Result<(), String> {
+	let mut audio = bytes[audio_start(bytes)..].to_vec();
+	if let Some(v1_start) = id3v1_start(&audio) {
+		change_id3v1(&mut audio[v1_start..]);
+	}
+	let mut new_bytes = encode_tag(version, frames);
+	new_bytes.extend_from_slice(&audio);
+
+	/* Every frame has to come back exactly as it was put in */
+	replace_safely(song, &new_bytes, |temp_path| {
+		std::fs::read(temp_path).ok()
+			.and_then(|bytes| read_raw_tag(&bytes).ok().flatten())
+			.is_some_and(|tag| tag.frames == frames)
+	})
+}
+
+/**
+ * Changes the tags of a song. Returns what it takes to undo that.
+ */
+pub fn write_tags(song: &str, changes: &[(TagField, String)]) -> Result<TagUndo, String> {
+	let bytes = std::fs::read(song).map_err(|err| format!("Couldn't read {}: {}", song, err))?;
+	let old_tag = read_raw_tag(&bytes).map_err(|err| format!("Left {} alone, {}", song, err))?;
+	let new_tags = apply_changes(&tags::read_tags(song), changes);
+	let (frames, mut undo) = build_tag(old_tag.as_ref(), &new_tags, changes);
+	undo.id3v1 = read_id3v1(&bytes);
+	replace_tag(song, &bytes, undo.version, &frames, |block| update_id3v1(block, &new_tags))?;
+	Ok(undo)
+}
+
+/**
+ * Undoes an edit: the fields it wrote get their old frames back, and the ID3v1 tag is put back as it was. Everything
+ * else in the tag stays as it is now. Returns what it takes to redo the edit.
+ */
+pub fn restore_tags(song: &str, undo: &TagUndo) -> Result<TagUndo, String> {
+	let bytes = std::fs::read(song).map_err(|err| format!("Couldn't read {}: {}", song, err))?;
+	let current = read_raw_tag(&bytes).map_err(|err| format!("Left {} alone, {}", song, err))?;
+	let Some(current) = current.filter(|tag| tag.version == undo.version) else {
+		return Err(format!("Left {} alone, its tag was replaced since the edit", song));
+	};
+	let ids: Vec<&str> = undo.fields.iter().flat_map(|field| frame_ids_of(*field).iter().copied()).collect();
+	let (edited, mut frames): (Vec<RawFrame>, Vec<RawFrame>) = current.frames.into_iter().partition(|frame| ids.contains(&frame.id.as_str()));
+	frames.extend(undo.frames.iter().cloned());
+	let redo = TagUndo {version: undo.version, fields: undo.fields.clone(), frames: edited, id3v1: read_id3v1(&bytes)};
+	replace_tag(song, &bytes, undo.version, &frames, |block| {
+		if let Some(old) = &undo.id3v1 {
+			block.copy_from_slice(old);
+		}
+	})?;
+	Ok(redo)
+}
+
+/**
+ * Puts new tags into the library record of a song, if it has one, so statistics show the new names.
+ */
+pub fn update_library_record(library: &mut library::Library, song: &str, song_tags: &tags::SongTags) {
+	if let Some(record) = library.songs.get_mut(song) {
+		record.title = song_tags.title.clone();
+		record.artist = song_tags.artist.clone();
+		record.album = song_tags.album.clone();
+		record.genre = song_tags.genre.clone();
+		record.track = song_tags.track.clone();
+	}
+}
+
+/* Each song that was changed with what it takes to undo that, and what went wrong */
+pub type TagWriteResult = (Vec<(String, TagUndo)>, Vec<String>);
+
+/**
+ * Tag writes running in the background, since rewriting many big files takes a while.
+ */
+pub struct TagWriteJob {
+	/* Number of songs done so far */
+	done: AtomicU32,
+	pub total: usize,
+	/* Set once it's over */
+	result: Mutex<Option<TagWriteResult>>,
+}
+
+impl TagWriteJob {
+	pub fn progress(&self) -> f32 {
+		self.done.load(Ordering::Relaxed) as f32 / self.total.max(1) as f32
+	}
+
+	pub fn result(&self) -> Option<TagWriteResult> {
+		self.result.lock().ok().and_then(|result| result.clone())
+	}
+}
+
+pub fn start_tag_write(edits: Vec<(String, TagChanges)>) -> Arc<TagWriteJob> {
+	start_job(edits, |song, changes| write_tags(song, changes))
+}
+
+pub fn start_tag_restore(undos: Vec<(String, TagUndo)>) -> Arc<TagWriteJob> {
+	start_job(undos, restore_tags)
+}
+
+fn start_job<T: Send + 'static>(songs: Vec<(String, T)>, write: fn(&str, &T) -> Result<TagUndo, String>) -> Arc<TagWriteJob> {
+	let job = Arc::new(TagWriteJob {done: AtomicU32::new(0), total: songs.len(), result: Mutex::new(None)});
+	let thread_job = Arc::clone(&job);
+	std::thread::spawn(move || {
+		let mut undos = Vec::new();
+		let mut errors = Vec::new();
+		for (song, item) in songs {
+			match write(&song, &item) {
+				Ok(undo) => undos.push((song, undo)),
+				Err(err) => errors.push(err),
+			}
+			thread_job.done.fetch_add(1, Ordering::Relaxed);
+		}
+		if let Ok(mut result) = thread_job.result.lock() {
+			*result = Some((undos, errors));
+		}
+	});
+	job
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	const AUDIO: [u8; 8] = [0xFF, 0xFB, 0x90, 0x00, 1, 2, 3, 4];
+
+	fn v23_song(name: &str) -> String {
+		let mut body = Vec::<u8>::new();
+		for (id, data) in [("TIT2", b"\0Old Title".to_vec()), ("TYER", b"\x001999".to_vec()), ("TCON", b"\0(17)".to_vec()), ("APIC", b"\0image/png\0\x03\0PNG".to_vec())] {
+			encode_frame(&mut body, &tags::Id3Frame {id: id.to_string(), data}, 3);
+		}
+		let mut bytes = b"ID3\x03\0\0".to_vec();
+		bytes.extend_from_slice(&synchsafe(body.len()));
+		bytes.extend_from_slice(&body);
+		bytes.extend_from_slice(&AUDIO);
+		let path = temp_file_path(name);
+		std::fs::write(&path, bytes).unwrap();
+		path
+	}
+
+	#[test]
+	fn writes_and_undoes_tags() {
+		let path = v23_song("edit.mp3");
+		let original = read_raw_tag(&std::fs::read(&path).unwrap()).unwrap().unwrap().frames;
+		let undo = write_tags(&path, &[(TagField::Title, "Новое".to_string()), (TagField::Artist, "Artist".to_string()), (TagField::Year, "".to_string())]).unwrap();
+		assert_eq!(undo.fields, vec![TagField::Title, TagField::Artist, TagField::Year]);
+		let edited = tags::read_tags(&path);
+		assert_eq!((edited.title.as_str(), edited.artist.as_str(), edited.year.as_str()), ("Новое", "Artist", ""));
+		let bytes = std::fs::read(&path).unwrap();
+		assert!(bytes.ends_with(&AUDIO));
+		/* Frames that weren't edited are kept */
+		assert!(tags::read_id3_frames(&path).unwrap().frames.iter().any(|frame| frame.id == "APIC"));
+
+		/* Undo puts back the edited frames as they were, and doesn't touch the rest (e.g. a genre by number) */
+		restore_tags(&path, &undo).unwrap();
+		let mut restored = read_raw_tag(&std::fs::read(&path).unwrap()).unwrap().unwrap().frames;
+		let _ = std::fs::remove_file(&path);
+		let mut original = original;
+		original.sort_by(|a, b| a.bytes.cmp(&b.bytes));
+		restored.sort_by(|a, b| a.bytes.cmp(&b.bytes));
+		assert_eq!(restored, original);
+		assert!(!std::path::Path::new(&format!("{}{}", path, TEMP_SUFFIX)).exists());
+	}
+
+	/* A tag from ready made frame bytes */
+	fn song_with_frames(name: &str, version: u8, frames: &[&[u8]]) -> String {
+		let body: Vec<u8> = frames.concat();
+		let mut bytes = b"ID3".to_vec();
+		bytes.extend_from_slice(&[version, 0, 0]);
+		bytes.extend_from_slice(&synchsafe(body.len()));
+		bytes.extend_from_slice(&body);
+		bytes.extend_from_slice(&AUDIO);
+		let path = temp_file_path(name);
+		std::fs::write(&path, bytes).unwrap();
+		path
+	}
+
+	#[test]
+	fn keeps_flagged_frames_byte_for_byte() {
+		/* A compressed frame (flag 0x80, decompressed size first) and a grouped one (flag 0x20, group byte first) */
+		let compressed: &[u8] = b"COMM\0\0\0\x08\0\x80\0\0\0\x40\x78\x9C\x01\x02";
+		let grouped: &[u8] = b"TXXX\0\0\0\x05\0\x20\x07\0a\0b";
+		let title: &[u8] = b"TIT2\0\0\0\x04\0\0\0Old";
+		let path = song_with_frames("flagged.mp3", 3, &[compressed, grouped, title]);
+		write_tags(&path, &[(TagField::Title, "New".to_string())]).unwrap();
+		let bytes = std::fs::read(&path).unwrap();
+		let _ = std::fs::remove_file(&path);
+		let tag = read_raw_tag(&bytes).unwrap().unwrap();
+		assert_eq!(tag.frames.iter().map(|frame| frame.bytes.as_slice()).take(2).collect::<Vec<&[u8]>>(), vec![compressed, grouped]);
+		assert_eq!(tag.frames[2].id, "TIT2");
+
+		/* ID3v2.4 frames with a data length indicator and unsynchronisation keep their flags too */
+		let v24_frame: &[u8] = b"TPE2\0\0\0\x09\0\x03\0\0\0\x04\0\xFF\0\xFE\0";
+		let path = song_with_frames("flagged-v24.mp3", 4, &[v24_frame]);
+		write_tags(&path, &[(TagField::Album, "Album".to_string())]).unwrap();
+		let bytes = std::fs::read(&path).unwrap();
+		let _ = std::fs::remove_file(&path);
+		assert_eq!(read_raw_tag(&bytes).unwrap().unwrap().frames[0].bytes, v24_frame);
+	}
+
+	#[test]
+	fn leaves_broken_tags_alone() {
+		/* The second frame says it's longer than the tag, what follows it can't be found */
+		let title: &[u8] = b"TIT2\0\0\0\x04\0\0\0Old";
+		let cut_off: &[u8] = b"APIC\0\0\x10\0\0\0\0image/png";
+		let path = song_with_frames("broken.mp3", 3, &[title, cut_off]);
+		let before = std::fs::read(&path).unwrap();
+		let result = write_tags(&path, &[(TagField::Title, "New".to_string())]);
+		let after = std::fs::read(&path).unwrap();
+		let _ = std::fs::remove_file(&path);
+		assert!(result.is_err_and(|err| err.contains("APIC")));
+		assert_eq!(before, after);
+	}
+
+	#[test]
+	fn tags_untagged_songs_with_id3v1() {
+		let mut bytes = AUDIO.to_vec();
+		let mut block = [0u8; 128];
+		block[0..3].copy_from_slice(b"TAG");
+		block[3..6].copy_from_slice(b"One");
+		block[33..36].copy_from_slice(b"Two");
+		bytes.extend_from_slice(&block);
+		let path = temp_file_path("v1-edit.mp3");
+		std::fs::write(&path, bytes).unwrap();
+		let undo = write_tags(&path, &[(TagField::Album, "Three".to_string())]).unwrap();
+		let edited = tags::read_tags(&path);
+		let bytes = std::fs::read(&path).unwrap();
+		/* The ID3v1 fields carry over into the new tag, and the ID3v1 tag gets the change too */
+		assert_eq!((edited.title.as_str(), edited.artist.as_str(), edited.album.as_str()), ("One", "Two", "Three"));
+		assert_eq!(&bytes[bytes.len() - 128 + 63..bytes.len() - 128 + 68], b"Three");
+
+		/* Undo leaves no ID3v2 frames behind, and the ID3v1 tag as it was */
+		restore_tags(&path, &undo).unwrap();
+		let bytes = std::fs::read(&path).unwrap();
+		let _ = std::fs::remove_file(&path);
+		assert!(read_raw_tag(&bytes).unwrap().unwrap().frames.is_empty());
+		assert_eq!(&bytes[bytes.len() - 128..], block);
+	}
+
+	#[test]
+	fn reads_tags_from_file_names() {
+		let changes = tags_from_file_name("%track% - %artist% - %title%", "/music/03 - Some Band - A Song - Live.mp3").unwrap();
+		assert_eq!(changes, vec![
+			(TagField::Track, "03".to_string()),
+			(TagField::Artist, "Some Band".to_string()),
+			(TagField::Title, "A Song - Live".to_string()),
+		]);
+		assert_eq!(tags_from_file_name("[%year%] %album%", "/m/[2001] Odyssey.mp3").unwrap()[1], (TagField::Album, "Odyssey".to_string()));
+		assert!(tags_from_file_name("%artist% - %title%", "/music/No Separator.mp3").is_none());
+		assert!(tags_from_file_name("%composer%", "/music/a.mp3").is_none());
+	}
+}
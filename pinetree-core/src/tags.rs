@@ -0,0 +1,436 @@
+/*
+ * Pinetree tag reading. Handles pulling song info (title, artist, etc.) out of mp3 files.
+ *
+ * This is a minimal ID3 reader. It only understands the frames Pinetree actually uses,
+ * and falls back to the old ID3v1 block at the end of the file if there is no ID3v2 tag.
+ */
+
+use std::io::{Read, Seek, SeekFrom};
+
+#[derive(Clone)]
+#[derive(Default)]
+pub struct SongTags {
+	pub title: String,
+	pub artist: String,
+	pub album: String,
+	pub genre: String,
+	pub track: String,
+	pub year: String,
+	/* "1" or "1/2", empty for single disc albums */
+	pub disc: String,
+	/* Set when an album has songs by different artists, e.g. compilations */
+	pub album_artist: String,
+}
+
+pub struct Id3Frame {
+	pub id: String,
+	pub data: Vec<u8>,
+}
+
+pub struct Id3Tag {
+	/* Major version, 2 to 4 */
+	pub version: u8,
+	pub frames: Vec<Id3Frame>,
+}
+
+pub fn synchsafe_to_usize(bytes: &[u8]) -> usize {
+	let mut value = 0;
+	for byte in bytes {
+		value = (value << 7) | (*byte & 0x7F) as usize;
+	}
+	value
+}
+
+pub fn be_to_usize(bytes: &[u8]) -> usize {
+	let mut value = 0;
+	for byte in bytes {
+		value = (value << 8) | *byte as usize;
+	}
+	value
+}
+
+/**
+ * Reverses the ID3 "unsynchronisation" scheme, which inserts a 0x00 after every 0xFF.
+ */
+pub fn remove_unsynchronisation(data: &[u8]) -> Vec<u8> {
+	let mut out = Vec::<u8>::with_capacity(data.len());
+	let mut i = 0;
+	while i < data.len() {
+		out.push(data[i]);
+		if data[i] == 0xFF && i + 1 < data.len() && data[i + 1] == 0x00 {
+			i += 1;
+		}
+		i += 1;
+	}
+	out
+}
+
+/**
+ * Length of the ID3v2 tag a file starts with (footer included), from its first 10 bytes. 0 if it has none.
+ */
+pub fn id3_tag_len(header: &[u8]) -> usize {
+	if header.len() < 10 || &header[0..3] != b"ID3" {
+		return 0;
+	}
+	let footer = if header[3] == 4 && header[5] & 0x10 != 0 {10} else {0};
+	10 + synchsafe_to_usize(&header[6..10]) + footer
+}
+
+/**
+ * Reads every frame of the ID3v2 tag at the start of the file.
+ * Returns None if there is no tag (or it is too broken to read).
+ */
+pub fn read_id3_frames(file_path: &str) -> Option<Id3Tag> {
+	let mut file = std::fs::File::open(file_path).ok()?;
+	let mut header = [0u8; 10];
+	file.read_exact(&mut header).ok()?;
+
+	if &header[0..3] != b"ID3" {
+		return None;
+	}
+	let version = header[3];
+	if !(2..=4).contains(&version) {
+		return None;
+	}
+	let flags = header[5];
+	let tag_size = synchsafe_to_usize(&header[6..10]);
+
+	let mut tag_data = vec![0u8; tag_size];
+	file.read_exact(&mut tag_data).ok()?;
+
+	/* ID3v2.4 does unsynchronisation per frame instead of for the whole tag */
+	if flags & 0x80 != 0 && version < 4 {
+		tag_data = remove_unsynchronisation(&tag_data);
+	}
+
+	let mut position = 0;
+	if flags & 0x40 != 0 && version > 2 {
+		/* Extended header, which nothing here cares about */
+		if tag_data.len() < 4 {return None;}
+		let extended_size = if version == 4 {
+			synchsafe_to_usize(&tag_data[0..4])
+		} else {
+			be_to_usize(&tag_data[0..4]) + 4
+		};
+		position = extended_size;
+	}
+
+	Some(Id3Tag {version, frames: parse_frames(&tag_data[position.min(tag_data.len())..], version)})
+}
+
+/**
+ * Reads the frames in a block of frame data. Used for the tag itself and for frames that contain
+ * frames of their own (CHAP).
+ */
+pub fn parse_frames(tag_data: &[u8], version: u8) -> Vec<Id3Frame> {
+	let mut position = 0;
+	let (id_len, header_len) = if version == 2 {(3, 6)} else {(4, 10)};
+	let mut frames = Vec::<Id3Frame>::new();
+
+	while position + header_len <= tag_data.len() {
+		let frame_header = &tag_data[position..position + header_len];
+		/* Padding */
+		if frame_header[0] == 0 {
+			break;
+		}
+		let id = String::from_utf8_lossy(&frame_header[0..id_len]).to_string();
+		let frame_size = match version {
+			2 => be_to_usize(&frame_header[3..6]),
+			3 => be_to_usize(&frame_header[4..8]),
+			_ => synchsafe_to_usize(&frame_header[4..8]),
+		};
+		let data_start = position + header_len;
+		let data_end = data_start + frame_size;
+		if data_end > tag_data.len() {
+			break;
+		}
+		let mut data = tag_data[data_start..data_end].to_vec();
+
+		if version == 4 {
+			let format_flags = frame_header[9];
+			if format_flags & 0x02 != 0 {
+				data = remove_unsynchronisation(&data);
+			}
+			/* Data length indicator, which is just extra bytes in front of the actual frame */
+			if format_flags & 0x01 != 0 && data.len() >= 4 {
+				data = data[4..].to_vec();
+			}
+		}
+
+		frames.push(Id3Frame {id, data});
+		position = data_end;
+	}
+
+	frames
+}
+
+fn decode_latin1(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| *b as char).collect()
+}
+
+fn decode_utf16(bytes: &[u8], big_endian: bool) -> String {
+	let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| {
+		if big_endian {
+			u16::from_be_bytes([pair[0], pair[1]])
+		} else {
+			u16::from_le_bytes([pair[0], pair[1]])
+		}
+	}).collect();
+	String::from_utf16_lossy(&units)
+}
+
+/**
+ * Decodes a string in one of the four ID3 text encodings.
+ * 0 = ISO-8859-1, 1 = UTF-16 with BOM, 2 = UTF-16BE, 3 = UTF-8
+ */
+pub fn decode_id3_string(encoding: u8, bytes: &[u8]) -> String {
+	match encoding {
+		1 => {
+			if bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] == 0xFE {
+				decode_utf16(&bytes[2..], false)
+			} else if bytes.len() >= 2 && bytes[0] == 0xFE && bytes[1] == 0xFF {
+				decode_utf16(&bytes[2..], true)
+			} else {
+				decode_utf16(bytes, false)
+			}
+		},
+		2 => decode_utf16(bytes, true),
+		3 => String::from_utf8_lossy(bytes).to_string(),
+		_ => decode_latin1(bytes),
+	}
+}
+
+/**
+ * Text frames can hold several null separated values (ID3v2.4). Only the first one is used.
+ */
+pub fn decode_text_frame(data: &[u8]) -> String {
+	if data.is_empty() {
+		return "".to_string();
+	}
+	let text = decode_id3_string(data[0], &data[1..]);
+	let first_value = text.split('\0').next().unwrap_or("");
+	first_value.trim().to_string()
+}
+
+/**
+ * ID3v1 genres are stored as a number. ID3v2 sometimes does this too, as "(17)" or just "17".
+ * Only the most common ones are listed, everything else just shows the number.
+ */
+fn genre_from_number(number: usize) -> String {
+	const GENRES: [&str; 26] = [
+		"Blues", "Classic Rock", "Country", "Dance", "Disco", "Funk", "Grunge", "Hip-Hop",
+		"Jazz", "Metal", "New Age", "Oldies", "Other", "Pop", "R&B", "Rap",
+		"Reggae", "Rock", "Techno", "Industrial", "Alternative", "Ska", "Death Metal", "Pranks",
+		"Soundtrack", "Euro-Techno",
+	];
+	if let Some(genre) = GENRES.get(number) {
+		genre.to_string()
+	} else {
+		format!("{}", number)
+	}
+}
+
+fn clean_genre(genre: &str) -> String {
+	let trimmed = genre.trim();
+	let inner = if trimmed.starts_with('(') && let Some(end) = trimmed.find(')') {
+		&trimmed[1..end]
+	} else {
+		trimmed
+	};
+	if let Ok(number) = inner.parse::<usize>() {
+		return genre_from_number(number);
+	}
+	trimmed.to_string()
+}
+
+fn read_id3v1(file_path: &str) -> Option<SongTags> {
+	let mut file = std::fs::File::open(file_path).ok()?;
+	file.seek(SeekFrom::End(-128)).ok()?;
+	let mut block = [0u8; 128];
+	file.read_exact(&mut block).ok()?;
+	if &block[0..3] != b"TAG" {
+		return None;
+	}
+	let field = |bytes: &[u8]| -> String {
+		decode_latin1(bytes).trim_end_matches('\0').trim().to_string()
+	};
+	let track = if block[125] == 0 && block[126] != 0 {format!("{}", block[126])} else {"".to_string()};
+	Some(SongTags {
+		title: field(&block[3..33]),
+		artist: field(&block[33..63]),
+		album: field(&block[63..93]),
+		year: field(&block[93..97]),
+		genre: if block[127] == 255 {"".to_string()} else {genre_from_number(block[127] as usize)},
+		track,
+		..Default::default()
+	})
+}
+
+/**
+ * Reads the tags of a song. Any tag that isn't present is left as an empty string.
+ */
+pub fn read_tags(file_path: &str) -> SongTags {
+	let mut tags = SongTags::default();
+	if let Some(tag) = read_id3_frames(file_path) {
+		for frame in &tag.frames {
+			let target = match frame.id.as_str() {
+				"TIT2" | "TT2" => &mut tags.title,
+				"TPE1" | "TP1" => &mut tags.artist,
+				"TALB" | "TAL" => &mut tags.album,
+				"TCON" | "TCO" => &mut tags.genre,
+				"TRCK" | "TRK" => &mut tags.track,
+				"TYER" | "TDRC" | "TYE" => &mut tags.year,
+				"TPOS" | "TPA" => &mut tags.disc,
+				"TPE2" | "TP2" => &mut tags.album_artist,
+				_ => continue,
+			};
+			if target.is_empty() {
+				*target = decode_text_frame(&frame.data);
+			}
+		}
+		tags.genre = clean_genre(&tags.genre);
+		/* TDRC is a full timestamp, only the year is wanted */
+		tags.year = tags.year.chars().take(4).collect();
+	} else if let Some(v1_tags) = read_id3v1(file_path) {
+		tags = v1_tags;
+	}
+	tags
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn text_frame(encoding: u8, text: &[u8]) -> Vec<u8> {
+		let mut data = vec![encoding];
+		data.extend_from_slice(text);
+		data
+	}
+
+	fn synchsafe(value: usize) -> [u8; 4] {
+		[(value >> 21) as u8 & 0x7F, (value >> 14) as u8 & 0x7F, (value >> 7) as u8 & 0x7F, value as u8 & 0x7F]
+	}
+
+	/* Builds an ID3v2 tag followed by a bit of (fake) audio */
+	fn id3v2(version: u8, flags: u8, frames: &[(&str, Vec<u8>)]) -> Vec<u8> {
+		let mut body = Vec::<u8>::new();
+		for (id, data) in frames {
+			body.extend_from_slice(id.as_bytes());
+			match version {
+				2 => body.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..]),
+				3 => body.extend_from_slice(&(data.len() as u32).to_be_bytes()),
+				_ => body.extend_from_slice(&synchsafe(data.len())),
+			}
+			if version > 2 {
+				body.extend_from_slice(&[0, 0]);
+			}
+			body.extend_from_slice(data);
+		}
+		/* Padding */
+		body.extend_from_slice(&[0; 16]);
+
+		let mut tag = b"ID3".to_vec();
+		tag.extend_from_slice(&[version, 0, flags]);
+		tag.extend_from_slice(&synchsafe(body.len()));
+		tag.extend_from_slice(&body);
+		tag.extend_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+		tag
+	}
+
+	fn read_tags_of(name: &str, bytes: &[u8]) -> SongTags {
+		let path = temp_file_path(name);
+		std::fs::write(&path, bytes).unwrap();
+		let tags = read_tags(&path);
+		let _ = std::fs::remove_file(&path);
+		tags
+	}
+
+	#[test]
+	fn reads_id3v23_latin1() {
+		let tags = read_tags_of("v23.mp3", &id3v2(3, 0, &[
+			("TIT2", text_frame(0, b"Caf\xE9")),
+			("TPE1", text_frame(0, b"Artist")),
+			("TALB", text_frame(0, b"Album\0")),
+			("TCON", text_frame(0, b"(17)")),
+			("TRCK", text_frame(0, b"3/12")),
+			("TYER", text_frame(0, b"1999")),
+			("TPOS", text_frame(0, b"2/2")),
+			("TPE2", text_frame(0, b"Various Artists")),
+		]));
+		assert_eq!(tags.title, "Café");
+		assert_eq!(tags.artist, "Artist");
+		assert_eq!(tags.album, "Album");
+		assert_eq!(tags.genre, "Rock");
+		assert_eq!(tags.track, "3/12");
+		assert_eq!(tags.year, "1999");
+		assert_eq!(tags.disc, "2/2");
+		assert_eq!(tags.album_artist, "Various Artists");
+	}
+
+	#[test]
+	fn reads_id3v24_utf8_and_utf16() {
+		let mut utf16 = vec![0xFF, 0xFE];
+		for unit in "Ελληνικά".encode_utf16() {
+			utf16.extend_from_slice(&unit.to_le_bytes());
+		}
+		let tags = read_tags_of("v24.mp3", &id3v2(4, 0, &[
+			("TIT2", text_frame(3, "Über".as_bytes())),
+			("TPE1", text_frame(1, &utf16)),
+			("TCON", text_frame(3, b"Jazz\0Blues")),
+			("TDRC", text_frame(3, b"2019-05-01")),
+		]));
+		assert_eq!(tags.title, "Über");
+		assert_eq!(tags.artist, "Ελληνικά");
+		assert_eq!(tags.genre, "Jazz");
+		assert_eq!(tags.year, "2019");
+	}
+
+	#[test]
+	fn reads_id3v22() {
+		let tags = read_tags_of("v22.mp3", &id3v2(2, 0, &[
+			("TT2", text_frame(0, b"Old Title")),
+			("TP1", text_frame(0, b"Old Artist")),
+		]));
+		assert_eq!(tags.title, "Old Title");
+		assert_eq!(tags.artist, "Old Artist");
+	}
+
+	#[test]
+	fn removes_unsynchronisation() {
+		assert_eq!(remove_unsynchronisation(&[0xFF, 0x00, 0xE0, 0x41, 0xFF, 0x00]), vec![0xFF, 0xE0, 0x41, 0xFF]);
+		let tags = read_tags_of("unsync.mp3", &id3v2(3, 0x80, &[
+			("TIT2", text_frame(0, b"Plain")),
+		]));
+		assert_eq!(tags.title, "Plain");
+	}
+
+	#[test]
+	fn falls_back_to_id3v1() {
+		let mut bytes = vec![0xFF, 0xFB, 0x90, 0x00];
+		let mut block = [0u8; 128];
+		block[0..3].copy_from_slice(b"TAG");
+		block[3..8].copy_from_slice(b"Title");
+		block[33..39].copy_from_slice(b"Artist");
+		block[63..68].copy_from_slice(b"Album");
+		block[93..97].copy_from_slice(b"1987");
+		block[126] = 4;
+		block[127] = 13;
+		bytes.extend_from_slice(&block);
+
+		let tags = read_tags_of("v1.mp3", &bytes);
+		assert_eq!(tags.title, "Title");
+		assert_eq!(tags.artist, "Artist");
+		assert_eq!(tags.album, "Album");
+		assert_eq!(tags.year, "1987");
+		assert_eq!(tags.track, "4");
+		assert_eq!(tags.genre, "Pop");
+	}
+
+	#[test]
+	fn untagged_file_has_empty_tags() {
+		let tags = read_tags_of("untagged.mp3", &[0xFF, 0xFB, 0x90, 0x00]);
+		assert!(tags.title.is_empty() && tags.artist.is_empty() && tags.genre.is_empty());
+		assert!(read_tags(&temp_file_path("missing.mp3")).title.is_empty());
+	}
+}
@@ -0,0 +1,11 @@
+/*
+ * Helpers shared by the unit tests.
+ */
+
+/**
+ * A path in the temp folder that no other test (or test run) uses, since tests run in parallel.
+ */
+pub fn temp_file_path(name: &str) -> String {
+	let path = std::env::temp_dir().join(format!("pinetree-test-{}-{}", std::process::id(), name));
+	path.to_string_lossy().to_string()
+}
@@ -0,0 +1,357 @@
+/*
+ * Pinetree track table. The rows and the sorting behind the table view of a folder or playlist.
+ *
+ * Sorting can use several columns: the first key decides, later keys break ties, and the path breaks whatever
+ * ties are left so the order never jumps around. Every folder and playlist remembers its own sort.
+ */
+
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::audio_frontend;
+use crate::library;
+use crate::tags;
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Eq)]
+#[derive(Hash)]
+#[derive(Debug)]
+pub enum TableColumn {
+	Title,
+	Artist,
+	Album,
+	Duration,
+	Track,
+	PlayCount,
+	DateAdded,
+	FileSize,
+}
+
+pub const ALL_COLUMNS: [TableColumn; 8] = [
+	TableColumn::Title,
+	TableColumn::Artist,
+	TableColumn::Album,
+	TableColumn::Duration,
+	TableColumn::Track,
+	TableColumn::PlayCount,
+	TableColumn::DateAdded,
+	TableColumn::FileSize,
+];
+
+pub fn column_to_str(column: TableColumn) -> &'static str {
+	match column {
+		TableColumn::Title => "Title",
+		TableColumn::Artist => "Artist",
+		TableColumn::Album => "Album",
+		TableColumn::Duration => "Duration",
+		TableColumn::Track => "Track",
+		TableColumn::PlayCount => "Play Count",
+		TableColumn::DateAdded => "Date Added",
+		TableColumn::FileSize => "File Size",
+	}
+}
+
+pub fn str_to_column(string: &str) -> Option<TableColumn> {
+	ALL_COLUMNS.iter().find(|column| column_to_str(**column) == string).copied()
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct SortKey {
+	pub column: TableColumn,
+	pub ascending: bool,
+}
+
+/**
+ * Sort keys as they're saved, e.g. "Artist+, Track-".
+ */
+pub fn sort_keys_to_str(keys: &[SortKey]) -> String {
+	keys.iter()
+		.map(|key| format!("{}{}", column_to_str(key.column), if key.ascending {"+"} else {"-"}))
+		.collect::<Vec<String>>()
+		.join(", ")
+}
+
+pub fn str_to_sort_keys(string: &str) -> Vec<SortKey> {
+	string.split(", ")
+		.filter_map(|key| {
+			let (name, ascending) = if let Some(name) = key.strip_suffix('-') {(name, false)} else {(key.strip_suffix('+').unwrap_or(key), true)};
+			str_to_column(name).map(|column| SortKey {column, ascending})
+		})
+		.collect()
+}
+
+/**
+ * Clicking a header sorts by that column, or flips it if it already is the main sort.
+ * With `add_key` (shift-click) the column becomes (or flips) an extra tie-breaking key instead.
+ */
+pub fn click_header(keys: &mut Vec<SortKey>, column: TableColumn, add_key: bool) {
+	if add_key {
+		if let Some(key) = keys.iter_mut().find(|key| key.column == column) {
+			key.ascending = !key.ascending;
+		} else {
+			keys.push(SortKey {column, ascending: true});
+		}
+	} else if let Some(first) = keys.first() && first.column == column {
+		let ascending = !first.ascending;
+		*keys = vec![SortKey {column, ascending}];
+	} else {
+		*keys = vec![SortKey {column, ascending: true}];
+	}
+}
+
+#[derive(Clone)]
+#[derive(Default)]
+#[derive(Debug)]
+pub struct TrackRow {
+	pub path: String,
+	pub title: String,
+	pub artist: String,
+	pub album: String,
+	pub duration_ms: u64,
+	/* 0 when the song has no track number */
+	pub track: u32,
+	pub play_count: u64,
+	/* Seconds since the unix epoch */
+	pub date_added: u64,
+	pub file_size: u64,
+}
+
+/**
+ * Reads "3" or "3/12" style track numbers.
+ */
+pub fn parse_track_number(track: &str) -> u32 {
+	track.split('/').next().and_then(|number| number.trim().parse().ok()).unwrap_or(0)
+}
+
+/**
+ * Builds a row from the library record if there is one, otherwise from the file's tags. The date added is when the
+ * file showed up on disk: its creation time, or its modification time on file systems that don't keep that.
+ */
+pub fn track_row(song: &str, record: Option<&library::SongRecord>) -> TrackRow {
+	let metadata = std::fs::metadata(song).ok();
+	let file_size = metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(0);
+	let date_added = metadata.as_ref()
+		.and_then(|metadata| metadata.created().or_else(|_| metadata.modified()).ok())
+		.and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+		.map(|since_epoch| since_epoch.as_secs())
+		.unwrap_or(0);
+	let (title, artist, album, track) = if let Some(record) = record {
+		(record.title.clone(), record.artist.clone(), record.album.clone(), record.track.clone())
+	} else {
+		let song_tags = tags::read_tags(song);
+		(song_tags.title, song_tags.artist, song_tags.album, song_tags.track)
+	};
+	let duration_ms = match record {
+		Some(record) if record.length_ms > 0 => record.length_ms,
+		_ => audio_frontend::get_song_len_ms(song) as u64,
+	};
+	TrackRow {
+		path: song.to_string(),
+		title,
+		artist,
+		album,
+		duration_ms,
+		track: parse_track_number(&track),
+		play_count: record.map(|record| record.play_count).unwrap_or(0),
+		date_added,
+		file_size,
+	}
+}
+
+/**
+ * Rows being built in the background, since that reads every file that isn't in the library yet.
+ */
+pub struct TrackRowJob {
+	done: AtomicU32,
+	pub total: usize,
+	result: Mutex<Option<Vec<TrackRow>>>,
+}
+
+impl TrackRowJob {
+	pub fn progress(&self) -> f32 {
+		self.done.load(Ordering::Relaxed) as f32 / self.total.max(1) as f32
+	}
+
+	pub fn result(&self) -> Option<Vec<TrackRow>> {
+		self.result.lock().ok().and_then(|result| result.clone())
+	}
+}
+
+/**
+ * The library is only locked to copy each song's record, the files are read after letting go of it.
+ */
+pub fn start_track_rows(songs: Vec<String>, library: Arc<Mutex<library::Library>>) -> Arc<TrackRowJob> {
+	let job = Arc::new(TrackRowJob {done: AtomicU32::new(0), total: songs.len(), result: Mutex::new(None)});
+	let thread_job = Arc::clone(&job);
+	std::thread::spawn(move || {
+		let mut rows = Vec::with_capacity(songs.len());
+		for song in &songs {
+			let record = library.lock().ok().and_then(|library| library.songs.get(song).cloned());
+			rows.push(track_row(song, record.as_ref()));
+			thread_job.done.fetch_add(1, Ordering::Relaxed);
+		}
+		if let Ok(mut result) = thread_job.result.lock() {
+			*result = Some(rows);
+		}
+	});
+	job
+}
+
+/* Songs without a title show (and sort by) their file name */
+pub fn display_title(row: &TrackRow) -> &str {
+	if row.title.trim().is_empty() {
+		std::path::Path::new(&row.path).file_stem().and_then(|stem| stem.to_str()).unwrap_or(&row.path)
+	} else {
+		&row.title
+	}
+}
+
+fn compare_column(a: &TrackRow, b: &TrackRow, column: TableColumn) -> cmp::Ordering {
+	match column {
+		TableColumn::Title => display_title(a).to_lowercase().cmp(&display_title(b).to_lowercase()),
+		TableColumn::Artist => a.artist.to_lowercase().cmp(&b.artist.to_lowercase()),
+		TableColumn::Album => a.album.to_lowercase().cmp(&b.album.to_lowercase()),
+		TableColumn::Duration => a.duration_ms.cmp(&b.duration_ms),
+		TableColumn::Track => a.track.cmp(&b.track),
+		TableColumn::PlayCount => a.play_count.cmp(&b.play_count),
+		TableColumn::DateAdded => a.date_added.cmp(&b.date_added),
+		TableColumn::FileSize => a.file_size.cmp(&b.file_size),
+	}
+}
+
+pub fn sort_rows(rows: &mut [TrackRow], keys: &[SortKey]) {
+	rows.sort_by(|a, b| {
+		keys.iter()
+			.map(|key| if key.ascending {compare_column(a, b, key.column)} else {compare_column(b, a, key.column)})
+			.find(|ordering| *ordering != cmp::Ordering::Equal)
+			.unwrap_or_else(|| a.path.to_lowercase().cmp(&b.path.to_lowercase()))
+	});
+}
+
+/**
+ * Which columns show, in which order. Sorts are keyed by folder path or by "Playlist: " and the playlist name.
+ */
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct TableSettings {
+	pub columns: Vec<TableColumn>,
+	pub sorts: HashMap<String, Vec<SortKey>>,
+}
+
+pub fn default_table_settings() -> TableSettings {
+	TableSettings {
+		columns: vec![TableColumn::Track, TableColumn::Title, TableColumn::Artist, TableColumn::Album, TableColumn::Duration, TableColumn::PlayCount],
+		sorts: HashMap::new(),
+	}
+}
+
+pub fn playlist_sort_id(playlist_name: &str) -> String {
+	format!("Playlist: {}", playlist_name)
+}
+
+/**
+ * Moves a visible column one place left (-1) or right (1).
+ */
+pub fn move_column(columns: &mut [TableColumn], column: TableColumn, direction: isize) {
+	if let Some(index) = columns.iter().position(|shown| *shown == column) {
+		let target = index as isize + direction;
+		if target >= 0 && (target as usize) < columns.len() {
+			columns.swap(index, target as usize);
+		}
+	}
+}
+
+pub fn format_file_size(bytes: u64) -> String {
+	if bytes >= 1024 * 1024 {
+		format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+	} else {
+		format!("{} KB", bytes.div_ceil(1024))
+	}
+}
+
+pub fn format_duration(ms: u64) -> String {
+	let secs = ms / 1000;
+	if secs >= 3600 {
+		format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+	} else {
+		format!("{}:{:02}", secs / 60, secs % 60)
+	}
+}
+
+/* YYYY-MM-DD, UTC */
+pub fn format_date(unix_time: u64) -> String {
+	let (year, month, day) = library::day_to_date(library::unix_time_to_day(unix_time));
+	format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn row(path: &str, artist: &str, album: &str, track: u32) -> TrackRow {
+		TrackRow {path: path.to_string(), artist: artist.to_string(), album: album.to_string(), track, ..Default::default()}
+	}
+
+	#[test]
+	fn sorts_by_several_keys() {
+		let mut rows = vec![
+			row("/m/c.mp3", "Band", "Second", 1),
+			row("/m/a.mp3", "band", "First", 2),
+			row("/m/b.mp3", "Band", "First", 1),
+			row("/m/d.mp3", "Another", "", 0),
+		];
+		let mut keys = Vec::new();
+		click_header(&mut keys, TableColumn::Artist, false);
+		click_header(&mut keys, TableColumn::Album, true);
+		click_header(&mut keys, TableColumn::Track, true);
+		sort_rows(&mut rows, &keys);
+		let paths: Vec<&str> = rows.iter().map(|row| row.path.as_str()).collect();
+		assert_eq!(paths, vec!["/m/d.mp3", "/m/b.mp3", "/m/a.mp3", "/m/c.mp3"]);
+
+		/* Shift-clicking a key again flips it, a plain click replaces all keys */
+		click_header(&mut keys, TableColumn::Track, true);
+		assert_eq!(keys[2], SortKey {column: TableColumn::Track, ascending: false});
+		click_header(&mut keys, TableColumn::Artist, false);
+		assert_eq!(keys, vec![SortKey {column: TableColumn::Artist, ascending: false}]);
+		sort_rows(&mut rows, &keys);
+		assert_eq!(rows[3].path, "/m/d.mp3");
+	}
+
+	#[test]
+	fn builds_rows_in_the_background() {
+		let song = crate::test_util::temp_file_path("track row.mp3");
+		std::fs::write(&song, vec![0u8; 3000]).unwrap();
+		let mut library = library::new_library(None);
+		library.songs.insert(song.clone(), library::SongRecord {title: "Known".to_string(), length_ms: 61000, play_count: 4, ..Default::default()});
+		let library = Arc::new(Mutex::new(library));
+
+		let job = start_track_rows(vec![song.clone()], Arc::clone(&library));
+		let rows = loop {
+			if let Some(rows) = job.result() {
+				break rows;
+			}
+			std::thread::sleep(std::time::Duration::from_millis(5));
+		};
+		std::fs::remove_file(&song).unwrap();
+		assert_eq!(job.progress(), 1.0);
+		assert_eq!((rows[0].title.as_str(), rows[0].duration_ms, rows[0].play_count, rows[0].file_size), ("Known", 61000, 4, 3000));
+	}
+
+	#[test]
+	fn sort_keys_round_trip() {
+		let keys = vec![SortKey {column: TableColumn::PlayCount, ascending: false}, SortKey {column: TableColumn::Title, ascending: true}];
+		assert_eq!(sort_keys_to_str(&keys), "Play Count-, Title+");
+		assert_eq!(str_to_sort_keys(&sort_keys_to_str(&keys)), keys);
+		assert_eq!(str_to_sort_keys("Made Up+, Album"), vec![SortKey {column: TableColumn::Album, ascending: true}]);
+		assert_eq!(parse_track_number("3/12"), 3);
+		assert_eq!(parse_track_number(""), 0);
+	}
+}
@@ -0,0 +1,360 @@
+/*
+ * Pinetree visualiser. The playing song's samples are copied out of the audio path by TapSource, which wraps
+ * the decoder, into a SampleTap. That is a lock-free ring buffer: the audio output thread only ever does a few
+ * atomic stores per sample, so a slow or stalled front-end can never hold up playback. The front-end then reads
+ * the newest samples whenever it redraws and turns them into a spectrum, stereo levels and an oscilloscope trace.
+ *
+ * Readers can race the writer and get a sample or two from the next lap around the buffer. That's fine for a
+ * visualisation, and it's why readers never ask for more than half the buffer.
+ */
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU16, AtomicU32, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use rodio::Source;
+
+/* About 90ms of 44.1kHz stereo */
+pub const TAP_CAPACITY: usize = 8192;
+const FFT_SIZE: usize = 2048;
+/* Anything quieter than this is drawn as nothing */
+const FLOOR_DB: f32 = -60.0;
+const LOWEST_BAND_HZ: f32 = 40.0;
+const HIGHEST_BAND_HZ: f32 = 16000.0;
+const SCOPE_MS: usize = 25;
+
+pub const MIN_BAND_COUNT: usize = 8;
+pub const MAX_BAND_COUNT: usize = 96;
+
+pub struct SampleTap {
+	samples: Box<[AtomicU32]>,
+	/* Total number of samples ever written. Only TapSource writes, and only one song plays at a time */
+	written: AtomicUsize,
+	channels: AtomicU16,
+	sample_rate: AtomicU32,
+}
+
+pub fn new_sample_tap() -> Arc<SampleTap> {
+	Arc::new(SampleTap {
+		samples: (0..TAP_CAPACITY).map(|_| AtomicU32::new(0)).collect(),
+		written: AtomicUsize::new(0),
+		channels: AtomicU16::new(2),
+		sample_rate: AtomicU32::new(44100),
+	})
+}
+
+impl SampleTap {
+	fn push(&self, sample: f32) {
+		let position = self.written.load(Ordering::Relaxed);
+		self.samples[position % TAP_CAPACITY].store(sample.to_bits(), Ordering::Relaxed);
+		self.written.store(position.wrapping_add(1), Ordering::Release);
+	}
+
+	pub fn written(&self) -> usize {
+		self.written.load(Ordering::Acquire)
+	}
+
+	pub fn channels(&self) -> u16 {
+		self.channels.load(Ordering::Relaxed).max(1)
+	}
+
+	pub fn sample_rate(&self) -> u32 {
+		self.sample_rate.load(Ordering::Relaxed).max(1)
+	}
+
+	/* The newest samples, oldest first. Interleaved like the song is */
+	pub fn latest(&self, count: usize) -> Vec<f32> {
+		let count = count.min(TAP_CAPACITY / 2);
+		let end = self.written();
+		let count = count.min(end);
+		let start = end - count;
+		(start..end).map(|position| f32::from_bits(self.samples[position % TAP_CAPACITY].load(Ordering::Relaxed))).collect()
+	}
+}
+
+/**
+ * Passes a source through unchanged, copying every sample into the tap on the way.
+ */
+pub struct TapSource<S> {
+	inner: S,
+	tap: Arc<SampleTap>,
+}
+
+pub fn tap_source<S: Source<Item = i16>>(inner: S, tap: Arc<SampleTap>) -> TapSource<S> {
+	tap.channels.store(inner.channels(), Ordering::Relaxed);
+	tap.sample_rate.store(inner.sample_rate(), Ordering::Relaxed);
+	TapSource {inner, tap}
+}
+
+impl<S: Source<Item = i16>> Iterator for TapSource<S> {
+	type Item = i16;
+
+	fn next(&mut self) -> Option<i16> {
+		let sample = self.inner.next()?;
+		self.tap.push(sample as f32 / i16::MAX as f32);
+		Some(sample)
+	}
+
+	fn size_hint(&self) -> (usize, Option<usize>) {
+		self.inner.size_hint()
+	}
+}
+
+impl<S: Source<Item = i16>> Source for TapSource<S> {
+	fn current_frame_len(&self) -> Option<usize> { self.inner.current_frame_len() }
+	fn channels(&self) -> u16 { self.inner.channels() }
+	fn sample_rate(&self) -> u32 { self.inner.sample_rate() }
+	fn total_duration(&self) -> Option<Duration> { self.inner.total_duration() }
+	fn try_seek(&mut self, pos: Duration) -> Result<(), rodio::source::SeekError> { self.inner.try_seek(pos) }
+}
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+pub struct VisualiserSettings {
+	/* Added to every level before it is drawn */
+	pub sensitivity_db: f32,
+	pub band_count: usize,
+	/* Time for a full bar to fall to nothing once the sound stops */
+	pub decay_secs: f32,
+	pub oscilloscope: bool,
+}
+
+pub fn default_visualiser_settings() -> VisualiserSettings {
+	VisualiserSettings {
+		sensitivity_db: 0.0,
+		band_count: 32,
+		decay_secs: 0.5,
+		oscilloscope: true,
+	}
+}
+
+/**
+ * What gets drawn. Bands and levels go from 0.0 (the floor) to 1.0 (full scale), the scope from -1.0 to 1.0.
+ */
+pub struct Visualiser {
+	pub bands: Vec<f32>,
+	/* Left and right RMS level */
+	pub levels: [f32; 2],
+	pub scope: Vec<f32>,
+	last_written: usize,
+}
+
+pub fn new_visualiser() -> Visualiser {
+	Visualiser {bands: Vec::new(), levels: [0.0; 2], scope: Vec::new(), last_written: 0}
+}
+
+fn db_to_display(db: f32, sensitivity_db: f32) -> f32 {
+	((db + sensitivity_db - FLOOR_DB) / -FLOOR_DB).clamp(0.0, 1.0)
+}
+
+fn amplitude_to_db(amplitude: f32) -> f32 {
+	if amplitude <= 0.0 {FLOOR_DB * 2.0} else {20.0 * amplitude.log10()}
+}
+
+/**
+ * In-place radix-2 FFT. The length has to be a power of two.
+ */
+fn fft(real: &mut [f32], imaginary: &mut [f32]) {
+	let n = real.len();
+	let mut j = 0;
+	for i in 1..n {
+		let mut bit = n >> 1;
+		while j & bit != 0 {
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+		if i < j {
+			real.swap(i, j);
+			imaginary.swap(i, j);
+		}
+	}
+	let mut length = 2;
+	while length <= n {
+		let angle = -2.0 * std::f32::consts::PI / length as f32;
+		for start in (0..n).step_by(length) {
+			for k in 0..length / 2 {
+				let (sin, cos) = (angle * k as f32).sin_cos();
+				let (even, odd) = (start + k, start + k + length / 2);
+				let odd_real = real[odd] * cos - imaginary[odd] * sin;
+				let odd_imaginary = real[odd] * sin + imaginary[odd] * cos;
+				real[odd] = real[even] - odd_real;
+				imaginary[odd] = imaginary[even] - odd_imaginary;
+				real[even] += odd_real;
+				imaginary[even] += odd_imaginary;
+			}
+		}
+		length <<= 1;
+	}
+}
+
+/**
+ * Band edges in Hz, spaced logarithmically since that's how pitch is heard. Has band_count + 1 entries.
+ */
+pub fn band_edges(band_count: usize, sample_rate: u32) -> Vec<f32> {
+	let highest = HIGHEST_BAND_HZ.min(sample_rate as f32 / 2.0);
+	let ratio = highest / LOWEST_BAND_HZ;
+	(0..=band_count).map(|i| LOWEST_BAND_HZ * ratio.powf(i as f32 / band_count as f32)).collect()
+}
+
+/**
+ * Band levels in dB (full scale) of the mono samples. Uses the loudest frequency bin in each band.
+ */
+pub fn spectrum_db(mono: &[f32], sample_rate: u32, band_count: usize) -> Vec<f32> {
+	let mut real = vec![0.0f32; FFT_SIZE];
+	let mut imaginary = vec![0.0f32; FFT_SIZE];
+	/* Newest samples at the end, zero padded at the start if there aren't enough */
+	let offset = FFT_SIZE - mono.len().min(FFT_SIZE);
+	let mut window_sum = 0.0;
+	for (i, value) in real.iter_mut().enumerate() {
+		/* Hann window, so the edges of the buffer don't smear across the spectrum */
+		let window = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (FFT_SIZE - 1) as f32).cos();
+		window_sum += window;
+		if i >= offset {
+			*value = mono[mono.len() - (FFT_SIZE - i)] * window;
+		}
+	}
+	fft(&mut real, &mut imaginary);
+
+	let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+	let edges = band_edges(band_count, sample_rate);
+	let mut bands = Vec::<f32>::with_capacity(band_count);
+	for band in 0..band_count {
+		let first_bin = ((edges[band] / bin_hz).floor() as usize).max(1);
+		let last_bin = ((edges[band + 1] / bin_hz).ceil() as usize).clamp(first_bin + 1, FFT_SIZE / 2);
+		let mut loudest = 0.0f32;
+		for bin in first_bin..last_bin {
+			/* Scaled so a full scale sine reads as 0 dB */
+			let magnitude = (real[bin] * real[bin] + imaginary[bin] * imaginary[bin]).sqrt() * 2.0 / window_sum;
+			loudest = loudest.max(magnitude);
+		}
+		bands.push(amplitude_to_db(loudest));
+	}
+	bands
+}
+
+fn fall_towards(displayed: f32, target: f32, fall: f32) -> f32 {
+	target.max(displayed - fall)
+}
+
+/**
+ * Reads the newest samples from the tap. elapsed_secs is the time since the last update, used for the decay.
+ * When nothing new was played (paused, stopped) everything falls towards zero.
+ */
+pub fn update_visualiser(visualiser: &mut Visualiser, tap: &SampleTap, settings: &VisualiserSettings, elapsed_secs: f32) {
+	let band_count = settings.band_count.clamp(MIN_BAND_COUNT, MAX_BAND_COUNT);
+	if visualiser.bands.len() != band_count {
+		visualiser.bands = vec![0.0; band_count];
+	}
+	let fall = if settings.decay_secs <= 0.0 {1.0} else {elapsed_secs / settings.decay_secs};
+
+	let written = tap.written();
+	let fresh = written != visualiser.last_written;
+	visualiser.last_written = written;
+	let channels = tap.channels() as usize;
+	let sample_rate = tap.sample_rate();
+	let samples = if fresh {tap.latest(FFT_SIZE * channels)} else {Vec::new()};
+	/* Don't start in the middle of a frame */
+	let samples = &samples[samples.len() % channels..];
+
+	let mut mono = Vec::<f32>::with_capacity(samples.len() / channels);
+	let mut sums_of_squares = [0.0f32; 2];
+	for frame in samples.chunks_exact(channels) {
+		mono.push(frame.iter().sum::<f32>() / channels as f32);
+		let left = frame[0];
+		let right = if channels > 1 {frame[1]} else {frame[0]};
+		sums_of_squares[0] += left * left;
+		sums_of_squares[1] += right * right;
+	}
+
+	let frame_count = mono.len().max(1) as f32;
+	for (level, sum_of_squares) in visualiser.levels.iter_mut().zip(sums_of_squares) {
+		let target = if mono.is_empty() {0.0} else {db_to_display(amplitude_to_db((sum_of_squares / frame_count).sqrt()), settings.sensitivity_db)};
+		*level = fall_towards(*level, target, fall);
+	}
+
+	let spectrum = if mono.is_empty() {vec![FLOOR_DB * 2.0; band_count]} else {spectrum_db(&mono, sample_rate, band_count)};
+	for (band, db) in visualiser.bands.iter_mut().zip(spectrum) {
+		*band = fall_towards(*band, db_to_display(db, settings.sensitivity_db), fall);
+	}
+
+	if settings.oscilloscope {
+		let scope_len = (sample_rate as usize * SCOPE_MS / 1000).min(mono.len());
+		visualiser.scope = mono[mono.len() - scope_len..].to_vec();
+	} else {
+		visualiser.scope.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sine(frequency: f32, sample_rate: u32, count: usize) -> Vec<f32> {
+		(0..count).map(|i| (2.0 * std::f32::consts::PI * frequency * i as f32 / sample_rate as f32).sin()).collect()
+	}
+
+	#[test]
+	fn tap_keeps_the_newest_samples() {
+		let tap = new_sample_tap();
+		assert!(tap.latest(10).is_empty());
+		for i in 0..TAP_CAPACITY + 5 {
+			tap.push(i as f32);
+		}
+		assert_eq!(tap.written(), TAP_CAPACITY + 5);
+		let expected: Vec<f32> = (TAP_CAPACITY + 2..TAP_CAPACITY + 5).map(|i| i as f32).collect();
+		assert_eq!(tap.latest(3), expected);
+		assert_eq!(tap.latest(TAP_CAPACITY).len(), TAP_CAPACITY / 2);
+	}
+
+	#[test]
+	fn tap_source_passes_samples_through() {
+		let tap = new_sample_tap();
+		let source = rodio::buffer::SamplesBuffer::new(1, 8000, vec![0i16, i16::MAX, -i16::MAX]);
+		let played: Vec<i16> = tap_source(source, tap.clone()).collect();
+		assert_eq!(played, vec![0, i16::MAX, -i16::MAX]);
+		assert_eq!(tap.latest(3), vec![0.0, 1.0, -1.0]);
+		assert_eq!((tap.channels(), tap.sample_rate()), (1, 8000));
+	}
+
+	#[test]
+	fn sine_lands_in_its_band() {
+		let sample_rate = 44100;
+		let bands = spectrum_db(&sine(1000.0, sample_rate, FFT_SIZE), sample_rate, 32);
+		let edges = band_edges(32, sample_rate);
+		let expected_band = edges.windows(2).position(|edge| edge[0] <= 1000.0 && 1000.0 < edge[1]).unwrap();
+		let loudest_band = bands.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1)).unwrap().0;
+		assert_eq!(loudest_band, expected_band);
+		/* A full scale sine is close to 0 dB, far away bands are way below it */
+		assert!(bands[expected_band] > -3.0 && bands[expected_band] < 1.0);
+		assert!(bands[0] < -40.0);
+	}
+
+	#[test]
+	fn levels_rise_and_decay() {
+		let tap = new_sample_tap();
+		let mut settings = default_visualiser_settings();
+		settings.oscilloscope = false;
+		let mut visualiser = new_visualiser();
+
+		for sample in sine(440.0, 44100, 4096) {
+			tap.push(sample);
+			tap.push(0.0);
+		}
+		update_visualiser(&mut visualiser, &tap, &settings, 0.1);
+		assert_eq!(visualiser.bands.len(), 32);
+		/* Full scale sine RMS is -3 dB, so the left channel is near the top and the silent right one is empty */
+		assert!(visualiser.levels[0] > 0.9);
+		assert_eq!(visualiser.levels[1], 0.0);
+		assert!(visualiser.scope.is_empty());
+
+		/* Nothing new was played, so the level falls by a fifth of the scale in a fifth of the decay time... */
+		let before = visualiser.levels[0];
+		update_visualiser(&mut visualiser, &tap, &settings, settings.decay_secs / 5.0);
+		assert!((before - visualiser.levels[0] - 0.2).abs() < 0.001);
+		/* ...and is gone after the full decay time */
+		update_visualiser(&mut visualiser, &tap, &settings, settings.decay_secs);
+		assert_eq!(visualiser.levels[0], 0.0);
+		assert!(visualiser.bands.iter().all(|band| *band == 0.0));
+	}
+}
@@ -0,0 +1,576 @@
+/*
+ * Pinetree folder watching. Keeps the song index and the folders a front-end has open up to date when songs get
+ * added, removed or renamed outside of Pinetree, so nobody has to press refresh.
+ *
+ * On Linux this uses inotify, with a watch on every folder in the library roots and on every open folder.
+ * Everywhere else, and when inotify can't be used (usually because the limit on watches was hit), the folders get
+ * listed again every few seconds and compared with the listing before.
+ *
+ * Front-ends say what they have open with watch_folders and pick up what changed with take_changes. The index is
+ * updated by the watcher itself.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::files;
+use crate::index;
+use crate::roots::{self, LibraryRoot};
+
+/* How often folders get listed again when they can't be watched */
+pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub enum EventKind {
+	/* Also what a rename looks like where the file ends up */
+	Created,
+	/* Also what a rename looks like where the file was */
+	Removed,
+	/* A file was written to */
+	Modified,
+}
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct FolderEvent {
+	pub kind: EventKind,
+	pub path: String,
+	pub is_dir: bool,
+}
+
+#[derive(Default)]
+pub struct FolderChanges {
+	/* Folders whose contents changed, and folders that are gone */
+	pub folders: HashSet<String>,
+	/* Songs that were deleted or moved away */
+	pub removed_songs: Vec<String>,
+}
+
+struct WatchList {
+	roots: Vec<LibraryRoot>,
+	/* Folders open in the front-end, watched without their subfolders */
+	folders: Vec<String>,
+	changed: bool,
+}
+
+pub struct FolderWatcher {
+	watch_list: Mutex<WatchList>,
+	changes: Mutex<FolderChanges>,
+	/* Set when inotify couldn't be used and the folders are listed every POLL_INTERVAL instead */
+	pub polling: AtomicBool,
+}
+
+fn is_song(path: &str) -> bool {
+	path.ends_with(".mp3")
+}
+
+fn parent_folder(path: &str) -> String {
+	std::path::Path::new(path).parent().and_then(|folder| folder.to_str()).unwrap_or("").to_string()
+}
+
+/**
+ * Starts watching the library roots. The index gets updated as songs come and go.
+ */
+pub fn start_watcher(song_index: Arc<Mutex<index::SongIndex>>, library_roots: Vec<LibraryRoot>) -> Arc<FolderWatcher> {
+	let watcher = Arc::new(FolderWatcher {
+		watch_list: Mutex::new(WatchList {roots: library_roots, folders: Vec::new(), changed: true}),
+		changes: Mutex::new(FolderChanges::default()),
+		polling: AtomicBool::new(false),
+	});
+	let thread_watcher = Arc::clone(&watcher);
+	thread::spawn(move || {
+		#[cfg(target_os = "linux")]
+		if let Some(mut inotify) = inotify::init() {
+			inotify::watch_loop(&thread_watcher, &song_index, &mut inotify);
+		}
+		thread_watcher.polling.store(true, Ordering::Relaxed);
+		/* Polling starts from scratch with whatever is watched now */
+		thread_watcher.watch_list.lock().unwrap().changed = true;
+		poll_loop(&thread_watcher, &song_index);
+	});
+	watcher
+}
+
+/**
+ * Sets what gets watched: the library roots with all their subfolders and the folders the front-end has open.
+ * Cheap to call every frame, nothing happens unless they changed.
+ */
+pub fn watch_folders(watcher: &FolderWatcher, library_roots: Vec<LibraryRoot>, mut folders: Vec<String>) {
+	folders.sort();
+	let mut watch_list = watcher.watch_list.lock().unwrap();
+	if watch_list.roots != library_roots || watch_list.folders != folders {
+		*watch_list = WatchList {roots: library_roots, folders, changed: true};
+	}
+}
+
+pub fn take_changes(watcher: &FolderWatcher) -> FolderChanges {
+	std::mem::take(&mut *watcher.changes.lock().unwrap())
+}
+
+/* The roots and open folders if they changed since last time */
+fn take_watch_list(watcher: &FolderWatcher) -> Option<(Vec<LibraryRoot>, Vec<String>)> {
+	let mut watch_list = watcher.watch_list.lock().unwrap();
+	if !watch_list.changed {
+		return None;
+	}
+	watch_list.changed = false;
+	Some((watch_list.roots.clone(), watch_list.folders.clone()))
+}
+
+/**
+ * Every folder to watch, with whether its subfolders are watched too. Open folders inside a root are left out.
+ */
+fn watched_folders(library_roots: &[LibraryRoot], folders: &[String]) -> Vec<(String, bool)> {
+	let mut watched: Vec<(String, bool)> = library_roots.iter().map(|root| (root.path.clone(), true)).collect();
+	for folder in folders {
+		if !library_roots.iter().any(|root| roots::is_under(folder, &root.path)) {
+			watched.push((folder.clone(), false));
+		}
+	}
+	watched
+}
+
+/**
+ * Updates the index for what happened and tells the front-end which folders changed.
+ */
+fn handle_events(watcher: &FolderWatcher, song_index: &Mutex<index::SongIndex>, library_roots: &[LibraryRoot], events: &[FolderEvent]) {
+	let in_library = |song: &str| roots::song_allowed(library_roots, song);
+	let mut removed = Vec::<String>::new();
+	let mut to_read = Vec::<String>::new();
+	let mut folders = HashSet::<String>::new();
+	for event in events {
+		if event.is_dir || is_song(&event.path) {
+			folders.insert(parent_folder(&event.path));
+		}
+		match (event.kind, event.is_dir) {
+			(EventKind::Removed, true) => {
+				folders.insert(event.path.clone());
+				/* An unmounted root looks like it was removed, its songs stay until it's back (like in a scan) */
+				if !library_roots.iter().any(|root| files::trim_slash_if_exists(&root.path) == files::trim_slash_if_exists(&event.path)) {
+					removed.extend(index::songs_under(&song_index.lock().unwrap(), &event.path));
+				}
+			},
+			(EventKind::Removed, false) if is_song(&event.path) => {
+				removed.push(event.path.clone());
+			},
+			(EventKind::Created, true) => {
+				/* Moved in with everything in it, or filled before the folder was watched */
+				let mut dir_map = HashMap::<String, files::Directory>::new();
+				files::init_directory_at_filepath_recursive(&event.path, &mut dir_map);
+				to_read.extend(dir_map.into_values().flat_map(|directory| directory.songs).filter(|song| in_library(song)));
+			},
+			(EventKind::Created | EventKind::Modified, false) if is_song(&event.path) && in_library(&event.path) => {
+				to_read.push(event.path.clone());
+			},
+			_ => {},
+		}
+	}
+	/* A song removed and created again (e.g. replaced by a tag editor) is still there */
+	removed.retain(|song| !to_read.contains(song));
+	to_read.sort();
+	to_read.dedup();
+	if !removed.is_empty() || !to_read.is_empty() {
+		index::update_songs(song_index, &removed, &to_read);
+	}
+	let mut changes = watcher.changes.lock().unwrap();
+	changes.folders.extend(folders);
+	changes.removed_songs.extend(removed);
+}
+
+/* When the watcher lost track, everything gets looked at again */
+fn rescan_everything(watcher: &FolderWatcher, song_index: &Mutex<index::SongIndex>, library_roots: &[LibraryRoot], folders: &[String]) {
+	index::scan_roots(song_index, library_roots);
+	let mut changes = watcher.changes.lock().unwrap();
+	changes.folders.extend(folders.iter().cloned());
+	changes.folders.extend(library_roots.iter().map(|root| root.path.clone()));
+}
+
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct FolderListing {
+	subfolders: Vec<String>,
+	/* Song path with its size and modification time */
+	songs: HashMap<String, (u64, Option<SystemTime>)>,
+}
+
+pub type Snapshot = HashMap<String, FolderListing>;
+
+fn list_folder(folder: &str) -> Option<FolderListing> {
+	let mut listing = FolderListing {subfolders: Vec::new(), songs: HashMap::new()};
+	for entry in std::fs::read_dir(folder).ok()?.flatten() {
+		let Ok(name) = entry.file_name().into_string() else {continue;};
+		let path = files::build_full_filepath(folder, &name);
+		if let Ok(file_type) = entry.file_type() && file_type.is_dir() {
+			listing.subfolders.push(path);
+		} else if is_song(&name) && let Ok(metadata) = entry.metadata() {
+			listing.songs.insert(path, (metadata.len(), metadata.modified().ok()));
+		}
+	}
+	Some(listing)
+}
+
+fn take_snapshot(watched: &[(String, bool)]) -> Snapshot {
+	let mut snapshot = Snapshot::new();
+	let mut to_list: Vec<(String, bool)> = watched.to_vec();
+	while let Some((folder, recursive)) = to_list.pop() {
+		if snapshot.contains_key(&folder) {
+			continue;
+		}
+		if let Some(listing) = list_folder(&folder) {
+			if recursive {
+				to_list.extend(listing.subfolders.iter().map(|subfolder| (subfolder.clone(), true)));
+			}
+			snapshot.insert(folder, listing);
+		}
+	}
+	snapshot
+}
+
+/**
+ * What happened between two listings of the same folders.
+ */
+pub fn diff_snapshots(before: &Snapshot, after: &Snapshot) -> Vec<FolderEvent> {
+	let event = |kind, path: &String, is_dir| FolderEvent {kind, path: path.clone(), is_dir};
+	let mut events = Vec::<FolderEvent>::new();
+	for (folder, old) in before {
+		let Some(new) = after.get(folder) else {
+			/* Only the topmost folder that disappeared, its parent's listing has the rest */
+			if !before.contains_key(&parent_folder(folder)) {
+				events.push(event(EventKind::Removed, folder, true));
+			}
+			continue;
+		};
+		events.extend(old.subfolders.iter().filter(|subfolder| !new.subfolders.contains(subfolder)).map(|subfolder| event(EventKind::Removed, subfolder, true)));
+		events.extend(new.subfolders.iter().filter(|subfolder| !old.subfolders.contains(subfolder)).map(|subfolder| event(EventKind::Created, subfolder, true)));
+		for (song, stats) in &old.songs {
+			match new.songs.get(song) {
+				None => events.push(event(EventKind::Removed, song, false)),
+				Some(new_stats) if new_stats != stats => events.push(event(EventKind::Modified, song, false)),
+				_ => {},
+			}
+		}
+		events.extend(new.songs.keys().filter(|song| !old.songs.contains_key(*song)).map(|song| event(EventKind::Created, song, false)));
+	}
+	events
+}
+
+fn poll_loop(watcher: &FolderWatcher, song_index: &Mutex<index::SongIndex>) {
+	let mut library_roots = Vec::<LibraryRoot>::new();
+	let mut watched = Vec::<(String, bool)>::new();
+	let mut snapshot = Snapshot::new();
+	loop {
+		if let Some((new_roots, folders)) = take_watch_list(watcher) {
+			watched = watched_folders(&new_roots, &folders);
+			library_roots = new_roots;
+			snapshot = take_snapshot(&watched);
+		}
+		thread::sleep(POLL_INTERVAL);
+		let new_snapshot = take_snapshot(&watched);
+		let events = diff_snapshots(&snapshot, &new_snapshot);
+		if !events.is_empty() {
+			handle_events(watcher, song_index, &library_roots, &events);
+		}
+		snapshot = new_snapshot;
+	}
+}
+
+#[cfg(target_os = "linux")]
+mod inotify {
+	use super::*;
+
+	const WATCH_MASK: u32 = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO | libc::IN_CLOSE_WRITE | libc::IN_ONLYDIR;
+	/* Size of struct inotify_event without the name */
+	const EVENT_HEADER_LEN: usize = 16;
+	/* How long to wait for events before looking at the watch list again */
+	const WAIT_MS: i32 = 500;
+
+	pub struct Inotify {
+		fd: i32,
+		paths: HashMap<i32, String>,
+		watches: HashMap<String, i32>,
+	}
+
+	impl Drop for Inotify {
+		fn drop(&mut self) {
+			unsafe {
+				libc::close(self.fd);
+			}
+		}
+	}
+
+	pub fn init() -> Option<Inotify> {
+		let fd = unsafe {libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC)};
+		if fd < 0 {
+			println!("Error in inotify::init: {}", std::io::Error::last_os_error());
+			return None;
+		}
+		Some(Inotify {fd, paths: HashMap::new(), watches: HashMap::new()})
+	}
+
+	/* False when the folder can't be watched because there are no watches left */
+	fn add_watch(inotify: &mut Inotify, folder: &str) -> bool {
+		if inotify.watches.contains_key(folder) {
+			return true;
+		}
+		let Ok(path) = std::ffi::CString::new(folder) else {return true;};
+		let wd = unsafe {libc::inotify_add_watch(inotify.fd, path.as_ptr(), WATCH_MASK)};
+		if wd < 0 {
+			let err = std::io::Error::last_os_error();
+			if err.raw_os_error() == Some(libc::ENOSPC) {
+				println!("Error in inotify::add_watch: out of watches, listing folders every {} seconds instead", POLL_INTERVAL.as_secs());
+				return false;
+			}
+			/* Gone already or not readable, nothing to watch */
+			return true;
+		}
+		inotify.paths.insert(wd, folder.to_string());
+		inotify.watches.insert(folder.to_string(), wd);
+		true
+	}
+
+	fn add_watches(inotify: &mut Inotify, folder: &str, recursive: bool) -> bool {
+		if !recursive {
+			return add_watch(inotify, folder);
+		}
+		let mut to_watch = vec![folder.to_string()];
+		while let Some(folder) = to_watch.pop() {
+			if !add_watch(inotify, &folder) {
+				return false;
+			}
+			if let Ok(entries) = std::fs::read_dir(&folder) {
+				for entry in entries.flatten() {
+					if let Ok(file_type) = entry.file_type() && file_type.is_dir() && let Ok(name) = entry.file_name().into_string() {
+						to_watch.push(files::build_full_filepath(&folder, &name));
+					}
+				}
+			}
+		}
+		true
+	}
+
+	fn remove_watches_under(inotify: &mut Inotify, folder: &str) {
+		let gone: Vec<String> = inotify.watches.keys().filter(|watched| roots::is_under(watched, folder)).cloned().collect();
+		for watched in gone {
+			if let Some(wd) = inotify.watches.remove(&watched) {
+				inotify.paths.remove(&wd);
+				unsafe {
+					libc::inotify_rm_watch(inotify.fd, wd);
+				}
+			}
+		}
+	}
+
+	/* False when some folder couldn't be watched */
+	fn update_watches(inotify: &mut Inotify, watched: &[(String, bool)]) -> bool {
+		let old: Vec<String> = inotify.watches.keys().cloned().collect();
+		for folder in old {
+			let still_watched = watched.iter().any(|(watched, recursive)| if *recursive {roots::is_under(&folder, watched)} else {folder == *watched});
+			if !still_watched && let Some(wd) = inotify.watches.remove(&folder) {
+				inotify.paths.remove(&wd);
+				unsafe {
+					libc::inotify_rm_watch(inotify.fd, wd);
+				}
+			}
+		}
+		watched.iter().all(|(folder, recursive)| add_watches(inotify, folder, *recursive))
+	}
+
+	/*
+	 * Splits what read() returned into (watch descriptor, mask, name) for every event.
+	 */
+	pub fn parse_events(buffer: &[u8]) -> Vec<(i32, u32, String)> {
+		let mut events = Vec::new();
+		let mut position = 0;
+		while position + EVENT_HEADER_LEN <= buffer.len() {
+			let field = |offset: usize| {
+				let bytes = &buffer[position + offset..position + offset + 4];
+				[bytes[0], bytes[1], bytes[2], bytes[3]]
+			};
+			let wd = i32::from_ne_bytes(field(0));
+			let mask = u32::from_ne_bytes(field(4));
+			let name_len = u32::from_ne_bytes(field(12)) as usize;
+			let Some(name) = buffer.get(position + EVENT_HEADER_LEN..position + EVENT_HEADER_LEN + name_len) else {break;};
+			/* The name is padded with zeroes */
+			let name = name.split(|byte| *byte == 0).next().unwrap_or(&[]);
+			events.push((wd, mask, String::from_utf8_lossy(name).to_string()));
+			position += EVENT_HEADER_LEN + name_len;
+		}
+		events
+	}
+
+	/* None when the kernel's event queue overflowed and events were lost */
+	fn read_events(inotify: &mut Inotify) -> Option<Vec<FolderEvent>> {
+		let mut poll_fd = libc::pollfd {fd: inotify.fd, events: libc::POLLIN, revents: 0};
+		if unsafe {libc::poll(&mut poll_fd, 1, WAIT_MS)} <= 0 {
+			return Some(Vec::new());
+		}
+		let mut buffer = vec![0u8; 64 * 1024];
+		let mut events = Vec::new();
+		loop {
+			let read = unsafe {libc::read(inotify.fd, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len())};
+			if read <= 0 {
+				break;
+			}
+			for (wd, mask, name) in parse_events(&buffer[..read as usize]) {
+				if mask & libc::IN_Q_OVERFLOW != 0 {
+					return None;
+				}
+				if mask & libc::IN_IGNORED != 0 {
+					if let Some(folder) = inotify.paths.remove(&wd) {
+						inotify.watches.remove(&folder);
+					}
+					continue;
+				}
+				let Some(folder) = inotify.paths.get(&wd) else {continue;};
+				let kind = if mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+					EventKind::Created
+				} else if mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+					EventKind::Removed
+				} else {
+					EventKind::Modified
+				};
+				events.push(FolderEvent {kind, path: files::build_full_filepath(folder, &name), is_dir: mask & libc::IN_ISDIR != 0});
+			}
+		}
+		Some(events)
+	}
+
+	/*
+	 * Watches until inotify runs out of watches, then returns so folders get listed instead.
+	 */
+	pub fn watch_loop(watcher: &FolderWatcher, song_index: &Mutex<index::SongIndex>, inotify: &mut Inotify) {
+		let mut library_roots = Vec::<LibraryRoot>::new();
+		let mut folders = Vec::<String>::new();
+		loop {
+			if let Some((new_roots, new_folders)) = take_watch_list(watcher) {
+				library_roots = new_roots;
+				folders = new_folders;
+				if !update_watches(inotify, &watched_folders(&library_roots, &folders)) {
+					return;
+				}
+			}
+			let Some(events) = read_events(inotify) else {
+				println!("Error in inotify::watch_loop: events were lost, rescanning");
+				rescan_everything(watcher, song_index, &library_roots, &folders);
+				continue;
+			};
+			for event in events.iter().filter(|event| event.is_dir) {
+				if event.kind == EventKind::Removed {
+					remove_watches_under(inotify, &event.path);
+				} else if event.kind == EventKind::Created
+				&& library_roots.iter().any(|root| roots::is_under(&event.path, &root.path))
+				&& !add_watches(inotify, &event.path, true) {
+					handle_events(watcher, song_index, &library_roots, &events);
+					return;
+				}
+			}
+			if !events.is_empty() {
+				handle_events(watcher, song_index, &library_roots, &events);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::temp_file_path;
+
+	fn listing(subfolders: &[&str], songs: &[(&str, u64)]) -> FolderListing {
+		FolderListing {
+			subfolders: subfolders.iter().map(|subfolder| subfolder.to_string()).collect(),
+			songs: songs.iter().map(|(song, len)| (song.to_string(), (*len, None))).collect(),
+		}
+	}
+
+	#[test]
+	fn diffs_folder_listings() {
+		let before: Snapshot = [
+			("/m".to_string(), listing(&["/m/A", "/m/B"], &[("/m/a.mp3", 1), ("/m/b.mp3", 1)])),
+			("/m/A".to_string(), listing(&[], &[("/m/A/x.mp3", 1)])),
+			("/m/B".to_string(), listing(&["/m/B/C"], &[])),
+			("/m/B/C".to_string(), listing(&[], &[("/m/B/C/y.mp3", 1)])),
+		].into_iter().collect();
+		let after: Snapshot = [
+			("/m".to_string(), listing(&["/m/A", "/m/D"], &[("/m/a.mp3", 2), ("/m/c.mp3", 1)])),
+			("/m/A".to_string(), listing(&[], &[("/m/A/x.mp3", 1)])),
+			("/m/D".to_string(), listing(&[], &[])),
+		].into_iter().collect();
+		let mut events = diff_snapshots(&before, &after);
+		events.sort_by(|a, b| a.path.cmp(&b.path));
+		let expected = vec![
+			FolderEvent {kind: EventKind::Removed, path: "/m/B".to_string(), is_dir: true},
+			FolderEvent {kind: EventKind::Created, path: "/m/D".to_string(), is_dir: true},
+			FolderEvent {kind: EventKind::Modified, path: "/m/a.mp3".to_string(), is_dir: false},
+			FolderEvent {kind: EventKind::Removed, path: "/m/b.mp3".to_string(), is_dir: false},
+			FolderEvent {kind: EventKind::Created, path: "/m/c.mp3".to_string(), is_dir: false},
+		];
+		assert_eq!(events, expected);
+		/* A watched folder that is gone entirely */
+		let events = diff_snapshots(&before, &Snapshot::new());
+		assert_eq!(events, vec![FolderEvent {kind: EventKind::Removed, path: "/m".to_string(), is_dir: true}]);
+	}
+
+	#[test]
+	fn keeps_the_index_up_to_date() {
+		let folder = temp_file_path("watched");
+		let album = files::build_full_filepath(&folder, "Album");
+		std::fs::create_dir_all(&album).unwrap();
+		let song = |name: &str| files::build_full_filepath(&album, name);
+		for name in ["a.mp3", "b.mp3"] {
+			std::fs::write(song(name), [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+		}
+		let mut root = roots::new_root(&folder);
+		root.excludes = vec!["Skip".to_string()];
+		let library_roots = vec![root];
+		let song_index = index::new_song_index();
+		index::scan_roots(&song_index, &library_roots);
+		let watcher = FolderWatcher {
+			watch_list: Mutex::new(WatchList {roots: library_roots.clone(), folders: Vec::new(), changed: false}),
+			changes: Mutex::new(FolderChanges::default()),
+			polling: AtomicBool::new(true),
+		};
+
+		let before = take_snapshot(&watched_folders(&library_roots, &[]));
+		std::fs::remove_file(song("a.mp3")).unwrap();
+		std::fs::write(song("c.mp3"), [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+		let skipped = files::build_full_filepath(&folder, "Skip");
+		std::fs::create_dir_all(&skipped).unwrap();
+		std::fs::write(files::build_full_filepath(&skipped, "d.mp3"), [0xFF, 0xFB, 0x90, 0x00]).unwrap();
+		let after = take_snapshot(&watched_folders(&library_roots, &[]));
+		handle_events(&watcher, &song_index, &library_roots, &diff_snapshots(&before, &after));
+
+		let indexed: Vec<String> = song_index.lock().unwrap().songs.keys().cloned().collect();
+		let changes = take_changes(&watcher);
+		std::fs::remove_file(song("b.mp3")).unwrap();
+		handle_events(&watcher, &song_index, &library_roots, &[FolderEvent {kind: EventKind::Removed, path: album.clone(), is_dir: true}]);
+		let emptied = song_index.lock().unwrap().songs.len();
+		let _ = std::fs::remove_dir_all(&folder);
+
+		assert_eq!(indexed, vec![song("b.mp3"), song("c.mp3")]);
+		assert_eq!(changes.removed_songs, vec![song("a.mp3")]);
+		assert!(changes.folders.contains(&album) && changes.folders.contains(&folder));
+		assert_eq!(emptied, 0);
+	}
+
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn parses_inotify_events() {
+		let mut buffer = Vec::new();
+		for (wd, mask, name) in [(1, libc::IN_CREATE, &b"song.mp3\0\0\0\0\0\0\0\0"[..]), (2, libc::IN_DELETE | libc::IN_ISDIR, &b"Old\0"[..])] {
+			buffer.extend_from_slice(&(wd as i32).to_ne_bytes());
+			buffer.extend_from_slice(&mask.to_ne_bytes());
+			buffer.extend_from_slice(&0u32.to_ne_bytes());
+			buffer.extend_from_slice(&(name.len() as u32).to_ne_bytes());
+			buffer.extend_from_slice(name);
+		}
+		assert_eq!(inotify::parse_events(&buffer), vec![(1, libc::IN_CREATE, "song.mp3".to_string()), (2, libc::IN_DELETE | libc::IN_ISDIR, "Old".to_string())]);
+	}
+}
@@ -0,0 +1,187 @@
+/*
+ * Pinetree waveform analyser. Decodes a song once and boils it down to a peak and RMS envelope, which the GUI
+ * draws as the seek bar. Envelopes are cached in the library, so each song only gets decoded once.
+ *
+ * Songs are analysed on a background thread. Front-ends push the songs they want onto the request channel,
+ * the most recent request is handled first so the playing song doesn't wait behind older ones.
+ */
+
+use std::sync::{Arc, Condvar, Mutex};
+
+use rodio::Source;
+
+use crate::library;
+
+/* Number of points in an envelope, independent of the song length */
+pub const WAVEFORM_POINTS: usize = 200;
+
+/* Samples are first summed into windows of this length, which get merged into WAVEFORM_POINTS at the end */
+const WINDOW_MS: u64 = 20;
+
+#[derive(Clone)]
+#[derive(PartialEq)]
+#[derive(Debug)]
+pub struct Waveform {
+	/* Both go from 0.0 to 1.0 (full scale) */
+	pub peaks: Vec<f32>,
+	pub rms: Vec<f32>,
+}
+
+#[derive(Clone)]
+#[derive(Copy)]
+#[derive(Default)]
+struct Window {
+	peak: f32,
+	sum_of_squares: f64,
+	sample_count: u64,
+}
+
+/**
+ * Builds the envelope from interleaved samples. Returns None when there are no samples at all.
+ */
+pub fn compute_waveform(samples: impl Iterator<Item = i16>, channels: u16, sample_rate: u32) -> Option<Waveform> {
+	let window_len = ((sample_rate as u64 * channels.max(1) as u64 * WINDOW_MS) / 1000).max(1);
+	let mut windows = Vec::<Window>::new();
+	let mut current = Window::default();
+	for sample in samples {
+		let value = sample as f32 / i16::MAX as f32;
+		current.peak = current.peak.max(value.abs());
+		current.sum_of_squares += (value * value) as f64;
+		current.sample_count += 1;
+		if current.sample_count == window_len {
+			windows.push(current);
+			current = Window::default();
+		}
+	}
+	if current.sample_count > 0 {
+		windows.push(current);
+	}
+	if windows.is_empty() {
+		return None;
+	}
+
+	let mut waveform = Waveform {peaks: Vec::with_capacity(WAVEFORM_POINTS), rms: Vec::with_capacity(WAVEFORM_POINTS)};
+	for point in 0..WAVEFORM_POINTS {
+		/* Short songs have fewer windows than points, those just repeat windows */
+		let start = point * windows.len() / WAVEFORM_POINTS;
+		let end = ((point + 1) * windows.len() / WAVEFORM_POINTS).max(start + 1).min(windows.len());
+		let mut merged = Window::default();
+		for window in &windows[start..end] {
+			merged.peak = merged.peak.max(window.peak);
+			merged.sum_of_squares += window.sum_of_squares;
+			merged.sample_count += window.sample_count;
+		}
+		waveform.peaks.push(merged.peak.min(1.0));
+		waveform.rms.push(((merged.sum_of_squares / merged.sample_count.max(1) as f64).sqrt() as f32).min(1.0));
+	}
+	Some(waveform)
+}
+
+pub fn analyse_file(file_path: &str) -> Option<Waveform> {
+	let file = std::fs::File::open(file_path).ok()?;
+	let decoder = rodio::Decoder::new_mp3(std::io::BufReader::new(file)).ok()?;
+	let channels = decoder.channels();
+	let sample_rate = decoder.sample_rate();
+	compute_waveform(decoder, channels, sample_rate)
+}
+
+/**
+ * Stores levels as one hex byte per point, which keeps the library file readable line by line.
+ */
+pub fn levels_to_str(levels: &[f32]) -> String {
+	let mut string = String::with_capacity(levels.len() * 2);
+	for level in levels {
+		string.push_str(&format!("{:02x}", (level.clamp(0.0, 1.0) * 255.0).round() as u8));
+	}
+	string
+}
+
+pub fn str_to_levels(string: &str) -> Option<Vec<f32>> {
+	if !string.len().is_multiple_of(2) || !string.is_ascii() {
+		return None;
+	}
+	let mut levels = Vec::<f32>::with_capacity(string.len() / 2);
+	for i in (0..string.len()).step_by(2) {
+		let byte = u8::from_str_radix(&string[i..i + 2], 16).ok()?;
+		levels.push(byte as f32 / 255.0);
+	}
+	Some(levels)
+}
+
+pub fn request_waveform(request_channel: &Arc<(Mutex<Vec<String>>, Condvar)>, song: &str) {
+	let (lock, cvar) = &**request_channel;
+	if let Ok(mut requests) = lock.lock() {
+		requests.retain(|request| request != song);
+		requests.push(song.to_string());
+		cvar.notify_one();
+	}
+}
+
+/**
+ * Runs forever, analysing requested songs that aren't in the library yet.
+ */
+pub fn analyser_loop(request_channel: Arc<(Mutex<Vec<String>>, Condvar)>, library: Arc<Mutex<library::Library>>) {
+	let (lock, cvar) = &*request_channel;
+	loop {
+		let song = {
+			let mut requests = if let Ok(requests) = lock.lock() {requests} else {return;};
+			while requests.is_empty() {
+				requests = if let Ok(requests) = cvar.wait(requests) {requests} else {return;};
+			}
+			requests.pop()
+		};
+		let song = if let Some(song) = song {song} else {continue;};
+
+		let already_analysed = library.lock().is_ok_and(|library| library.songs.get(&song).is_some_and(|record| record.waveform.is_some()));
+		if already_analysed {
+			continue;
+		}
+		/* The library isn't locked while decoding, that takes a while */
+		if let Some(waveform) = analyse_file(&song) {
+			if let Ok(mut library) = library.lock() {
+				library::get_or_create_record(&mut library, &song).waveform = Some(waveform);
+			}
+		} else {
+			println!("Error in waveform analysis: couldn't decode {}", song);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn envelope_follows_the_signal() {
+		/* One second of silence followed by one second of a full scale square wave, mono at 1000 Hz */
+		let samples = (0..2000).map(|i| if i < 1000 {0} else if i % 2 == 0 {i16::MAX} else {-i16::MAX});
+		let waveform = compute_waveform(samples, 1, 1000).unwrap();
+		assert_eq!(waveform.peaks.len(), WAVEFORM_POINTS);
+		assert_eq!(waveform.rms.len(), WAVEFORM_POINTS);
+		assert_eq!(waveform.peaks[0], 0.0);
+		assert_eq!(waveform.rms[WAVEFORM_POINTS / 4], 0.0);
+		assert!((waveform.peaks[WAVEFORM_POINTS - 1] - 1.0).abs() < 0.001);
+		assert!((waveform.rms[WAVEFORM_POINTS * 3 / 4] - 1.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn short_songs_still_fill_the_envelope() {
+		let waveform = compute_waveform([i16::MAX / 2; 10].into_iter(), 2, 44100).unwrap();
+		assert_eq!(waveform.peaks.len(), WAVEFORM_POINTS);
+		assert!(waveform.peaks.iter().all(|peak| (peak - 0.5).abs() < 0.001));
+		assert!(compute_waveform(std::iter::empty(), 2, 44100).is_none());
+	}
+
+	#[test]
+	fn levels_round_trip() {
+		let levels = vec![0.0, 1.0, 0.5, 0.25];
+		let string = levels_to_str(&levels);
+		assert_eq!(string, "00ff8040");
+		let read_back = str_to_levels(&string).unwrap();
+		for (level, read) in levels.iter().zip(read_back) {
+			assert!((level - read).abs() < 1.0 / 255.0);
+		}
+		assert!(str_to_levels("0").is_none());
+		assert!(str_to_levels("zz").is_none());
+	}
+}
@@ -0,0 +1,227 @@
+mod common;
+
+use common::*;
+use pinetree_core::audio_frontend::{ABLoop, LoopBehavior, MessageToAudio, PrevBehavior, SleepTimer};
+use pinetree_core::resume;
+
+#[test]
+fn plays_a_song() {
+	let folder = test_folder("plays_a_song");
+	let song = write_silent_mp3(&folder, "song.mp3", 10000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	let data = player.wait_for("the song to start", |data| data.song_name == song);
+	assert!(data.error_message.is_none());
+	assert!(!data.is_paused);
+	assert!(data.song_length.abs_diff(10000) < 100, "length was {}", data.song_length);
+	/* Plays get recorded off the audio thread */
+	player.wait_for("the play to be recorded", |_| player.library.lock().unwrap().songs.get(&song).is_some_and(|record| record.play_count == 1));
+}
+
+#[test]
+fn reports_songs_that_cant_be_played() {
+	let folder = test_folder("reports_errors");
+	let missing = format!("{}/missing.mp3", folder);
+	let not_mp3 = format!("{}/text.mp3", folder);
+	std::fs::write(&not_mp3, "this isn't an mp3").unwrap();
+	let player = start_player(88);
+
+	player.send(MessageToAudio::PlaySong(missing.clone()));
+	let data = player.wait_for("an error", |data| data.error_message.is_some());
+	assert!(data.error_message.unwrap().contains("missing.mp3"));
+	assert!(data.song_name.is_empty());
+
+	player.send(MessageToAudio::ClearError);
+	player.wait_for("the error to clear", |data| data.error_message.is_none());
+
+	player.send(MessageToAudio::PlaySong(not_mp3));
+	let data = player.wait_for("an error", |data| data.error_message.is_some());
+	assert!(data.error_message.unwrap().contains("Invalid mp3"));
+}
+
+#[test]
+fn pauses_and_changes_volume() {
+	let folder = test_folder("pauses");
+	let song = write_silent_mp3(&folder, "song.mp3", 10000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	player.wait_for("the song to start", |data| data.song_name == song);
+
+	player.send(MessageToAudio::TogglePause);
+	let paused = player.wait_for("pause", |data| data.is_paused);
+	std::thread::sleep(std::time::Duration::from_millis(100));
+	/* The position doesn't move while paused */
+	assert_eq!(player.data().playback_position, paused.playback_position);
+
+	player.send(MessageToAudio::UpdateVolume(0.25));
+	player.wait_for("the new volume", |data| data.volume == 0.25);
+
+	player.send(MessageToAudio::TogglePause);
+	player.wait_for("unpause", |data| !data.is_paused);
+}
+
+#[test]
+fn seeks() {
+	let folder = test_folder("seeks");
+	let song = write_silent_mp3(&folder, "song.mp3", 10000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	player.wait_for("the song to start", |data| data.song_name == song);
+
+	player.send(MessageToAudio::Seek(6000));
+	player.send(MessageToAudio::SeekStop);
+	let data = player.wait_for("the seek", |data| data.playback_position >= 6000);
+	assert!(data.playback_position < 7000, "position was {}", data.playback_position);
+
+	/* A seek that is over right away keeps playing, even close to the end */
+	player.send(MessageToAudio::SeekOnce(9500));
+	let data = player.wait_for("the seek near the end", |data| data.playback_position >= 9500 || data.song_name.is_empty());
+	assert!(!data.is_paused);
+}
+
+#[test]
+fn moves_through_the_song_collection() {
+	let folder = test_folder("collection");
+	let songs: Vec<String> = ["a.mp3", "b.mp3", "c.mp3"].iter().map(|name| write_silent_mp3(&folder, name, 10000)).collect();
+	let player = start_player(88);
+
+	player.send(MessageToAudio::UpdateEndBehavior(LoopBehavior::Next));
+	player.send(MessageToAudio::UpdatePrevBehavior(PrevBehavior::History));
+	player.send(MessageToAudio::SetSongCollection(songs.clone(), Some(0)));
+	player.wait_for("the settings", |_| true);
+
+	player.send(MessageToAudio::PlaySong(songs[0].clone()));
+	player.wait_for("the first song", |data| data.song_name == songs[0]);
+	player.send(MessageToAudio::SongEnd);
+	player.wait_for("the second song", |data| data.song_name == songs[1]);
+	player.send(MessageToAudio::SongEnd);
+	player.wait_for("the third song", |data| data.song_name == songs[2]);
+	player.send(MessageToAudio::SongEnd);
+	player.wait_for("wrapping around", |data| data.song_name == songs[0]);
+
+	player.send(MessageToAudio::PreviousSong);
+	player.wait_for("going back in history", |data| data.song_name == songs[2]);
+	player.send(MessageToAudio::PreviousSong);
+	player.wait_for("going back in history", |data| data.song_name == songs[1]);
+
+	player.send(MessageToAudio::UpdateEndBehavior(LoopBehavior::Loop));
+	player.wait_for("the settings", |_| true);
+	player.send(MessageToAudio::SongEnd);
+	let data = player.wait_for("the song to restart", |data| data.song_name == songs[1] && data.playback_position < 1000);
+	assert!(data.error_message.is_none());
+}
+
+#[test]
+fn stops_looping_a_deleted_song() {
+	let folder = test_folder("loops_deleted");
+	let song = write_silent_mp3(&folder, "song.mp3", 10000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::UpdateEndBehavior(LoopBehavior::Loop));
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	player.wait_for("the song to start", |data| data.song_name == song);
+	std::fs::remove_file(&song).unwrap();
+	player.send(MessageToAudio::SongEnd);
+	let data = player.wait_for("playback to stop", |data| data.song_name.is_empty());
+	assert!(data.error_message.is_none());
+}
+
+#[test]
+fn plays_to_the_end_and_stops() {
+	let folder = test_folder("plays_through");
+	let song = write_silent_mp3(&folder, "short.mp3", 1000);
+	/* About 10 times faster than real time */
+	let player = start_player(880);
+
+	player.send(MessageToAudio::UpdateEndBehavior(LoopBehavior::Stop));
+	player.wait_for("the settings", |_| true);
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	/* The song can be over before the first check, so the library is what shows it was played */
+	let finished = || {
+		let library = player.library.lock().unwrap();
+		library.songs.get(&song).is_some_and(|record| record.completion_count + record.skip_count == 1)
+	};
+	let data = player.wait_for("the song to end", |data| data.song_name.is_empty() && finished());
+	assert!(data.error_message.is_none());
+	assert_eq!(player.library.lock().unwrap().songs[&song].play_count, 1);
+}
+
+#[test]
+fn repeats_an_ab_loop() {
+	let folder = test_folder("ab_loop");
+	let song = write_silent_mp3(&folder, "song.mp3", 10000);
+	let other = write_silent_mp3(&folder, "other.mp3", 10000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	player.wait_for("the song to start", |data| data.song_name == song);
+	player.send(MessageToAudio::SetABLoop(Some(ABLoop {start_ms: 2000, end_ms: 2300, count_in_ms: 0, speed_step: 0.1, target_speed: 1.2})));
+	/* Every repeat speeds up by 0.1, so reaching 1.2 means it went around at least twice */
+	let data = player.wait_for("the loop to repeat", |data| data.speed >= 1.2);
+	assert!(data.ab_loop.is_some());
+	for _ in 0..10 {
+		let position = player.data().playback_position;
+		assert!((2000..2400).contains(&position), "position was {}", position);
+		std::thread::sleep(std::time::Duration::from_millis(30));
+	}
+
+	/* The loop belongs to the song it was set for */
+	player.send(MessageToAudio::PlaySong(other.clone()));
+	let data = player.wait_for("the other song to start", |data| data.song_name == other);
+	assert!(data.ab_loop.is_none());
+}
+
+#[test]
+fn resumes_files_in_audiobook_folders() {
+	let folder = test_folder("resume");
+	let book = write_silent_mp3(&folder, "book.mp3", 10000);
+	let other = write_silent_mp3(&folder, "other.mp3", 10000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::UpdateResumeSettings(resume::ResumeSettings {threshold_mins: 0, audiobook_folders: vec![folder.clone()]}));
+	player.send(MessageToAudio::PlaySong(book.clone()));
+	player.wait_for("the book to start", |data| data.song_name == book);
+	player.send(MessageToAudio::Seek(6000));
+	player.wait_for("the seek", |data| data.playback_position >= 6000);
+
+	player.send(MessageToAudio::PlaySong(other.clone()));
+	player.wait_for("the other file to start", |data| data.song_name == other);
+	let remembered = player.library.lock().unwrap().songs[&book].resume_position_ms;
+	assert!((6000..7000).contains(&remembered), "remembered {}", remembered);
+
+	player.send(MessageToAudio::PlaySong(book.clone()));
+	let data = player.wait_for("the book to resume", |data| data.song_name == book);
+	let expected = remembered - resume::RESUME_REWIND_MS;
+	assert!(data.playback_position >= expected && data.playback_position < expected + 1000, "position was {}", data.playback_position);
+}
+
+#[test]
+fn sleep_timer_fades_out_and_pauses() {
+	let folder = test_folder("sleep_timer");
+	let song = write_silent_mp3(&folder, "song.mp3", 10000);
+	let short = write_silent_mp3(&folder, "short.mp3", 2000);
+	let player = start_player(88);
+
+	player.send(MessageToAudio::PlaySong(song.clone()));
+	player.wait_for("the song to start", |data| data.song_name == song);
+	player.send(MessageToAudio::SetSleepTimer(Some(SleepTimer::AfterTime(800)), 500));
+	let data = player.wait_for("the fade", |data| data.sleep_timer.is_some_and(|timer| timer.fading));
+	assert!(!data.is_paused);
+	let data = player.wait_for("the timer to pause", |data| data.is_paused);
+	/* Paused, not stopped */
+	assert!(data.sleep_timer.is_none());
+	assert_eq!(data.song_name, song);
+	assert!(data.playback_position > 0);
+
+	player.send(MessageToAudio::UpdateEndBehavior(LoopBehavior::Loop));
+	player.send(MessageToAudio::PlaySong(short.clone()));
+	player.wait_for("the short song to start", |data| data.song_name == short && !data.is_paused);
+	player.send(MessageToAudio::SetSleepTimer(Some(SleepTimer::AfterSongs(1)), 0));
+	let data = player.wait_for("the end of the song", |data| data.is_paused);
+	assert!(data.sleep_timer.is_none());
+	assert_eq!(data.song_name, short);
+	assert!(data.playback_position > 1000, "paused at {}", data.playback_position);
+}
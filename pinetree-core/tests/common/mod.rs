@@ -0,0 +1,106 @@
+/*
+ * Shared setup for the integration tests. Runs the real audio thread on an idle sink,
+ * with a fake speaker thread pulling the samples out of it, so no sound card is needed.
+ */
+
+#![allow(dead_code)]
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use pinetree_core::audio_frontend::{self, MessageToAudio, RodioData};
+use pinetree_core::{library, scrobble, visualiser};
+
+/* MPEG-1 Layer III, 128 kbps, 44100 Hz, stereo, no CRC */
+const FRAME_HEADER: [u8; 4] = [0xFF, 0xFB, 0x90, 0x00];
+const FRAME_LEN: usize = 417;
+const SAMPLES_PER_FRAME: usize = 1152;
+
+pub struct TestPlayer {
+	pub send: Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>,
+	pub receive: Arc<(Mutex<Vec<RodioData>>, Condvar)>,
+	pub library: Arc<Mutex<library::Library>>,
+}
+
+/**
+ * A folder in the temp directory for one test, emptied if it exists already.
+ */
+pub fn test_folder(name: &str) -> String {
+	let path = std::env::temp_dir().join(format!("pinetree-it-{}-{}", std::process::id(), name));
+	let _ = std::fs::remove_dir_all(&path);
+	std::fs::create_dir_all(&path).unwrap();
+	path.to_string_lossy().to_string()
+}
+
+/**
+ * Writes an mp3 of silence that is (roughly) the given length. Every frame is a header followed by zeros,
+ * which decodes to silence since none of the frames carry any audio data.
+ */
+pub fn write_silent_mp3(folder: &str, name: &str, length_ms: usize) -> String {
+	let frame_count = (length_ms * 44100).div_ceil(SAMPLES_PER_FRAME * 1000);
+	let mut bytes = Vec::<u8>::with_capacity(frame_count * FRAME_LEN);
+	for _ in 0..frame_count {
+		bytes.extend_from_slice(&FRAME_HEADER);
+		bytes.resize(bytes.len() + FRAME_LEN - FRAME_HEADER.len(), 0);
+	}
+	let path = std::path::Path::new(folder).join(name).to_string_lossy().to_string();
+	std::fs::write(&path, bytes).unwrap();
+	path
+}
+
+/**
+ * Starts the audio thread. The speaker pulls `samples_per_ms` samples every millisecond,
+ * where 88 is about real time for a stereo 44100 Hz song.
+ */
+pub fn start_player(samples_per_ms: usize) -> TestPlayer {
+	let send = Arc::new((Mutex::new(Vec::<MessageToAudio>::new()), Condvar::new()));
+	let receive = Arc::new((Mutex::new(Vec::<RodioData>::new()), Condvar::new()));
+	let library = Arc::new(Mutex::new(library::new_library(None)));
+	let scrobbler = Arc::new(Mutex::new(scrobble::init_scrobbler(None)));
+
+	let (sink, mut speaker) = rodio::Sink::new_idle();
+	std::thread::spawn(move || {
+		loop {
+			for _ in 0..samples_per_ms {
+				speaker.next();
+			}
+			std::thread::sleep(Duration::from_millis(1));
+		}
+	});
+
+	let audio_send = Arc::clone(&send);
+	let audio_receive = Arc::clone(&receive);
+	let audio_library = Arc::clone(&library);
+	std::thread::spawn(move || {
+		audio_frontend::audio_thread_loop_with_sink(None, sink, audio_send, audio_receive, audio_library, scrobbler, visualiser::new_sample_tap());
+	});
+
+	TestPlayer {send, receive, library}
+}
+
+impl TestPlayer {
+	pub fn send(&self, message: MessageToAudio) {
+		audio_frontend::send_audio_signal(&self.send, message);
+	}
+
+	pub fn data(&self) -> RodioData {
+		audio_frontend::request_rodio_data(&self.send, &self.receive)
+	}
+
+	/**
+	 * Messages get handled last in, first out, so checking the result of one means waiting until it shows up.
+	 */
+	pub fn wait_for(&self, what: &str, condition: impl Fn(&RodioData) -> bool) -> RodioData {
+		let start = Instant::now();
+		loop {
+			let data = self.data();
+			if condition(&data) {
+				return data;
+			}
+			if start.elapsed() > Duration::from_secs(10) {
+				panic!("Timed out waiting for {} (song: {:?}, paused: {}, error: {:?})", what, data.song_name, data.is_paused, data.error_message);
+			}
+			std::thread::sleep(Duration::from_millis(5));
+		}
+	}
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use common::*;
+use pinetree_core::audio_frontend::{LoopBehavior, MessageToAudio};
+use pinetree_core::remote;
+
+/* Keeps trying until the server is listening */
+fn command(line: &str, data_dir: &str) -> String {
+	let start = Instant::now();
+	loop {
+		match remote::send_command(line, Some(data_dir)) {
+			Ok(response) => return response,
+			Err(err) if start.elapsed() > Duration::from_secs(10) => panic!("Couldn't send {}: {}", line, err),
+			Err(_) => std::thread::sleep(Duration::from_millis(20)),
+		}
+	}
+}
+
+fn wait_for_status(data_dir: &str, expected: &str) -> String {
+	let start = Instant::now();
+	loop {
+		let status = command("status --json", data_dir);
+		if status.contains(expected) {
+			return status;
+		}
+		if start.elapsed() > Duration::from_secs(10) {
+			panic!("Status never contained {}, last status was {}", expected, status);
+		}
+		std::thread::sleep(Duration::from_millis(10));
+	}
+}
+
+#[test]
+fn controls_playback_over_the_socket() {
+	/* Its own data folder, so it gets its own socket and doesn't talk to a real Pinetree */
+	let folder = test_folder("remote_control");
+	let song = write_silent_mp3(&folder, "remote song.mp3", 10000);
+	write_silent_mp3(&folder, "remote song 2.mp3", 10000);
+	let player = start_player(88);
+
+	let send = Arc::clone(&player.send);
+	let receive = Arc::clone(&player.receive);
+	let library = Arc::clone(&player.library);
+	let data_dir = folder.clone();
+	std::thread::spawn(move || {
+		remote::remote_server_loop(send, receive, library, Some(data_dir));
+	});
+
+	assert_eq!(command("status", &folder), "State: stopped\nVolume: 0.75\n");
+	assert!(command("seek 10", &folder).starts_with("Error: no song is playing"));
+
+	assert_eq!(command(&format!("play {}", song), &folder), "OK\n");
+	let status = wait_for_status(&folder, "\"state\":\"playing\"");
+	assert!(status.contains("\"title\":\"remote song\""));
+	assert!(status.contains("\"length_ms\":10"));
+
+	assert_eq!(command("pause", &folder), "OK\n");
+	wait_for_status(&folder, "\"state\":\"paused\"");
+	assert_eq!(command("toggle", &folder), "OK\n");
+	wait_for_status(&folder, "\"state\":\"playing\"");
+
+	assert_eq!(command("volume 0.5", &folder), "OK\n");
+	wait_for_status(&folder, "\"volume\":0.50");
+
+	/* Playing a song from outside goes on through its folder */
+	player.send(MessageToAudio::UpdateEndBehavior(LoopBehavior::Next));
+	assert_eq!(command("next", &folder), "OK\n");
+	wait_for_status(&folder, "\"title\":\"remote song 2\"");
+
+	assert!(command("seek soon", &folder).starts_with("Error: invalid time"));
+	assert!(command("volume loud", &folder).starts_with("Error: invalid volume"));
+	assert!(command("dance", &folder).starts_with("Error: unknown command"));
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use common::*;
+use pinetree_core::record;
+
+#[test]
+fn renders_songs_faster_than_real_time() {
+	let folder = test_folder("render");
+	let first = write_silent_mp3(&folder, "first.mp3", 4000);
+	let second = write_silent_mp3(&folder, "second.mp3", 2000);
+	let path = format!("{}/render.wav", folder);
+
+	let start = Instant::now();
+	let job = record::start_render(vec![first, second], path.clone(), record::RecordFormat::Wav, record::RenderSettings {speed: 2.0, ..record::default_render_settings()});
+	while job.result().is_none() {
+		assert!(start.elapsed() < Duration::from_secs(10), "The render never finished");
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	/* 6 seconds of songs played at double speed would take 3 seconds in real time */
+	assert!(start.elapsed() < Duration::from_secs(2));
+	assert_eq!(job.result(), Some(Ok(path.clone())));
+	assert_eq!(job.progress(), 1.0);
+
+	let reader = hound::WavReader::open(&path).unwrap();
+	assert_eq!(reader.spec().channels, record::RECORD_CHANNELS);
+	assert_eq!(reader.spec().sample_rate, record::RECORD_SAMPLE_RATE);
+	let length_ms = reader.duration() as u64 * 1000 / record::RECORD_SAMPLE_RATE as u64;
+	assert!(length_ms.abs_diff(3000) < 150, "rendered {} ms", length_ms);
+
+	let job = record::start_render(vec![format!("{}/missing.mp3", folder)], path, record::RecordFormat::Flac, record::default_render_settings());
+	while job.result().is_none() {
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	assert!(job.result().unwrap().unwrap_err().contains("missing.mp3"));
+}
+
+#[test]
+fn exports_a_song_with_effects() {
+	let folder = test_folder("export");
+	let song = write_silent_mp3(&folder, "song.mp3", 3000);
+	let path = format!("{}/export.flac", folder);
+
+	let settings = record::RenderSettings {speed: 1.5, fade_in_ms: 500, fade_out_ms: 1000, normalize: true, ..record::default_render_settings()};
+	let job = record::start_render(vec![song.clone()], path.clone(), record::RecordFormat::Flac, settings);
+	let start = Instant::now();
+	while job.result().is_none() {
+		assert!(start.elapsed() < Duration::from_secs(10), "The export never finished");
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	assert_eq!(job.result(), Some(Ok(path.clone())));
+	/* Silence has no peak to normalize */
+	assert_eq!(record::normalization_gain(&song), Ok(1.0));
+
+	let decoder = rodio::Decoder::new_flac(std::fs::File::open(&path).unwrap()).unwrap();
+	let samples: Vec<i16> = decoder.collect();
+	let length_ms = samples.len() as u64 * 1000 / (record::RECORD_SAMPLE_RATE as u64 * record::RECORD_CHANNELS as u64);
+	assert!(length_ms.abs_diff(2000) < 150, "exported {} ms", length_ms);
+	assert!(samples.iter().all(|sample| *sample == 0));
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use common::*;
+use pinetree_core::{index, roots, watch};
+
+fn wait_for_index(song_index: &Arc<Mutex<index::SongIndex>>, what: &str, condition: impl Fn(&index::SongIndex) -> bool) {
+	let start = Instant::now();
+	while !condition(&song_index.lock().unwrap()) {
+		/* Long enough for the polling fallback too */
+		assert!(start.elapsed() < watch::POLL_INTERVAL * 3, "The index never {}", what);
+		std::thread::sleep(Duration::from_millis(20));
+	}
+}
+
+#[test]
+fn watcher_follows_songs_coming_and_going() {
+	let folder = test_folder("watch");
+	let first = write_silent_mp3(&folder, "first.mp3", 100);
+	let song_index = index::new_song_index();
+	let library_roots = vec![roots::new_root(&folder)];
+	index::scan_roots(&song_index, &library_roots);
+	let watcher = watch::start_watcher(Arc::clone(&song_index), library_roots);
+	/* Give the watcher a moment to set up its watches */
+	std::thread::sleep(Duration::from_millis(300));
+
+	let second = write_silent_mp3(&folder, "second.mp3", 100);
+	wait_for_index(&song_index, "picked up a new song", |index| index.songs.contains_key(&second));
+
+	let album = format!("{}/Album", folder);
+	let staging = test_folder("watch-staging");
+	let moved_in = write_silent_mp3(&staging, "third.mp3", 100);
+	std::fs::rename(&staging, &album).unwrap();
+	let third = moved_in.replacen(&staging, &album, 1);
+	wait_for_index(&song_index, "picked up a folder moved in", |index| index.songs.contains_key(&third));
+
+	let renamed = format!("{}/renamed.mp3", folder);
+	std::fs::rename(&first, &renamed).unwrap();
+	wait_for_index(&song_index, "followed a rename", |index| index.songs.contains_key(&renamed) && !index.songs.contains_key(&first));
+
+	std::fs::remove_dir_all(&album).unwrap();
+	wait_for_index(&song_index, "dropped a deleted folder", |index| !index.songs.contains_key(&third));
+
+	let changes = watch::take_changes(&watcher);
+	let _ = std::fs::remove_dir_all(&folder);
+	assert!(changes.folders.contains(&folder));
+	assert!(changes.removed_songs.contains(&first) && changes.removed_songs.contains(&third));
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use common::*;
+use pinetree_core::{library, waveform};
+
+#[test]
+fn analyser_stores_waveforms_in_the_library() {
+	let folder = test_folder("waveform");
+	let song = write_silent_mp3(&folder, "silence.mp3", 2000);
+
+	let analysed = waveform::analyse_file(&song).unwrap();
+	assert_eq!(analysed.peaks.len(), waveform::WAVEFORM_POINTS);
+	assert!(analysed.peaks.iter().all(|peak| *peak == 0.0));
+	assert!(waveform::analyse_file(&format!("{}/missing.mp3", folder)).is_none());
+
+	let library = Arc::new(Mutex::new(library::new_library(None)));
+	let requests = Arc::new((Mutex::new(Vec::<String>::new()), Condvar::new()));
+	let analyser_library = Arc::clone(&library);
+	let analyser_requests = Arc::clone(&requests);
+	std::thread::spawn(move || waveform::analyser_loop(analyser_requests, analyser_library));
+
+	waveform::request_waveform(&requests, &song);
+	let start = Instant::now();
+	while library.lock().unwrap().songs.get(&song).is_none_or(|record| record.waveform.is_none()) {
+		assert!(start.elapsed() < Duration::from_secs(10), "The waveform was never analysed");
+		std::thread::sleep(Duration::from_millis(10));
+	}
+	assert_eq!(library.lock().unwrap().songs[&song].waveform, Some(analysed));
+}
@@ -0,0 +1,135 @@
+/*
+ * Cover art textures for the GUI.
+ *
+ * Finding and decoding a picture can take a while (some files embed multi-megabyte scans), so that happens on a
+ * loader thread and the art shows up a frame or two later. The newest request is loaded first, which is whatever
+ * just scrolled into view. Decoded pictures are scaled down and kept as egui textures, and only the most recently
+ * shown ones are kept so a big library doesn't fill up memory.
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use eframe::egui;
+use pinetree_core::art;
+
+/* Most songs to keep art for. The one shown least recently is dropped first */
+pub const COVER_CACHE_LIMIT: usize = 128;
+/* Pictures are scaled down to fit in this, which is as big as they get shown */
+const MAX_COVER_SIZE: u32 = 320;
+
+type LoadedCover = (String, Option<egui::ColorImage>);
+
+struct CoverLoader {
+	requests: Arc<(Mutex<Vec<String>>, Condvar)>,
+	loaded: Arc<Mutex<Vec<LoadedCover>>>,
+}
+
+pub struct CoverArtCache {
+	/* Keyed by song path, None for songs without art so they aren't looked at again. The number is when it was last shown */
+	textures: HashMap<String, (Option<egui::TextureHandle>, u64)>,
+	uses: u64,
+	/* Started the first time art is asked for, since it needs the egui context to wake the GUI up */
+	loader: Option<CoverLoader>,
+}
+
+pub fn new_cover_art_cache() -> CoverArtCache {
+	CoverArtCache {
+		textures: HashMap::new(),
+		uses: 0,
+		loader: None,
+	}
+}
+
+fn decode_cover(song: &str) -> Option<egui::ColorImage> {
+	let bytes = art::find_cover_art(song)?;
+	let image = match image::load_from_memory(&bytes) {
+		Ok(image) => image,
+		Err(err) => {
+			println!("Error in decode_cover: {} ({})", err, song);
+			return None;
+		},
+	};
+	let image = if image.width() > MAX_COVER_SIZE || image.height() > MAX_COVER_SIZE {
+		image.thumbnail(MAX_COVER_SIZE, MAX_COVER_SIZE)
+	} else {
+		image
+	};
+	let rgba = image.to_rgba8();
+	Some(egui::ColorImage::from_rgba_unmultiplied([rgba.width() as usize, rgba.height() as usize], rgba.as_raw()))
+}
+
+fn start_loader(ctx: &egui::Context) -> CoverLoader {
+	let requests = Arc::new((Mutex::new(Vec::<String>::new()), Condvar::new()));
+	let loaded = Arc::new(Mutex::new(Vec::<LoadedCover>::new()));
+	let thread_requests = Arc::clone(&requests);
+	let thread_loaded = Arc::clone(&loaded);
+	let ctx = ctx.clone();
+	thread::spawn(move || {
+		let (lock, cvar) = &*thread_requests;
+		loop {
+			let song = {
+				let mut requests = lock.lock().unwrap();
+				while requests.is_empty() {
+					requests = cvar.wait(requests).unwrap();
+				}
+				requests.pop().unwrap()
+			};
+			let cover = decode_cover(&song);
+			thread_loaded.lock().unwrap().push((song, cover));
+			ctx.request_repaint();
+		}
+	});
+	CoverLoader {requests, loaded}
+}
+
+fn receive_loaded(cache: &mut CoverArtCache, ctx: &egui::Context) {
+	let loaded = if let Some(loader) = &cache.loader {std::mem::take(&mut *loader.loaded.lock().unwrap())} else {return;};
+	for (song, image) in loaded {
+		let texture = image.map(|image| ctx.load_texture(format!("Cover art {}", song), image, egui::TextureOptions::LINEAR));
+		cache.textures.insert(song, (texture, cache.uses));
+	}
+	while cache.textures.len() > COVER_CACHE_LIMIT {
+		let oldest = cache.textures.iter().min_by_key(|(_, (_, last_used))| *last_used).map(|(song, _)| song.clone());
+		if let Some(oldest) = oldest {
+			/* Dropping the last handle frees the texture */
+			cache.textures.remove(&oldest);
+		}
+	}
+}
+
+/**
+ * The cover art of a song, if it has any and it is loaded. Art that isn't loaded yet gets requested and
+ * shows up in a later frame.
+ */
+pub fn cover_texture(cache: &mut CoverArtCache, ctx: &egui::Context, song: &str) -> Option<egui::TextureHandle> {
+	receive_loaded(cache, ctx);
+	cache.uses += 1;
+	if let Some((texture, last_used)) = cache.textures.get_mut(song) {
+		*last_used = cache.uses;
+		return texture.clone();
+	}
+	let loader = cache.loader.get_or_insert_with(|| start_loader(ctx));
+	let (lock, cvar) = &*loader.requests;
+	let mut requests = lock.lock().unwrap();
+	/* Asked again, so it is (still) on screen and moves to the front of the queue */
+	requests.retain(|request| request != song);
+	requests.push(song.to_string());
+	/* Whatever was asked for longest ago has scrolled out of view by now */
+	if requests.len() > COVER_CACHE_LIMIT {
+		requests.remove(0);
+	}
+	cvar.notify_one();
+	None
+}
+
+/**
+ * Forgets everything, e.g. after a refresh when a cover.jpg may have been added.
+ */
+pub fn clear_cover_art(cache: &mut CoverArtCache) {
+	cache.textures.clear();
+	if let Some(loader) = &cache.loader {
+		loader.requests.0.lock().unwrap().clear();
+	}
+}
@@ -0,0 +1,150 @@
+/*
+ * System-wide hotkeys, so Pinetree can be controlled while another program is focused.
+ *
+ * The keys are grabbed with the global-hotkey crate: X11 key grabs on Linux, RegisterHotKey on Windows and
+ * Carbon hotkeys on macOS. On a Wayland session X11 grabs only see keys while an XWayland window is focused,
+ * there the desktop's own media key handling talks to Pinetree over MPRIS instead.
+ *
+ * The bindings are the global ones from the shortcut editor. Key presses are handled on their own thread and
+ * go straight to the audio thread, so they work even when the window is minimized and not being redrawn.
+ */
+
+use std::sync::{Arc, Condvar, Mutex};
+
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use global_hotkey::hotkey::{Code, HotKey, Modifiers};
+use pinetree_core::audio_frontend::{self, MessageToAudio, RodioData};
+use pinetree_core::config::PersistentData;
+use pinetree_core::shortcuts::{self, KeyBinding, ShortcutAction};
+
+type AudioSendChannel = Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>;
+type AudioReceiveChannel = Arc<(Mutex<Vec<RodioData>>, Condvar)>;
+
+pub struct GlobalHotkeys {
+	manager: GlobalHotKeyManager,
+	registered: Vec<HotKey>,
+	/* The global bindings and media key setting the registered hotkeys were made from */
+	registered_settings: Option<(Vec<Option<KeyBinding>>, bool)>,
+	/* Hotkey id to action, shared with the thread that handles the key presses */
+	active_actions: Arc<Mutex<Vec<(u32, ShortcutAction)>>>,
+	/* Bindings that couldn't be registered, usually because another program already grabbed them */
+	pub errors: Vec<String>,
+}
+
+/**
+ * Has to be called from the main thread, on Windows and macOS the hotkeys are delivered through its event loop.
+ */
+pub fn start_global_hotkeys(audio_send: AudioSendChannel, audio_receive: AudioReceiveChannel) -> Result<GlobalHotkeys, String> {
+	let manager = match GlobalHotKeyManager::new() {
+		Ok(manager) => manager,
+		Err(err) => return Err(format!("Global shortcuts are unavailable: {}", err)),
+	};
+	let active_actions = Arc::new(Mutex::new(Vec::<(u32, ShortcutAction)>::new()));
+	let thread_actions = active_actions.clone();
+	std::thread::spawn(move || {
+		while let Ok(event) = GlobalHotKeyEvent::receiver().recv() {
+			if event.state() != HotKeyState::Pressed {
+				continue;
+			}
+			let action = thread_actions.lock().unwrap().iter().find(|(id, _)| *id == event.id()).map(|(_, action)| *action);
+			if let Some(action) = action {
+				run_global_action(action, &audio_send, &audio_receive);
+			}
+		}
+	});
+	Ok(GlobalHotkeys {manager, registered: Vec::new(), registered_settings: None, active_actions, errors: Vec::new()})
+}
+
+fn run_global_action(action: ShortcutAction, audio_send: &AudioSendChannel, audio_receive: &AudioReceiveChannel) {
+	match action {
+		ShortcutAction::TogglePause => audio_frontend::send_audio_signal(audio_send, MessageToAudio::TogglePause),
+		ShortcutAction::NextSong => audio_frontend::send_audio_signal(audio_send, MessageToAudio::SongEnd),
+		ShortcutAction::PreviousSong => audio_frontend::send_audio_signal(audio_send, MessageToAudio::PreviousSong),
+		ShortcutAction::VolumeUp | ShortcutAction::VolumeDown => {
+			/* The GUI picks up the new volume from the audio thread, the same as with MPRIS */
+			let audio_data = audio_frontend::request_rodio_data(audio_send, audio_receive);
+			let step = if action == ShortcutAction::VolumeUp {shortcuts::VOLUME_STEP} else {-shortcuts::VOLUME_STEP};
+			let slider_volume = (audio_frontend::inverse_volume_curve(audio_data.volume) + step).clamp(-0.2, 1.0);
+			audio_frontend::send_audio_signal(audio_send, MessageToAudio::UpdateVolume(audio_frontend::volume_curve(slider_volume)));
+		},
+		_ => {},
+	}
+}
+
+/**
+ * Turns a binding from the shortcut editor (egui key names) into a hotkey.
+ */
+fn binding_to_hotkey(binding: &KeyBinding) -> Option<HotKey> {
+	let mut modifiers = Modifiers::empty();
+	if binding.ctrl {
+		/* Ctrl in a binding means Cmd on macOS, like it does in egui */
+		if cfg!(target_os = "macos") {modifiers |= Modifiers::SUPER;} else {modifiers |= Modifiers::CONTROL;}
+	}
+	if binding.shift {modifiers |= Modifiers::SHIFT;}
+	if binding.alt {modifiers |= Modifiers::ALT;}
+
+	/* global-hotkey understands most egui key names already, these are the ones that differ */
+	let key_name = match binding.key.as_str() {
+		"OpenBracket" => "BracketLeft",
+		"CloseBracket" => "BracketRight",
+		"Backtick" => "Backquote",
+		"Equals" => "Equal",
+		key => key,
+	};
+	let hotkey: HotKey = key_name.parse().ok()?;
+	Some(HotKey::new(Some(modifiers), hotkey.key))
+}
+
+fn media_key_hotkeys() -> Vec<(HotKey, ShortcutAction)> {
+	/* X11 has no separate play/pause keysym, the play key is the one that toggles */
+	let play_pause = if cfg!(target_os = "linux") {Code::MediaPlay} else {Code::MediaPlayPause};
+	vec![
+		(HotKey::new(None, play_pause), ShortcutAction::TogglePause),
+		(HotKey::new(None, Code::MediaTrackNext), ShortcutAction::NextSong),
+		(HotKey::new(None, Code::MediaTrackPrevious), ShortcutAction::PreviousSong),
+	]
+}
+
+impl GlobalHotkeys {
+	/* Registers whatever the settings ask for. Cheap when nothing changed, so it can be called every frame */
+	pub fn update(&mut self, persistent_data: &PersistentData) {
+		let settings = (
+			persistent_data.shortcuts.iter().map(|shortcut| shortcut.global_binding.clone()).collect::<Vec<Option<KeyBinding>>>(),
+			persistent_data.media_keys,
+		);
+		if self.registered_settings.as_ref() == Some(&settings) {
+			return;
+		}
+
+		let mut wanted = Vec::<(HotKey, ShortcutAction)>::new();
+		let mut errors = Vec::<String>::new();
+		for shortcut in &persistent_data.shortcuts {
+			if let Some(binding) = &shortcut.global_binding && shortcuts::can_be_global(shortcut.action) {
+				if let Some(hotkey) = binding_to_hotkey(binding) {
+					wanted.push((hotkey, shortcut.action));
+				} else {
+					errors.push(format!("{} can't be used as a global shortcut", shortcuts::binding_to_str(&shortcut.global_binding)));
+				}
+			}
+		}
+		if persistent_data.media_keys {
+			wanted.extend(media_key_hotkeys());
+		}
+		for hotkey in &self.registered {
+			let _ = self.manager.unregister(*hotkey);
+		}
+		self.registered.clear();
+		let mut active_actions = Vec::<(u32, ShortcutAction)>::new();
+		for (hotkey, action) in &wanted {
+			if let Err(err) = self.manager.register(*hotkey) {
+				errors.push(format!("Couldn't register {} for \"{}\": {}", hotkey, shortcuts::action_description(*action), err));
+			} else {
+				active_actions.push((hotkey.id(), *action));
+				self.registered.push(*hotkey);
+			}
+		}
+		*self.active_actions.lock().unwrap() = active_actions;
+		self.registered_settings = Some(settings);
+		self.errors = errors;
+	}
+}
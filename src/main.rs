@@ -7,14 +7,21 @@
  */
 
 use std::sync::{Arc, Mutex, Condvar};
-use std::thread;
-use std::collections::HashMap;
-use std::io::BufRead;
+use std::collections::{HashMap, HashSet};
 use std::panic;
 
 use eframe::egui;
-mod audio_frontend;
-use audio_frontend::*;
+use pinetree_core::{args, audio_frontend, chapters, duplicates, index, library, lyrics, record, remote, resume, roots, scrobble, search, shortcuts, tag_editor, tags, track_table, visualiser, watch, waveform};
+use pinetree_core::shortcuts::ShortcutAction;
+use pinetree_core::audio_frontend::*;
+use pinetree_core::config::*;
+use pinetree_core::engine::*;
+use pinetree_core::files::*;
+use pinetree_core::playlist::*;
+#[cfg(target_family = "unix")]
+mod tui;
+mod hotkeys;
+mod cover_art;
 
 
 #[derive(PartialEq)]
@@ -22,6 +29,9 @@ use audio_frontend::*;
 enum LeftPanelMode {
 	Files,
 	Playlists,
+	Artists,
+	Albums,
+	Search,
 	DeletePlaylist,
 	SelectSongs,
 	RemoveSongs,
@@ -39,12 +49,6 @@ enum LeftPanelMode {
 // 	time_listened_ms: u64,
 // 	// playlists: Vec<String>
 // }
-#[derive(Debug)]
-struct Directory {
-	filepath_identifier: String,
-	subdirectories: Vec<String>,
-	songs: Vec<String>
-}
 
 #[derive(PartialEq)]
 enum FileActions {
@@ -64,25 +68,7 @@ enum FileActions {
 	AddSongToPlaylistStrong(usize),
 }
 
-struct Playlist {
-	name: String,
-	songs: Vec<String>,
-	is_open: bool,
-}
-
-#[derive(PartialEq)]
-#[derive(Clone)]
-enum ThemePref {
-	DARK,
-	LIGHT,
-}
 
-fn theme_to_str(theme: &ThemePref) -> String {
-	return match theme {
-		ThemePref::DARK => "Dark".to_string(),
-		ThemePref::LIGHT => "Light".to_string(),
-	};
-}
 
 #[derive(PartialEq)]
 enum CentralPanelMode {
@@ -90,38 +76,19 @@ enum CentralPanelMode {
 	InstallationSuccess,
 	Settings,
 	PlayerMode,
+	Tracks,
+	/* Tracks sub-page */
+	TagEditor,
+	/* Tracks sub-page */
+	Duplicates,
+	Visualiser,
+	Statistics,
 	About,
+	/* Settings sub-page */
+	Shortcuts,
 }
 
-pub const CURRENT_VERSION: &str = "OPEN BETA 5";
-
-struct PersistentData {
-	data_file_exists: bool,
-	hide_directories_by_default: bool,
-	data_file_version: String,
-	default_directory: String,
-	theme: ThemePref,
-	playlists: Vec<Playlist>,
-	default_on_finish: audio_frontend::LoopBehavior,
-	default_volume: f32,
-	shuffle_memory: usize,
-	prev_behavior: audio_frontend::PrevBehavior,
-}
 
-fn default_persistent_data() -> PersistentData {
-	PersistentData {
-		data_file_exists: false,
-		hide_directories_by_default: false,
-		data_file_version: CURRENT_VERSION.to_string(),
-		default_directory: "".to_string(),
-		theme: ThemePref::DARK,
-		playlists: init_playlist_from_filepath(""),
-		default_on_finish: audio_frontend::LoopBehavior::Stop,
-		default_volume: audio_frontend::DEFAULT_VOLUME,
-		shuffle_memory: 3,
-		prev_behavior: audio_frontend::PrevBehavior::History,
-	}
-}
 
 struct InstallerData {
 	// create_taskbar_shortcut: bool,
@@ -132,117 +99,6 @@ struct InstallerData {
 	default_song_folder: String,
 }
 
-/**
- * This code was mostly lifted from the dirs-sys with changes
- * https://github.com/dirs-dev/dirs-sys-rs/
- * 
- * It felt irresponsible adding a dependency for this when the code is simple,
- * and I don't need the entirety of the dependency
- */
-#[cfg(target_os = "windows")]
-mod windows_folders {
-	use std::ffi::OsString;
-	use std::os::windows::ffi::OsStringExt;
-	use windows_sys::Win32::UI::Shell;
-
-	pub fn try_get_winfolder(folder_id: windows_sys::core::GUID) -> Option<std::path::PathBuf> {
-		unsafe {
-			let mut path_ptr: windows_sys::core::PWSTR = std::ptr::null_mut();
-			let result = Shell::SHGetKnownFolderPath(&folder_id, 0,std::ptr::null_mut(),&mut path_ptr);
-			if result == 0 {
-				let len = windows_sys::Win32::Globalization::lstrlenW(path_ptr) as usize;
-				let path = std::slice::from_raw_parts(path_ptr, len);
-				let ostr: OsString = OsStringExt::from_wide(path);
-				windows_sys::Win32::System::Com::CoTaskMemFree(path_ptr as *const std::ffi::c_void);
-				Some(std::path::PathBuf::from(ostr))
-			} else {
-				windows_sys::Win32::System::Com::CoTaskMemFree(path_ptr as *const std::ffi::c_void);
-				None
-			}
-		}
-	}
-
-	pub fn roaming_app_data() -> Option<std::path::PathBuf> {
-		try_get_winfolder(Shell::FOLDERID_RoamingAppData)
-	}
-
-	// pub fn local_app_data() -> Option<std::path::PathBuf> {
-	// 	try_get_winfolder(Shell::FOLDERID_LocalAppData)
-	// }
-
-	pub fn music() -> Option<std::path::PathBuf> {
-		try_get_winfolder(Shell::FOLDERID_Music)
-	}
-
-	// pub fn desktop() -> Option<std::path::PathBuf> {
-	// 	try_get_winfolder(Shell::FOLDERID_Desktop)
-	// }
-}
-
-#[cfg(target_family = "unix")]
-mod unix_folders {
-	use std::env;
-
-	/**
-	* Returns ~/
-	*/
-	pub fn home() -> Option<String> {
-		let mut r_str = None;
-		if let Some(home) = env::home_dir() && let Some(path_string) = home.as_os_str().to_str() {
-			r_str = Some(path_string.to_string());
-		}
-		return r_str;
-	}
-}
-
-fn default_install_path() -> String {
-	#[cfg(target_os = "windows")] {
-		let appdata = windows_folders::roaming_app_data();
-		if let Some(appdata_folder) = appdata
-		&& let Some(path_string) = appdata_folder.as_os_str().to_str() {
-			return path_string.to_string();
-		}
-	}
-	#[cfg(target_os = "linux")] {
-		let home = unix_folders::home();
-		if let Some(home_folder) = home {
-			let local_share = build_full_filepath(&home_folder, ".local/share");
-			return local_share.to_string();
-		}
-	}
-	#[cfg(target_os = "macos")] {
-		let home = unix_folders::home();
-		if let Some(home_folder) = home {
-			let local_share = build_full_filepath(&home_folder, "Library/Application Support");
-			return local_share.to_string();
-		}
-	}
-	"".to_string()
-}
-fn default_song_path() -> String {
-	#[cfg(target_os = "windows")] {
-		let appdata = windows_folders::music();
-		if let Some(appdata_folder) = appdata
-		&& let Some(path_string) = appdata_folder.as_os_str().to_str() {
-			return path_string.to_string();
-		}
-	}
-	#[cfg(target_os = "linux")] {
-		let home = unix_folders::home();
-		if let Some(home_folder) = home {
-			let local_share = build_full_filepath(&home_folder, "Music");
-			return local_share.to_string();
-		}
-	}
-	#[cfg(target_os = "macos")] {
-		let home = unix_folders::home();
-		if let Some(home_folder) = home {
-			let local_share = build_full_filepath(&home_folder, "Music");
-			return local_share.to_string();
-		}
-	}
-	"".to_string()
-}
 fn default_installer_data() -> InstallerData {
 	return InstallerData {
 		// create_taskbar_shortcut: false,
@@ -270,6 +126,8 @@ struct MyApp {
 	
 	song_speed: f32,
 	song_volume: f32,
+	/* Sink volume the audio thread reported last frame. Used to notice volume changes made outside the GUI (e.g. MPRIS) */
+	last_reported_volume: Option<f32>,
 
 	active_directory_filepath: String,
 
@@ -302,252 +160,134 @@ struct MyApp {
 	hide_fp: bool,
 
 	save_err: SaveError,
+	/* The shortcut editor is waiting for a key press for this action. The bool is whether it's the global binding */
+	shortcut_being_set: Option<(ShortcutAction, bool)>,
+	global_hotkeys: Result<hotkeys::GlobalHotkeys, String>,
+	/* Set by the jump to current song shortcut, the file list scrolls to the song on the next frame */
+	scroll_to_current_song: bool,
 
 	pinned_mode: bool,
 	shuffle_memory: usize,
 	shuffle_memory_text: String,
 
 	prev_behavior: audio_frontend::PrevBehavior,
-}
-
-fn str_to_theme_preference(string: &str) -> ThemePref {
-	return match string {
-		"Dark" => ThemePref::DARK,
-		"Light" => ThemePref::LIGHT,
-
-		/* Default is dark */
-		&_ => ThemePref::DARK,
-	};
-}
-
 
-fn build_playlist_tree(playlists: &Vec<Playlist>) -> Vec<PlaylistTreeElement> {
-	let mut vec: Vec<PlaylistTreeElement> = Vec::<PlaylistTreeElement>::new();
-	let mut i = 0;
-	for playlist in playlists {
-		vec.push(PlaylistTreeElement {
-			song_name: None,
-			playlist_position: i,
-		});
-		if playlist.is_open {
-			for song in &playlist.songs {
-				vec.push(PlaylistTreeElement {
-					song_name: Some(song.clone()),
-					playlist_position: 0,
-				});
-			}
-		}
-		i += 1;
-	}
-	return vec;
-}
-
-/**
-* The playlist data takes the following form. We can assume that playlist names and file paths don't
-* contain newlines even if they're technically legal on unix systems because egui wouldn't be able to render it anyways.
-* That is the only character type we can assume is illegal though.
-*
-* Playlist: playlist_name
-* song_filepath
-* song_filepath
-* song_filepath
-* ...
-* Playlist: playlist_2_name
-* ...
-*/
-fn init_playlist_from_filepath(fp: &str) -> Vec<Playlist> {
-	let file = std::fs::File::open(fp);
-	let mut playlists = Vec::<Playlist>::new();
-	let mut current_songs = Vec::<String>::new();
-	let mut playlist_name: Option<String> = None;
-
-	if let Ok(file) = file {
-		let reader = std::io::BufReader::new(file);
-		for line in reader.lines() {
-			if let Ok(line) = line {
-				// Drop the current playlist (add to vec)
-				if line.starts_with("Playlist: ") {
-					if let Some(name) = playlist_name {
-						playlists.push(Playlist {
-							name: name,
-							songs: current_songs,
-							is_open: false,
-						});
-						current_songs = Vec::<String>::new();
-					}
-					playlist_name = Some((&line[10..]).to_string());
-				} else {
-					current_songs.push(line);
-				}
-			}
-		}
-	}
-	if let Some(name) = playlist_name {
-		playlists.push(Playlist {
-			name: name,
-			songs: current_songs,
-			is_open: false,
-		});
-	}
-	return playlists;
+	library: Arc<Mutex<library::Library>>,
+	statistics_period: library::ListeningPeriod,
+
+	scrobbler: Arc<Mutex<scrobble::Scrobbler>>,
+
+	waveform_request_channel: Arc<(Mutex<Vec<String>>, Condvar)>,
+	/* Waveform of the song that was playing last frame, so the library doesn't get locked every frame */
+	current_waveform: Option<(String, waveform::Waveform)>,
+	/* Last song the analyser was asked about, so it only gets asked once */
+	waveform_requested_for: String,
+
+	sample_tap: Arc<visualiser::SampleTap>,
+	record_tap: Arc<record::RecordTap>,
+	/* The file the last recording went to, or why it couldn't start */
+	last_recording: Option<Result<String, String>>,
+	/* What is being rendered (a playlist name) and how far along it is */
+	render_job: Option<(String, Arc<record::RenderJob>)>,
+	export_fade_in_secs: f32,
+	export_fade_out_secs: f32,
+	export_normalize: bool,
+	/* Rows of the track table, the folder or playlist they're for, and the sort they're in */
+	track_rows: Option<(String, Vec<track_table::SortKey>, Vec<track_table::TrackRow>)>,
+	/* Rows being built for the folder or playlist */
+	track_row_job: Option<(String, Arc<track_table::TrackRowJob>)>,
+
+	song_index: Arc<Mutex<index::SongIndex>>,
+	folder_watcher: Arc<watch::FolderWatcher>,
+	/* The song that was playing when its file got deleted, it plays on from what was already read */
+	deleted_song: Option<String>,
+	/* Artist picked in the Artists view, and the album opened in the Artists or Albums view */
+	browse_artist: Option<String>,
+	browse_album: Option<index::Album>,
+	/* Built from the index when its generation changes, not every frame */
+	artist_list: Option<(u64, Vec<(String, usize)>)>,
+	/* Albums shown, with the index generation and the artist (if any) they were built for */
+	album_list: Option<(u64, Option<String>, Vec<index::Album>)>,
+	cover_art: cover_art::CoverArtCache,
+
+	/* Everything the library search looks through, with the index generation and playlists it was built from */
+	search_entries: Option<((u64, u64), Vec<search::SearchEntry>)>,
+	library_search: search::Search,
+	library_search_text: String,
+
+	/* Songs picked in the tag editor */
+	tag_selection: HashSet<String>,
+	/* Whether each field gets changed, and what to */
+	tag_fields: Vec<(tag_editor::TagField, bool, String)>,
+	/* The single selected song the fields were filled in from */
+	tag_fields_from: Option<String>,
+	tag_pattern: String,
+	tag_use_pattern: bool,
+	/* Tags as they are on disk, read once per song for the preview and the song info */
+	tag_current: HashMap<String, tags::SongTags>,
+	/* Whether the running write undoes an earlier one, which then shouldn't be undoable itself */
+	tag_write_job: Option<(bool, Arc<tag_editor::TagWriteJob>)>,
+	/* One entry per edit: the songs it changed with what it takes to undo that */
+	tag_undo: Vec<Vec<(String, tag_editor::TagUndo)>>,
+	tag_errors: Vec<String>,
+
+	duplicate_scan: Option<Arc<duplicates::DuplicateScan>>,
+	/* What the last search found, minus the groups dealt with since */
+	duplicate_groups: Option<Vec<duplicates::DuplicateGroup>>,
+	/* Groups whose other copies are about to be deleted, waiting for a yes */
+	duplicate_confirm: Option<Vec<usize>>,
+	duplicate_errors: Vec<String>,
+	visualiser: visualiser::Visualiser,
+	last_visualiser_update: std::time::Instant,
+
+	/* Lyrics of the song they were loaded for, None inside if that song has none */
+	lyrics: Option<(String, Option<lyrics::Lyrics>)>,
+	lyrics_offset_ms: i64,
+	/* Line that was highlighted last frame, the lyrics only scroll when it changes so they can still be scrolled by hand */
+	last_lyrics_line: Option<usize>,
+
+	/* Speed the audio thread reported last frame, an A-B loop can speed the song up on its own */
+	last_reported_speed: Option<f32>,
+	/* A and B points of the loop for ab_loop_song. The loop starts once both are set */
+	ab_loop_song: String,
+	loop_start: Option<usize>,
+	loop_end: Option<usize>,
+	loop_count_in_secs: f32,
+	loop_speed_step: f32,
+	loop_target_speed: f32,
+	/* Bookmarks of the song they were loaded for, so the library doesn't get locked every frame */
+	bookmarks: Option<(String, Vec<library::Bookmark>)>,
+	new_bookmark_name: String,
+	sleep_minutes: u64,
+	sleep_songs: usize,
+
+	/* Resume settings the audio thread has, so they only get sent when they change */
+	sent_resume_settings: Option<resume::ResumeSettings>,
+	/* Chapters of the song they were loaded for */
+	chapters: Option<(String, Vec<chapters::Chapter>)>,
 }
 
-fn find_persistent_data() -> (PersistentData, String) {
-	let mut ret_str = "".to_string();
-	let data_file = if let Ok(same_dir) = std::fs::File::open("internal_pinetree_data.txt") {
-		ret_str = "./".to_string();
-		same_dir
-	} else {
-		let pinetree_folder = &build_full_filepath(&default_install_path(), "Pinetree");
-		let internal_data_file = build_full_filepath(pinetree_folder, "internal_pinetree_data.txt");
-
-		if let Ok(default_install_location) = std::fs::File::open(internal_data_file) {
-			ret_str = build_full_filepath(&default_install_path(), "Pinetree");
-			default_install_location
-		} else {
-			return (default_persistent_data(), ret_str);
-		}
-	} ;
-	let mut persistent_data = default_persistent_data();
-
-	persistent_data.data_file_exists = true;
-	let reader = std::io::BufReader::new(data_file);
 
-	let mut current_songs = Vec::<String>::new();
-	let mut playlist_name: Option<String> = None;
 
-	#[derive(PartialEq)]
-	enum State {
-		Version,
-		Settings,
-		Playlists,
-	}
 
-	let mut current_state = State::Version;
-	
-	for line in reader.lines() {
-		if let Ok(line) = line {
-			let version_header = "VERSION: ";
-			let settings_header = "SETTINGS";
-			let playlists_header = "PLAYLISTS";
-
-			if line.starts_with(version_header) {
-				persistent_data.data_file_version = line[version_header.len()..].to_string();
-			}
-			else if line.starts_with(settings_header) {
-				current_state = State::Settings;
-			}
-			else if line.starts_with(playlists_header) {
-				current_state = State::Playlists;
-			}
-			else if current_state == State::Settings {
-				let theme_identifier = "Theme: ";
-				let default_directory_identifier = "Default Directory: ";
-				let hide_dirs_identifier = "Hide Directories: "; 
-				let default_end_behavior_identifier = "Default End Behavior: "; 
-				let default_volume_identifier = "Default Volume: ";
-				let shuffle_memory_identifier = "Shuffle Memory: ";
-				let prev_behavior_identifier= "Default Prev Behavior: ";
-				if line.starts_with(theme_identifier) {
-					persistent_data.theme = str_to_theme_preference(&line[theme_identifier.len()..]);
-				} else if line.starts_with(default_directory_identifier) {
-					persistent_data.default_directory = (line[default_directory_identifier.len()..]).to_string();
-				} else if line.starts_with(hide_dirs_identifier) {
-					match &line[hide_dirs_identifier.len()..] {
-						"true" => {
-							persistent_data.hide_directories_by_default = true;
-						},
-						"false" => {
-							persistent_data.hide_directories_by_default = false;
-						},
-						_ => {},
-					}
-				} else if line.starts_with(default_end_behavior_identifier) {
-					match &line[default_end_behavior_identifier.len()..] {
-						"Loop" => {
-							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Loop;
-						},
-						"Stop" => {
-							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Stop;
-						},
-						"Shuffle" => {
-							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Shuffle;
-						},
-						"Next" => {
-							persistent_data.default_on_finish = audio_frontend::LoopBehavior::Next;
-						},
-						_ => {},
-					}
-				} else if line.starts_with(default_volume_identifier) {
-					persistent_data.default_volume = line[default_volume_identifier.len()..].parse().unwrap_or(audio_frontend::DEFAULT_VOLUME);
-				} else if line.starts_with(shuffle_memory_identifier) {
-					persistent_data.shuffle_memory = line[shuffle_memory_identifier.len()..].parse().unwrap_or(3);
-				} else if line.starts_with(prev_behavior_identifier) {
-					match &line[prev_behavior_identifier.len()..] {
-						"Above" => {
-							persistent_data.prev_behavior = audio_frontend::PrevBehavior::Above;
-						},
-						"History" => {
-							persistent_data.prev_behavior = audio_frontend::PrevBehavior::History;
-						},
-						_ => {},
-					}
-				}
-			}
-			else if current_state == State::Playlists {
-				/* Prevents trailing newlines from causing problems */
-				if line.len() < 1 {continue;}
 
-				if line.starts_with("Playlist: ") {
-					if let Some(name) = playlist_name {
-						persistent_data.playlists.push(Playlist {
-							name: name,
-							songs: current_songs,
-							is_open: false,
-						});
-						current_songs = Vec::<String>::new();
-					}
-					playlist_name = Some((&line[10..]).to_string());
-				} else {
-					current_songs.push(line);
-				}
-			}
-		}
-	}
-	/* Playlists are currently the last thing in the internal data */
-	if let Some(name) = playlist_name {
-		persistent_data.playlists.push(Playlist {
-			name: name,
-			songs: current_songs,
-			is_open: false,
-		});
-	}
 
-	return (persistent_data, ret_str);
-}
 
-impl Default for MyApp {
-	fn default() -> Self {
-		let message_param: Vec<MessageToAudio> = Vec::<MessageToAudio>::new();
-		let gui_message_param: Vec<RodioData> = Vec::<RodioData>::new();
-		
-		let audio_thread_recieve = Arc::new((Mutex::new(message_param), Condvar::new()));
-		let gui_thread_send = Arc::clone(&audio_thread_recieve);
-		
-		let audio_thread_send = Arc::new((Mutex::new(gui_message_param), Condvar::new()));
-		let gui_thread_recieve = Arc::clone(&audio_thread_send);
-		
-		thread::spawn(move || {
-			audio_thread_loop(audio_thread_recieve, audio_thread_send);
-		});
-		
-		let (persistent_data, installed_location) = find_persistent_data();
+impl MyApp {
+	fn new(startup_args: &args::StartupArgs) -> Self {
+		let (persistent_data, installed_location) = find_persistent_data(startup_args.data_dir.as_deref(), startup_args.no_install);
+		let engine = start_engine(startup_args, &persistent_data, &installed_location);
+		let start_folder = find_start_folder(startup_args, &persistent_data, &engine.audio_message_channel);
+		let global_hotkeys = hotkeys::start_global_hotkeys(engine.audio_message_channel.clone(), engine.audio_receive_channel.clone());
 
 		let mut dir_map = HashMap::<String, Directory>::new();
-		init_directory_at_filepath(&persistent_data.default_directory, &mut dir_map);
+		init_directory_at_filepath(&start_folder, &mut dir_map);
 
-		let start_mode = if persistent_data.data_file_exists {
-			if persistent_data.data_file_version != CURRENT_VERSION {
+		let start_mode = if startup_args.no_install {
+			CentralPanelMode::PlayerMode
+		} else if persistent_data.data_file_exists {
+			/* Profiles in a data folder don't have their own copy of the executable */
+			if persistent_data.data_file_version != CURRENT_VERSION && startup_args.data_dir.is_none() {
 				println!("Updating executable");
 				let target_exe_path = build_full_filepath(&installed_location, "pinetree.exe");
 	
@@ -571,19 +311,26 @@ impl Default for MyApp {
 
 		initialize_crash_logger(crash_log_location);
 
+		let loop_behavior = if startup_args.shuffle {
+			audio_frontend::LoopBehavior::Shuffle
+		} else {
+			clone_loop_behavior(&persistent_data.default_on_finish)
+		};
+
 		Self {
 			first_frame_rendered: false,
-			loop_behavior: clone_loop_behavior(&persistent_data.default_on_finish),
+			loop_behavior,
 			browse_mode: LeftPanelMode::Files,
-			current_song_folder: persistent_data.default_directory.clone(),
+			current_song_folder: start_folder.clone(),
 			song_speed: DEFAULT_SPEED,
 			search_text: "".to_string(),
 			genre_search_text: "".to_string(),
 			artist_search_text: "".to_string(),
 			advanced_search_active: false,
-			song_volume: persistent_data.default_volume,
+			song_volume: startup_args.volume.unwrap_or(persistent_data.default_volume),
+			last_reported_volume: None,
 			// songs_list: song_entry_list,
-			active_directory_filepath: persistent_data.default_directory.clone(),
+			active_directory_filepath: start_folder,
 			directory_map: dir_map,
 			// song_map: HashMap::<String, Song>::new(),
 			directory_tree: None,
@@ -599,8 +346,8 @@ impl Default for MyApp {
 
 			edit_playlist_data: None,
 
-			audio_message_channel: gui_thread_send,
-			audio_receive_channel: gui_thread_recieve,
+			audio_message_channel: engine.audio_message_channel,
+			audio_receive_channel: engine.audio_receive_channel,
 			hide_fp: persistent_data.hide_directories_by_default.clone(),
 			shuffle_memory: persistent_data.shuffle_memory,
 			shuffle_memory_text: format!("{}", persistent_data.shuffle_memory),
@@ -610,93 +357,1246 @@ impl Default for MyApp {
 
 			central_panel_mode: start_mode,
 
-			installer_data: default_installer_data(),
-			installed_location: installed_location,
+			installer_data: default_installer_data(),
+			installed_location: installed_location,
+
+			installer_error: None,
+
+			save_err: SaveError::None,
+			shortcut_being_set: None,
+			global_hotkeys,
+			scroll_to_current_song: false,
+
+			pinned_mode: false,
+
+			library: engine.library,
+			statistics_period: library::ListeningPeriod::Day,
+
+			scrobbler: engine.scrobbler,
+
+			waveform_request_channel: engine.waveform_request_channel,
+			current_waveform: None,
+			waveform_requested_for: "".to_string(),
+
+			sample_tap: engine.sample_tap,
+			record_tap: engine.record_tap,
+			last_recording: None,
+			render_job: None,
+			export_fade_in_secs: 0.0,
+			export_fade_out_secs: 0.0,
+			export_normalize: false,
+			track_rows: None,
+			track_row_job: None,
+			song_index: engine.song_index,
+			folder_watcher: engine.folder_watcher,
+			deleted_song: None,
+			browse_artist: None,
+			browse_album: None,
+			artist_list: None,
+			album_list: None,
+			cover_art: cover_art::new_cover_art_cache(),
+			search_entries: None,
+			library_search: search::new_search(),
+			library_search_text: "".to_string(),
+			tag_selection: HashSet::new(),
+			tag_fields: tag_editor::ALL_TAG_FIELDS.iter().map(|field| (*field, false, "".to_string())).collect(),
+			tag_fields_from: None,
+			tag_pattern: "%artist% - %title%".to_string(),
+			tag_use_pattern: false,
+			tag_current: HashMap::new(),
+			tag_write_job: None,
+			tag_undo: Vec::new(),
+			tag_errors: Vec::new(),
+			duplicate_scan: None,
+			duplicate_groups: None,
+			duplicate_confirm: None,
+			duplicate_errors: Vec::new(),
+			visualiser: visualiser::new_visualiser(),
+			last_visualiser_update: std::time::Instant::now(),
+
+			lyrics: None,
+			lyrics_offset_ms: 0,
+			last_lyrics_line: None,
+
+			last_reported_speed: None,
+			ab_loop_song: "".to_string(),
+			loop_start: None,
+			loop_end: None,
+			loop_count_in_secs: 0.0,
+			loop_speed_step: 0.0,
+			loop_target_speed: 1.0,
+			bookmarks: None,
+			new_bookmark_name: "".to_string(),
+			sleep_minutes: 30,
+			sleep_songs: 3,
+
+			sent_resume_settings: None,
+			chapters: None,
+		}
+	}
+
+	/* Asks the analyser for the song's waveform if the library doesn't have it yet */
+	fn find_waveform(&mut self, song: &str) -> Option<&waveform::Waveform> {
+		if song.is_empty() {
+			return None;
+		}
+		if self.current_waveform.as_ref().is_none_or(|(name, _)| name != song) {
+			self.current_waveform = None;
+			if let Ok(library) = self.library.lock()
+			&& let Some(record) = library.songs.get(song)
+			&& let Some(song_waveform) = &record.waveform {
+				self.current_waveform = Some((song.to_string(), song_waveform.clone()));
+			} else if self.waveform_requested_for != song {
+				waveform::request_waveform(&self.waveform_request_channel, song);
+				self.waveform_requested_for = song.to_string();
+			}
+		}
+		self.current_waveform.as_ref().map(|(_, song_waveform)| song_waveform)
+	}
+
+	/* Everything except pause, next/previous song and refresh, those are handled where their buttons are */
+	fn handle_shortcut_actions(&mut self, actions: &[ShortcutAction], audio_data: &audio_frontend::RodioData) {
+		for action in actions {
+			match action {
+				ShortcutAction::SeekForward | ShortcutAction::LongSeekForward | ShortcutAction::SeekBackward | ShortcutAction::LongSeekBackward => {
+					let step_ms = if *action == ShortcutAction::SeekForward || *action == ShortcutAction::SeekBackward {
+						self.persistent_data.seek_step_secs
+					} else {
+						self.persistent_data.long_seek_step_secs
+					} as usize * 1000;
+					let seek_pos = if *action == ShortcutAction::SeekForward || *action == ShortcutAction::LongSeekForward {
+						(audio_data.playback_position + step_ms).min(audio_data.song_length)
+					} else {
+						audio_data.playback_position.saturating_sub(step_ms)
+					};
+					send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekOnce(seek_pos));
+				},
+				ShortcutAction::VolumeUp | ShortcutAction::VolumeDown => {
+					let step = if *action == ShortcutAction::VolumeUp {shortcuts::VOLUME_STEP} else {-shortcuts::VOLUME_STEP};
+					self.song_volume = (self.song_volume + step).clamp(-0.2, 1.0);
+					send_audio_signal(&self.audio_message_channel, MessageToAudio::UpdateVolume(volume_curve(self.song_volume)));
+				},
+				ShortcutAction::SpeedUp | ShortcutAction::SpeedDown => {
+					let step = if *action == ShortcutAction::SpeedUp {shortcuts::SPEED_STEP} else {-shortcuts::SPEED_STEP};
+					/* Rounded so repeated steps don't drift away from e.g. exactly 1.0 */
+					self.song_speed = ((self.song_speed + step).clamp(0.5, 2.0) * 10.0).round() / 10.0;
+					send_audio_signal(&self.audio_message_channel, MessageToAudio::UpdateSpeed(self.song_speed));
+				},
+				ShortcutAction::ToggleLoopMode => {
+					self.loop_behavior = audio_frontend::next_loop_behavior(&self.loop_behavior);
+					send_audio_signal(&self.audio_message_channel, MessageToAudio::UpdateEndBehavior(clone_loop_behavior(&self.loop_behavior)));
+				},
+				ShortcutAction::JumpToCurrentSong => {
+					if audio_data.song_name.is_empty() || self.edit_playlist_data.is_some() {
+						continue;
+					}
+					self.browse_mode = LeftPanelMode::Files;
+					let in_tree = self.directory_tree.as_ref().is_some_and(|tree| tree.iter().any(|element| element.name == audio_data.song_name));
+					if !in_tree {
+						self.current_song_folder = song_folder_go_up(&audio_data.song_name);
+						self.directory_tree = None;
+						self.searched_directory_tree = None;
+						self.active_directory_filepath = self.current_song_folder.clone();
+						self.active_search_text = "".to_string();
+					}
+					self.scroll_to_current_song = true;
+				},
+				ShortcutAction::SetLoopStart | ShortcutAction::SetLoopEnd => {
+					if !audio_data.song_name.is_empty() {
+						self.set_loop_point(*action == ShortcutAction::SetLoopStart, audio_data.playback_position);
+					}
+				},
+				ShortcutAction::ClearLoop => {
+					self.loop_start = None;
+					self.loop_end = None;
+					self.send_ab_loop();
+				},
+				ShortcutAction::AddBookmark => {
+					if !audio_data.song_name.is_empty() {
+						self.add_bookmark(&audio_data.song_name, audio_data.playback_position, "");
+					}
+				},
+				ShortcutAction::NextBookmark | ShortcutAction::PreviousBookmark => {
+					let bookmarks = self.find_bookmarks(&audio_data.song_name);
+					let bookmark = if *action == ShortcutAction::NextBookmark {
+						library::next_bookmark(bookmarks, audio_data.playback_position)
+					} else {
+						library::previous_bookmark(bookmarks, audio_data.playback_position)
+					};
+					if let Some(position) = bookmark.map(|bookmark| bookmark.position_ms) {
+						send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekOnce(position));
+					}
+				},
+				ShortcutAction::NextChapter | ShortcutAction::PreviousChapter => {
+					let song_chapters = self.find_chapters(&audio_data.song_name);
+					let chapter = if *action == ShortcutAction::NextChapter {
+						chapters::next_chapter(song_chapters, audio_data.playback_position)
+					} else {
+						chapters::previous_chapter(song_chapters, audio_data.playback_position)
+					};
+					if let Some(position) = chapter.map(|chapter| chapter.start_ms) {
+						send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekOnce(position));
+					}
+				},
+				ShortcutAction::TogglePause | ShortcutAction::NextSong | ShortcutAction::PreviousSong | ShortcutAction::Refresh => {},
+			}
+		}
+	}
+
+	/* Tells the audio thread about the loop, or that there is none if a point is missing */
+	fn send_ab_loop(&self) {
+		let ab_loop = if let (Some(start_ms), Some(end_ms)) = (self.loop_start, self.loop_end) && start_ms < end_ms {
+			Some(audio_frontend::ABLoop {
+				start_ms,
+				end_ms,
+				count_in_ms: (self.loop_count_in_secs * 1000.0) as u64,
+				speed_step: self.loop_speed_step,
+				target_speed: self.loop_target_speed,
+			})
+		} else {
+			None
+		};
+		send_audio_signal(&self.audio_message_channel, MessageToAudio::SetABLoop(ab_loop));
+	}
+
+	/* Setting a point past the other one drops the other one, so the points can be set in any order */
+	fn set_loop_point(&mut self, is_start: bool, position: usize) {
+		if is_start {
+			self.loop_start = Some(position);
+			if self.loop_end.is_some_and(|end| end <= position) {
+				self.loop_end = None;
+			}
+		} else {
+			self.loop_end = Some(position);
+			if self.loop_start.is_some_and(|start| start >= position) {
+				self.loop_start = None;
+			}
+		}
+		self.send_ab_loop();
+	}
+
+	fn find_bookmarks(&mut self, song: &str) -> &[library::Bookmark] {
+		if self.bookmarks.as_ref().is_none_or(|(name, _)| name != song) {
+			let bookmarks = if let Ok(library) = self.library.lock() && let Some(record) = library.songs.get(song) {
+				record.bookmarks.clone()
+			} else {
+				Vec::new()
+			};
+			self.bookmarks = Some((song.to_string(), bookmarks));
+		}
+		self.bookmarks.as_ref().map(|(_, bookmarks)| bookmarks.as_slice()).unwrap_or_default()
+	}
+
+	/* An empty name gets replaced with the position */
+	fn add_bookmark(&mut self, song: &str, position: usize, name: &str) {
+		let name = if name.trim().is_empty() {format_position(position)} else {name.trim().to_string()};
+		if let Ok(mut library) = self.library.lock() {
+			let record = library::get_or_create_record(&mut library, song);
+			library::add_bookmark(record, position, &name);
+			self.bookmarks = Some((song.to_string(), record.bookmarks.clone()));
+		}
+	}
+
+	fn remove_bookmark(&mut self, song: &str, index: usize) {
+		if let Ok(mut library) = self.library.lock() {
+			let record = library::get_or_create_record(&mut library, song);
+			if index < record.bookmarks.len() {
+				record.bookmarks.remove(index);
+			}
+			self.bookmarks = Some((song.to_string(), record.bookmarks.clone()));
+		}
+	}
+
+	fn find_chapters(&mut self, song: &str) -> &[chapters::Chapter] {
+		if self.chapters.as_ref().is_none_or(|(name, _)| name != song) {
+			let song_chapters = if song.is_empty() {Vec::new()} else {chapters::find_chapters(song)};
+			self.chapters = Some((song.to_string(), song_chapters));
+		}
+		self.chapters.as_ref().map(|(_, song_chapters)| song_chapters.as_slice()).unwrap_or_default()
+	}
+
+	fn render_chapters(&mut self, ui: &mut egui::Ui, audio_data: &audio_frontend::RodioData) {
+		let song_chapters = self.find_chapters(&audio_data.song_name).to_vec();
+		let current = chapters::current_chapter(&song_chapters, audio_data.playback_position);
+		egui::ScrollArea::vertical().id_salt("Chapters").max_height(150.0).show(ui, |ui| {
+			for (i, chapter) in song_chapters.iter().enumerate() {
+				ui.horizontal(|ui| {
+					if ui.button(format_position(chapter.start_ms)).on_hover_text("Jump to the chapter").clicked() {
+						send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekOnce(chapter.start_ms));
+					}
+					if Some(i) == current {
+						ui.label(egui::RichText::new(&chapter.title).strong());
+					} else {
+						ui.label(&chapter.title);
+					}
+				});
+			}
+		});
+	}
+
+	fn render_ab_loop(&mut self, ui: &mut egui::Ui, audio_data: &audio_frontend::RodioData) {
+		let has_song = !audio_data.song_name.is_empty();
+		ui.horizontal(|ui| {
+			let point_text = |point: Option<usize>| point.map(format_position).unwrap_or("-".to_string());
+			if ui.add_enabled(has_song, egui::Button::new(format!("A: {}", point_text(self.loop_start))))
+			.on_hover_text("Sets the start of the loop to the current position").clicked() {
+				self.set_loop_point(true, audio_data.playback_position);
+			}
+			if ui.add_enabled(has_song, egui::Button::new(format!("B: {}", point_text(self.loop_end))))
+			.on_hover_text("Sets the end of the loop to the current position").clicked() {
+				self.set_loop_point(false, audio_data.playback_position);
+			}
+			if ui.button("Clear").clicked() {
+				self.loop_start = None;
+				self.loop_end = None;
+				self.send_ab_loop();
+			}
+			if audio_data.ab_loop.is_some() {
+				ui.label("Looping");
+			}
+		});
+		let mut changed = false;
+		ui.horizontal_wrapped(|ui| {
+			ui.label("Count-in: ");
+			changed |= ui.add(egui::DragValue::new(&mut self.loop_count_in_secs).range(0.0..=10.0).speed(0.1).suffix(" s"))
+				.on_hover_text("Silence before every repeat").changed();
+			ui.label("Speed up by: ");
+			changed |= ui.add(egui::DragValue::new(&mut self.loop_speed_step).range(0.0..=0.5).speed(0.01).max_decimals(2))
+				.on_hover_text("Added to the speed after every repeat, 0 keeps the speed the same").changed();
+			ui.label("up to: ");
+			changed |= ui.add(egui::DragValue::new(&mut self.loop_target_speed).range(0.5..=2.0).speed(0.01).max_decimals(2).suffix("x")).changed();
+		});
+		if changed {
+			self.send_ab_loop();
+		}
+	}
+
+	fn render_sleep_timer(&mut self, ui: &mut egui::Ui, audio_data: &audio_frontend::RodioData) {
+		let fade_ms = self.persistent_data.sleep_fade_secs * 1000;
+		if let Some(status) = audio_data.sleep_timer {
+			ui.horizontal(|ui| {
+				let mut text = match status.remaining {
+					audio_frontend::SleepTimer::AfterTime(ms) => {
+						let secs = ms.div_ceil(1000);
+						format!("Stopping in {}:{:02}", secs / 60, secs % 60)
+					},
+					audio_frontend::SleepTimer::AfterSongs(1) => "Stopping after this song".to_string(),
+					audio_frontend::SleepTimer::AfterSongs(songs) => format!("Stopping after {} songs", songs),
+				};
+				if status.fading {
+					text += ", fading out";
+				}
+				ui.label(text);
+				if ui.button("Cancel").clicked() {
+					send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSleepTimer(None, fade_ms));
+				}
+			});
+		}
+		ui.horizontal_wrapped(|ui| {
+			ui.add(egui::DragValue::new(&mut self.sleep_minutes).range(1..=600).suffix(" min"));
+			if ui.button("Start").on_hover_text("Pauses after this many minutes").clicked() {
+				send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSleepTimer(Some(audio_frontend::SleepTimer::AfterTime(self.sleep_minutes * 60000)), fade_ms));
+			}
+			ui.separator();
+			ui.add(egui::DragValue::new(&mut self.sleep_songs).range(1..=100).suffix(" songs"));
+			if ui.button("Start").on_hover_text("Pauses at the end of this many songs, counting the current one").clicked() {
+				send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSleepTimer(Some(audio_frontend::SleepTimer::AfterSongs(self.sleep_songs)), fade_ms));
+			}
+			ui.separator();
+			if ui.button("End of this song").clicked() {
+				send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSleepTimer(Some(audio_frontend::SleepTimer::AfterSongs(1)), fade_ms));
+			}
+		});
+		ui.horizontal(|ui| {
+			ui.label("Fade out over: ");
+			ui.add(egui::DragValue::new(&mut self.persistent_data.sleep_fade_secs).range(0..=120).suffix(" s"))
+				.on_hover_text("Applies to timers started after changing it");
+		});
+	}
+
+	fn render_recording(&mut self, ui: &mut egui::Ui, audio_data: &audio_frontend::RodioData) {
+		let format = self.persistent_data.record_format;
+		ui.horizontal(|ui| {
+			if self.record_tap.is_recording() {
+				if ui.button("■ Stop").clicked() {
+					self.last_recording = record::stop_recording(&self.record_tap).map(Ok);
+				}
+				ui.label(format!("Recording {} to {}", format_position(self.record_tap.recorded_ms() as usize), extract_file_name(&self.record_tap.path())));
+			} else {
+				if ui.button("⏺ Record").on_hover_text("Records everything that plays, with the speed and volume it plays at").clicked() {
+					let path = record::output_path(&self.persistent_data.record_folder, &self.persistent_data.record_template, &audio_data.song_name, format);
+					self.last_recording = record::start_recording(&self.record_tap, &path, format).err().map(Err);
+				}
+				match &self.last_recording {
+					Some(Ok(path)) => {ui.label(format!("Saved {}", extract_file_name(path))).on_hover_text(path);},
+					Some(Err(err)) => {ui.label(egui::RichText::new(err).color(egui::Color32::RED));},
+					None => {},
+				}
+			}
+		});
+		ui.horizontal_wrapped(|ui| {
+			ui.label("Format: ");
+			egui::ComboBox::from_id_salt("Record Format")
+				.selected_text(record::format_to_str(format))
+				.show_ui(ui, |ui| {
+					for option in [record::RecordFormat::Wav, record::RecordFormat::Flac] {
+						ui.selectable_value(&mut self.persistent_data.record_format, option, record::format_to_str(option));
+					}
+				});
+			ui.label("Name: ");
+			ui.add(egui::TextEdit::singleline(&mut self.persistent_data.record_template).desired_width(160.0))
+				.on_hover_text("{song}, {date} and {time} get filled in");
+		});
+		ui.horizontal(|ui| {
+			ui.label("Folder: ");
+			ui.add(egui::TextEdit::singleline(&mut self.persistent_data.record_folder).desired_width(200.0));
+			if ui.button("Current folder").clicked() {
+				self.persistent_data.record_folder = self.current_song_folder.clone();
+			}
+		});
+		ui.horizontal_wrapped(|ui| {
+			let rendering = self.render_job.as_ref().is_some_and(|(_, job)| job.result().is_none());
+			if ui.add_enabled(!rendering && !audio_data.song_name.is_empty(), egui::Button::new("Export this song"))
+			.on_hover_text("Writes the song to a file at the current speed, without playing it").clicked() {
+				self.export_song(&audio_data.song_name);
+			}
+			ui.label("Fade in: ");
+			ui.add(egui::DragValue::new(&mut self.export_fade_in_secs).range(0.0..=30.0).speed(0.1).suffix(" s"));
+			ui.label("Fade out: ");
+			ui.add(egui::DragValue::new(&mut self.export_fade_out_secs).range(0.0..=30.0).speed(0.1).suffix(" s"));
+			ui.checkbox(&mut self.export_normalize, "Normalize").on_hover_text("Makes the loudest part of the song peak at -1 dB");
+		});
+		render_job_status(ui, &mut self.render_job);
+	}
+
+	fn export_song(&mut self, song: &str) {
+		let format = self.persistent_data.record_format;
+		let path = record::output_path(&self.persistent_data.record_folder, &self.persistent_data.record_template, song, format);
+		let settings = record::RenderSettings {
+			speed: self.song_speed,
+			fade_in_ms: (self.export_fade_in_secs * 1000.0) as u64,
+			fade_out_ms: (self.export_fade_out_secs * 1000.0) as u64,
+			normalize: self.export_normalize,
+			..record::default_render_settings()
+		};
+		let job = record::start_render(vec![song.to_string()], path, format, settings);
+		self.render_job = Some((extract_file_name(song).to_string(), job));
+	}
+
+	/* The tags of a song as they are on disk, read only the first time they're asked for */
+	fn current_tags(&mut self, song: &str) -> &tags::SongTags {
+		self.tag_current.entry(song.to_string()).or_insert_with(|| tags::read_tags(song))
+	}
+
+	fn render_song_info(&mut self, ui: &mut egui::Ui, song: &str) {
+		if song.is_empty() {
+			return;
+		}
+		let song_tags = self.current_tags(song).clone();
+		egui::Grid::new("Song info").num_columns(2).show(ui, |ui| {
+			for field in tag_editor::ALL_TAG_FIELDS {
+				let value = tag_editor::field_value(&song_tags, field);
+				if !value.is_empty() {
+					ui.label(egui::RichText::new(tag_editor::field_to_str(field)).weak());
+					ui.label(value);
+					ui.end_row();
+				}
+			}
+		});
+		if ui.button("Edit tags").clicked() {
+			self.tag_selection = HashSet::from([song.to_string()]);
+			self.central_panel_mode = CentralPanelMode::TagEditor;
+		}
+	}
+
+	/*
+	 * The changes to make to each selected song: the fields that were ticked, plus what the file name pattern
+	 * reads out of the song's name (for the fields that weren't ticked).
+	 */
+	fn tag_edits(&self, songs: &[String]) -> Vec<(String, tag_editor::TagChanges)> {
+		let field_changes: tag_editor::TagChanges = self.tag_fields.iter()
+			.filter(|(_, change, _)| *change)
+			.map(|(field, _, value)| (*field, value.clone()))
+			.collect();
+		songs.iter()
+			.filter(|song| self.tag_selection.contains(*song))
+			.filter_map(|song| {
+				let mut changes = field_changes.clone();
+				if self.tag_use_pattern && let Some(from_name) = tag_editor::tags_from_file_name(&self.tag_pattern, song) {
+					for (field, value) in from_name {
+						if !changes.iter().any(|(changed, _)| *changed == field) {
+							changes.push((field, value));
+						}
+					}
+				}
+				if changes.is_empty() {None} else {Some((song.clone(), changes))}
+			})
+			.collect()
+	}
+
+	/* Picks up a finished tag write: remembers it for undo and makes everything showing tags read them again */
+	fn finish_tag_write(&mut self) {
+		let (is_undo, result) = if let Some((is_undo, job)) = &self.tag_write_job && let Some(result) = job.result() {(*is_undo, result)} else {return;};
+		self.tag_write_job = None;
+		let (previous_tags, errors) = result;
+		let songs: Vec<String> = previous_tags.iter().map(|(song, _)| song.clone()).collect();
+		if let Ok(mut library) = self.library.lock() {
+			for song in &songs {
+				tag_editor::update_library_record(&mut library, song, &tags::read_tags(song));
+			}
+		}
+		index::reindex_songs(&self.song_index, &songs);
+		if !is_undo && !previous_tags.is_empty() {
+			self.tag_undo.push(previous_tags);
+		}
+		self.tag_errors = errors;
+		self.tag_current.clear();
+		self.tag_fields_from = None;
+		self.track_rows = None;
+		self.track_row_job = None;
+	}
+
+	/*
+	 * Picks up songs and folders that changed on disk: open folders get read again (or closed if they're gone)
+	 * and the trees built from them get rebuilt, which also gives the audio thread the new song collection.
+	 */
+	fn apply_folder_changes(&mut self, current_song: &str) {
+		let open_folders: Vec<String> = self.directory_map.keys().cloned().collect();
+		watch::watch_folders(&self.folder_watcher, roots::library_roots(&self.persistent_data), open_folders);
+		let changes = watch::take_changes(&self.folder_watcher);
+		if changes.folders.is_empty() && changes.removed_songs.is_empty() {
+			return;
+		}
+		let mut tree_changed = false;
+		for folder in &changes.folders {
+			if self.directory_map.contains_key(folder) {
+				if !init_directory_at_filepath(folder, &mut self.directory_map) {
+					self.directory_map.remove(folder);
+				}
+				tree_changed = true;
+			}
+		}
+		/* The open folder itself is gone, go up to the closest one that is still there */
+		if !self.current_song_folder.is_empty() && !std::path::Path::new(&self.current_song_folder).is_dir() {
+			while !self.current_song_folder.is_empty() && !std::path::Path::new(&self.current_song_folder).is_dir() {
+				self.current_song_folder = song_folder_go_up(&self.current_song_folder);
+			}
+			self.active_directory_filepath = self.current_song_folder.clone();
+			self.search_text = "".to_string();
+			tree_changed = true;
+		}
+		if tree_changed {
+			self.directory_tree = None;
+			self.searched_directory_tree = None;
+			self.active_search_text = "".to_string();
+			self.track_rows = None;
+			self.track_row_job = None;
+		}
+		for song in &changes.removed_songs {
+			self.tag_current.remove(song);
+			self.tag_selection.remove(song);
+		}
+		if changes.removed_songs.iter().any(|song| song == current_song) {
+			self.deleted_song = Some(current_song.to_string());
+		} else if self.deleted_song.as_ref().is_some_and(|song| std::path::Path::new(song).exists()) {
+			self.deleted_song = None;
+		}
+	}
+
+	fn render_tag_editor(&mut self, ui: &mut egui::Ui) {
+		ui.horizontal(|ui| {
+			if ui.button("⬅").on_hover_text("Back to the tracks").clicked() {
+				self.central_panel_mode = CentralPanelMode::Tracks;
+			}
+			ui.heading("Edit Tags");
+		});
+		/* The open folder or playlist, plus anything picked from elsewhere (e.g. the song playing) */
+		let mut songs = self.track_table_source().map(|(_, songs)| songs).unwrap_or_default();
+		let mut elsewhere: Vec<String> = self.tag_selection.iter().filter(|song| !songs.contains(song)).cloned().collect();
+		elsewhere.sort();
+		songs.splice(0..0, elsewhere);
+		if songs.is_empty() {
+			ui.label("Open a folder or a playlist to edit the tags of its songs");
+			return;
+		}
+
+		ui.horizontal(|ui| {
+			if ui.button("Select all").clicked() {
+				self.tag_selection = songs.iter().cloned().collect();
+			}
+			if ui.button("Select none").clicked() {
+				self.tag_selection.clear();
+			}
+			ui.label(format!("{} of {} selected", self.tag_selection.len(), songs.len()));
+		});
+		egui::ScrollArea::vertical().id_salt("Tag songs").max_height(150.0).show(ui, |ui| {
+			ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+			for song in &songs {
+				let mut selected = self.tag_selection.contains(song);
+				if ui.checkbox(&mut selected, extract_file_name(song)).on_hover_text(song).changed() {
+					if selected {self.tag_selection.insert(song.clone());} else {self.tag_selection.remove(song);}
+				}
+			}
+		});
+
+		/* Editing a single song starts from its current tags */
+		let single = if self.tag_selection.len() == 1 {self.tag_selection.iter().next().cloned()} else {None};
+		if single.is_some() && single != self.tag_fields_from && let Some(song) = &single {
+			let song_tags = self.current_tags(song).clone();
+			for (field, change, value) in &mut self.tag_fields {
+				*change = false;
+				*value = tag_editor::field_value(&song_tags, *field).to_string();
+			}
+			self.tag_fields_from = single;
+		}
+
+		ui.add_space(5.0);
+		ui.label(egui::RichText::new("Ticked fields are set on every selected song, an empty field gets removed.").weak());
+		egui::Grid::new("Tag fields").num_columns(2).show(ui, |ui| {
+			for (field, change, value) in &mut self.tag_fields {
+				ui.checkbox(change, tag_editor::field_to_str(*field));
+				if ui.add(egui::TextEdit::singleline(value).desired_width(250.0)).changed() {
+					*change = true;
+				}
+				ui.end_row();
+			}
+		});
+		ui.horizontal(|ui| {
+			ui.checkbox(&mut self.tag_use_pattern, "From file names:");
+			if ui.add(egui::TextEdit::singleline(&mut self.tag_pattern).desired_width(250.0)).changed() {
+				self.tag_use_pattern = true;
+			}
+		}).response.on_hover_text("Reads the unticked fields out of the file names. Use %title%, %artist%, %album%, %genre%, %track% and %year%");
+
+		let edits = self.tag_edits(&songs);
+		ui.add_space(5.0);
+		ui.label(egui::RichText::new(format!("Preview ({} songs change)", edits.len())).strong());
+		let preview: Vec<(String, tags::SongTags, tags::SongTags)> = edits.iter()
+			.map(|(song, changes)| {
+				let current = self.current_tags(song).clone();
+				let new_tags = tag_editor::apply_changes(&current, changes);
+				(song.clone(), current, new_tags)
+			})
+			.collect();
+		egui::ScrollArea::both().id_salt("Tag preview").max_height(200.0).show(ui, |ui| {
+			egui::Grid::new("Tag preview grid").striped(true).show(ui, |ui| {
+				ui.label(egui::RichText::new("File").strong());
+				for field in tag_editor::ALL_TAG_FIELDS {
+					ui.label(egui::RichText::new(tag_editor::field_to_str(field)).strong());
+				}
+				ui.end_row();
+				for (song, current, new_tags) in &preview {
+					ui.label(extract_file_name(song));
+					for field in tag_editor::ALL_TAG_FIELDS {
+						let (old, new) = (tag_editor::field_value(current, field), tag_editor::field_value(new_tags, field));
+						if old == new {
+							ui.label(new);
+						} else {
+							ui.label(egui::RichText::new(new).strong().color(ui.visuals().warn_fg_color)).on_hover_text(format!("Was: {}", old));
+						}
+					}
+					ui.end_row();
+				}
+			});
+		});
+
+		ui.add_space(5.0);
+		if let Some((_, job)) = &self.tag_write_job {
+			ui.horizontal(|ui| {
+				ui.add(egui::ProgressBar::new(job.progress()).desired_width(150.0).show_percentage());
+				ui.label(format!("Writing tags of {} songs", job.total));
+			});
+		} else {
+			ui.horizontal(|ui| {
+				if ui.add_enabled(!edits.is_empty(), egui::Button::new("Apply")).clicked() {
+					self.tag_write_job = Some((false, tag_editor::start_tag_write(edits)));
+				}
+				if ui.add_enabled(!self.tag_undo.is_empty(), egui::Button::new("Undo")).on_hover_text("Puts back the tags from before the last edit").clicked()
+				&& let Some(previous_tags) = self.tag_undo.pop() {
+					self.tag_write_job = Some((true, tag_editor::start_tag_restore(previous_tags)));
+				}
+			});
+		}
+		for error in &self.tag_errors {
+			ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+		}
+	}
+
+	/* Deletes the other copies in the groups, then drops the groups from the list */
+	fn remove_duplicates(&mut self, groups: &[usize]) {
+		let Some(found) = &mut self.duplicate_groups else {return;};
+		let mut removed = Vec::new();
+		self.duplicate_errors.clear();
+		if let Ok(mut library) = self.library.lock() {
+			for group in groups.iter().filter_map(|i| found.get(*i)) {
+				let (group_removed, errors) = duplicates::remove_copies(group, &mut self.persistent_data.playlists, &mut library);
+				removed.extend(group_removed);
+				self.duplicate_errors.extend(errors);
+			}
+			if library::write_library(&library).is_err() {
+				println!("Error in saving library");
+			}
+		}
+		let mut i = 0;
+		found.retain(|_| {
+			i += 1;
+			!groups.contains(&(i - 1))
+		});
+		if removed.is_empty() {
+			return;
+		}
+		index::update_songs(&self.song_index, &removed, &[]);
+		for song in &removed {
+			self.tag_current.remove(song);
+			self.tag_selection.remove(song);
+		}
+		self.playlist_tree = None;
+		self.searched_playlist_tree = None;
+		self.track_rows = None;
+		self.track_row_job = None;
+		if self.persistent_data.data_file_exists {
+			let write_to = build_full_filepath(&self.installed_location, "internal_pinetree_data.txt");
+			if write_internal_data(&write_to, &self.persistent_data).is_err() {
+				println!("Error in saving");
+			}
+		}
+	}
+
+	fn render_duplicates(&mut self, ui: &mut egui::Ui) {
+		ui.horizontal(|ui| {
+			if ui.button("⬅").on_hover_text("Back to the tracks").clicked() {
+				self.central_panel_mode = CentralPanelMode::Tracks;
+			}
+			ui.heading("Duplicates");
+		});
+		ui.label(egui::RichText::new("Finds identical files, the same recording encoded or tagged differently, and songs with the same artist and title.").weak());
+		if let Some(scan) = &self.duplicate_scan {
+			ui.horizontal(|ui| {
+				ui.add(egui::ProgressBar::new(scan.progress()).desired_width(150.0).show_percentage());
+				ui.label(format!("Looking through {} songs", scan.total / 2));
+			});
+			return;
+		}
+		ui.horizontal(|ui| {
+			let (songs, scanning): (Vec<index::IndexedSong>, bool) = if let Ok(index) = self.song_index.lock() {(index.songs.values().cloned().collect(), index.scanning)} else {(Vec::new(), false)};
+			if ui.add_enabled(!songs.is_empty(), egui::Button::new("Search library")).on_hover_text("Reads every song, this can take a while the first time").clicked() {
+				self.duplicate_scan = Some(duplicates::start_duplicate_scan(songs, Arc::clone(&self.library)));
+				self.duplicate_confirm = None;
+				self.duplicate_errors.clear();
+			}
+			if scanning {
+				ui.label(egui::RichText::new("The library is still being indexed").weak());
+			}
+		});
+		for error in &self.duplicate_errors {
+			ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+		}
+		let Some(groups) = &mut self.duplicate_groups else {return;};
+		if groups.is_empty() {
+			ui.label("No duplicates found");
+			return;
+		}
+
+		let mut remove: Option<Vec<usize>> = None;
+		if let Some(confirm) = &self.duplicate_confirm {
+			let file_count: usize = confirm.iter().filter_map(|i| groups.get(*i)).map(|group| group.copies.len() - 1).sum();
+			ui.horizontal(|ui| {
+				ui.label(egui::RichText::new(format!("Delete {} files?", file_count)).strong().color(ui.visuals().warn_fg_color));
+				if ui.button("Yes").clicked() {
+					remove = Some(confirm.clone());
+				}
+				if ui.button("No").clicked() {
+					remove = Some(Vec::new());
+				}
+			});
+		} else {
+			/* Only identical files are safe to remove in one go, the other kinds can be different takes of a song */
+			let identical: Vec<usize> = (0..groups.len()).filter(|i| groups[*i].kind == duplicates::DuplicateKind::SameFile).collect();
+			if !identical.is_empty() && ui.button(format!("Keep the picked copy in all {} groups of identical files", identical.len())).on_hover_text("Deletes every other copy").clicked() {
+				self.duplicate_confirm = Some(identical);
+			}
+		}
+
+		let mut ignored: Option<usize> = None;
+		egui::ScrollArea::vertical().id_salt("Duplicate groups").show(ui, |ui| {
+			ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+			for (i, group) in groups.iter_mut().enumerate() {
+				ui.add_space(5.0);
+				ui.label(egui::RichText::new(duplicates::kind_to_str(group.kind)).strong());
+				for (j, copy) in group.copies.iter().enumerate() {
+					ui.horizontal(|ui| {
+						ui.radio_value(&mut group.keep, j, "").on_hover_text("Keep this one");
+						ui.label(format!("{} kbps · {} · {:.1} MB", copy.bitrate_kbps, format_position(copy.length_ms as usize), copy.size as f64 / 1_000_000.0));
+						if j == group.best {
+							ui.label(egui::RichText::new("(best)").weak());
+						}
+						ui.label(&copy.path).on_hover_text(&copy.path);
+					});
+				}
+				ui.horizontal(|ui| {
+					if ui.button("Keep picked").on_hover_text("Deletes the other copies").clicked() {
+						self.duplicate_confirm = Some(vec![i]);
+					}
+					if ui.button("Ignore").on_hover_text("Not duplicates, leave them all").clicked() {
+						ignored = Some(i);
+					}
+				});
+			}
+		});
+
+		if let Some(i) = ignored {
+			groups.remove(i);
+			self.duplicate_confirm = None;
+		}
+		if let Some(groups) = remove {
+			self.duplicate_confirm = None;
+			self.remove_duplicates(&groups);
+		}
+	}
+
+	/* The open playlist, or the folder being browsed, along with the id its sort is saved under */
+	fn track_table_source(&self) -> Option<(String, Vec<String>)> {
+		if self.browse_mode == LeftPanelMode::Files {
+			self.directory_map.get(&self.active_directory_filepath).map(|directory| (self.active_directory_filepath.clone(), directory.songs.clone()))
+		} else {
+			self.active_playlist_index
+				.and_then(|index| self.persistent_data.playlists.get(index))
+				.map(|playlist| (track_table::playlist_sort_id(&playlist.name), playlist.songs.clone()))
+		}
+	}
+
+	fn render_track_table(&mut self, ui: &mut egui::Ui, current_song: &str) {
+		let (id, songs) = if let Some(source) = self.track_table_source() {source} else {
+			ui.vertical_centered(|ui| ui.label("Open a folder or a playlist to see its tracks"));
+			return;
+		};
+		if self.track_rows.as_ref().is_none_or(|(rows_id, _, rows)| *rows_id != id || rows.len() != songs.len()) {
+			if self.track_row_job.as_ref().is_none_or(|(job_id, job)| *job_id != id || job.total != songs.len()) {
+				self.track_row_job = Some((id.clone(), track_table::start_track_rows(songs, Arc::clone(&self.library))));
+			}
+			if let Some((_, job)) = &self.track_row_job && let Some(rows) = job.result() {
+				self.track_rows = Some((id.clone(), Vec::new(), rows));
+				self.track_row_job = None;
+			} else if let Some((_, job)) = &self.track_row_job {
+				ui.horizontal(|ui| {
+					ui.add(egui::ProgressBar::new(job.progress()).desired_width(150.0).show_percentage());
+					ui.label(format!("Reading {} songs", job.total));
+				});
+				ui.ctx().request_repaint_after(std::time::Duration::from_millis(100));
+				return;
+			}
+		}
+		let mut keys = self.persistent_data.table.sorts.get(&id).cloned().unwrap_or_default();
+		if let Some((_, sorted_by, rows)) = &mut self.track_rows && *sorted_by != keys {
+			track_table::sort_rows(rows, &keys);
+			*sorted_by = keys.clone();
+		}
+		ui.horizontal(|ui| {
+			if ui.button("Edit tags").on_hover_text("Change the tags of some or all of these songs").clicked() {
+				self.central_panel_mode = CentralPanelMode::TagEditor;
+			}
+			if ui.button("Find duplicates").on_hover_text("Look for songs that are in the library more than once").clicked() {
+				self.central_panel_mode = CentralPanelMode::Duplicates;
+			}
+		});
+		let rows = if let Some((_, _, rows)) = &self.track_rows {rows} else {return;};
+		ui.label(egui::RichText::new("Click a header to sort, shift-click to sort by more columns. Right-click a header to move or hide columns.").weak());
+
+		let columns = self.persistent_data.table.columns.clone();
+		let mut clicked_header: Option<(track_table::TableColumn, bool)> = None;
+		let mut moved_column: Option<(track_table::TableColumn, isize)> = None;
+		let mut toggled_column: Option<track_table::TableColumn> = None;
+		let mut played: Option<usize> = None;
+		let row_height = ui.text_style_height(&egui::TextStyle::Body) + 6.0;
+		/* Resized widths are kept per column layout */
+		ui.push_id(&columns, |ui| {
+			let mut table = egui_extras::TableBuilder::new(ui)
+				.striped(true)
+				.cell_layout(egui::Layout::left_to_right(egui::Align::Center))
+				.column(egui_extras::Column::auto());
+			for column in &columns {
+				let width = match column {
+					track_table::TableColumn::Title => 220.0,
+					track_table::TableColumn::Artist | track_table::TableColumn::Album => 140.0,
+					_ => 80.0,
+				};
+				table = table.column(egui_extras::Column::initial(width).at_least(30.0).resizable(true).clip(true));
+			}
+			table.header(row_height, |mut header| {
+				header.col(|_| {});
+				for column in &columns {
+					header.col(|ui| {
+						let mut text = track_table::column_to_str(*column).to_string();
+						if let Some(index) = keys.iter().position(|key| key.column == *column) {
+							text += if keys[index].ascending {" ▲"} else {" ▼"};
+							if keys.len() > 1 {
+								text += &format!("{}", index + 1);
+							}
+						}
+						let response = ui.add(egui::Label::new(egui::RichText::new(text).strong()).sense(egui::Sense::click()));
+						if response.clicked() {
+							clicked_header = Some((*column, ui.input(|input| input.modifiers.shift)));
+						}
+						response.context_menu(|ui| {
+							if ui.button("Move left").clicked() {
+								moved_column = Some((*column, -1));
+								ui.close_menu();
+							}
+							if ui.button("Move right").clicked() {
+								moved_column = Some((*column, 1));
+								ui.close_menu();
+							}
+							ui.separator();
+							for option in track_table::ALL_COLUMNS {
+								let mut shown = columns.contains(&option);
+								if ui.checkbox(&mut shown, track_table::column_to_str(option)).clicked() {
+									toggled_column = Some(option);
+								}
+							}
+						});
+					});
+				}
+			}).body(|body| {
+				body.rows(row_height, rows.len(), |mut table_row| {
+					let index = table_row.index();
+					let row = &rows[index];
+					let is_current = row.path == current_song;
+					table_row.set_selected(is_current);
+					table_row.col(|ui| {
+						if ui.small_button("▶").clicked() {
+							played = Some(index);
+						}
+					});
+					for column in &columns {
+						table_row.col(|ui| {
+							let text = match column {
+								track_table::TableColumn::Title => track_table::display_title(row).to_string(),
+								track_table::TableColumn::Artist => row.artist.clone(),
+								track_table::TableColumn::Album => row.album.clone(),
+								track_table::TableColumn::Duration => track_table::format_duration(row.duration_ms),
+								track_table::TableColumn::Track => if row.track > 0 {row.track.to_string()} else {"".to_string()},
+								track_table::TableColumn::PlayCount => row.play_count.to_string(),
+								track_table::TableColumn::DateAdded => if row.date_added > 0 {track_table::format_date(row.date_added)} else {"".to_string()},
+								track_table::TableColumn::FileSize => track_table::format_file_size(row.file_size),
+							};
+							if is_current {
+								ui.label(egui::RichText::new(text).strong());
+							} else {
+								ui.label(text);
+							}
+						});
+					}
+				});
+			});
+		});
+
+		if let Some(index) = played {
+			/* Next and previous go through the table in the order it's sorted in */
+			let collection: Vec<String> = rows.iter().map(|row| row.path.clone()).collect();
+			let song = collection[index].clone();
+			send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSongCollection(collection, Some(index)));
+			send_audio_signal(&self.audio_message_channel, MessageToAudio::PlaySong(song));
+		}
+		if let Some((column, add_key)) = clicked_header {
+			track_table::click_header(&mut keys, column, add_key);
+			self.persistent_data.table.sorts.insert(id, keys);
+		}
+		if let Some((column, direction)) = moved_column {
+			track_table::move_column(&mut self.persistent_data.table.columns, column, direction);
+		}
+		if let Some(column) = toggled_column {
+			let shown = &mut self.persistent_data.table.columns;
+			if let Some(index) = shown.iter().position(|shown_column| *shown_column == column) {
+				if shown.len() > 1 {
+					shown.remove(index);
+				}
+			} else {
+				shown.push(column);
+			}
+		}
+	}
 
-			installer_error: None,
+	fn render_bookmarks(&mut self, ui: &mut egui::Ui, audio_data: &audio_frontend::RodioData) {
+		let song = &audio_data.song_name;
+		if song.is_empty() {
+			ui.vertical_centered(|ui| ui.label("No song playing"));
+			return;
+		}
+		ui.horizontal(|ui| {
+			let name_field = ui.add(egui::TextEdit::singleline(&mut self.new_bookmark_name).hint_text("Bookmark name..."));
+			let entered = name_field.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+			if ui.button("Add").on_hover_text("Bookmarks the current position").clicked() || entered {
+				let name = std::mem::take(&mut self.new_bookmark_name);
+				self.add_bookmark(song, audio_data.playback_position, &name);
+			}
+		});
+		let bookmarks = self.find_bookmarks(song).to_vec();
+		if bookmarks.is_empty() {
+			ui.label("No bookmarks yet");
+		}
+		let mut removed: Option<usize> = None;
+		for (i, bookmark) in bookmarks.iter().enumerate() {
+			ui.horizontal(|ui| {
+				if ui.button(format_position(bookmark.position_ms)).on_hover_text("Jump to the bookmark").clicked() {
+					send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekOnce(bookmark.position_ms));
+				}
+				ui.label(&bookmark.name);
+				if ui.small_button("🗑").on_hover_text("Remove the bookmark").clicked() {
+					removed = Some(i);
+				}
+			});
+		}
+		if let Some(index) = removed {
+			self.remove_bookmark(song, index);
+		}
+	}
 
-			save_err: SaveError::None,
+	fn render_lyrics(&mut self, ui: &mut egui::Ui, song: &str, playback_position: usize) {
+		if song.is_empty() {
+			ui.vertical_centered(|ui| ui.label("No song playing"));
+			return;
+		}
+		if self.lyrics.as_ref().is_none_or(|(name, _)| name != song) {
+			self.lyrics = Some((song.to_string(), lyrics::find_lyrics(song)));
+			self.lyrics_offset_ms = if let Ok(library) = self.library.lock() && let Some(record) = library.songs.get(song) {record.lyrics_offset_ms} else {0};
+			self.last_lyrics_line = None;
+		}
+		let song_lyrics = if let Some((_, Some(song_lyrics))) = &self.lyrics {
+			song_lyrics
+		} else {
+			ui.vertical_centered(|ui| ui.label("No lyrics found").on_hover_text("Looks for an .lrc file with the same name as the song, or lyrics embedded in its tags"));
+			return;
+		};
 
-			pinned_mode: false,
+		if song_lyrics.synced {
+			let previous_offset = self.lyrics_offset_ms;
+			ui.horizontal(|ui| {
+				ui.label("Offset: ");
+				if ui.button("-0.5 s").on_hover_text("Show the lyrics earlier").clicked() {
+					self.lyrics_offset_ms -= 500;
+				}
+				ui.label(format!("{:+.1} s", self.lyrics_offset_ms as f32 / 1000.0));
+				if ui.button("+0.5 s").on_hover_text("Show the lyrics later").clicked() {
+					self.lyrics_offset_ms += 500;
+				}
+				if ui.button("Reset").clicked() {
+					self.lyrics_offset_ms = 0;
+				}
+			});
+			if previous_offset != self.lyrics_offset_ms && let Ok(mut library) = self.library.lock() {
+				library::get_or_create_record(&mut library, song).lyrics_offset_ms = self.lyrics_offset_ms;
+			}
 		}
-	}
-}
 
-fn trim_slash_if_exists(to_trim: &str) -> &str {
-	if to_trim.ends_with('/') || to_trim.ends_with('\\') {
-		return &to_trim[..to_trim.len() - 1];
-	} else {
-		return &to_trim;
+		let current_line = lyrics::current_line(song_lyrics, playback_position as u64, self.lyrics_offset_ms);
+		let line_changed = current_line != self.last_lyrics_line;
+		self.last_lyrics_line = current_line;
+		egui::ScrollArea::vertical().auto_shrink([false, false]).show(ui, |ui| {
+			ui.vertical_centered(|ui| {
+				for (i, line) in song_lyrics.lines.iter().enumerate() {
+					let text = if line.text.is_empty() {"♪"} else {&line.text};
+					let text = if Some(i) == current_line {
+						egui::RichText::new(text).strong().size(16.0).color(ui.visuals().strong_text_color())
+					} else {
+						egui::RichText::new(text).weak()
+					};
+					let response = if song_lyrics.synced {
+						ui.add(egui::Label::new(text).sense(egui::Sense::click())).on_hover_cursor(egui::CursorIcon::PointingHand)
+					} else {
+						ui.label(text)
+					};
+					if response.clicked() && let Some(position) = lyrics::line_position(line, self.lyrics_offset_ms) {
+						send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekOnce(position));
+					}
+					if Some(i) == current_line && line_changed {
+						response.scroll_to_me(Some(egui::Align::Center));
+					}
+				}
+			});
+		});
 	}
-}
 
-fn build_full_filepath(input_first_half: &str, input_second_half: &str) -> String {
-	let mergeable_first_half = trim_slash_if_exists(input_first_half);
-	#[cfg(target_family = "windows")]
-	return format!("{}\\{}", mergeable_first_half, input_second_half);
-	#[cfg(target_family = "unix")]
-	return format!("{}/{}", mergeable_first_half, input_second_half);
-}
+	fn render_visualiser(&mut self, ui: &mut egui::Ui) {
+		let now = std::time::Instant::now();
+		let elapsed = now.duration_since(self.last_visualiser_update).as_secs_f32();
+		self.last_visualiser_update = now;
+		visualiser::update_visualiser(&mut self.visualiser, &self.sample_tap, &self.persistent_data.visualiser, elapsed);
+		/* The rest of the GUI is fine at 8 fps, this isn't */
+		ui.ctx().request_repaint_after(std::time::Duration::from_millis(33));
+
+		ui.horizontal_wrapped(|ui| {
+			let settings = &mut self.persistent_data.visualiser;
+			ui.label("Sensitivity: ");
+			ui.add(egui::Slider::new(&mut settings.sensitivity_db, -20.0..=20.0).suffix(" dB").step_by(0.5));
+			ui.label("Bands: ");
+			ui.add(egui::Slider::new(&mut settings.band_count, visualiser::MIN_BAND_COUNT..=visualiser::MAX_BAND_COUNT));
+			ui.label("Decay: ");
+			ui.add(egui::Slider::new(&mut settings.decay_secs, 0.0..=2.0).suffix(" s"));
+			ui.checkbox(&mut settings.oscilloscope, "Oscilloscope");
+		});
+		self.render_save_settings(ui);
+		ui.add_space(5.0);
+
+		let visuals = ui.visuals().clone();
+		let scope_height = if self.persistent_data.visualiser.oscilloscope {80.0} else {0.0};
+		let meter_height = 2.0 * 12.0 + ui.spacing().item_spacing.y;
+		let spectrum_height = (ui.available_height() - scope_height - meter_height - 3.0 * ui.spacing().item_spacing.y).max(60.0);
+
+		let (spectrum_rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), spectrum_height), egui::Sense::hover());
+		let painter = ui.painter_at(spectrum_rect);
+		painter.rect_filled(spectrum_rect, 2.0, visuals.extreme_bg_color);
+		let band_width = spectrum_rect.width() / self.visualiser.bands.len().max(1) as f32;
+		for (i, band) in self.visualiser.bands.iter().enumerate() {
+			let left = spectrum_rect.left() + i as f32 * band_width;
+			let bar = egui::Rect::from_min_max(
+				egui::pos2(left + 1.0, spectrum_rect.bottom() - band * spectrum_rect.height()),
+				egui::pos2(left + band_width - 1.0, spectrum_rect.bottom()));
+			painter.rect_filled(bar, 0.0, visuals.selection.bg_fill);
+		}
 
-fn init_directory_at_filepath(directory_filepath: &str, dir_map: &mut HashMap<String, Directory>) -> bool {
-	let read_result = std::fs::read_dir(directory_filepath);
-	if let Ok(paths) = read_result {
-		let mut songs_vec = Vec::<String>::new();
-		let mut subdirectory_vec = Vec::<String>::new();
+		for (channel, name) in ["L", "R"].iter().enumerate() {
+			ui.horizontal(|ui| {
+				ui.label(*name);
+				let (meter_rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 12.0), egui::Sense::hover());
+				let painter = ui.painter_at(meter_rect);
+				painter.rect_filled(meter_rect, 2.0, visuals.extreme_bg_color);
+				let level = self.visualiser.levels[channel];
+				/* Green up to -12 dB, yellow up to -3 dB, red above that */
+				let color = if level > 0.95 {egui::Color32::RED} else if level > 0.8 {egui::Color32::YELLOW} else {egui::Color32::GREEN};
+				let filled = egui::Rect::from_min_max(meter_rect.min, egui::pos2(meter_rect.left() + level * meter_rect.width(), meter_rect.bottom()));
+				painter.rect_filled(filled, 2.0, color.gamma_multiply(0.8));
+			});
+		}
 
-		for path in paths {
-			if let Ok(valid_path) = path {
-				if let Ok(file_name) = valid_path.file_name().into_string() {
+		if self.persistent_data.visualiser.oscilloscope {
+			let (scope_rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), scope_height), egui::Sense::hover());
+			let painter = ui.painter_at(scope_rect);
+			painter.rect_filled(scope_rect, 2.0, visuals.extreme_bg_color);
+			let scope = &self.visualiser.scope;
+			if scope.len() > 1 {
+				let step = scope_rect.width() / (scope.len() - 1) as f32;
+				let points: Vec<egui::Pos2> = scope.iter().enumerate().map(|(i, sample)| {
+					egui::pos2(scope_rect.left() + i as f32 * step, scope_rect.center().y - sample.clamp(-1.0, 1.0) * scope_rect.height() / 2.0)
+				}).collect();
+				painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, visuals.selection.bg_fill)));
+			} else {
+				painter.hline(scope_rect.x_range(), scope_rect.center().y, egui::Stroke::new(1.5, visuals.selection.bg_fill));
+			}
+		}
+	}
 
-					if let Ok(file_type) = valid_path.file_type() && file_type.is_dir() {
-						subdirectory_vec.push(build_full_filepath(directory_filepath, &file_name));
-					} else if file_name.ends_with(".mp3") {
-						songs_vec.push(build_full_filepath(directory_filepath, &file_name));
+	fn render_save_settings(&mut self, ui: &mut egui::Ui) {
+		if ui.button("Save").clicked() {
+			/* TODO: Error handling */
+			let write_to = build_full_filepath(&self.installed_location, "internal_pinetree_data.txt");
+			if let Ok(_) = write_internal_data(&write_to, &self.persistent_data) {
+				self.save_err = SaveError::Success;
+			} else {
+				self.save_err = SaveError::Error(format!("Error: Failed to save internal data"))
+			}
+		}
+		match &mut self.save_err {
+			SaveError::None => {},
+			SaveError::Success => {
+				ui.horizontal(|ui| {
+					if ui.button("X").clicked() {
+						self.save_err = SaveError::None;
 					}
-
+					ui.label("Successfully saved settings");
+				});
+			},
+			SaveError::Error(string) => {
+				let mut clear = false;
+				ui.horizontal(|ui| {
+					if ui.button(egui::RichText::new("X").color(egui::Color32::RED)).clicked() {
+						clear = true;
+					} else {
+						ui.label(egui::RichText::new(string.clone()).color(egui::Color32::RED));
+					}
+				});
+				if clear {
+					self.save_err = SaveError::None;
 				}
-			}
+			},
 		}
-		/* Is there a more efficient way? This makes copies */
-		songs_vec.sort_by_key(|name| name.to_lowercase());
-		subdirectory_vec.sort_by_key(|name| name.to_lowercase());
-
-		dir_map.insert(directory_filepath.to_string(), Directory {filepath_identifier: directory_filepath.to_string(), subdirectories: subdirectory_vec, songs: songs_vec});
-		return true;
-	} else {
-		return false;
 	}
-}
 
-fn init_directory_at_filepath_recursive(directory_filepath: &str, dir_map: &mut HashMap<String, Directory>) -> bool {
-	let read_result = std::fs::read_dir(directory_filepath);
-	if let Ok(paths) = read_result {
-		let mut songs_vec = Vec::<String>::new();
-		let mut subdirectory_vec = Vec::<String>::new();
-		for path in paths {
-			if let Ok(valid_path) = path {
-				if let Ok(file_name) = valid_path.file_name().into_string() {
-					if let Ok(file_type) = valid_path.file_type() && file_type.is_dir() {
-						subdirectory_vec.push(build_full_filepath(directory_filepath, &file_name));
-					} else if file_name.ends_with(".mp3") {
-						songs_vec.push(build_full_filepath(directory_filepath, &file_name));
-					} 
+	fn render_shortcut_editor(&mut self, ui: &mut egui::Ui) {
+		ui.horizontal(|ui| {
+			if ui.button("← Back").clicked() {
+				self.shortcut_being_set = None;
+				self.central_panel_mode = CentralPanelMode::Settings;
+			}
+		});
+		ui.vertical_centered(|ui| {
+			ui.heading("Keyboard Shortcuts");
+			ui.add_space(5.0);
+		});
+		let conflicts = shortcuts::find_conflicts(&self.persistent_data.shortcuts);
+		egui::Grid::new("shortcut_grid").striped(true).show(ui, |ui| {
+			ui.label("");
+			ui.label("Shortcut");
+			ui.label("");
+			ui.label("");
+			ui.label("Global shortcut").on_hover_text("Works while Pinetree isn't focused");
+			ui.end_row();
+			for action in shortcuts::ALL_ACTIONS {
+				let conflicting = shortcuts::has_conflict(&conflicts, action);
+				ui.label(shortcuts::action_description(action));
+				let binding = shortcuts::find_binding(&self.persistent_data.shortcuts, action).cloned();
+				if shortcut_button(ui, &binding, self.shortcut_being_set == Some((action, false)), conflicting).clicked() {
+					self.shortcut_being_set = Some((action, false));
+				}
+				if ui.button("Clear").clicked() {
+					shortcuts::set_binding(&mut self.persistent_data.shortcuts, action, None);
 				}
+				if ui.button("Default").clicked() {
+					shortcuts::set_binding(&mut self.persistent_data.shortcuts, action, shortcuts::default_binding(action));
+				}
+				if shortcuts::can_be_global(action) {
+					let global_binding = shortcuts::find_global_binding(&self.persistent_data.shortcuts, action).cloned();
+					if shortcut_button(ui, &global_binding, self.shortcut_being_set == Some((action, true)), conflicting).clicked() {
+						self.shortcut_being_set = Some((action, true));
+					}
+					if ui.button("Clear").clicked() {
+						shortcuts::set_global_binding(&mut self.persistent_data.shortcuts, action, None);
+					}
+				}
+				ui.end_row();
 			}
+		});
+		ui.add_space(5.0);
+		ui.horizontal(|ui| {
+			ui.label("Seek step: ");
+			ui.add(egui::DragValue::new(&mut self.persistent_data.seek_step_secs).range(1..=600).suffix(" s"));
+			ui.label("Long seek step: ");
+			ui.add(egui::DragValue::new(&mut self.persistent_data.long_seek_step_secs).range(1..=600).suffix(" s"));
+		});
+		ui.horizontal(|ui| {
+			ui.label("Grab media keys: ");
+			ui.checkbox(&mut self.persistent_data.media_keys, "");
+		}).response.on_hover_text_at_pointer("Play/pause, next and previous keys control Pinetree while other programs are focused.\nMost Linux desktops already send these over MPRIS, which doesn't need this.");
+		match &self.global_hotkeys {
+			Ok(global_hotkeys) => {
+				for error in &global_hotkeys.errors {
+					ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+				}
+			},
+			Err(error) => {
+				ui.label(egui::RichText::new(error).color(egui::Color32::RED));
+			},
 		}
-		/* Is there a more efficient way? This makes copies */
-		songs_vec.sort_by_key(|name| name.to_lowercase());
-		subdirectory_vec.sort_by_key(|name| name.to_lowercase());
-
-		for directory in &subdirectory_vec {
-			init_directory_at_filepath_recursive(directory, dir_map);
+		if ui.button("Reset all to defaults").clicked() {
+			self.persistent_data.shortcuts = shortcuts::default_shortcuts();
+			self.persistent_data.seek_step_secs = shortcuts::DEFAULT_SEEK_STEP_SECS;
+			self.persistent_data.long_seek_step_secs = shortcuts::DEFAULT_LONG_SEEK_STEP_SECS;
 		}
-
-		dir_map.insert(directory_filepath.to_string(), Directory {filepath_identifier: directory_filepath.to_string(), subdirectories: subdirectory_vec, songs: songs_vec});
-
-		return true;
-	} else {
-		return false;
+		for (first, second) in &conflicts {
+			ui.label(egui::RichText::new(format!("\"{}\" and \"{}\" use the same shortcut, only the first one will work",
+				shortcuts::action_description(*first), shortcuts::action_description(*second))).color(egui::Color32::RED));
+		}
+		ui.add_space(5.0);
+		self.render_save_settings(ui);
 	}
 }
 
@@ -714,6 +1614,9 @@ fn song_browse_mode_to_str(song_browse_mode: &LeftPanelMode) -> &'static str {
 	match song_browse_mode {
 		LeftPanelMode::Files => "Files",
 		LeftPanelMode::Playlists => "Playlists",
+		LeftPanelMode::Artists => "Artists",
+		LeftPanelMode::Albums => "Albums",
+		LeftPanelMode::Search => "Search",
 		LeftPanelMode::DeletePlaylist => "Delete",
 		LeftPanelMode::RemoveSongs => "Remove", 
 		LeftPanelMode::SelectSongs => "Select", 
@@ -721,64 +1624,158 @@ fn song_browse_mode_to_str(song_browse_mode: &LeftPanelMode) -> &'static str {
 	}
 }
 
+/* Changes whenever a playlist is added, renamed or gets songs added or removed */
+fn playlists_key(playlists: &[Playlist]) -> u64 {
+	use std::hash::{Hash, Hasher};
+	let mut hasher = std::hash::DefaultHasher::new();
+	for playlist in playlists {
+		playlist.name.hash(&mut hasher);
+		playlist.songs.len().hash(&mut hasher);
+	}
+	hasher.finish()
+}
+
 /**
-* Backs up from e.g. ~/Music/ to ~/
-*/
-fn song_folder_go_up(input_string: &str) -> String {
-	let last_char = {
-		// This is here because otherwise with an extra slash it will back up to the same folder and just delete the slash.
-		let operated_str = trim_slash_if_exists(input_string);
-		let last_bslash = operated_str.rfind('\\');
-		let last_slash = operated_str.rfind('/');
+ * Text with the characters at the given (character) positions highlighted, for search results.
+ */
+fn highlighted_text(ui: &egui::Ui, text: &str, positions: &[usize], is_current_song: bool) -> egui::text::LayoutJob {
+	let font_id = egui::TextStyle::Body.resolve(ui.style());
+	let mut job = egui::text::LayoutJob::default();
+	let mut positions = positions.iter().peekable();
+	for (i, c) in text.chars().enumerate() {
+		let matched = positions.next_if(|position| **position == i).is_some();
+		let format = egui::TextFormat {
+			font_id: font_id.clone(),
+			color: if matched {ui.visuals().strong_text_color()} else {ui.visuals().text_color()},
+			underline: if matched || is_current_song {egui::Stroke::new(1.0, ui.visuals().text_color())} else {egui::Stroke::NONE},
+			..Default::default()
+		};
+		job.append(c.encode_utf8(&mut [0; 4]), 0.0, format);
+	}
+	job
+}
+
+fn browse_mode_selector(ui: &mut egui::Ui, browse_mode: &mut LeftPanelMode) {
+	egui::ComboBox::from_label("")
+		.selected_text(song_browse_mode_to_str(browse_mode))
+		.show_ui(ui, |ui| {
+			ui.selectable_value(browse_mode, LeftPanelMode::Files, "Files");
+			ui.selectable_value(browse_mode, LeftPanelMode::Playlists, "Playlists");
+			ui.selectable_value(browse_mode, LeftPanelMode::Artists, "Artists");
+			ui.selectable_value(browse_mode, LeftPanelMode::Albums, "Albums");
+			ui.selectable_value(browse_mode, LeftPanelMode::Search, "Search");
+		});
+}
+
+/**
+ * Plays a song of an album with the whole album as the song collection, so next and previous go through it in order.
+ */
+fn play_album(audio_message_channel: &Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>, album: &index::Album, start: usize) {
+	if let Some(song) = album.songs.get(start) {
+		send_audio_signal(audio_message_channel, MessageToAudio::SetSongCollection(index::album_paths(album), Some(start)));
+		send_audio_signal(audio_message_channel, MessageToAudio::PlaySong(song.path.clone()));
+	}
+}
 
-		match (last_bslash, last_slash) {
-			(Some(bslash), Some(slash)) => {
-				if bslash > slash {bslash} else {slash}
+fn render_album_tracks(ui: &mut egui::Ui,
+	album: &index::Album,
+	cover: Option<&egui::TextureHandle>,
+	current_song: &str,
+	audio_message_channel: &Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>)
+{
+	let year = if album.year.is_empty() {"".to_string()} else {format!(" ({})", album.year)};
+	ui.horizontal(|ui| {
+		if let Some(cover) = cover {
+			paint_cover(ui, cover, ALBUM_TILE_SIZE);
+		}
+		ui.vertical(|ui| {
+			ui.label(format!("{}{}", album.artist, year));
+			if ui.button("Play album").clicked() {
+				play_album(audio_message_channel, album, 0);
 			}
-			(Some(bslash), None) => bslash,
-			(None, Some(slash)) => slash,
-			(None, None) => 0,
+		});
+	});
+	ui.add_space(5.0);
+	let multiple_discs = album.songs.iter().any(|song| song.disc > 1);
+	egui::ScrollArea::vertical().show(ui, |ui| {
+		for (i, song) in album.songs.iter().enumerate() {
+			ui.horizontal(|ui| {
+				ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+				if ui.button("▶").clicked() {
+					play_album(audio_message_channel, album, i);
+				}
+				let number = match (multiple_discs, song.track) {
+					(_, 0) => "".to_string(),
+					(true, track) => format!("{}-{:02} ", song.disc.max(1), track),
+					(false, track) => format!("{}. ", track),
+				};
+				let text = egui::RichText::new(format!("{}{}", number, index::display_title(song)));
+				ui.label(if song.path == current_song {text.underline().strong()} else {text});
+			});
 		}
-	};
-	format!("{}", if last_char == 0 {""} else {&input_string[0..last_char]})
+	});
 }
 
-fn extract_file_name(input_string: &str) -> &str {
-	let last_bslash = input_string.rfind('\\');
-	let last_slash = input_string.rfind('/');
+/* Width of the album grid tiles, in points */
+const ALBUM_TILE_SIZE: f32 = 100.0;
+/* Size of the cover art thumbnails in lists of albums */
+const COVER_THUMBNAIL_SIZE: f32 = 32.0;
+/* Most space the cover art of the current song takes up in the player panel */
+const PLAYER_COVER_SIZE: f32 = 240.0;
 
-	let start = match (last_bslash, last_slash) {
-		(Some(bslash), Some(slash)) => {
-			if bslash > slash {bslash} else {slash}
-		}
-		(Some(bslash), None) => bslash,
-		(None, Some(slash)) => slash,
-		(None, None) => 0,
-	};
-	return if start == 0 {input_string} else {&input_string[start + 1..]};
+/**
+ * Paints cover art in a square of the given size, keeping its aspect ratio. Returns the square's response.
+ */
+fn paint_cover(ui: &mut egui::Ui, cover: &egui::TextureHandle, size: f32) -> egui::Response {
+	let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::click());
+	paint_cover_in(ui, cover, rect);
+	response
 }
 
-fn extract_folder_name(input_string: &str) -> &str {
-	let input_string = if input_string.ends_with('\\') {
-		&input_string[..input_string.len() - 1]
-	} else {
-		input_string
-	};
-	let last_bslash = input_string.rfind('\\');
-	let last_slash = input_string.rfind('/');
+/* Fits the cover into the rect, keeping its aspect ratio */
+fn paint_cover_in(ui: &egui::Ui, cover: &egui::TextureHandle, rect: egui::Rect) {
+	let texture_size = cover.size_vec2();
+	let scale = (rect.width() / texture_size.x).min(rect.height() / texture_size.y);
+	let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+	ui.painter().image(cover.id(), egui::Rect::from_center_size(rect.center(), texture_size * scale), uv, egui::Color32::WHITE);
+}
 
-	let start = match (last_bslash, last_slash) {
-		(Some(bslash), Some(slash)) => {
-			if bslash > slash {bslash} else {slash}
+/**
+ * A tile in the album grid. Returns whether it was clicked. The cover is only asked for when the tile is on screen.
+ */
+fn render_album_tile(ui: &mut egui::Ui, album: &index::Album, cover: impl FnOnce() -> Option<egui::TextureHandle>, size: f32) -> bool {
+	let mut clicked = false;
+	ui.vertical(|ui| {
+		ui.set_width(size);
+		ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+		let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::click());
+		if ui.is_rect_visible(rect) {
+			if let Some(cover) = cover() {
+				paint_cover_in(ui, &cover, rect);
+			} else {
+				/* Albums without art (or whose art is still loading) get their first letter */
+				let initial = album.name.chars().next().map(|initial| initial.to_uppercase().to_string()).unwrap_or_default();
+				ui.painter().rect_filled(rect, 4.0, ui.visuals().faint_bg_color);
+				ui.painter().text(rect.center(), egui::Align2::CENTER_CENTER, initial, egui::FontId::proportional(size / 2.5), ui.visuals().weak_text_color());
+			}
 		}
-		(Some(bslash), None) => bslash,
-		(None, Some(slash)) => slash,
-		(None, None) => 0,
-	};
-	return if start == 0 {input_string} else {&input_string[start + 1..]};
+		if response.hovered() {
+			ui.painter().rect_stroke(response.rect, 4.0, ui.visuals().widgets.hovered.fg_stroke);
+		}
+		clicked = response.on_hover_text(format!("{}\n{}", album.name, album.artist)).clicked();
+		clicked |= ui.add(egui::Label::new(egui::RichText::new(&album.name).strong()).sense(egui::Sense::click())).clicked();
+		ui.label(egui::RichText::new(&album.artist).weak());
+	});
+	clicked
 }
 
-fn render_song_entry_ui_element(ui: &mut egui::Ui, current_song: &str, is_current_song: bool, depth: usize, edit_playlist_data: &Option<PlaylistEditData>) -> FileActions {
+fn render_song_entry_ui_element(ui: &mut egui::Ui,
+	current_song: &str,
+	is_current_song: bool,
+	depth: usize,
+	edit_playlist_data: &Option<PlaylistEditData>,
+	play_state: Option<resume::PlayState>) -> FileActions
+{
 	let mut return_value = FileActions::None;
 	ui.horizontal(|ui| {
 		ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
@@ -803,6 +1800,13 @@ fn render_song_entry_ui_element(ui: &mut egui::Ui, current_song: &str, is_curren
 			}
 		}
 
+		match play_state {
+			Some(resume::PlayState::Unplayed) => {ui.label("○").on_hover_text("Not played yet");},
+			Some(resume::PlayState::InProgress) => {ui.label("◎").on_hover_text("Started, continues where it was left off");},
+			Some(resume::PlayState::Played) => {ui.label(egui::RichText::new("✔").weak()).on_hover_text("Played");},
+			None => {},
+		}
+
 		if is_current_song {
 			ui.label(egui::RichText::new(extract_file_name(current_song)).underline().strong());
 		}
@@ -841,54 +1845,212 @@ fn render_directory_entry_ui_element(ui: &mut egui::Ui, current_directory: &str,
 	return return_value;
 }
 
-struct DirTreeElement {
-	name: String,
-	depth: usize,
-	is_dir: bool,
-	/* For directories only, indicating whether it is active or not */
-	is_active: bool,
+
+fn binding_matches(binding: &shortcuts::KeyBinding, key: egui::Key, modifiers: egui::Modifiers) -> bool {
+	let mut pattern = egui::Modifiers::NONE;
+	if binding.ctrl {pattern = pattern | egui::Modifiers::COMMAND;}
+	if binding.shift {pattern = pattern | egui::Modifiers::SHIFT;}
+	if binding.alt {pattern = pattern | egui::Modifiers::ALT;}
+	egui::Key::from_name(&binding.key) == Some(key) && modifiers.matches_exact(pattern)
 }
 
-fn get_dir_tree_elements(output_vec: &mut Vec<DirTreeElement>, directory_string: &str, map: &HashMap<String, Directory>, depth: usize) {
-	let map_result = map.get(directory_string);
-	if let Some(directory) = map_result {
-		for subdirectory_string in &directory.subdirectories {
-			let is_active = match map.get(subdirectory_string) {Some(_) => true, None => false};
-			output_vec.push(DirTreeElement {
-				name: subdirectory_string.to_string(),
-				depth: depth,
-				is_dir: true,
-				is_active: is_active
-			});
-			if is_active {get_dir_tree_elements(output_vec, subdirectory_string, map, depth + 1)};
-		}
+/**
+ * Takes the key presses that match a shortcut out of this frame's input, so nothing else reacts to them.
+ * Shortcuts without Ctrl or Alt don't fire while something has focus, otherwise they would get in the way of typing.
+ */
+fn take_shortcut_actions(ctx: &egui::Context, shortcuts: &[shortcuts::Shortcut]) -> Vec<ShortcutAction> {
+	let focused = ctx.memory(|memory| memory.focused().is_some());
+	let mut actions = Vec::<ShortcutAction>::new();
+	ctx.input_mut(|input| {
+		input.events.retain(|event| {
+			if let egui::Event::Key {key, modifiers, pressed: true, ..} = event {
+				for shortcut in shortcuts {
+					if let Some(binding) = &shortcut.binding
+					&& (binding.ctrl || binding.alt || !focused)
+					&& binding_matches(binding, *key, *modifiers) {
+						actions.push(shortcut.action);
+						return false;
+					}
+				}
+			}
+			true
+		});
+	});
+	actions
+}
 
-		for song_string in &directory.songs {
-			output_vec.push(DirTreeElement {
-				name: song_string.to_string(),
-				depth: depth,
-				is_dir: false,
-				is_active: false,
-			});
+/**
+ * Seek bar that draws the song's peak (faint) and RMS (solid) envelope, with the played part in the selection color.
+ * Until the waveform is analysed it is just a flat bar. Click or drag to seek, playback_pos is updated like a slider would.
+ */
+fn waveform_seek_bar(ui: &mut egui::Ui,
+	size: egui::Vec2,
+	song_waveform: Option<&waveform::Waveform>,
+	playback_pos: &mut usize,
+	song_length: usize,
+	loop_points: (Option<usize>, Option<usize>),
+	bookmarks: &[usize]) -> egui::Response
+{
+	let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+	if (response.dragged() || response.clicked()) && let Some(pointer) = response.interact_pointer_pos() {
+		let fraction = ((pointer.x - rect.left()) / rect.width()).clamp(0.0, 1.0);
+		*playback_pos = (fraction as f64 * song_length as f64) as usize;
+	}
+	if !ui.is_rect_visible(rect) {
+		return response;
+	}
+
+	let visuals = ui.visuals();
+	let played_color = visuals.selection.bg_fill;
+	let unplayed_color = visuals.widgets.inactive.bg_fill;
+	let painter = ui.painter_at(rect);
+	painter.rect_filled(rect, 2.0, visuals.extreme_bg_color);
+
+	let played_fraction = if song_length == 0 {0.0} else {(*playback_pos as f32 / song_length as f32).min(1.0)};
+	let playhead_x = rect.left() + played_fraction * rect.width();
+	let center_y = rect.center().y;
+	if let Some(song_waveform) = song_waveform && !song_waveform.peaks.is_empty() {
+		let point_width = rect.width() / song_waveform.peaks.len() as f32;
+		for (i, peak) in song_waveform.peaks.iter().enumerate() {
+			let x = rect.left() + (i as f32 + 0.5) * point_width;
+			let color = if x <= playhead_x {played_color} else {unplayed_color};
+			let rms = song_waveform.rms.get(i).copied().unwrap_or(0.0);
+			/* Always at least a pixel, otherwise silence would leave gaps in the bar */
+			let peak_height = (peak * rect.height() / 2.0).max(0.5);
+			let rms_height = (rms * rect.height() / 2.0).max(0.5);
+			let stroke_width = point_width.max(1.0);
+			painter.line_segment([egui::pos2(x, center_y - peak_height), egui::pos2(x, center_y + peak_height)],
+				egui::Stroke::new(stroke_width, color.gamma_multiply(0.45)));
+			painter.line_segment([egui::pos2(x, center_y - rms_height), egui::pos2(x, center_y + rms_height)],
+				egui::Stroke::new(stroke_width, color));
 		}
+	} else {
+		let track = egui::Rect::from_min_max(egui::pos2(rect.left(), center_y - 2.0), egui::pos2(rect.right(), center_y + 2.0));
+		painter.rect_filled(track, 2.0, unplayed_color);
+		painter.rect_filled(egui::Rect::from_min_max(track.min, egui::pos2(playhead_x, track.bottom())), 2.0, played_color);
+	}
+
+	let position_x = |position: usize| rect.left() + if song_length == 0 {0.0} else {(position as f32 / song_length as f32).min(1.0)} * rect.width();
+	let loop_color = visuals.warn_fg_color;
+	if let (Some(start), Some(end)) = loop_points {
+		let region = egui::Rect::from_min_max(egui::pos2(position_x(start), rect.top()), egui::pos2(position_x(end), rect.bottom()));
+		painter.rect_filled(region, 0.0, loop_color.gamma_multiply(0.15));
+	}
+	for point in [loop_points.0, loop_points.1].into_iter().flatten() {
+		let x = position_x(point);
+		painter.line_segment([egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())], egui::Stroke::new(1.5, loop_color));
+	}
+	for bookmark in bookmarks {
+		/* A small notch at the top, so bookmarks don't cover the waveform */
+		let x = position_x(*bookmark);
+		painter.add(egui::Shape::convex_polygon(
+			vec![egui::pos2(x - 4.0, rect.top()), egui::pos2(x + 4.0, rect.top()), egui::pos2(x, rect.top() + 6.0)],
+			visuals.hyperlink_color, egui::Stroke::NONE));
+	}
+	painter.line_segment([egui::pos2(playhead_x, rect.top()), egui::pos2(playhead_x, rect.bottom())], visuals.widgets.active.fg_stroke);
+	response
+}
+
+/**
+ * Formats a position in a song as e.g. 1:23.4, for loop points and bookmarks.
+ */
+/* Progress of the render running in the background, or how the last one went */
+fn render_job_status(ui: &mut egui::Ui, render_job: &mut Option<(String, Arc<record::RenderJob>)>) {
+	let mut dismissed = false;
+	if let Some((name, job)) = render_job {
+		ui.horizontal(|ui| {
+			match job.result() {
+				None => {
+					ui.add(egui::ProgressBar::new(job.progress()).desired_width(150.0).show_percentage());
+					ui.label(format!("Rendering {}", name));
+					if ui.button("Cancel").clicked() {
+						job.cancel();
+					}
+				},
+				Some(Ok(path)) => {
+					ui.label(format!("Rendered {}", extract_file_name(&path))).on_hover_text(&path);
+					dismissed = ui.small_button("X").clicked();
+				},
+				Some(Err(err)) => {
+					ui.label(egui::RichText::new(format!("Couldn't render {}: {}", name, err)).color(egui::Color32::RED));
+					dismissed = ui.small_button("X").clicked();
+				},
+			}
+		});
+	}
+	if dismissed {
+		*render_job = None;
+	}
+}
+
+/* Renders at normal speed and full volume, one song after the other */
+fn start_playlist_render(persistent_data: &PersistentData, playlist: &Playlist) -> (String, Arc<record::RenderJob>) {
+	let format = persistent_data.record_format;
+	let path = record::output_path(&persistent_data.record_folder, &persistent_data.record_template, &playlist.name, format);
+	let job = record::start_render(playlist.songs.clone(), path, format, record::default_render_settings());
+	(playlist.name.clone(), job)
+}
+
+fn format_position(ms: usize) -> String {
+	let secs = ms / 1000;
+	format!("{}:{:02}.{}", secs / 60, secs % 60, (ms % 1000) / 100)
+}
+
+fn shortcut_button(ui: &mut egui::Ui, binding: &Option<shortcuts::KeyBinding>, capturing: bool, conflicting: bool) -> egui::Response {
+	let text = if capturing {
+		egui::RichText::new("Press a key... (Esc to cancel)").italics()
+	} else if conflicting && binding.is_some() {
+		egui::RichText::new(shortcuts::binding_to_str(binding)).color(egui::Color32::RED)
+	} else {
+		egui::RichText::new(shortcuts::binding_to_str(binding))
+	};
+	let button = ui.add_sized([200.0, ui.spacing().interact_size.y], egui::Button::new(text));
+	if conflicting && binding.is_some() {
+		button.on_hover_text("Another action uses the same shortcut")
+	} else {
+		button
 	}
 }
 
-pub const REFRESH: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::R);
-pub const PAUSE: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::P);
-pub const PREV_SONG: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::ArrowLeft);
-pub const NEXT_SONG: egui::KeyboardShortcut = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::ArrowRight);
+/**
+ * Used by the shortcut editor to grab the next key that gets pressed, along with its modifiers.
+ */
+fn capture_key_press(ctx: &egui::Context) -> Option<(egui::Key, egui::Modifiers)> {
+	ctx.input_mut(|input| {
+		let index = input.events.iter().position(|event| matches!(event, egui::Event::Key {pressed: true, ..}))?;
+		if let egui::Event::Key {key, modifiers, ..} = input.events.remove(index) {
+			Some((key, modifiers))
+		} else {
+			None
+		}
+	})
+}
 
 fn render_directory_elements(
 	ui: &mut egui::Ui,
 	directory_tree_vec: &Option<Vec<DirTreeElement>>,
 	searched_vec: &Option<Vec<usize>>,
 	active_song_name: &str,
-	edit_playlist_data: &Option<PlaylistEditData>) -> FileActions {
+	edit_playlist_data: &Option<PlaylistEditData>,
+	scroll_to_active_song: bool,
+	play_state_of: &dyn Fn(&str) -> Option<resume::PlayState>) -> FileActions {
 	let mut file_action = FileActions::None;
 	if let Some(directory_tree_elements) = directory_tree_vec {
 		let row_count = if let Some(s) = searched_vec {s.len()} else {directory_tree_elements.len()};
-		egui::ScrollArea::vertical().show_rows(ui, 16.0, row_count, |ui, row_range| {
+		let mut scroll_area = egui::ScrollArea::vertical();
+		if scroll_to_active_song {
+			let active_row = (0..row_count).position(|row| {
+				let index = if let Some(search_vec) = searched_vec {search_vec.get(row).copied()} else {Some(row)};
+				index.and_then(|index| directory_tree_elements.get(index)).is_some_and(|element| element.name == active_song_name)
+			});
+			if let Some(row) = active_row {
+				/* Rows are only rendered while visible, so this has to scroll by offset instead of scroll_to_me */
+				let row_height = 16.0 + ui.spacing().item_spacing.y;
+				let centering = ui.available_height() / 2.0;
+				scroll_area = scroll_area.vertical_scroll_offset((row as f32 * row_height - centering).max(0.0));
+			}
+		}
+		scroll_area.show_rows(ui, 16.0, row_count, |ui, row_range| {
 			ui.set_min_width(ui.available_rect_before_wrap().size().x);
 	
 			for row in row_range {
@@ -906,7 +2068,7 @@ fn render_directory_elements(
 						render_directory_entry_ui_element(ui, &element.name, element.is_active, element.depth)
 					} else {
 						let is_active_song = &element.name == active_song_name;
-						render_song_entry_ui_element(ui, &element.name, is_active_song, element.depth, edit_playlist_data)
+						render_song_entry_ui_element(ui, &element.name, is_active_song, element.depth, edit_playlist_data, play_state_of(&element.name))
 					};
 					match re_code {
 						FileActions::None => {},
@@ -972,7 +2134,7 @@ fn render_playlist_elements(ui: &mut egui::Ui,
 				if let Some(element) = playlist_tree_elements.get(get_element) {
 					if let Some(song_name) = &element.song_name {
 						let is_active_song = song_name == active_song_name;
-						if let FileActions::PlaySong(song) = render_song_entry_ui_element(ui, &song_name, is_active_song, song_depth, &None) {
+						if let FileActions::PlaySong(song) = render_song_entry_ui_element(ui, &song_name, is_active_song, song_depth, &None, None) {
 							file_action = FileActions::PlaySong(song.clone());
 						}
 					} else {
@@ -1002,25 +2164,6 @@ fn render_playlist_elements(ui: &mut egui::Ui,
 	return file_action;
 }
 
-/**
- * Starting to not be a big fan of Rust
- * 
- * Were this in a slightly looser language I'd just do this with a doubly linked list to make every operation O(1) but I'm stuck using v*ctors for this.
- * Doubly linked lists are a pain in the ass in Rust
- * 
- * Maybe I'll go back and do the un-safe stuff should this become a real performance issue, though I imagine there are more pressing areas in this code.
- */
-fn range_swap(vec: &mut Vec<String>, start: usize, end_inclusive: usize) {
-	if start > end_inclusive {
-		for i in (end_inclusive + 1..=start).rev() {
-            vec.swap(i, i - 1);
-        }
-	} else {
-		for i in start..end_inclusive {
-			vec.swap(i, i + 1);
-		}
-	}
-}
 
 fn drag_vec_element(ui: &mut egui::Ui, playlist_edit_data: &mut PlaylistEditData, row_height: f32, scroll_rect: egui::Rect, scroll_offset: f32) -> usize{
 	if let Some(pointer_pos) = ui.input(|i| i.pointer.interact_pos()) && let Some(dragged_element) = playlist_edit_data.current_dragged_element {
@@ -1174,65 +2317,6 @@ fn set_visuals(ctx: &egui::Context, theme: &ThemePref) {
 	}
 }
 
-fn default_on_finish_to_str(behavior: &audio_frontend::LoopBehavior) -> &'static str {
-	match behavior {
-		audio_frontend::LoopBehavior::Stop => {
-			"Stop"
-		},
-		audio_frontend::LoopBehavior::Loop => {
-			"Loop"
-		},
-		audio_frontend::LoopBehavior::Next => {
-			"Next"
-		},
-		audio_frontend::LoopBehavior::Shuffle => {
-			"Shuffle"
-		},
-	}
-}
-
-fn prev_behavior_to_str(p: &audio_frontend::PrevBehavior) -> &str {
-	return match *p {
-		audio_frontend::PrevBehavior::Above => {
-			"Above"
-		},
-		audio_frontend::PrevBehavior::History => {
-			"History"
-		},
-	};
-}
-
-fn write_internal_data(path: &str, persistent_data: &PersistentData) -> Result<(), Box<dyn std::error::Error>>{
-	use std::io::prelude::*;
-	let file = std::fs::File::create(path);
-	if let Ok(mut file) = file {
-		let mut data_to_write = format!("VERSION: {}\n", CURRENT_VERSION);
-
-		data_to_write = format!("{}{}", data_to_write, "SETTINGS\n");
-		data_to_write = format!("{}Theme: {}\n", data_to_write, theme_to_str(&persistent_data.theme));
-		data_to_write = format!("{}Default Directory: {}\n", data_to_write, persistent_data.default_directory);
-		data_to_write = format!("{}Default End Behavior: {}\n", data_to_write, default_on_finish_to_str(&persistent_data.default_on_finish));
-		data_to_write = format!("{}Default Prev Behavior: {}\n", data_to_write, prev_behavior_to_str(&persistent_data.prev_behavior));
-		data_to_write = format!("{}Default Volume: {}\n", data_to_write, persistent_data.default_volume.to_string());
-		data_to_write = format!("{}Hide Directories: {}\n", data_to_write, 
-			if persistent_data.hide_directories_by_default {"true"}
-			else {"false"}
-		);
-		data_to_write = format!("{}Shuffle Memory: {}\n", data_to_write, persistent_data.shuffle_memory);
-
-		data_to_write = format!("{}{}", data_to_write, "PLAYLISTS\n");
-
-		for playlist in &persistent_data.playlists {
-			data_to_write = format!("{}Playlist: {}\n", data_to_write, playlist.name);
-			
-			for song in &playlist.songs {
-				data_to_write = format!("{}{}\n", data_to_write, song);
-			}
-		}
-		file.write_all(data_to_write.as_bytes())?;
-	}
-	return Ok(());
-}
 
 /**
 * Returns None on success. Otherwise, returns a string with the relevant error.
@@ -1269,11 +2353,6 @@ fn run_install(install_data: &InstallerData, persistent_data: &PersistentData) -
 	}
 }
 
-#[derive(Clone)]
-enum PlaylistElementType {
-	Song,
-	_Directory,
-}
 
 #[derive(PartialEq)]
 #[derive(Clone)]
@@ -1329,47 +2408,15 @@ fn init_playlist_edit_data(playlists: &Vec<Playlist>, index: usize) -> PlaylistE
 	};
 	if let Some(playlist) = playlists.get(index) {
 		new_edit_data.playlist_name = playlist.name.to_string();
-		for song in &playlist.songs {
-			new_edit_data.edit_map.insert(song.clone(), PlaylistElementType::Song);
-			new_edit_data.edit_vec.push(song.clone());
-		}
-	}
-	return new_edit_data;
-}
-
-/**
- * Builds an ordered vec that looks like the following:
- * - Existing elements preserve their place in the new vec
- * - Added elements are on the end, sorted alphabetically
- */
-fn rebuild_ordered_vec(existing_ordered_vec: &mut Vec<String>, existence_map: &mut HashMap<String, PlaylistElementType>) -> Vec<String> {
-	let mut new_ordered_vec = Vec::<String>::new();
-	let mut existence_map_new = HashMap::<String, PlaylistElementType>::new();
-	for song in existing_ordered_vec {
-		if existence_map.contains_key(song) {
-			new_ordered_vec.push(song.clone());
-			existence_map_new.insert(song.clone(), existence_map.get(song).unwrap_or(&PlaylistElementType::Song).clone());
-		}
-	}
-
-	let mut keymap = Vec::<String>::new();
-	for (song, _) in existence_map {
-		if !existence_map_new.contains_key(song) {
-			keymap.push(song.clone());
+		for song in &playlist.songs {
+			new_edit_data.edit_map.insert(song.clone(), PlaylistElementType::Song);
+			new_edit_data.edit_vec.push(song.clone());
 		}
 	}
-
-	// TODO: Fix this to remove all the copying
-	keymap.sort_by_key(|name| name.to_lowercase());
-
-	/* Combine */
-	for element in keymap {
-		new_ordered_vec.push(element);
-	}
-
-	return new_ordered_vec;
+	return new_edit_data;
 }
 
+
 enum SaveError {
 	None,
 	Success,
@@ -1440,6 +2487,19 @@ fn search_bar(ui: &mut egui::Ui, ctx: &egui::Context, search_text: &mut String)
 }
 
 impl eframe::App for MyApp {
+	/* The audio thread only saves the library when songs change, so the resume position and anything edited since would get lost */
+	fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+		let audio_data = request_rodio_data(&self.audio_message_channel, &self.audio_receive_channel);
+		if let Ok(mut library) = self.library.lock() {
+			if !audio_data.song_name.is_empty() {
+				resume::remember_position(&mut library, &self.persistent_data.resume, &audio_data.song_name, audio_data.playback_position);
+			}
+			if library::write_library(&library).is_err() {
+				println!("Error in saving library");
+			}
+		}
+	}
+
 	fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
 		if !self.first_frame_rendered {
 			set_visuals(&ctx, &self.persistent_data.theme);
@@ -1453,6 +2513,51 @@ impl eframe::App for MyApp {
 		}
 		// 8 fps
 		let audio_data = request_rodio_data(&mut self.audio_message_channel, &mut self.audio_receive_channel);
+		if let Some(last_volume) = self.last_reported_volume
+		&& audio_data.volume != last_volume
+		&& audio_data.volume != volume_curve(self.song_volume) {
+			self.song_volume = inverse_volume_curve(audio_data.volume);
+		}
+		self.last_reported_volume = Some(audio_data.volume);
+		if let Some(last_speed) = self.last_reported_speed
+		&& audio_data.speed != last_speed
+		&& audio_data.speed != self.song_speed {
+			self.song_speed = audio_data.speed;
+		}
+		self.last_reported_speed = Some(audio_data.speed);
+		/* The audio thread drops the loop when the song changes */
+		if self.ab_loop_song != audio_data.song_name {
+			self.ab_loop_song = audio_data.song_name.clone();
+			self.loop_start = None;
+			self.loop_end = None;
+		}
+		let shortcut_actions = if let Some((action, global)) = self.shortcut_being_set {
+			if let Some((key, modifiers)) = capture_key_press(ctx) {
+				if key != egui::Key::Escape {
+					let binding = shortcuts::KeyBinding {ctrl: modifiers.command, shift: modifiers.shift, alt: modifiers.alt, key: key.name().to_string()};
+					if global {
+						shortcuts::set_global_binding(&mut self.persistent_data.shortcuts, action, Some(binding));
+					} else {
+						shortcuts::set_binding(&mut self.persistent_data.shortcuts, action, Some(binding));
+					}
+				}
+				self.shortcut_being_set = None;
+			}
+			Vec::new()
+		} else {
+			take_shortcut_actions(ctx, &self.persistent_data.shortcuts)
+		};
+		self.handle_shortcut_actions(&shortcut_actions, &audio_data);
+		if let Ok(global_hotkeys) = &mut self.global_hotkeys {
+			global_hotkeys.update(&self.persistent_data);
+		}
+		if self.sent_resume_settings.as_ref() != Some(&self.persistent_data.resume) {
+			send_audio_signal(&self.audio_message_channel, MessageToAudio::UpdateResumeSettings(self.persistent_data.resume.clone()));
+			self.sent_resume_settings = Some(self.persistent_data.resume.clone());
+		}
+		let song_waveform = self.find_waveform(&audio_data.song_name).cloned();
+		let bookmark_positions: Vec<usize> = self.find_bookmarks(&audio_data.song_name).iter().map(|bookmark| bookmark.position_ms).collect();
+		let loop_points = (self.loop_start, self.loop_end);
 		ctx.request_repaint_after(std::time::Duration::from_millis(125));
 		let height = ctx.available_rect().height();
 		if height > 80.0 {
@@ -1512,6 +2617,8 @@ impl eframe::App for MyApp {
 			});
 		}
 		
+		self.apply_folder_changes(&audio_data.song_name);
+
 		let active_directory: Option<&Directory> = {
 			if let Some(dir) = self.directory_map.get(&self.active_directory_filepath) {
 				Some(dir)
@@ -1538,7 +2645,7 @@ impl eframe::App for MyApp {
 					send_audio_signal(&self.audio_message_channel, MessageToAudio::UpdateEndBehavior(clone_loop_behavior(&self.loop_behavior)));
 				}
 				ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
-				if let Some(err) = audio_data.error_message {
+				if let Some(err) = &audio_data.error_message {
 					if ui.button(egui::RichText::new("x").color(egui::Color32::RED).line_height(Some(16.0))).clicked() {
 						send_audio_signal(&self.audio_message_channel, MessageToAudio::ClearError);
 					}
@@ -1546,6 +2653,10 @@ impl eframe::App for MyApp {
 				}
 				else {
 					ui.label(if audio_data.song_name == "" {format!("No song playing")} else {format!("Now playing: {}", extract_file_name(&audio_data.song_name))});
+					if self.deleted_song.as_ref() == Some(&audio_data.song_name) {
+						ui.label(egui::RichText::new("(deleted)").color(egui::Color32::YELLOW))
+							.on_hover_text("The file was deleted while playing. It plays to the end, then playback moves on without it.");
+					}
 				}
 			});
 			ui.horizontal(|ui| {
@@ -1564,13 +2675,13 @@ impl eframe::App for MyApp {
 					egui::RichText::new(raw_text).line_height(line_height).size(icon_size)
 				};
 
-				if ui.button(prev_text).clicked() || ctx.input_mut(|i| i.consume_shortcut(&PREV_SONG)) {
+				if ui.button(prev_text).clicked() || shortcut_actions.contains(&ShortcutAction::PreviousSong) {
 					send_audio_signal(&self.audio_message_channel, MessageToAudio::PreviousSong);
 				}
-				if ui.button(pause_play_text).clicked() || ctx.input_mut(|i| i.consume_shortcut(&PAUSE)) {
+				if ui.button(pause_play_text).clicked() || shortcut_actions.contains(&ShortcutAction::TogglePause) {
 					send_audio_signal(&self.audio_message_channel, MessageToAudio::TogglePause);
 				}
-				if ui.button(skip_text).clicked() || ctx.input_mut(|i| i.consume_shortcut(&NEXT_SONG)) {
+				if ui.button(skip_text).clicked() || shortcut_actions.contains(&ShortcutAction::NextSong) {
 					send_audio_signal(&self.audio_message_channel, MessageToAudio::SongEnd);
 				}
 
@@ -1606,35 +2717,15 @@ impl eframe::App for MyApp {
 				let remaining_width = ui.available_width();
 				ui.spacing_mut().slider_width = remaining_width;
 
-				let seeker = ui.add_sized([remaining_width, ui.spacing().interact_size.y],
-					egui::Slider::new(&mut playback_pos, 0..=(audio_data.song_length))
-					.handle_shape(egui::style::HandleShape::Rect { aspect_ratio: 1.0 })
-					.show_value(false)
-					.trailing_fill(true)
-					// Fill color can be adjusted with ui.visuals_mut().selection.bg_fill = Color32::{INSERT COLOR HERE};
-				);
+				let seeker = waveform_seek_bar(ui, egui::vec2(remaining_width, ui.spacing().interact_size.y * 1.5),
+					song_waveform.as_ref(), &mut playback_pos, audio_data.song_length, loop_points, &bookmark_positions);
 
-				if seeker.dragged() {
+				if seeker.dragged() || seeker.clicked() {
 					send_audio_signal(&self.audio_message_channel, MessageToAudio::Seek(playback_pos));
 				}
 				if seeker.drag_stopped() {
 					send_audio_signal(&self.audio_message_channel, MessageToAudio::SeekStop);
 				}
-
-				let mut focused = false;
-				ctx.memory( |memory| {
-					focused = !memory.focused().is_none();
-				});
-				if !focused {
-					if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-						let seek_pos = audio_data.playback_position + 5000; // 5 seconds
-						send_audio_signal(&self.audio_message_channel, MessageToAudio::Seek(seek_pos))
-					}
-					if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-						let seek_pos = audio_data.playback_position.saturating_sub(5000); // 5 seconds
-						send_audio_signal(&self.audio_message_channel, MessageToAudio::Seek(seek_pos))
-					}
-				}
 			});
 		});
 
@@ -1652,18 +2743,13 @@ impl eframe::App for MyApp {
 				LeftPanelMode::Files => {
 					ui.horizontal(|ui| {
 						if let None = &self.edit_playlist_data {
-							egui::ComboBox::from_label("")
-								.selected_text(song_browse_mode_to_str(&self.browse_mode))
-								.show_ui(ui, |ui| {
-									ui.selectable_value(&mut self.browse_mode, LeftPanelMode::Files, "Files");
-									ui.selectable_value(&mut self.browse_mode, LeftPanelMode::Playlists, "Playlists");
-								});
+							browse_mode_selector(ui, &mut self.browse_mode);
 						}
 						/* TODO */
 						// if ui.button("Advanced").clicked() {
 						// 	self.advanced_search_active = !self.advanced_search_active;
 						// }
-						request_refresh = ui.button("Refresh").clicked() || ctx.input_mut(|i| i.consume_shortcut(&REFRESH));
+						request_refresh = ui.button("Refresh").clicked() || shortcut_actions.contains(&ShortcutAction::Refresh);
 					});
 
 					if self.browse_mode == LeftPanelMode::Playlists {
@@ -1737,6 +2823,32 @@ impl eframe::App for MyApp {
 						}
 						ui.label(egui::RichText::new(extract_folder_name(&self.active_directory_filepath)).strong());
 					});
+					let library_roots = roots::library_roots(&self.persistent_data);
+					if !library_roots.is_empty() {
+						let current_root = roots::root_of(&library_roots, &self.current_song_folder).map(|root| root.name.clone());
+						let mut selected_root: Option<String> = None;
+						ui.horizontal(|ui| {
+							ui.label("Library: ");
+							egui::ComboBox::from_id_salt("library_root")
+								.selected_text(current_root.as_deref().unwrap_or("Other folder"))
+								.show_ui(ui, |ui| {
+									for root in &library_roots {
+										if ui.selectable_label(current_root.as_ref() == Some(&root.name), &root.name).clicked() {
+											selected_root = Some(root.path.clone());
+										}
+									}
+								});
+						});
+						if let Some(root_path) = selected_root {
+							self.current_song_folder = root_path;
+							self.directory_tree = None;
+							self.searched_directory_tree = None;
+							self.active_directory_filepath = self.current_song_folder.clone();
+							self.active_search_text = "".to_string();
+							self.search_text = "".to_string();
+							request_refresh = true;
+						}
+					}
 					ui.add_space(5.0);
 
 					if let Some(active_directory) = active_directory {
@@ -1776,7 +2888,9 @@ impl eframe::App for MyApp {
 								};
 							}
 						}
-						let faction = render_directory_elements(ui, &self.directory_tree, &self.searched_directory_tree, &audio_data.song_name, &self.edit_playlist_data);
+						let play_state_of = |song: &str| self.library.lock().ok().and_then(|library| resume::displayed_play_state(&self.persistent_data.resume, song, library.songs.get(song)));
+						let faction = render_directory_elements(ui, &self.directory_tree, &self.searched_directory_tree, &audio_data.song_name, &self.edit_playlist_data, self.scroll_to_current_song, &play_state_of);
+						self.scroll_to_current_song = false;
 						if file_action == FileActions::None {
 							file_action = faction;
 						}
@@ -1787,17 +2901,12 @@ impl eframe::App for MyApp {
 				LeftPanelMode::Playlists => {
 					ui.horizontal(|ui| {
 						if let None = &self.edit_playlist_data {
-							egui::ComboBox::from_label("")
-								.selected_text(song_browse_mode_to_str(&self.browse_mode))
-								.show_ui(ui, |ui| {
-									ui.selectable_value(&mut self.browse_mode, LeftPanelMode::Files, "Files");
-									ui.selectable_value(&mut self.browse_mode, LeftPanelMode::Playlists, "Playlists");
-								});
+							browse_mode_selector(ui, &mut self.browse_mode);
 						}
 						// if ui.button("Advanced").clicked() {
 						// 	self.advanced_search_active = !self.advanced_search_active;
 						// }
-						request_refresh = ui.button("Refresh").clicked() || ctx.input_mut(|i| i.consume_shortcut(&REFRESH));
+						request_refresh = ui.button("Refresh").clicked() || shortcut_actions.contains(&ShortcutAction::Refresh);
 					});
 					if self.browse_mode == LeftPanelMode::Files {
 						if let Some(dirtree) = &self.directory_tree {
@@ -1928,7 +3037,13 @@ impl eframe::App for MyApp {
 							if ui.button("Delete").clicked() {
 								self.browse_mode = LeftPanelMode::DeletePlaylist;
 							}
+							let rendering = self.render_job.as_ref().is_some_and(|(_, job)| job.result().is_none());
+							if ui.add_enabled(!rendering, egui::Button::new("Render to file")).on_hover_text("Plays the whole playlist into one file, in the recording format and folder").clicked()
+							&& let Some(playlist) = self.persistent_data.playlists.get(active_playlist_index) {
+								self.render_job = Some(start_playlist_render(&self.persistent_data, playlist));
+							}
 						});
+						render_job_status(ui, &mut self.render_job);
 						ui.add_space(5.0);
 					}
 
@@ -1971,6 +3086,187 @@ impl eframe::App for MyApp {
 						ui.label("No saved playlists found");
 					}
 				},
+				LeftPanelMode::Artists | LeftPanelMode::Albums | LeftPanelMode::Search => {
+					ui.horizontal(|ui| {
+						if self.edit_playlist_data.is_none() {
+							browse_mode_selector(ui, &mut self.browse_mode);
+						}
+						request_refresh = ui.button("Refresh").clicked() || shortcut_actions.contains(&ShortcutAction::Refresh);
+					});
+					/* Rebuilding the tree also sends its songs as the new song collection */
+					match self.browse_mode {
+						LeftPanelMode::Files => {
+							self.directory_tree = None;
+							self.searched_directory_tree = None;
+							self.active_search_text = "".to_string();
+						},
+						LeftPanelMode::Playlists => {
+							self.playlist_tree = None;
+							self.searched_playlist_tree = None;
+						},
+						_ => {},
+					}
+
+					let (generation, scanning, song_count) = {
+						let song_index = self.song_index.lock().unwrap();
+						(song_index.generation, song_index.scanning, song_index.songs.len())
+					};
+					if scanning {
+						ui.label(egui::RichText::new(format!("Reading tags... {} songs so far", song_count)).weak());
+					} else if song_count == 0 {
+						ui.label("No tagged songs found in the default directory");
+					}
+
+					if self.browse_mode == LeftPanelMode::Search {
+						let key = (generation, playlists_key(&self.persistent_data.playlists));
+						if self.search_entries.as_ref().is_none_or(|(built_for, _)| *built_for != key) {
+							let entries = search::build_entries(&self.song_index.lock().unwrap(), &self.persistent_data.playlists);
+							self.search_entries = Some((key, entries));
+							search::reset_search(&mut self.library_search);
+						}
+						let response = ui.add(egui::TextEdit::singleline(&mut self.library_search_text).hint_text("Search everything..."))
+							.on_hover_text("Searches every song in the library and every playlist. The letters only have to show up in order");
+						if let Some((_, entries)) = &self.search_entries {
+							search::update_search(&mut self.library_search, entries, &self.library_search_text);
+							let mut chosen = None;
+							if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+								chosen = Some(0);
+								response.request_focus();
+							}
+							let matches = &self.library_search.matches;
+							if !self.library_search_text.trim().is_empty() {
+								ui.label(egui::RichText::new(if matches.is_empty() {"Nothing found".to_string()} else {format!("{} best matches", matches.len())}).weak());
+							}
+							egui::ScrollArea::vertical().show(ui, |ui| {
+								for (i, found) in matches.iter().enumerate() {
+									let entry = &entries[found.entry];
+									ui.horizontal(|ui| {
+										ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+										let hover = match &entry.target {
+											search::SearchTarget::Song(path) => path.clone(),
+											search::SearchTarget::Playlist(_) => "Playlist".to_string(),
+										};
+										if ui.button("▶").on_hover_text(hover).clicked() {
+											chosen = Some(i);
+										}
+										if matches!(entry.target, search::SearchTarget::Playlist(_)) {
+											ui.label("☰");
+										}
+										let is_current = entry.target == search::SearchTarget::Song(audio_data.song_name.clone());
+										ui.label(highlighted_text(ui, &entry.text, &found.positions, is_current));
+									});
+								}
+							});
+							if let Some(found) = chosen.and_then(|i| matches.get(i)) {
+								match &entries[found.entry].target {
+									search::SearchTarget::Song(song) => {
+										/* Next goes on to the next best match */
+										let collection: Vec<String> = matches.iter().filter_map(|found| match &entries[found.entry].target {
+											search::SearchTarget::Song(song) => Some(song.clone()),
+											search::SearchTarget::Playlist(_) => None,
+										}).collect();
+										let position = collection.iter().position(|in_collection| in_collection == song);
+										send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSongCollection(collection, position));
+										send_audio_signal(&self.audio_message_channel, MessageToAudio::PlaySong(song.clone()));
+									},
+									search::SearchTarget::Playlist(name) => {
+										if let Some(playlist) = self.persistent_data.playlists.iter().find(|playlist| playlist.name == *name)
+										&& let Some(first) = playlist.songs.first() {
+											send_audio_signal(&self.audio_message_channel, MessageToAudio::SetSongCollection(playlist.songs.clone(), Some(0)));
+											send_audio_signal(&self.audio_message_channel, MessageToAudio::PlaySong(first.clone()));
+										}
+									},
+								}
+							}
+						}
+					} else if let Some(album) = &self.browse_album {
+						let mut go_back = false;
+						ui.horizontal(|ui| {
+							ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+							go_back = ui.button("↑").clicked();
+							ui.label(egui::RichText::new(&album.name).strong());
+						});
+						let cover = album.songs.first().and_then(|song| cover_art::cover_texture(&mut self.cover_art, ctx, &song.path));
+						render_album_tracks(ui, album, cover.as_ref(), &audio_data.song_name, &self.audio_message_channel);
+						if go_back {
+							self.browse_album = None;
+						}
+					} else if self.browse_mode == LeftPanelMode::Artists && self.browse_artist.is_none() {
+						if self.artist_list.as_ref().is_none_or(|(built_for, _)| *built_for != generation) {
+							self.artist_list = Some((generation, index::artists(&self.song_index.lock().unwrap())));
+						}
+						let mut picked = None;
+						if let Some((_, artists)) = &self.artist_list {
+							egui::ScrollArea::vertical().show(ui, |ui| {
+								ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+								for (artist, album_count) in artists {
+									let albums_text = if *album_count == 1 {"1 album".to_string()} else {format!("{} albums", album_count)};
+									if ui.selectable_label(false, artist).on_hover_text(albums_text).clicked() {
+										picked = Some(artist.clone());
+									}
+								}
+							});
+						}
+						if picked.is_some() {
+							self.browse_artist = picked;
+						}
+					} else {
+						let artist = if self.browse_mode == LeftPanelMode::Artists {self.browse_artist.clone()} else {None};
+						if let Some(artist) = &artist {
+							let mut go_back = false;
+							ui.horizontal(|ui| {
+								ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+								go_back = ui.button("↑").clicked();
+								ui.label(egui::RichText::new(artist).strong());
+							});
+							if go_back {
+								self.browse_artist = None;
+							}
+						}
+						if self.album_list.as_ref().is_none_or(|(built_for, built_artist, _)| *built_for != generation || *built_artist != artist) {
+							let albums = index::albums(&self.song_index.lock().unwrap(), artist.as_deref());
+							self.album_list = Some((generation, artist.clone(), albums));
+						}
+						let mut opened = None;
+						if let Some((_, _, albums)) = &self.album_list {
+							if artist.is_some() {
+								/* Only the rows on screen get laid out, and ask for their covers */
+								let row_height = COVER_THUMBNAIL_SIZE.max(ui.spacing().interact_size.y);
+								egui::ScrollArea::vertical().show_rows(ui, row_height, albums.len(), |ui, range| {
+									ui.style_mut().wrap_mode = Some(egui::TextWrapMode::Truncate);
+									for album in &albums[range] {
+										let year = if album.year.is_empty() {"".to_string()} else {format!(" ({})", album.year)};
+										let cover = album.songs.first().and_then(|song| cover_art::cover_texture(&mut self.cover_art, ctx, &song.path));
+										ui.horizontal(|ui| {
+											let thumbnail = if let Some(cover) = &cover {
+												paint_cover(ui, cover, COVER_THUMBNAIL_SIZE)
+											} else {
+												ui.allocate_exact_size(egui::vec2(COVER_THUMBNAIL_SIZE, COVER_THUMBNAIL_SIZE), egui::Sense::click()).1
+											};
+											if thumbnail.clicked() || ui.selectable_label(false, format!("{}{}", album.name, year)).clicked() {
+												opened = Some(album.clone());
+											}
+										});
+									}
+								});
+							} else {
+								egui::ScrollArea::vertical().show(ui, |ui| {
+									ui.horizontal_wrapped(|ui| {
+										for album in albums {
+											let cover = || album.songs.first().and_then(|song| cover_art::cover_texture(&mut self.cover_art, ctx, &song.path));
+											if render_album_tile(ui, album, cover, ALBUM_TILE_SIZE) {
+												opened = Some(album.clone());
+											}
+										}
+									});
+								});
+							}
+						}
+						if opened.is_some() {
+							self.browse_album = opened;
+						}
+					}
+				},
 				LeftPanelMode::DeletePlaylist => {
 					if let Some(playlist_index) = self.active_playlist_index {
 						ui.vertical_centered(|ui| {
@@ -2004,7 +3300,7 @@ impl eframe::App for MyApp {
 						// if ui.button("Advanced").clicked() {
 						// 	self.advanced_search_active = !self.advanced_search_active;
 						// }
-						request_refresh = ui.button("Refresh").clicked() || ctx.input_mut(|i| i.consume_shortcut(&REFRESH));
+						request_refresh = ui.button("Refresh").clicked() || shortcut_actions.contains(&ShortcutAction::Refresh);
 					});
 
 					ui.horizontal(|ui| {
@@ -2139,7 +3435,8 @@ impl eframe::App for MyApp {
 									};
 								}
 							}
-							file_action = render_directory_elements(ui, &self.directory_tree, &self.searched_directory_tree, &audio_data.song_name, &self.edit_playlist_data);
+							let play_state_of = |song: &str| self.library.lock().ok().and_then(|library| resume::displayed_play_state(&self.persistent_data.resume, song, library.songs.get(song)));
+							file_action = render_directory_elements(ui, &self.directory_tree, &self.searched_directory_tree, &audio_data.song_name, &self.edit_playlist_data, false, &play_state_of);
 						} else {
 							ui.label("Error: Directory does not exist");
 						}
@@ -2474,6 +3771,19 @@ impl eframe::App for MyApp {
 			self.active_search_text = "".to_string();
 			self.active_search_text_playlists = "".to_string();
 			self.directory_map.clear();
+			self.track_rows = None;
+			self.track_row_job = None;
+			let library_roots = roots::library_roots(&self.persistent_data);
+			if !library_roots.is_empty() && !self.song_index.lock().unwrap().scanning {
+				index::start_scan(Arc::clone(&self.song_index), library_roots);
+			}
+			cover_art::clear_cover_art(&mut self.cover_art);
+		}
+
+		self.finish_tag_write();
+		if let Some(scan) = &self.duplicate_scan && let Some(groups) = scan.result() {
+			self.duplicate_groups = Some(groups);
+			self.duplicate_scan = None;
 		}
 
 		egui::CentralPanel::default().show(ctx, |ui| {
@@ -2484,12 +3794,30 @@ impl eframe::App for MyApp {
 					egui::RichText::new("Player")
 				};
 				
-				let settings_text = if self.central_panel_mode == CentralPanelMode::Settings {
+				let tracks_text = if self.central_panel_mode == CentralPanelMode::Tracks || self.central_panel_mode == CentralPanelMode::TagEditor || self.central_panel_mode == CentralPanelMode::Duplicates {
+					egui::RichText::new("Tracks").underline().strong()
+				} else {
+					egui::RichText::new("Tracks")
+				};
+
+				let visualiser_text = if self.central_panel_mode == CentralPanelMode::Visualiser {
+					egui::RichText::new("Visualiser").underline().strong()
+				} else {
+					egui::RichText::new("Visualiser")
+				};
+
+				let settings_text = if self.central_panel_mode == CentralPanelMode::Settings || self.central_panel_mode == CentralPanelMode::Shortcuts {
 					egui::RichText::new("Settings").underline().strong()
 				} else {
 					egui::RichText::new("Settings")
 				};
 
+				let statistics_text = if self.central_panel_mode == CentralPanelMode::Statistics {
+					egui::RichText::new("Statistics").underline().strong()
+				} else {
+					egui::RichText::new("Statistics")
+				};
+
 				let about_text = if self.central_panel_mode == CentralPanelMode::About {
 					egui::RichText::new("About").underline().strong()
 				} else {
@@ -2500,15 +3828,31 @@ impl eframe::App for MyApp {
 				if ui.button(player_text).clicked() {
 					self.central_panel_mode = CentralPanelMode::PlayerMode;
 				}
+				if ui.button(tracks_text).clicked() {
+					self.central_panel_mode = CentralPanelMode::Tracks;
+				}
+				if ui.button(visualiser_text).clicked() {
+					self.central_panel_mode = CentralPanelMode::Visualiser;
+				}
 				if ui.button(settings_text).clicked() {
 					self.central_panel_mode = CentralPanelMode::Settings;
 				}
+				if ui.button(statistics_text).clicked() {
+					self.central_panel_mode = CentralPanelMode::Statistics;
+				}
 				if ui.button(about_text).clicked() {
 					self.central_panel_mode = CentralPanelMode::About;
 				}
 			});
 			match self.central_panel_mode {
 				CentralPanelMode::PlayerMode => {
+					if !audio_data.song_name.is_empty() && let Some(cover) = cover_art::cover_texture(&mut self.cover_art, ctx, &audio_data.song_name) {
+						ui.vertical_centered(|ui| {
+							let size = PLAYER_COVER_SIZE.min(ui.available_width());
+							paint_cover(ui, &cover, size);
+						});
+						ui.add_space(5.0);
+					}
 					ui.vertical_centered(|ui| {
 						ui.heading("Player Parameters");
 						ui.add_space(5.0);
@@ -2532,12 +3876,51 @@ impl eframe::App for MyApp {
 						}
 					});
 
+					if !self.find_chapters(&audio_data.song_name).is_empty() {
+						ui.vertical_centered(|ui| {
+							ui.add_space(5.0);
+							ui.heading("Chapters");
+							ui.add_space(5.0);
+						});
+						self.render_chapters(ui, &audio_data);
+					}
+					ui.vertical_centered(|ui| {
+						ui.add_space(5.0);
+						ui.heading("A-B Loop");
+						ui.add_space(5.0);
+					});
+					self.render_ab_loop(ui, &audio_data);
+					ui.vertical_centered(|ui| {
+						ui.add_space(5.0);
+						ui.heading("Sleep Timer");
+						ui.add_space(5.0);
+					});
+					self.render_sleep_timer(ui, &audio_data);
+					ui.vertical_centered(|ui| {
+						ui.add_space(5.0);
+						ui.heading("Recording");
+						ui.add_space(5.0);
+					});
+					self.render_recording(ui, &audio_data);
+					ui.vertical_centered(|ui| {
+						ui.add_space(5.0);
+						ui.heading("Bookmarks");
+						ui.add_space(5.0);
+					});
+					self.render_bookmarks(ui, &audio_data);
+
 					ui.vertical_centered(|ui| {
 						ui.add_space(5.0);
 						ui.heading("Song Info");
 						ui.add_space(5.0);
-						ui.label("Currently unimplemented (coming in final release)");
 					});
+					self.render_song_info(ui, &audio_data.song_name);
+					ui.vertical_centered(|ui| {
+						ui.add_space(5.0);
+						ui.heading("Lyrics");
+						ui.add_space(5.0);
+					});
+					self.render_lyrics(ui, &audio_data.song_name, audio_data.playback_position);
 				},
 				CentralPanelMode::Settings => {
 					ui.vertical_centered(|ui| {
@@ -2622,6 +4005,64 @@ impl eframe::App for MyApp {
 							});
 						}
 					});
+					ui.horizontal(|ui| {
+						ui.label("Library folders: ");
+						let folder = self.current_song_folder.clone();
+						if ui.button("Add current folder").on_hover_text("Scan the folder into the library and list it in the library switcher").clicked()
+						&& !self.persistent_data.library_roots.iter().any(|root| root.path == folder) {
+							/* Adding the first root replaces the default folder as the library, so it stays in it */
+							if self.persistent_data.library_roots.is_empty() && !self.persistent_data.default_directory.is_empty() && folder != self.persistent_data.default_directory {
+								self.persistent_data.library_roots.push(roots::new_root(&self.persistent_data.default_directory));
+							}
+							self.persistent_data.library_roots.push(roots::new_root(&folder));
+						}
+						let scanning = self.song_index.lock().unwrap().scanning;
+						if ui.add_enabled(!scanning, egui::Button::new("Rescan")).on_hover_text("Scan the library folders again with their new settings").clicked() {
+							index::start_scan(Arc::clone(&self.song_index), roots::library_roots(&self.persistent_data));
+						}
+						if self.folder_watcher.polling.load(std::sync::atomic::Ordering::Relaxed) {
+							ui.label(format!("(checked for changes every {} s)", watch::POLL_INTERVAL.as_secs()))
+								.on_hover_text("The folders can't be watched, so they get looked through again now and then");
+						}
+					});
+					let mut removed_root: Option<usize> = None;
+					for (i, root) in self.persistent_data.library_roots.iter_mut().enumerate() {
+						ui.horizontal(|ui| {
+							ui.add_space(10.0);
+							if ui.small_button("🗑").on_hover_text("Take the folder out of the library").clicked() {
+								removed_root = Some(i);
+							}
+							ui.add(egui::TextEdit::singleline(&mut root.name).desired_width(100.0));
+							ui.label(if self.hide_fp {"(Hidden)"} else {&root.path});
+						});
+						ui.horizontal(|ui| {
+							ui.add_space(30.0);
+							let mut all_subfolders = root.max_depth.is_none();
+							if ui.checkbox(&mut all_subfolders, "All subfolders").changed() {
+								root.max_depth = if all_subfolders {None} else {Some(1)};
+							}
+							if let Some(max_depth) = &mut root.max_depth {
+								ui.add(egui::DragValue::new(max_depth).range(0..=32).suffix(" levels"));
+							}
+						});
+						ui.horizontal(|ui| {
+							ui.add_space(30.0);
+							ui.label("Skip: ");
+							/* Split on commas as typed, empty and padded entries get ignored and aren't saved */
+							let mut excludes = root.excludes.join(",");
+							if ui.add(egui::TextEdit::singleline(&mut excludes).hint_text("Subfolders, e.g. Samples, Old").desired_width(140.0)).changed() {
+								root.excludes = excludes.split(',').map(|exclude| exclude.to_string()).collect();
+							}
+							ui.label("Only: ");
+							let mut globs = root.globs.join(",");
+							if ui.add(egui::TextEdit::singleline(&mut globs).hint_text("Files, e.g. *.mp3, * - *").desired_width(140.0)).changed() {
+								root.globs = globs.split(',').map(|glob| glob.to_string()).collect();
+							}
+						});
+					}
+					if let Some(index) = removed_root {
+						self.persistent_data.library_roots.remove(index);
+					}
 					ui.horizontal(|ui| {
 						ui.label("Hide File Paths by Default: ");
 						ui.checkbox(&mut self.persistent_data.hide_directories_by_default, "");
@@ -2654,6 +4095,71 @@ impl eframe::App for MyApp {
 							}
 						}
 					});
+					ui.horizontal(|ui| {
+						ui.label("Resume files longer than: ");
+						ui.add(egui::DragValue::new(&mut self.persistent_data.resume.threshold_mins).range(0..=600).suffix(" min"));
+					}).response.on_hover_text_at_pointer("Long files (podcasts, audiobooks) continue where they were left off.\n0 only does this for files in audiobook/podcast folders.");
+					ui.horizontal(|ui| {
+						ui.label("Audiobook/podcast folders: ");
+						let folder = self.current_song_folder.clone();
+						if ui.button("Add current folder").on_hover_text("Every file in the folder remembers its position and shows whether it was played").clicked()
+						&& !self.persistent_data.resume.audiobook_folders.contains(&folder) {
+							self.persistent_data.resume.audiobook_folders.push(folder);
+						}
+					});
+					let mut removed_folder: Option<usize> = None;
+					for (i, folder) in self.persistent_data.resume.audiobook_folders.iter().enumerate() {
+						ui.horizontal(|ui| {
+							ui.add_space(10.0);
+							if ui.small_button("🗑").on_hover_text("Stop treating the folder as audiobooks").clicked() {
+								removed_folder = Some(i);
+							}
+							ui.label(if self.hide_fp {"(Hidden)"} else {folder});
+						});
+					}
+					if let Some(index) = removed_folder {
+						self.persistent_data.resume.audiobook_folders.remove(index);
+					}
+					ui.horizontal(|ui| {
+						ui.label("Scrobble log: ");
+						egui::ComboBox::from_label("    ")
+							.selected_text(match self.persistent_data.scrobble_log_format {
+								scrobble::ScrobbleLogFormat::Off => "Off",
+								scrobble::ScrobbleLogFormat::Rockbox => "Rockbox (.scrobbler.log)",
+								scrobble::ScrobbleLogFormat::ListenBrainz => "ListenBrainz (JSON lines)",
+								scrobble::ScrobbleLogFormat::Both => "Both",
+							})
+							.show_ui(ui, |ui| {
+								ui.selectable_value(&mut self.persistent_data.scrobble_log_format, scrobble::ScrobbleLogFormat::Off, "Off");
+								ui.selectable_value(&mut self.persistent_data.scrobble_log_format, scrobble::ScrobbleLogFormat::Rockbox, "Rockbox (.scrobbler.log)");
+								ui.selectable_value(&mut self.persistent_data.scrobble_log_format, scrobble::ScrobbleLogFormat::ListenBrainz, "ListenBrainz (JSON lines)");
+								ui.selectable_value(&mut self.persistent_data.scrobble_log_format, scrobble::ScrobbleLogFormat::Both, "Both");
+							}
+						);
+					}).response.on_hover_text_at_pointer("Songs count as listened after half their length or 4 minutes. Logs are written to the install folder.");
+					ui.horizontal(|ui| {
+						ui.label("Submit listens to: ");
+						ui.add(egui::TextEdit::singleline(&mut self.persistent_data.scrobble_endpoint).hint_text("http://localhost:8080 (empty = off)"));
+					}).response.on_hover_text_at_pointer("A server implementing the ListenBrainz submit-listens API. Only plain http is supported.");
+					ui.horizontal(|ui| {
+						ui.label("Submission token: ");
+						ui.add(egui::TextEdit::singleline(&mut self.persistent_data.scrobble_token).password(true));
+					});
+					if let Ok(mut scrobbler) = self.scrobbler.lock() {
+						scrobbler.log_format = self.persistent_data.scrobble_log_format;
+						if scrobbler.endpoint != self.persistent_data.scrobble_endpoint {
+							scrobbler.endpoint = self.persistent_data.scrobble_endpoint.clone();
+						}
+						if scrobbler.token != self.persistent_data.scrobble_token {
+							scrobbler.token = self.persistent_data.scrobble_token.clone();
+						}
+						if !scrobbler.queue.is_empty() {
+							ui.label(format!("{} listens waiting to be submitted", scrobbler.queue.len()));
+						}
+						if let Some(err) = &scrobbler.last_submit_error {
+							ui.label(egui::RichText::new(err).color(egui::Color32::RED));
+						}
+					}
 					#[cfg(target_os = "windows")] {
 						ui.horizontal(|ui| {
 							use windows_sys::Win32::Foundation::HWND;
@@ -2682,39 +4188,26 @@ impl eframe::App for MyApp {
 						}).response.on_hover_text_at_pointer("This option is not supported on Linux - certain window managers do not support this.");
 					}
 					
-					if ui.button("Save").clicked() {
-						/* TODO: Error handling */
-						let write_to = build_full_filepath(&self.installed_location, "internal_pinetree_data.txt");
-						if let Ok(_) = write_internal_data(&write_to, &self.persistent_data) {
-							self.save_err = SaveError::Success;
-						} else {
-							self.save_err = SaveError::Error(format!("Error: Failed to save internal data"))
-						}
-					}
-					match &mut self.save_err {
-						SaveError::None => {},
-						SaveError::Success => {
-							ui.horizontal(|ui| {
-								if ui.button("X").clicked() {
-									self.save_err = SaveError::None;
-								}
-								ui.label("Successfully saved settings");
-							});
-						},
-						SaveError::Error(string) => {
-							let mut clear = false;
-							ui.horizontal(|ui| {
-								if ui.button(egui::RichText::new("X").color(egui::Color32::RED)).clicked() {
-									clear = true;
-								} else {
-									ui.label(egui::RichText::new(string.clone()).color(egui::Color32::RED));
-								}
-							});
-							if clear {
-								self.save_err = SaveError::None;
-							}
-						},
+					if ui.button("Keyboard shortcuts...").clicked() {
+						self.central_panel_mode = CentralPanelMode::Shortcuts;
 					}
+
+					self.render_save_settings(ui);
+				},
+				CentralPanelMode::Shortcuts => {
+					self.render_shortcut_editor(ui);
+				},
+				CentralPanelMode::Tracks => {
+					self.render_track_table(ui, &audio_data.song_name);
+				},
+				CentralPanelMode::TagEditor => {
+					self.render_tag_editor(ui);
+				},
+				CentralPanelMode::Duplicates => {
+					self.render_duplicates(ui);
+				},
+				CentralPanelMode::Visualiser => {
+					self.render_visualiser(ui);
 				},
 				CentralPanelMode::Installer => {
 					ui.vertical_centered(|ui| {
@@ -2747,6 +4240,13 @@ impl eframe::App for MyApp {
 							} else {
 								self.central_panel_mode = CentralPanelMode::InstallationSuccess;
 								self.installed_location = self.installer_data.install_path.clone();
+								let pinetree_folder = build_full_filepath(&self.installed_location, "Pinetree");
+								if let Ok(mut library) = self.library.lock() {
+									library.file_path = Some(build_full_filepath(&pinetree_folder, library::LIBRARY_FILE_NAME));
+								}
+								if let Ok(mut scrobbler) = self.scrobbler.lock() {
+									scrobbler.folder = Some(pinetree_folder);
+								}
 								self.active_directory_filepath = self.installer_data.default_song_folder.clone();
 								self.current_song_folder = self.installer_data.default_song_folder.clone();
 								ctx.request_repaint();
@@ -2768,6 +4268,19 @@ impl eframe::App for MyApp {
 
 					ui.label("Additional note: Creating shortcuts/taskbar icons automatically is unfortunately not supported because it would mean requiring admin permissions on Windows");
 				},
+				CentralPanelMode::Statistics => {
+					egui::ScrollArea::vertical().show(ui, |ui| {
+						ui.vertical_centered(|ui| {
+							ui.heading("Statistics");
+							ui.add_space(5.0);
+						});
+						if let Ok(library) = self.library.lock() {
+							render_statistics(ui, &library, &mut self.statistics_period);
+						} else {
+							ui.label("Error: Failed to read the library");
+						}
+					});
+				},
 				CentralPanelMode::About => {
 					egui::ScrollArea::vertical().show(ui, |ui| {
 						ui.vertical_centered(|ui| {
@@ -2814,6 +4327,69 @@ impl eframe::App for MyApp {
 	}
 }
 
+fn render_statistics(ui: &mut egui::Ui, library: &library::Library, period: &mut library::ListeningPeriod) {
+	ui.heading("Listening Time");
+	ui.horizontal(|ui| {
+		ui.selectable_value(period, library::ListeningPeriod::Day, "Per day");
+		ui.selectable_value(period, library::ListeningPeriod::Week, "Per week");
+		ui.selectable_value(period, library::ListeningPeriod::Month, "Per month");
+	});
+	let periods = library::listening_time_per_period(library, *period, 7);
+	let longest = periods.iter().map(|(_, ms)| *ms).max().unwrap_or(0).max(1);
+	egui::Grid::new("listening_time_grid").num_columns(3).striped(true).show(ui, |ui| {
+		for (label, ms) in &periods {
+			ui.label(label);
+			ui.add(egui::ProgressBar::new(*ms as f32 / longest as f32).desired_width(150.0));
+			ui.label(library::format_listening_time(*ms));
+			ui.end_row();
+		}
+	});
+
+	ui.add_space(10.0);
+	ui.heading("Top Tracks");
+	let tracks = library::top_tracks(library, 10);
+	if tracks.is_empty() {
+		ui.label("Nothing has been played yet");
+	} else {
+		egui::Grid::new("top_tracks_grid").num_columns(5).striped(true).show(ui, |ui| {
+			ui.label(egui::RichText::new("Title").strong());
+			ui.label(egui::RichText::new("Artist").strong());
+			ui.label(egui::RichText::new("Plays").strong());
+			ui.label(egui::RichText::new("Skips").strong());
+			ui.label(egui::RichText::new("Listened").strong());
+			ui.end_row();
+			for (song, record) in tracks {
+				ui.label(library::display_title(song, record)).on_hover_text(song);
+				ui.label(&record.artist);
+				ui.label(format!("{}", record.play_count));
+				ui.label(format!("{}", record.skip_count));
+				ui.label(library::format_listening_time(record.time_listened_ms));
+				ui.end_row();
+			}
+		});
+	}
+
+	ui.add_space(10.0);
+	ui.heading("Top Artists");
+	let artists = library::top_artists(library, 10);
+	if artists.is_empty() {
+		ui.label("Nothing has been played yet");
+	} else {
+		egui::Grid::new("top_artists_grid").num_columns(3).striped(true).show(ui, |ui| {
+			ui.label(egui::RichText::new("Artist").strong());
+			ui.label(egui::RichText::new("Plays").strong());
+			ui.label(egui::RichText::new("Listened").strong());
+			ui.end_row();
+			for (artist, plays, listened) in artists {
+				ui.label(if artist.is_empty() {"Unknown artist".to_string()} else {artist});
+				ui.label(format!("{}", plays));
+				ui.label(library::format_listening_time(listened));
+				ui.end_row();
+			}
+		});
+	}
+}
+
 fn initialize_crash_logger(pinetree_directory: &str) {
 	let log_location = build_full_filepath(pinetree_directory, "crash_report.log").to_string();
 	panic::set_hook(Box::new(move |panic_info| {
@@ -2845,6 +4421,43 @@ fn initialize_crash_logger(pinetree_directory: &str) {
 }
 
 fn main() -> eframe::Result {
+	let command_line: Vec<String> = std::env::args().skip(1).collect();
+	let startup_args = match args::parse_args(&command_line) {
+		Ok(startup_args) => startup_args,
+		Err(err) => {
+			eprintln!("Error: {}\n\n{}", err, args::USAGE);
+			std::process::exit(2);
+		},
+	};
+	if startup_args.show_help {
+		println!("{}", args::USAGE);
+		return Ok(());
+	}
+	if let Some(ctl_args) = &startup_args.ctl_args {
+		std::process::exit(remote::run_ctl(ctl_args, startup_args.data_dir.as_deref()));
+	}
+	/* If Pinetree is already open, the song gets played there instead of opening a second window */
+	if let Some(open_path) = &startup_args.open_path && std::path::Path::new(open_path).is_file() {
+		let command = format!("play {}", open_path);
+		if let Ok(response) = remote::send_command(&command, startup_args.data_dir.as_deref()) && !response.starts_with("Error") {
+			return Ok(());
+		}
+	}
+
+	if startup_args.tui {
+		#[cfg(target_family = "unix")]
+		if let Err(err) = tui::run_tui(&startup_args) {
+			eprintln!("Error: {}", err);
+			std::process::exit(1);
+		}
+		#[cfg(not(target_family = "unix"))] {
+			eprintln!("Error: The terminal interface is only available on Linux and macOS");
+			std::process::exit(1);
+		}
+		#[cfg(target_family = "unix")]
+		return Ok(());
+	}
+
 	let img = eframe::icon_data::from_png_bytes(include_bytes!("./../resources/Pinetree_Logo.png"));
 	let options = if let Ok(img) = img {
 		eframe::NativeOptions {
@@ -2862,6 +4475,6 @@ fn main() -> eframe::Result {
 	eframe::run_native(
 		"Pinetree Music Player",
 		options,
-		Box::new(|_cc| Ok(Box::new(MyApp::default()))),
+		Box::new(move |_cc| Ok(Box::new(MyApp::new(&startup_args)))),
 	)
 }
@@ -0,0 +1,605 @@
+/*
+ * Pinetree terminal interface (--tui). A keyboard driven front-end for when there is no display, e.g. over SSH.
+ *
+ * It talks to the same audio thread as the GUI and uses the same persistent data, directory and playlist code,
+ * it just draws with plain ANSI escape codes instead of egui. Only unix terminals are supported.
+ */
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Arc, Condvar, Mutex};
+
+use pinetree_core::audio_frontend::{self, LoopBehavior, MessageToAudio, RodioData};
+use pinetree_core::config::{self, PersistentData};
+use pinetree_core::engine;
+use pinetree_core::files::{self, Directory, DirTreeElement};
+use pinetree_core::{library, resume, roots, watch};
+
+/* How often the screen gets redrawn when no key is pressed */
+const REFRESH_INTERVAL_MS: i32 = 250;
+const SEEK_STEP_MS: i64 = 5000;
+const VOLUME_STEP: f32 = 0.05;
+
+pub const KEY_HELP: &str = "space pause  n/p next/prev  \u{2190}/\u{2192} seek  +/- volume  m mode  / search  tab playlists  q quit";
+
+/**
+ * Puts the terminal in raw mode on the alternate screen, and puts everything back when dropped
+ * (including when Pinetree panics, so the terminal isn't left unusable).
+ */
+struct RawTerminal {
+	original: libc::termios,
+}
+
+fn enable_raw_mode() -> Option<RawTerminal> {
+	unsafe {
+		if libc::isatty(libc::STDIN_FILENO) == 0 || libc::isatty(libc::STDOUT_FILENO) == 0 {
+			return None;
+		}
+		let mut original: libc::termios = std::mem::zeroed();
+		if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+			return None;
+		}
+		let mut raw = original;
+		libc::cfmakeraw(&mut raw);
+		if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) != 0 {
+			return None;
+		}
+		/* Alternate screen, hidden cursor */
+		print!("\x1b[?1049h\x1b[?25l");
+		let _ = std::io::stdout().flush();
+		Some(RawTerminal {original})
+	}
+}
+
+impl Drop for RawTerminal {
+	fn drop(&mut self) {
+		print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+		let _ = std::io::stdout().flush();
+		unsafe {
+			libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+		}
+	}
+}
+
+/* (columns, rows) */
+fn terminal_size() -> (usize, usize) {
+	unsafe {
+		let mut size: libc::winsize = std::mem::zeroed();
+		if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 && size.ws_row > 0 {
+			return (size.ws_col as usize, size.ws_row as usize);
+		}
+	}
+	(80, 24)
+}
+
+#[derive(PartialEq)]
+enum Key {
+	Char(char),
+	Up,
+	Down,
+	Left,
+	Right,
+	PageUp,
+	PageDown,
+	Home,
+	End,
+	Enter,
+	Backspace,
+	Tab,
+	Escape,
+	CtrlC,
+}
+
+/**
+ * Turns raw terminal input into keys. Anything that isn't understood gets dropped.
+ */
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+	let mut keys = Vec::<Key>::new();
+	let mut i = 0;
+	while i < bytes.len() {
+		match bytes[i] {
+			0x1b => {
+				/* Escape sequences look like ESC [ A or ESC O A, or ESC [ 5 ~ for the ones with numbers */
+				if i + 2 < bytes.len() && (bytes[i + 1] == b'[' || bytes[i + 1] == b'O') {
+					let mut end = i + 2;
+					while end < bytes.len() && (bytes[end].is_ascii_digit() || bytes[end] == b';') {
+						end += 1;
+					}
+					if end >= bytes.len() {
+						break;
+					}
+					let key = match (&bytes[i + 2..end], bytes[end]) {
+						(_, b'A') => Some(Key::Up),
+						(_, b'B') => Some(Key::Down),
+						(_, b'C') => Some(Key::Right),
+						(_, b'D') => Some(Key::Left),
+						(_, b'H') | (b"1", b'~') | (b"7", b'~') => Some(Key::Home),
+						(_, b'F') | (b"4", b'~') | (b"8", b'~') => Some(Key::End),
+						(b"5", b'~') => Some(Key::PageUp),
+						(b"6", b'~') => Some(Key::PageDown),
+						_ => None,
+					};
+					if let Some(key) = key {
+						keys.push(key);
+					}
+					i = end + 1;
+				} else {
+					keys.push(Key::Escape);
+					i += 1;
+				}
+			},
+			b'\r' | b'\n' => {keys.push(Key::Enter); i += 1;},
+			0x7f | 0x08 => {keys.push(Key::Backspace); i += 1;},
+			b'\t' => {keys.push(Key::Tab); i += 1;},
+			0x03 => {keys.push(Key::CtrlC); i += 1;},
+			byte => {
+				/* UTF-8 characters can be up to 4 bytes long */
+				let len = match byte {
+					0xF0..=0xFF => 4,
+					0xE0..=0xEF => 3,
+					0xC0..=0xDF => 2,
+					_ => 1,
+				};
+				let end = std::cmp::min(i + len, bytes.len());
+				if let Ok(text) = std::str::from_utf8(&bytes[i..end]) && let Some(c) = text.chars().next() && !c.is_control() {
+					keys.push(Key::Char(c));
+				}
+				i = end;
+			},
+		}
+	}
+	keys
+}
+
+/**
+ * Waits up to the refresh interval for input.
+ */
+fn read_keys() -> Vec<Key> {
+	let mut poll_fd = libc::pollfd {fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0};
+	let ready = unsafe {libc::poll(&mut poll_fd, 1, REFRESH_INTERVAL_MS)};
+	if ready <= 0 {
+		return Vec::new();
+	}
+	let mut buffer = [0u8; 256];
+	let read = unsafe {libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr() as *mut libc::c_void, buffer.len())};
+	if read <= 0 {
+		return Vec::new();
+	}
+	parse_keys(&buffer[..read as usize])
+}
+
+#[derive(PartialEq)]
+enum TuiView {
+	Files,
+	Playlists,
+}
+
+enum Row {
+	Directory {path: String, depth: usize, is_open: bool},
+	Song {path: String, depth: usize},
+	Playlist {index: usize},
+}
+
+struct TuiApp {
+	persistent_data: PersistentData,
+	audio_message_channel: Arc<(Mutex<Vec<MessageToAudio>>, Condvar)>,
+	audio_receive_channel: Arc<(Mutex<Vec<RodioData>>, Condvar)>,
+
+	view: TuiView,
+	current_song_folder: String,
+	directory_map: HashMap<String, Directory>,
+	folder_watcher: Arc<watch::FolderWatcher>,
+	/* None = the list of playlists is shown */
+	active_playlist_index: Option<usize>,
+
+	search_text: String,
+	typing_search: bool,
+
+	selected: usize,
+	scroll: usize,
+
+	loop_behavior: LoopBehavior,
+	song_volume: f32,
+	last_reported_volume: Option<f32>,
+	quit: bool,
+}
+
+fn loop_behavior_label(loop_behavior: &LoopBehavior) -> &'static str {
+	match loop_behavior {
+		LoopBehavior::Stop => "Stop",
+		LoopBehavior::Loop => "Loop",
+		LoopBehavior::Next => "Next",
+		LoopBehavior::Shuffle => "Shuffle",
+	}
+}
+
+fn format_time(ms: usize) -> String {
+	let secs = ms / 1000;
+	format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+/**
+ * Cuts text down to the given number of columns. Every character is assumed to be one column wide.
+ */
+fn fit(text: &str, width: usize) -> String {
+	text.chars().take(width).collect()
+}
+
+fn row_name(app: &TuiApp, row: &Row) -> String {
+	match row {
+		Row::Directory {path, ..} => files::extract_folder_name(path).to_string(),
+		Row::Song {path, ..} => files::extract_file_name(path).to_string(),
+		Row::Playlist {index} => app.persistent_data.playlists.get(*index).map(|playlist| playlist.name.clone()).unwrap_or_default(),
+	}
+}
+
+/**
+ * Every row of the current view, before searching.
+ */
+fn build_rows(app: &TuiApp) -> Vec<Row> {
+	let mut rows = Vec::<Row>::new();
+	match app.view {
+		TuiView::Files => {
+			let mut tree = Vec::<DirTreeElement>::new();
+			files::get_dir_tree_elements(&mut tree, &app.current_song_folder, &app.directory_map, 0);
+			for element in tree {
+				if element.is_dir {
+					rows.push(Row::Directory {path: element.name, depth: element.depth, is_open: element.is_active});
+				} else {
+					rows.push(Row::Song {path: element.name, depth: element.depth});
+				}
+			}
+		},
+		TuiView::Playlists => {
+			if let Some(index) = app.active_playlist_index {
+				if let Some(playlist) = app.persistent_data.playlists.get(index) {
+					for song in &playlist.songs {
+						rows.push(Row::Song {path: song.clone(), depth: 0});
+					}
+				}
+			} else {
+				for index in 0..app.persistent_data.playlists.len() {
+					rows.push(Row::Playlist {index});
+				}
+			}
+		},
+	}
+	rows
+}
+
+/**
+ * The rows that are actually shown, i.e. the ones matching the search (same matching as the GUI search bar).
+ */
+fn visible_rows(app: &TuiApp) -> Vec<Row> {
+	let rows = build_rows(app);
+	if app.search_text.is_empty() {
+		return rows;
+	}
+	let compare_to = app.search_text.to_lowercase();
+	rows.into_iter().filter(|row| row_name(app, row).to_lowercase().contains(&compare_to)).collect()
+}
+
+fn send(app: &TuiApp, message: MessageToAudio) {
+	audio_frontend::send_audio_signal(&app.audio_message_channel, message);
+}
+
+/**
+ * Plays a song, making everything around it (the whole folder tree or playlist, ignoring the search)
+ * the collection that next/previous/shuffle work on.
+ */
+fn play_song(app: &TuiApp, song: &str) {
+	let collection: Vec<String> = build_rows(app).into_iter().filter_map(|row| match row {
+		Row::Song {path, ..} => Some(path),
+		_ => None,
+	}).collect();
+	let position = collection.iter().position(|path| path == song);
+	send(app, MessageToAudio::SetSongCollection(collection, position));
+	send(app, MessageToAudio::PlaySong(song.to_string()));
+}
+
+fn reset_list(app: &mut TuiApp) {
+	app.selected = 0;
+	app.scroll = 0;
+	app.search_text = "".to_string();
+	app.typing_search = false;
+}
+
+fn activate_row(app: &mut TuiApp, row: &Row) {
+	match row {
+		Row::Directory {path, is_open, ..} => {
+			if *is_open {
+				app.directory_map.remove(path);
+			} else {
+				files::init_directory_at_filepath(path, &mut app.directory_map);
+			}
+		},
+		Row::Song {path, ..} => play_song(app, path),
+		Row::Playlist {index} => {
+			app.active_playlist_index = Some(*index);
+			reset_list(app);
+		},
+	}
+}
+
+fn go_back(app: &mut TuiApp) {
+	match app.view {
+		TuiView::Files => {
+			app.current_song_folder = files::song_folder_go_up(&app.current_song_folder);
+			files::init_directory_at_filepath(&app.current_song_folder, &mut app.directory_map);
+		},
+		TuiView::Playlists => {
+			app.active_playlist_index = None;
+		},
+	}
+	reset_list(app);
+}
+
+fn seek_by(app: &TuiApp, audio_data: &RodioData, offset_ms: i64) {
+	if audio_data.song_name.is_empty() {
+		return;
+	}
+	let target = (audio_data.playback_position as i64 + offset_ms).clamp(0, audio_data.song_length as i64);
+	send(app, MessageToAudio::SeekOnce(target as usize));
+}
+
+fn set_volume(app: &mut TuiApp, volume: f32) {
+	app.song_volume = volume.clamp(-0.2, 1.0);
+	send(app, MessageToAudio::UpdateVolume(audio_frontend::volume_curve(app.song_volume)));
+}
+
+fn handle_key(app: &mut TuiApp, key: Key, audio_data: &RodioData, page_height: usize) {
+	let rows = visible_rows(app);
+
+	if app.typing_search {
+		match key {
+			Key::Char(c) => {
+				app.search_text.push(c);
+				app.selected = 0;
+			},
+			Key::Backspace => {
+				app.search_text.pop();
+				app.selected = 0;
+			},
+			Key::Enter => {
+				app.typing_search = false;
+				if let Some(row) = rows.get(app.selected) {
+					activate_row(app, row);
+				}
+			},
+			Key::Escape | Key::CtrlC => {
+				app.typing_search = false;
+				app.search_text = "".to_string();
+			},
+			Key::Up => app.selected = app.selected.saturating_sub(1),
+			Key::Down => app.selected += 1,
+			_ => {},
+		}
+		return;
+	}
+
+	match key {
+		Key::Char('q') | Key::CtrlC => app.quit = true,
+		Key::Up | Key::Char('k') => app.selected = app.selected.saturating_sub(1),
+		Key::Down | Key::Char('j') => app.selected += 1,
+		Key::PageUp => app.selected = app.selected.saturating_sub(page_height),
+		Key::PageDown => app.selected += page_height,
+		Key::Home | Key::Char('g') => app.selected = 0,
+		Key::End | Key::Char('G') => app.selected = rows.len().saturating_sub(1),
+		Key::Enter => {
+			if let Some(row) = rows.get(app.selected) {
+				activate_row(app, row);
+			}
+		},
+		Key::Char('l') => {
+			if let Some(Row::Directory {path, ..}) = rows.get(app.selected) {
+				app.current_song_folder = path.clone();
+				files::init_directory_at_filepath(path, &mut app.directory_map);
+				reset_list(app);
+			} else if let Some(row @ Row::Playlist {..}) = rows.get(app.selected) {
+				activate_row(app, row);
+			}
+		},
+		Key::Backspace | Key::Char('h') => go_back(app),
+		Key::Escape => {
+			app.search_text = "".to_string();
+		},
+		Key::Tab => {
+			app.view = if app.view == TuiView::Files {TuiView::Playlists} else {TuiView::Files};
+			reset_list(app);
+		},
+		Key::Char('/') => {
+			app.typing_search = true;
+			app.search_text = "".to_string();
+			app.selected = 0;
+		},
+		Key::Char(' ') if !audio_data.song_name.is_empty() => send(app, MessageToAudio::TogglePause),
+		Key::Char('n') => send(app, MessageToAudio::SongEnd),
+		Key::Char('p') => send(app, MessageToAudio::PreviousSong),
+		Key::Left => seek_by(app, audio_data, -SEEK_STEP_MS),
+		Key::Right => seek_by(app, audio_data, SEEK_STEP_MS),
+		Key::Char('+') | Key::Char('=') => set_volume(app, app.song_volume + VOLUME_STEP),
+		Key::Char('-') => set_volume(app, app.song_volume - VOLUME_STEP),
+		Key::Char('m') => {
+			app.loop_behavior = audio_frontend::next_loop_behavior(&app.loop_behavior);
+			send(app, MessageToAudio::UpdateEndBehavior(audio_frontend::clone_loop_behavior(&app.loop_behavior)));
+		},
+		_ => {},
+	}
+}
+
+fn draw(app: &mut TuiApp, audio_data: &RodioData, width: usize, height: usize) {
+	let rows = visible_rows(app);
+	let list_height = height.saturating_sub(5).max(1);
+
+	if app.selected >= rows.len() {
+		app.selected = rows.len().saturating_sub(1);
+	}
+	if app.selected < app.scroll {
+		app.scroll = app.selected;
+	}
+	if app.selected >= app.scroll + list_height {
+		app.scroll = app.selected + 1 - list_height;
+	}
+
+	let mut out = String::new();
+	let mut line = |row: usize, style: &str, text: &str| {
+		out.push_str(&format!("\x1b[{};1H{}{}\x1b[K\x1b[0m", row + 1, style, fit(text, width)));
+	};
+
+	/* Header */
+	let location = match app.view {
+		TuiView::Files => format!("Files: {}", app.current_song_folder),
+		TuiView::Playlists => match app.active_playlist_index.and_then(|index| app.persistent_data.playlists.get(index)) {
+			Some(playlist) => format!("Playlist: {}", playlist.name),
+			None => "Playlists".to_string(),
+		},
+	};
+	line(0, "\x1b[7m", &format!(" Pinetree Player  |  {}{}", location, " ".repeat(width)));
+
+	/* Search */
+	let search_line = if app.typing_search {
+		format!("/{}_", app.search_text)
+	} else if !app.search_text.is_empty() {
+		format!("/{}  (esc to clear)", app.search_text)
+	} else {
+		"".to_string()
+	};
+	line(1, "", &search_line);
+
+	/* List */
+	for i in 0..list_height {
+		let index = app.scroll + i;
+		let Some(row) = rows.get(index) else {
+			line(2 + i, "", "");
+			continue;
+		};
+		let (depth, marker, is_current) = match row {
+			Row::Directory {depth, is_open, ..} => (*depth, if *is_open {"\u{25be} "} else {"\u{25b8} "}, false),
+			Row::Song {path, depth} => (*depth, "  ", *path == audio_data.song_name),
+			Row::Playlist {..} => (0, "\u{2261} ", false),
+		};
+		let mut style = "".to_string();
+		if index == app.selected {
+			style.push_str("\x1b[7m");
+		}
+		if is_current {
+			style.push_str("\x1b[1m");
+		}
+		let text = format!("{}{}{}{}", "  ".repeat(depth), marker, row_name(app, row), if index == app.selected {" ".repeat(width)} else {"".to_string()});
+		line(2 + i, &style, &text);
+	}
+	if rows.is_empty() {
+		let message = match app.view {
+			TuiView::Files if !app.directory_map.contains_key(&app.current_song_folder) => "Error: Directory does not exist",
+			_ if !app.search_text.is_empty() => "Nothing matches the search",
+			_ => "Nothing here",
+		};
+		line(2, "", message);
+	}
+
+	/* Now playing */
+	if let Some(err) = &audio_data.error_message {
+		line(height - 3, "\x1b[31m", err);
+	} else if audio_data.song_name.is_empty() {
+		line(height - 3, "", "No song playing");
+	} else {
+		line(height - 3, "", &format!("Now playing: {}", files::extract_file_name(&audio_data.song_name)));
+	}
+
+	/* Seek bar */
+	let state = if audio_data.song_name.is_empty() {"\u{25a0}"} else if audio_data.is_paused {"\u{25b6}"} else {"\u{23f8}"};
+	let volume = if app.song_volume <= -0.195 {"muted".to_string()} else {format!("{:.0}%", app.song_volume.max(0.0) * 100.0)};
+	let left = format!("{} {} ", state, format_time(audio_data.playback_position));
+	let right = format!(" {}  Vol {}  {}", format_time(audio_data.song_length), volume, loop_behavior_label(&app.loop_behavior));
+	let bar_width = width.saturating_sub(left.chars().count() + right.chars().count()).max(1);
+	let filled = std::cmp::min(bar_width, (bar_width * audio_data.playback_position).checked_div(audio_data.song_length).unwrap_or(0));
+	let bar = format!("{}{}", "\u{2501}".repeat(filled), "\u{2500}".repeat(bar_width - filled));
+	line(height - 2, "", &format!("{}{}{}", left, bar, right));
+
+	line(height - 1, "\x1b[2m", KEY_HELP);
+
+	print!("{}", out);
+	let _ = std::io::stdout().flush();
+}
+
+/**
+ * Runs the terminal interface until the user quits. Returns an error message if the terminal can't be used.
+ */
+pub fn run_tui(startup_args: &pinetree_core::args::StartupArgs) -> Result<(), String> {
+	let (persistent_data, installed_location) = config::find_persistent_data(startup_args.data_dir.as_deref(), startup_args.no_install);
+	let terminal = enable_raw_mode().ok_or("The terminal interface needs to be run in a terminal".to_string())?;
+
+	let engine = engine::start_engine(startup_args, &persistent_data, &installed_location);
+	let start_folder = engine::find_start_folder(startup_args, &persistent_data, &engine.audio_message_channel);
+	let mut directory_map = HashMap::<String, Directory>::new();
+	files::init_directory_at_filepath(&start_folder, &mut directory_map);
+
+	let loop_behavior = if startup_args.shuffle {
+		LoopBehavior::Shuffle
+	} else {
+		audio_frontend::clone_loop_behavior(&persistent_data.default_on_finish)
+	};
+	let mut app = TuiApp {
+		song_volume: startup_args.volume.unwrap_or(persistent_data.default_volume),
+		audio_message_channel: engine.audio_message_channel,
+		audio_receive_channel: engine.audio_receive_channel,
+		view: TuiView::Files,
+		current_song_folder: start_folder,
+		directory_map,
+		folder_watcher: engine.folder_watcher,
+		active_playlist_index: None,
+		search_text: "".to_string(),
+		typing_search: false,
+		selected: 0,
+		scroll: 0,
+		loop_behavior,
+		last_reported_volume: None,
+		quit: false,
+		persistent_data,
+	};
+	app.persistent_data.playlists.sort_by_key(|playlist| playlist.name.to_lowercase());
+
+	send(&app, MessageToAudio::UpdateEndBehavior(audio_frontend::clone_loop_behavior(&app.loop_behavior)));
+	send(&app, MessageToAudio::UpdateVolume(audio_frontend::volume_curve(app.song_volume)));
+	send(&app, MessageToAudio::UpdatePrevBehavior(app.persistent_data.prev_behavior));
+	send(&app, MessageToAudio::UpdateShuffleMemory(app.persistent_data.shuffle_memory));
+	send(&app, MessageToAudio::UpdateResumeSettings(app.persistent_data.resume.clone()));
+
+	print!("\x1b[2J");
+	while !app.quit {
+		let audio_data = audio_frontend::request_rodio_data(&app.audio_message_channel, &app.audio_receive_channel);
+		/* Keeps the volume in sync when it gets changed remotely, same as the GUI */
+		if let Some(last_volume) = app.last_reported_volume
+		&& audio_data.volume != last_volume
+		&& audio_data.volume != audio_frontend::volume_curve(app.song_volume) {
+			app.song_volume = audio_frontend::inverse_volume_curve(audio_data.volume);
+		}
+		app.last_reported_volume = Some(audio_data.volume);
+
+		/* Folders that changed on disk get read again */
+		watch::watch_folders(&app.folder_watcher, roots::library_roots(&app.persistent_data), app.directory_map.keys().cloned().collect());
+		for folder in watch::take_changes(&app.folder_watcher).folders {
+			if app.directory_map.contains_key(&folder) && !files::init_directory_at_filepath(&folder, &mut app.directory_map) {
+				app.directory_map.remove(&folder);
+			}
+		}
+
+		let (width, height) = terminal_size();
+		draw(&mut app, &audio_data, width, height.max(6));
+		for key in read_keys() {
+			handle_key(&mut app, key, &audio_data, height.saturating_sub(5).max(1));
+		}
+	}
+	/* Same as the GUI does on exit, the audio thread only saves the library when songs change */
+	let audio_data = audio_frontend::request_rodio_data(&app.audio_message_channel, &app.audio_receive_channel);
+	if let Ok(mut library) = engine.library.lock() {
+		if !audio_data.song_name.is_empty() {
+			resume::remember_position(&mut library, &app.persistent_data.resume, &audio_data.song_name, audio_data.playback_position);
+		}
+		if library::write_library(&library).is_err() {
+			println!("Error in saving library");
+		}
+	}
+	drop(terminal);
+	Ok(())
+}